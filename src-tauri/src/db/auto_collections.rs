@@ -0,0 +1,347 @@
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use chrono::Utc;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use super::collections::{add_media_to_collection, remove_collection};
+
+/// Fewest local movies needed before a franchise gets a collection
+const MIN_ITEMS: usize = 2;
+
+/// Folder name endings that mark a folder as a franchise grouping
+const FOLDER_MARKERS: &[&str] = &[
+    "collection", "trilogy", "duology", "quadrilogy", "saga", "franchise", "anthology",
+];
+
+/// A collection the generator wants to exist
+#[derive(Debug, Clone)]
+struct AutoCollection {
+    source: String,
+    name: String,
+    description: Option<String>,
+    poster_path: Option<String>,
+    /// In display order (release year, then title)
+    media_ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct AutoCollectionReport {
+    pub created: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+/// Franchises from TMDB collection membership of matched movies
+fn tmdb_franchises(conn: &Connection) -> Result<Vec<AutoCollection>> {
+    let mut stmt = conn.prepare(
+        "SELECT tc.tmdb_id, tc.name, tc.overview, tc.poster_path, m.id
+         FROM tmdb_collections tc
+         JOIN tmdb_collection_items tci ON tci.collection_id = tc.tmdb_id
+         JOIN tmdb_media tm ON tm.tmdb_id = tci.tmdb_movie_id AND tm.media_type = 'movie'
+         JOIN media_files m ON m.id = tm.media_id
+         WHERE m.is_deleted = 0
+         ORDER BY tc.tmdb_id, m.year IS NULL, m.year, COALESCE(m.title, m.file_name) COLLATE NOCASE",
+    )?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, i64>(4)?,
+        ))
+    })?;
+
+    let mut franchises: BTreeMap<i64, AutoCollection> = BTreeMap::new();
+    for row in rows {
+        let (tmdb_id, name, overview, poster_path, media_id) = row?;
+        franchises
+            .entry(tmdb_id)
+            .or_insert_with(|| AutoCollection {
+                source: format!("tmdb:{}", tmdb_id),
+                name,
+                description: overview,
+                poster_path,
+                media_ids: Vec::new(),
+            })
+            .media_ids
+            .push(media_id);
+    }
+
+    Ok(franchises.into_values().collect())
+}
+
+/// Nearest ancestor folder named like a franchise ("Alien Collection", "The Matrix Trilogy")
+fn franchise_folder(file_path: &str) -> Option<(String, String)> {
+    let normalized = file_path.replace('\\', "/");
+    let mut folder = Path::new(&normalized).parent();
+
+    while let Some(dir) = folder {
+        let name = dir.file_name()?.to_string_lossy().to_string();
+        // Ignore trailing tags like "(1979-1997)" or "[1080p]"
+        let mut lower = name.to_lowercase();
+        while let Some(open) = match lower.trim_end().chars().last() {
+            Some(')') => lower.rfind('('),
+            Some(']') => lower.rfind('['),
+            _ => None,
+        } {
+            lower.truncate(open);
+        }
+        let lower = lower.trim_end_matches(|c: char| !c.is_alphanumeric());
+        if FOLDER_MARKERS.iter().any(|marker| lower.ends_with(marker)) {
+            return Some((dir.to_string_lossy().to_string(), name));
+        }
+        folder = dir.parent();
+    }
+
+    None
+}
+
+/// Franchises from folder layout
+fn folder_franchises(conn: &Connection) -> Result<Vec<AutoCollection>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, file_path FROM media_files
+         WHERE is_deleted = 0 AND media_type = 'movie'
+         ORDER BY year IS NULL, year, COALESCE(title, file_name) COLLATE NOCASE",
+    )?;
+    let movies = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut folders: BTreeMap<String, AutoCollection> = BTreeMap::new();
+    for (media_id, file_path) in movies {
+        let Some((folder, name)) = franchise_folder(&file_path) else { continue };
+        folders
+            .entry(folder.clone())
+            .or_insert_with(|| AutoCollection {
+                source: format!("folder:{}", folder),
+                name,
+                description: None,
+                poster_path: None,
+                media_ids: Vec::new(),
+            })
+            .media_ids
+            .push(media_id);
+    }
+
+    Ok(folders.into_values().collect())
+}
+
+/// Bring generated franchise collections in line with the library.
+///
+/// Builds one collection per TMDB collection and per franchise-named folder
+/// with at least two local movies (folders already covered by a TMDB
+/// franchise with the same movies are skipped), adds and removes members as
+/// media comes and goes, and deletes generated collections that no longer
+/// apply. Collections without an `auto_source` are never touched, items the
+/// user added are kept, and collections the user deleted stay deleted.
+pub fn sync_auto_collections(conn: &Connection) -> Result<AutoCollectionReport> {
+    let tx = conn.unchecked_transaction()?;
+    let report = sync_collections(&tx)?;
+    tx.commit()?;
+    Ok(report)
+}
+
+fn sync_collections(conn: &Connection) -> Result<AutoCollectionReport> {
+    let mut desired = tmdb_franchises(conn)?;
+    let covered: Vec<HashSet<i64>> = desired.iter().map(|c| c.media_ids.iter().copied().collect()).collect();
+    for folder in folder_franchises(conn)? {
+        let ids: HashSet<i64> = folder.media_ids.iter().copied().collect();
+        if !covered.iter().any(|tmdb| ids.is_subset(tmdb)) {
+            desired.push(folder);
+        }
+    }
+    let mut stmt = conn.prepare("SELECT auto_source FROM dismissed_auto_collections")?;
+    let dismissed: HashSet<String> = stmt.query_map([], |row| row.get(0))?.collect::<Result<_>>()?;
+    desired.retain(|c| c.media_ids.len() >= MIN_ITEMS && !dismissed.contains(&c.source));
+
+    let mut report = AutoCollectionReport::default();
+    let now = Utc::now().to_rfc3339();
+
+    for collection in &desired {
+        let existing: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM collections WHERE auto_source = ?1",
+                params![collection.source],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let collection_id = match existing {
+            Some(id) => id,
+            None => {
+                conn.execute(
+                    "INSERT INTO collections (name, description, type, created_at, updated_at, poster_path, auto_source)
+                     VALUES (?1, ?2, 'franchise', ?3, ?3, ?4, ?5)",
+                    params![collection.name, collection.description, now, collection.poster_path, collection.source],
+                )?;
+                report.created += 1;
+                conn.last_insert_rowid()
+            }
+        };
+
+        let mut stmt = conn.prepare("SELECT media_id, source = 'auto' FROM collection_items WHERE collection_id = ?1")?;
+        let items: Vec<(i64, bool)> = stmt
+            .query_map(params![collection_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_>>()?;
+        let current: HashSet<i64> = items.iter().map(|(id, _)| *id).collect();
+        let wanted: HashSet<i64> = collection.media_ids.iter().copied().collect();
+
+        let stale: Vec<i64> = items
+            .iter()
+            .filter(|(id, is_auto)| *is_auto && !wanted.contains(id))
+            .map(|(id, _)| *id)
+            .collect();
+        for media_id in &stale {
+            conn.execute(
+                "DELETE FROM collection_items WHERE collection_id = ?1 AND media_id = ?2",
+                params![collection_id, media_id],
+            )?;
+        }
+        let mut added = 0;
+        for media_id in collection.media_ids.iter().filter(|id| !current.contains(id)) {
+            add_media_to_collection(conn, collection_id, *media_id)?;
+            conn.execute(
+                "UPDATE collection_items SET source = 'auto' WHERE collection_id = ?1 AND media_id = ?2",
+                params![collection_id, media_id],
+            )?;
+            added += 1;
+        }
+
+        if existing.is_some() && (added > 0 || !stale.is_empty()) {
+            conn.execute("UPDATE collections SET updated_at = ?1 WHERE id = ?2", params![now, collection_id])?;
+            report.updated += 1;
+        }
+    }
+
+    let wanted_sources: HashSet<&str> = desired.iter().map(|c| c.source.as_str()).collect();
+    let mut stmt = conn.prepare("SELECT id, auto_source FROM collections WHERE auto_source IS NOT NULL")?;
+    let generated = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>>>()?;
+    for (collection_id, source) in generated {
+        if !wanted_sources.contains(source.as_str()) {
+            remove_collection(conn, collection_id)?;
+            report.removed += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use crate::db::collections::{create_collection, delete_collection, get_collection_media, get_collections_with_counts};
+    use crate::db::test_support::{self, TestMedia};
+
+    fn add_movie(conn: &Connection, path: &str, year: i32) -> i64 {
+        test_support::add_media(conn, &TestMedia { path, title: Some(path), year: Some(year), ..TestMedia::default() })
+    }
+
+    fn match_tmdb(conn: &Connection, media_id: i64, tmdb_id: i64) {
+        conn.execute(
+            "INSERT INTO tmdb_media (media_id, tmdb_id, media_type) VALUES (?1, ?2, 'movie')",
+            params![media_id, tmdb_id],
+        ).unwrap();
+    }
+
+    fn auto_collections(conn: &Connection) -> Vec<(String, Vec<i64>)> {
+        let mut collections: Vec<(String, Vec<i64>)> = get_collections_with_counts(conn)
+            .unwrap()
+            .into_iter()
+            .filter(|c| c.is_auto)
+            .map(|c| (c.name, get_collection_media(conn, c.id).unwrap().iter().map(|i| i.id).collect()))
+            .collect();
+        collections.sort();
+        collections
+    }
+
+    #[test]
+    fn test_sync_tmdb_and_folder_franchises() -> Result<()> {
+        let conn = init_db()?;
+        let matrix = add_movie(&conn, "/movies/The Matrix (1999)/The Matrix.mkv", 1999);
+        let reloaded = add_movie(&conn, "/movies/The Matrix Reloaded (2003)/Reloaded.mkv", 2003);
+        let alien3 = add_movie(&conn, "/movies/Alien Collection/Alien 3.mkv", 1992);
+        let alien = add_movie(&conn, "/movies/Alien Collection/Alien (1979)/Alien.mkv", 1979);
+        let lone = add_movie(&conn, "/movies/Heat Collection/Heat.mkv", 1995);
+
+        conn.execute("INSERT INTO tmdb_collections (tmdb_id, name, overview) VALUES (2344, 'The Matrix Collection', 'Neo')", [])?;
+        conn.execute("INSERT INTO tmdb_collection_items VALUES (2344, 603), (2344, 604), (2344, 605)", [])?;
+        match_tmdb(&conn, matrix, 603);
+        match_tmdb(&conn, reloaded, 604);
+
+        let user = create_collection(&conn, "Favourites", None)?;
+        add_media_to_collection(&conn, user, lone)?;
+
+        let report = sync_auto_collections(&conn)?;
+        assert_eq!(report, AutoCollectionReport { created: 2, updated: 0, removed: 0 });
+        assert_eq!(
+            auto_collections(&conn),
+            vec![
+                ("Alien Collection".to_string(), vec![alien, alien3]),
+                ("The Matrix Collection".to_string(), vec![matrix, reloaded]),
+            ]
+        );
+
+        // Re-running is a no-op
+        assert_eq!(sync_auto_collections(&conn)?, AutoCollectionReport::default());
+
+        // Media going away shrinks, then removes, the franchise; user collections are left alone
+        conn.execute("UPDATE media_files SET is_deleted = 1 WHERE id IN (?1, ?2)", params![alien3, lone])?;
+        let report = sync_auto_collections(&conn)?;
+        assert_eq!(report, AutoCollectionReport { created: 0, updated: 0, removed: 1 });
+        assert_eq!(auto_collections(&conn).len(), 1);
+        assert_eq!(get_collection_media(&conn, user)?.len(), 0);
+        assert!(get_collections_with_counts(&conn)?.iter().any(|c| c.id == user && !c.is_auto));
+
+        // New matches are picked up
+        let revolutions = add_movie(&conn, "/movies/Revolutions.mkv", 2003);
+        match_tmdb(&conn, revolutions, 605);
+        assert_eq!(sync_auto_collections(&conn)?.updated, 1);
+        assert_eq!(
+            auto_collections(&conn),
+            vec![("The Matrix Collection".to_string(), vec![matrix, reloaded, revolutions])]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_keeps_user_items_and_dismissed_collections() -> Result<()> {
+        let conn = init_db()?;
+        let alien = add_movie(&conn, "/movies/Alien Collection/Alien.mkv", 1979);
+        let aliens = add_movie(&conn, "/movies/Alien Collection/Aliens.mkv", 1986);
+        let prometheus = add_movie(&conn, "/movies/Prometheus.mkv", 2012);
+        add_movie(&conn, "/movies/Heat Collection/Heat.mkv", 1995);
+        add_movie(&conn, "/movies/Heat Collection/Thief.mkv", 1981);
+        sync_auto_collections(&conn)?;
+
+        // Something the user put in a generated collection stays there
+        let alien_id = get_collections_with_counts(&conn)?.into_iter().find(|c| c.name == "Alien Collection").unwrap().id;
+        add_media_to_collection(&conn, alien_id, prometheus)?;
+        assert_eq!(sync_auto_collections(&conn)?, AutoCollectionReport::default());
+        assert_eq!(get_collection_media(&conn, alien_id)?.iter().map(|i| i.id).collect::<Vec<_>>(), vec![alien, aliens, prometheus]);
+
+        // A generated collection the user deleted isn't created again
+        let heat_id = get_collections_with_counts(&conn)?.into_iter().find(|c| c.name == "Heat Collection").unwrap().id;
+        delete_collection(&conn, heat_id)?;
+        assert_eq!(sync_auto_collections(&conn)?, AutoCollectionReport::default());
+        assert_eq!(auto_collections(&conn).len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_franchise_folder() {
+        assert_eq!(
+            franchise_folder("/m/The Lord of the Rings Trilogy/Fellowship (2001)/f.mkv"),
+            Some(("/m/The Lord of the Rings Trilogy".to_string(), "The Lord of the Rings Trilogy".to_string()))
+        );
+        assert_eq!(
+            franchise_folder("D:\\Movies\\Bond Collection [1962-2021]\\Dr No.mkv").map(|f| f.1),
+            Some("Bond Collection [1962-2021]".to_string())
+        );
+        assert_eq!(franchise_folder("/m/Collections Misc/Heat.mkv"), None);
+    }
+}
@@ -0,0 +1,223 @@
+//! Editing the metadata of many files at once: setting the show, season or
+//! year, renumbering episodes and rewriting titles with a regex. Changes are
+//! manual edits, so each changed field is locked and recorded in the edit
+//! history (see `metadata_overrides`).
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashSet};
+
+use super::metadata_overrides::{edit_media_metadata, media_field_value};
+use super::operations::get_media_file_by_id;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BulkEditError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Invalid title pattern: {0}")]
+    InvalidPattern(String),
+    #[error("Media {0} not found")]
+    MediaNotFound(i64),
+}
+
+/// One change applied to every file of a bulk edit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BulkOperation {
+    SetShow { show_title: String },
+    SetSeason { season: i32 },
+    /// Number the files' episodes in the order their ids were given
+    RenumberEpisodes { start: i32 },
+    /// None clears the year
+    SetYear { year: Option<i32> },
+    /// Replace matches in the title; the replacement may use `$1` or `$name`.
+    /// A title replaced down to nothing is cleared.
+    TitleRegex { pattern: String, replacement: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkEdit {
+    /// Ids listed more than once are edited once, at their first position
+    pub media_ids: Vec<i64>,
+    /// Applied in order, so a title pattern sees the titles set before it
+    pub operations: Vec<BulkOperation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub old: Value,
+    pub new: Value,
+}
+
+/// The resulting values of one file; files that don't change are left out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkEditResult {
+    pub media_id: i64,
+    pub file_name: String,
+    pub changes: BTreeMap<String, FieldChange>,
+}
+
+/// Apply a bulk edit in one transaction, or with `dry_run` only work out the
+/// values it would produce. Nothing is written if any file fails.
+pub fn bulk_edit_metadata(conn: &Connection, edit: &BulkEdit, dry_run: bool) -> Result<Vec<BulkEditResult>, BulkEditError> {
+    // Compile patterns up front so a bad one fails before anything is written
+    let patterns = edit
+        .operations
+        .iter()
+        .map(|operation| match operation {
+            BulkOperation::TitleRegex { pattern, .. } => {
+                regex::Regex::new(pattern).map(Some).map_err(|e| BulkEditError::InvalidPattern(e.to_string()))
+            }
+            _ => Ok(None),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Read inside the write transaction, so nothing can change between working
+    // out the values and writing them
+    let tx = if dry_run { None } else { Some(conn.unchecked_transaction()?) };
+
+    let mut seen = HashSet::new();
+    let media_ids: Vec<i64> = edit.media_ids.iter().copied().filter(|id| seen.insert(*id)).collect();
+    let mut results = Vec::new();
+    for (position, &media_id) in media_ids.iter().enumerate() {
+        let media = get_media_file_by_id(conn, media_id)?.ok_or(BulkEditError::MediaNotFound(media_id))?;
+        let mut values: BTreeMap<String, Value> = BTreeMap::new();
+
+        for (operation, pattern) in edit.operations.iter().zip(&patterns) {
+            match operation {
+                BulkOperation::SetShow { show_title } => {
+                    values.insert("show_title".to_string(), json!(show_title));
+                }
+                BulkOperation::SetSeason { season } => {
+                    values.insert("season".to_string(), json!(season));
+                }
+                BulkOperation::RenumberEpisodes { start } => {
+                    values.insert("episode".to_string(), json!(start + position as i32));
+                }
+                BulkOperation::SetYear { year } => {
+                    values.insert("year".to_string(), json!(year));
+                }
+                BulkOperation::TitleRegex { replacement, .. } => {
+                    let Some(regex) = pattern else { continue };
+                    let title = values.get("title").cloned().unwrap_or_else(|| media_field_value(&media, "title"));
+                    if let Some(title) = title.as_str() {
+                        let renamed = regex.replace_all(title, replacement.as_str()).trim().to_string();
+                        let renamed = if renamed.is_empty() { Value::Null } else { json!(renamed) };
+                        values.insert("title".to_string(), renamed);
+                    }
+                }
+            }
+        }
+
+        let changes: BTreeMap<String, FieldChange> = values
+            .into_iter()
+            .filter_map(|(field, new)| {
+                let old = media_field_value(&media, &field);
+                (old != new).then_some((field, FieldChange { old, new }))
+            })
+            .collect();
+        if !changes.is_empty() {
+            results.push(BulkEditResult { media_id, file_name: media.file_name.clone(), changes });
+        }
+    }
+
+    if let Some(tx) = tx {
+        for result in &results {
+            let values = result.changes.iter().map(|(field, change)| (field.clone(), change.new.clone())).collect();
+            edit_media_metadata(&tx, result.media_id, &values)?;
+        }
+        tx.commit()?;
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use crate::db::metadata_overrides::get_metadata_edits;
+    use crate::db::test_support::{self, TestMedia};
+
+    fn add_episode(conn: &Connection, path: &str, title: &str, episode: i32) -> i64 {
+        let media = TestMedia {
+            path,
+            media_type: "tv_episode",
+            title: Some(title),
+            season: Some(2),
+            episode: Some(episode),
+            ..TestMedia::default()
+        };
+        test_support::add_media(conn, &media)
+    }
+
+    #[test]
+    fn test_bulk_edit_preview_and_apply() {
+        let conn = init_db().unwrap();
+        add_episode(&conn, "/tv/e1.mkv", "firefly 1x01 - Serenity", 7);
+        add_episode(&conn, "/tv/e2.mkv", "firefly 1x02 - The Train Job", 3);
+        let edit = BulkEdit {
+            media_ids: vec![1, 2],
+            operations: vec![
+                BulkOperation::SetShow { show_title: "Firefly".to_string() },
+                BulkOperation::SetSeason { season: 1 },
+                BulkOperation::RenumberEpisodes { start: 1 },
+                BulkOperation::TitleRegex { pattern: r"^firefly \d+x\d+ - ".to_string(), replacement: String::new() },
+            ],
+        };
+
+        let preview = bulk_edit_metadata(&conn, &edit, true).unwrap();
+        assert_eq!(preview[1].changes["title"].new, json!("The Train Job"));
+        assert_eq!(preview[1].changes["episode"], FieldChange { old: json!(3), new: json!(2) });
+        assert!(get_metadata_edits(&conn, 1).unwrap().is_empty());
+
+        assert_eq!(bulk_edit_metadata(&conn, &edit, false).unwrap(), preview);
+        let media = get_media_file_by_id(&conn, 1).unwrap().unwrap();
+        assert_eq!((media.title.as_deref(), media.season_number, media.episode_number), (Some("Serenity"), Some(1), Some(1)));
+        assert_eq!(media_field_value(&media, "show_title"), json!("Firefly"));
+        assert_eq!(get_metadata_edits(&conn, 1).unwrap().len(), 4);
+        // Applying it again changes nothing
+        assert!(bulk_edit_metadata(&conn, &edit, true).unwrap().is_empty());
+
+        let bad = BulkEdit { media_ids: vec![1], operations: vec![BulkOperation::TitleRegex { pattern: "(".to_string(), replacement: String::new() }] };
+        assert!(matches!(bulk_edit_metadata(&conn, &bad, true), Err(BulkEditError::InvalidPattern(_))));
+        let missing = BulkEdit { media_ids: vec![1, 99], operations: vec![BulkOperation::SetYear { year: Some(2002) }] };
+        assert!(matches!(bulk_edit_metadata(&conn, &missing, false), Err(BulkEditError::MediaNotFound(99))));
+        assert_eq!(get_media_file_by_id(&conn, 1).unwrap().unwrap().year, None);
+    }
+    #[test]
+    fn test_bulk_edit_duplicate_ids_are_numbered_once() {
+        let conn = init_db().unwrap();
+        let a = add_episode(&conn, "/tv/a.mkv", "A", 5);
+        let b = add_episode(&conn, "/tv/b.mkv", "B", 6);
+
+        let edit = BulkEdit { media_ids: vec![a, b, a], operations: vec![BulkOperation::RenumberEpisodes { start: 1 }] };
+        let results = bulk_edit_metadata(&conn, &edit, false).unwrap();
+        assert_eq!(results.iter().map(|r| r.media_id).collect::<Vec<_>>(), vec![a, b]);
+        assert_eq!(get_media_file_by_id(&conn, a).unwrap().unwrap().episode_number, Some(1));
+        assert_eq!(get_media_file_by_id(&conn, b).unwrap().unwrap().episode_number, Some(2));
+    }
+
+    #[test]
+    fn test_bulk_edit_clears_title_and_year() {
+        let conn = init_db().unwrap();
+        let dated = test_support::add_media(&conn, &TestMedia { path: "/m/a.mkv", title: Some("Sample"), year: Some(2001), ..TestMedia::default() });
+        let undated = test_support::add_media(&conn, &TestMedia { path: "/m/b.mkv", title: Some("Other"), ..TestMedia::default() });
+
+        let edit = BulkEdit {
+            media_ids: vec![dated, undated],
+            operations: vec![
+                BulkOperation::TitleRegex { pattern: "^Sample$".to_string(), replacement: String::new() },
+                BulkOperation::SetYear { year: None },
+            ],
+        };
+        let results = bulk_edit_metadata(&conn, &edit, false).unwrap();
+        // The undated file keeps its title and has no year to clear
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].changes["title"], FieldChange { old: json!("Sample"), new: Value::Null });
+        assert_eq!(results[0].changes["year"], FieldChange { old: json!(2001), new: Value::Null });
+
+        let media = get_media_file_by_id(&conn, dated).unwrap().unwrap();
+        assert_eq!((media.title, media.year), (None, None));
+    }
+}
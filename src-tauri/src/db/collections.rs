@@ -1,5 +1,6 @@
 use rusqlite::{Connection, Result, params};
 use chrono::Utc;
+use crate::db::ordering::{append_key, move_items, OrderedList};
 
 /// Collection model
 #[allow(dead_code)]
@@ -101,8 +102,19 @@ pub fn update_collection(
     Ok(())
 }
 
-/// Delete a collection
+/// Delete a collection. A generated one is remembered as dismissed so the
+/// franchise sync doesn't bring it back.
 pub fn delete_collection(conn: &Connection, collection_id: i64) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO dismissed_auto_collections (auto_source)
+         SELECT auto_source FROM collections WHERE id = ?1 AND auto_source IS NOT NULL",
+        params![collection_id],
+    )?;
+    remove_collection(conn, collection_id)
+}
+
+/// Delete a collection and its items
+pub(crate) fn remove_collection(conn: &Connection, collection_id: i64) -> Result<()> {
     // Delete collection items first
     conn.execute(
         "DELETE FROM collection_items WHERE collection_id = ?1",
@@ -126,10 +138,12 @@ pub fn add_media_to_collection(
 ) -> Result<()> {
     let now = Utc::now().to_rfc3339();
     
+    let sort_key = append_key(conn, OrderedList::Collection, collection_id)?;
+    
     conn.execute(
-        "INSERT OR IGNORE INTO collection_items (collection_id, media_id, added_at)
-         VALUES (?1, ?2, ?3)",
-        params![collection_id, media_id, &now],
+        "INSERT OR IGNORE INTO collection_items (collection_id, media_id, added_at, sort_key)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![collection_id, media_id, &now, sort_key],
     )?;
     
     // Update collection updated_at
@@ -174,6 +188,7 @@ pub struct CollectionMediaItem {
     pub media_type: String,
     pub duration: Option<i64>,
     pub added_at: String,
+    pub position: i32,
 }
 
 pub fn get_collection_media(conn: &Connection, collection_id: i64) -> Result<Vec<CollectionMediaItem>> {
@@ -183,10 +198,10 @@ pub fn get_collection_media(conn: &Connection, collection_id: i64) -> Result<Vec
          FROM collection_items ci
          JOIN media_files m ON ci.media_id = m.id
          WHERE ci.collection_id = ?1 AND m.is_deleted = 0
-         ORDER BY ci.added_at DESC"
+         ORDER BY ci.sort_key, ci.id"
     )?;
     
-    let items = stmt.query_map(params![collection_id], |row| {
+    let mut items = stmt.query_map(params![collection_id], |row| {
         Ok(CollectionMediaItem {
             id: row.get(0)?,
             file_path: row.get(1)?,
@@ -196,13 +211,48 @@ pub fn get_collection_media(conn: &Connection, collection_id: i64) -> Result<Vec
             media_type: row.get(5)?,
             duration: row.get(6)?,
             added_at: row.get(7)?,
+            position: 0,
         })
     })?
     .collect::<Result<Vec<_>>>()?;
+
+    for (index, item) in items.iter_mut().enumerate() {
+        item.position = index as i32;
+    }
     
     Ok(items)
 }
 
+/// Move a collection item to `new_position` (0-based)
+pub fn reorder_collection_item(
+    conn: &Connection,
+    collection_id: i64,
+    media_id: i64,
+    new_position: i32,
+) -> Result<()> {
+    move_collection_items(conn, collection_id, &[media_id], new_position.max(0) as usize)?;
+    Ok(())
+}
+
+/// Move several collection items as a block so the first one ends up at
+/// `to_index`; returns how many items moved
+pub fn move_collection_items(
+    conn: &Connection,
+    collection_id: i64,
+    media_ids: &[i64],
+    to_index: usize,
+) -> Result<usize> {
+    let moved = move_items(conn, OrderedList::Collection, collection_id, media_ids, to_index)?;
+
+    // Update collection updated_at
+    conn.execute(
+        "UPDATE collections SET updated_at = ?1 WHERE id = ?2",
+        params![Utc::now().to_rfc3339(), collection_id],
+    )?;
+
+    Ok(moved)
+}
+
 /// Get collections with item count
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct CollectionWithCount {
@@ -212,12 +262,16 @@ pub struct CollectionWithCount {
     pub created_at: String,
     pub updated_at: String,
     pub item_count: i32,
+    /// e.g. 'franchise'; None for plain user collections
+    pub collection_type: Option<String>,
+    /// Generated by `sync_auto_collections` rather than curated by the user
+    pub is_auto: bool,
 }
 
 pub fn get_collections_with_counts(conn: &Connection) -> Result<Vec<CollectionWithCount>> {
     let mut stmt = conn.prepare(
         "SELECT c.id, c.name, c.description, c.created_at, c.updated_at,
-                COUNT(ci.media_id) as item_count
+                COUNT(ci.media_id) as item_count, c.type, c.auto_source IS NOT NULL
          FROM collections c
          LEFT JOIN collection_items ci ON c.id = ci.collection_id
          GROUP BY c.id
@@ -232,6 +286,8 @@ pub fn get_collections_with_counts(conn: &Connection) -> Result<Vec<CollectionWi
             created_at: row.get(3)?,
             updated_at: row.get(4)?,
             item_count: row.get(5)?,
+            collection_type: row.get(6)?,
+            is_auto: row.get(7)?,
         })
     })?
     .collect::<Result<Vec<_>>>()?;
@@ -269,6 +325,7 @@ mod tests {
             last_modified: Utc::now().to_rfc3339(),
             is_deleted: false,
             metadata_json: None,
+            is_locked: false,
         };
         add_media_file(conn, &media)
     }
@@ -0,0 +1,288 @@
+//! Facets over the TMDB metadata of matched files: genres, keywords,
+//! production countries, languages, ratings and decades, with counts.
+
+use rusqlite::{Connection, ToSql};
+
+use super::models::FilterCriteria;
+use super::query_parser::FilterError;
+
+/// TMDB rating (0-10) of a file's matched title, NULL when unmatched
+pub(crate) const RATING_SQL: &str = "(SELECT md.vote_average FROM tmdb_media t \
+    JOIN tmdb_metadata md ON md.tmdb_id = t.tmdb_id AND md.media_type = t.media_type WHERE t.media_id = m.id)";
+
+/// TMDB popularity of a file's matched title, NULL when unmatched
+pub(crate) const POPULARITY_SQL: &str = "(SELECT md.popularity FROM tmdb_media t \
+    JOIN tmdb_metadata md ON md.tmdb_id = t.tmdb_id AND md.media_type = t.media_type WHERE t.media_id = m.id)";
+
+/// Keywords listed in a facet response; genres and the rest are small enough to list in full
+const MAX_KEYWORDS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Facet {
+    Genre,
+    Keyword,
+    Country,
+    Language,
+}
+
+impl Facet {
+    fn table_and_column(&self) -> (&'static str, &'static str) {
+        match self {
+            Facet::Genre => ("tmdb_genres", "name"),
+            Facet::Keyword => ("tmdb_keywords", "name"),
+            Facet::Country => ("tmdb_countries", "code"),
+            Facet::Language => ("tmdb_languages", "code"),
+        }
+    }
+
+    /// The stored values a user-supplied value stands for
+    fn expand(&self, value: &str) -> Vec<String> {
+        let value = value.trim();
+        match self {
+            Facet::Genre => canonical_genres(value),
+            Facet::Country => vec![value.to_uppercase()],
+            Facet::Language => vec![value.to_lowercase()],
+            Facet::Keyword => vec![value.to_string()],
+        }
+    }
+}
+
+/// TMDB genre names for a genre as people type it; TV uses combined genres
+/// like "Sci-Fi & Fantasy" that should match their movie counterparts
+fn canonical_genres(genre: &str) -> Vec<String> {
+    let names: &[&str] = match genre.to_lowercase().replace(['-', ' '], "").as_str() {
+        "scifi" | "sf" | "sciencefiction" => &["Science Fiction", "Sci-Fi & Fantasy"],
+        "fantasy" => &["Fantasy", "Sci-Fi & Fantasy"],
+        "action" | "adventure" => return vec![capitalize(genre), "Action & Adventure".to_string()],
+        "war" | "politics" => return vec![capitalize(genre), "War & Politics".to_string()],
+        "doc" | "docs" | "documentary" => &["Documentary"],
+        "animated" | "animation" | "anime" => &["Animation"],
+        "romcom" => &["Romance", "Comedy"],
+        _ => return vec![genre.to_string()],
+    };
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars.flat_map(|c| c.to_lowercase())).collect())
+        .unwrap_or_default()
+}
+
+/// Condition over `media_files m`: the file's matched title has any of `values`.
+/// `bind` stores a parameter and returns its placeholder. None when there are no values.
+pub(crate) fn facet_condition(facet: Facet, values: &[String], mut bind: impl FnMut(String) -> String) -> Option<String> {
+    let (table, column) = facet.table_and_column();
+    let placeholders: Vec<String> = values
+        .iter()
+        .flat_map(|v| facet.expand(v))
+        .filter(|v| !v.is_empty())
+        .map(&mut bind)
+        .collect();
+    if placeholders.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "EXISTS (SELECT 1 FROM tmdb_media t JOIN {table} f ON f.tmdb_id = t.tmdb_id AND f.media_type = t.media_type \
+         WHERE t.media_id = m.id AND f.{column} COLLATE NOCASE IN ({}))",
+        placeholders.join(", ")
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FacetCount {
+    pub value: String,
+    /// Files in the current filter with this value
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MediaFacets {
+    pub genres: Vec<FacetCount>,
+    /// The most common keywords
+    pub keywords: Vec<FacetCount>,
+    /// ISO 3166-1 codes
+    pub countries: Vec<FacetCount>,
+    /// ISO 639-1 codes
+    pub languages: Vec<FacetCount>,
+    /// Whole-number rating buckets: "7" covers 7.0 up to 8.0
+    pub ratings: Vec<FacetCount>,
+    /// "1980" covers 1980-1989
+    pub decades: Vec<FacetCount>,
+}
+
+/// Facet counts over the files matching `criteria`
+pub fn get_media_facets(conn: &Connection, criteria: &FilterCriteria) -> Result<MediaFacets, FilterError> {
+    let (conditions, params) = super::operations::filter_conditions(criteria)?;
+    let params_refs: Vec<&dyn ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+    let count = |value_sql: &str, joins: &str, order: &str, limit: Option<usize>| -> rusqlite::Result<Vec<FacetCount>> {
+        let mut sql = format!(
+            "SELECT {value_sql} AS value, COUNT(DISTINCT m.id) AS count
+             FROM media_files m {joins}
+             WHERE m.is_deleted = 0{conditions} AND {value_sql} IS NOT NULL
+             GROUP BY value ORDER BY {order}"
+        );
+        if let Some(limit) = limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_refs.as_slice(), |row| {
+            Ok(FacetCount {
+                value: row.get::<_, rusqlite::types::Value>(0).map(|v| match v {
+                    rusqlite::types::Value::Integer(i) => i.to_string(),
+                    rusqlite::types::Value::Text(s) => s,
+                    other => format!("{:?}", other),
+                })?,
+                count: row.get::<_, i64>(1)? as usize,
+            })
+        })?;
+        rows.collect()
+    };
+
+    let facet = |facet: Facet, limit: Option<usize>| {
+        let (table, column) = facet.table_and_column();
+        count(
+            &format!("f.{}", column),
+            &format!(
+                "JOIN tmdb_media t ON t.media_id = m.id \
+                 JOIN {table} f ON f.tmdb_id = t.tmdb_id AND f.media_type = t.media_type"
+            ),
+            "count DESC, value COLLATE NOCASE",
+            limit,
+        )
+    };
+
+    Ok(MediaFacets {
+        genres: facet(Facet::Genre, None)?,
+        keywords: facet(Facet::Keyword, Some(MAX_KEYWORDS))?,
+        countries: facet(Facet::Country, None)?,
+        languages: facet(Facet::Language, None)?,
+        ratings: count(&format!("CAST({} AS INTEGER)", RATING_SQL), "", "value DESC", None)?,
+        decades: count("(m.year / 10) * 10", "", "value DESC", None)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use crate::db::models::{PageRequest, PlaylistType, SortKey};
+    use crate::db::operations::filter_media;
+    use crate::db::playlists::{add_playlist_rule, create_playlist, get_playlist_media};
+    use rusqlite::params;
+
+    fn library() -> Connection {
+        let conn = init_db().unwrap();
+        let movies: &[(&str, i32, &str, f64, &str, &str, &str)] = &[
+            ("Alien", 1979, r#"["Horror", "Science Fiction"]"#, 8.2, r#"[{"id": 1, "name": "space"}]"#, r#"[{"iso_3166_1": "GB", "name": "United Kingdom"}]"#, r#"["en"]"#),
+            ("Aliens", 1986, r#"[{"id": 28, "name": "Action"}, {"id": 878, "name": "Science Fiction"}]"#, 7.9, r#"["space", "marine"]"#, r#"["US"]"#, r#"["en"]"#),
+            ("Brazil", 1985, r#"["Science Fiction", "Comedy"]"#, 6.9, "[]", r#"["GB"]"#, r#"["en"]"#),
+            ("Amélie", 2001, r#"["Comedy", "Romance"]"#, 7.9, "[]", r#"["FR"]"#, r#"[{"iso_639_1": "fr", "name": "Français"}]"#),
+        ];
+        for (i, (title, year, genres, rating, keywords, countries, languages)) in movies.iter().enumerate() {
+            conn.execute(
+                "INSERT INTO media_files (file_path, file_hash, file_name, file_size, media_type, title, year, last_modified)
+                 VALUES (?1, 'hash', ?1, 1, 'movie', ?2, ?3, datetime('now'))",
+                params![format!("/m/{}.mkv", title), title, year],
+            ).unwrap();
+            let media_id = conn.last_insert_rowid();
+            conn.execute(
+                "INSERT INTO tmdb_metadata (tmdb_id, media_type, title, vote_average, genres_json, keywords_json, production_countries_json, languages_json)
+                 VALUES (?1, 'movie', ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![i as i64 + 1, title, rating, genres, keywords, countries, languages],
+            ).unwrap();
+            conn.execute(
+                "INSERT INTO tmdb_media (media_id, tmdb_id, media_type) VALUES (?1, ?2, 'movie')",
+                params![media_id, i as i64 + 1],
+            ).unwrap();
+        }
+        // An unmatched file has no facets
+        conn.execute(
+            "INSERT INTO media_files (file_path, file_hash, file_name, file_size, media_type, title, year, last_modified)
+             VALUES ('/m/home.mkv', 'hash', 'home.mkv', 1, 'video', 'Home video', 1987, datetime('now'))",
+            [],
+        ).unwrap();
+        conn
+    }
+
+    fn titles(media: &[crate::db::models::MediaFile]) -> Vec<&str> {
+        media.iter().map(|m| m.title.as_deref().unwrap_or("")).collect()
+    }
+
+    fn playlist_titles(conn: &Connection, playlist_id: i64) -> Vec<String> {
+        let mut titles: Vec<String> = get_playlist_media(conn, playlist_id).unwrap().into_iter().filter_map(|m| m.title).collect();
+        titles.sort();
+        titles
+    }
+
+    #[test]
+    fn test_facet_tables_follow_metadata() -> rusqlite::Result<()> {
+        let conn = library();
+        let genres: i64 = conn.query_row("SELECT COUNT(*) FROM tmdb_genres", [], |row| row.get(0))?;
+        assert_eq!(genres, 8);
+        let country: String = conn.query_row("SELECT code FROM tmdb_countries WHERE tmdb_id = 1", [], |row| row.get(0))?;
+        assert_eq!(country, "GB");
+
+        conn.execute("UPDATE tmdb_metadata SET genres_json = '[\"Drama\"]' WHERE tmdb_id = 3", [])?;
+        let brazil: Vec<String> = conn
+            .prepare("SELECT name FROM tmdb_genres WHERE tmdb_id = 3")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        assert_eq!(brazil, vec!["Drama"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_facet_filters_and_counts() {
+        let conn = library();
+        let page = PageRequest { sort: Some(SortKey::Title), ..PageRequest::default() };
+
+        // "Sci-Fi from the 80s rated above 7"
+        let criteria = FilterCriteria {
+            genres: Some(vec!["sci-fi".to_string()]),
+            min_year: Some(1980),
+            max_year: Some(1989),
+            min_rating: Some(7.0),
+            ..FilterCriteria::default()
+        };
+        assert_eq!(titles(&filter_media(&conn, &criteria, &page).unwrap()), vec!["Aliens"]);
+
+        let criteria = FilterCriteria { countries: Some(vec!["gb".to_string()]), ..FilterCriteria::default() };
+        assert_eq!(titles(&filter_media(&conn, &criteria, &page).unwrap()), vec!["Alien", "Brazil"]);
+        let criteria = FilterCriteria { languages: Some(vec!["FR".to_string()]), ..FilterCriteria::default() };
+        assert_eq!(titles(&filter_media(&conn, &criteria, &page).unwrap()), vec!["Amélie"]);
+
+        let facets = get_media_facets(&conn, &FilterCriteria::default()).unwrap();
+        assert_eq!(facets.genres[0], FacetCount { value: "Science Fiction".to_string(), count: 3 });
+        assert_eq!(facets.keywords[0], FacetCount { value: "space".to_string(), count: 2 });
+        assert_eq!(facets.ratings.iter().map(|f| (f.value.as_str(), f.count)).collect::<Vec<_>>(), vec![("8", 1), ("7", 2), ("6", 1)]);
+        assert_eq!(facets.decades[1], FacetCount { value: "1980".to_string(), count: 3 });
+
+        // Counts follow the active filter
+        let scifi = FilterCriteria { genres: Some(vec!["Science Fiction".to_string()]), ..FilterCriteria::default() };
+        let facets = get_media_facets(&conn, &scifi).unwrap();
+        assert_eq!(facets.countries, vec![
+            FacetCount { value: "GB".to_string(), count: 2 },
+            FacetCount { value: "US".to_string(), count: 1 },
+        ]);
+    }
+
+    #[test]
+    fn test_smart_playlist_facet_rules() {
+        let conn = library();
+        let playlist_id = create_playlist(&conn, "80s sci-fi", None, PlaylistType::Smart).unwrap();
+        add_playlist_rule(&conn, playlist_id, "genre", "equals", "Sci-Fi", None).unwrap();
+        add_playlist_rule(&conn, playlist_id, "year", "between", "1980..1989", None).unwrap();
+        add_playlist_rule(&conn, playlist_id, "rating", "gt", "7", None).unwrap();
+        assert_eq!(playlist_titles(&conn, playlist_id), vec!["Aliens"]);
+
+        let playlist_id = create_playlist(&conn, "Not British", None, PlaylistType::Smart).unwrap();
+        add_playlist_rule(&conn, playlist_id, "country", "notequals", "GB", None).unwrap();
+        add_playlist_rule(&conn, playlist_id, "keyword", "notequals", "marine", None).unwrap();
+        assert_eq!(playlist_titles(&conn, playlist_id), vec!["Amélie", "Home video"]);
+    }
+}
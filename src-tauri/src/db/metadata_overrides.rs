@@ -0,0 +1,420 @@
+//! Per-field metadata locks and the history of manual edits.
+//!
+//! Scans and metadata providers write media_files as usual. A field the user
+//! edited or locked also has a `metadata_overrides` row with their value,
+//! written back over whatever a later scan or refresh produced, and the
+//! latest produced ("scanned") value, so the field can be reverted to it.
+//! `media_files.is_locked` is set while a file has any locked field. Every
+//! change is recorded in `metadata_edits` and can be undone field by field.
+
+use rusqlite::{Connection, OptionalExtension, Result, Row, params};
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+
+use super::models::MediaFile;
+use super::operations::get_media_file_by_id;
+
+/// A locked field
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MetadataOverride {
+    pub field: String,
+    /// The user's value
+    pub value: Value,
+    /// The value scans and providers last produced
+    pub scanned: Value,
+    pub updated_at: String,
+}
+
+/// One change to a field, as recorded in `metadata_edits`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MetadataEdit {
+    pub id: i64,
+    pub media_id: i64,
+    pub field: String,
+    /// "edit", "lock", "unlock", "revert" or "undo"
+    pub action: String,
+    pub old_value: Value,
+    pub new_value: Value,
+    /// Whether the field was locked before the change
+    pub was_locked: bool,
+    /// Set once the change has been undone
+    pub undone: bool,
+    pub edited_at: String,
+}
+
+const OVERRIDE_COLUMNS: &str = "field, value_json, scanned_json, updated_at";
+const EDIT_COLUMNS: &str = "id, media_id, field, action, old_value_json, new_value_json, was_locked, undone, edited_at";
+
+fn parse(json: &str) -> Value {
+    serde_json::from_str(json).unwrap_or(Value::Null)
+}
+
+fn edit_from_row(row: &Row) -> Result<MetadataEdit> {
+    Ok(MetadataEdit {
+        id: row.get(0)?,
+        media_id: row.get(1)?,
+        field: row.get(2)?,
+        action: row.get(3)?,
+        old_value: parse(&row.get::<_, String>(4)?),
+        new_value: parse(&row.get::<_, String>(5)?),
+        was_locked: row.get(6)?,
+        undone: row.get(7)?,
+        edited_at: row.get(8)?,
+    })
+}
+
+fn load_media(conn: &Connection, media_id: i64) -> Result<MediaFile> {
+    get_media_file_by_id(conn, media_id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)
+}
+
+fn override_from_row(row: &Row) -> Result<MetadataOverride> {
+    Ok(MetadataOverride {
+        field: row.get(0)?,
+        value: parse(&row.get::<_, String>(1)?),
+        scanned: parse(&row.get::<_, String>(2)?),
+        updated_at: row.get(3)?,
+    })
+}
+
+fn metadata_map(media: &MediaFile) -> Map<String, Value> {
+    media
+        .metadata_json
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default()
+}
+
+/// A field's value on a file: title, year, season and episode are columns,
+/// anything else a metadata_json key
+pub fn media_field_value(media: &MediaFile, field: &str) -> Value {
+    match field {
+        "title" => json!(media.title),
+        "year" => json!(media.year),
+        "season" => json!(media.season_number),
+        "episode" => json!(media.episode_number),
+        _ => metadata_map(media).remove(field).unwrap_or(Value::Null),
+    }
+}
+
+/// Write field values to a file, ignoring locks; null clears a field
+pub fn set_media_fields(conn: &Connection, media_id: i64, values: &BTreeMap<String, Value>) -> Result<()> {
+    let mut media = load_media(conn, media_id)?;
+    let mut map = metadata_map(&media);
+    for (field, value) in values {
+        let int = || value.as_i64().or_else(|| value.as_str()?.trim().parse().ok()).map(|v| v as i32);
+        match field.as_str() {
+            "title" => media.title = value.as_str().map(String::from),
+            "year" => media.year = int(),
+            "season" => media.season_number = int(),
+            "episode" => media.episode_number = int(),
+            _ if value.is_null() => {
+                map.remove(field);
+            }
+            _ => {
+                map.insert(field.clone(), value.clone());
+            }
+        }
+    }
+    let metadata_json = if map.is_empty() && media.metadata_json.is_none() { None } else { serde_json::to_string(&map).ok() };
+
+    conn.execute(
+        "UPDATE media_files SET title = ?2, year = ?3, season_number = ?4, episode_number = ?5, metadata_json = ?6
+         WHERE id = ?1",
+        params![media_id, media.title, media.year, media.season_number, media.episode_number, metadata_json],
+    )?;
+    Ok(())
+}
+
+/// A file's locked fields
+pub fn get_metadata_overrides(conn: &Connection, media_id: i64) -> Result<Vec<MetadataOverride>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM metadata_overrides WHERE media_id = ?1 ORDER BY field",
+        OVERRIDE_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![media_id], override_from_row)?;
+    rows.collect()
+}
+
+/// History of a file's fields, newest first
+pub fn get_metadata_edits(conn: &Connection, media_id: i64) -> Result<Vec<MetadataEdit>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM metadata_edits WHERE media_id = ?1 ORDER BY id DESC",
+        EDIT_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![media_id], edit_from_row)?;
+    rows.collect()
+}
+
+/// Write a file's locked values back after a scan or provider refresh wrote
+/// the row, keeping what it produced as the scanned value. A produced value
+/// equal to the user's is most likely the user's value read back and
+/// written again, so it doesn't replace the scanned one. Returns the number
+/// of locked fields.
+pub fn apply_metadata_overrides(conn: &Connection, media_id: i64) -> Result<usize> {
+    let overrides = get_metadata_overrides(conn, media_id)?;
+    if overrides.is_empty() {
+        return Ok(0);
+    }
+    let media = load_media(conn, media_id)?;
+
+    let mut values = BTreeMap::new();
+    for o in &overrides {
+        let produced = media_field_value(&media, &o.field);
+        if produced != o.value {
+            conn.execute(
+                "UPDATE metadata_overrides SET scanned_json = ?3 WHERE media_id = ?1 AND field = ?2",
+                params![media_id, o.field, produced.to_string()],
+            )?;
+        }
+        values.insert(o.field.clone(), o.value.clone());
+    }
+    set_media_fields(conn, media_id, &values)?;
+    Ok(overrides.len())
+}
+
+fn get_override(conn: &Connection, media_id: i64, field: &str) -> Result<Option<MetadataOverride>> {
+    conn.query_row(
+        &format!("SELECT {} FROM metadata_overrides WHERE media_id = ?1 AND field = ?2", OVERRIDE_COLUMNS),
+        params![media_id, field],
+        override_from_row,
+    )
+    .optional()
+}
+
+/// Lock a field to a value; a newly locked field remembers `scanned`
+fn put_override(conn: &Connection, media_id: i64, field: &str, value: &Value, scanned: &Value) -> Result<()> {
+    conn.execute(
+        "INSERT INTO metadata_overrides (media_id, field, value_json, scanned_json, updated_at)
+         VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP)
+         ON CONFLICT(media_id, field) DO UPDATE SET
+            value_json = excluded.value_json,
+            updated_at = excluded.updated_at",
+        params![media_id, field, value.to_string(), scanned.to_string()],
+    )?;
+    Ok(())
+}
+
+fn remove_override(conn: &Connection, media_id: i64, field: &str) -> Result<()> {
+    conn.execute("DELETE FROM metadata_overrides WHERE media_id = ?1 AND field = ?2", params![media_id, field])?;
+    Ok(())
+}
+
+fn sync_lock_flag(conn: &Connection, media_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE media_files SET is_locked = EXISTS (SELECT 1 FROM metadata_overrides o WHERE o.media_id = media_files.id)
+         WHERE id = ?1",
+        params![media_id],
+    )?;
+    Ok(())
+}
+
+fn record_edit(
+    conn: &Connection,
+    media_id: i64,
+    field: &str,
+    action: &str,
+    (old_value, new_value): (&Value, &Value),
+    was_locked: bool,
+) -> Result<MetadataEdit> {
+    conn.execute(
+        "INSERT INTO metadata_edits (media_id, field, action, old_value_json, new_value_json, was_locked)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![media_id, field, action, old_value.to_string(), new_value.to_string(), was_locked],
+    )?;
+    conn.query_row(
+        &format!("SELECT {} FROM metadata_edits WHERE id = ?1", EDIT_COLUMNS),
+        params![conn.last_insert_rowid()],
+        edit_from_row,
+    )
+}
+
+/// Set fields by hand, locking each one against scans and providers. Only
+/// the given fields change; null clears a field and keeps it locked empty.
+/// Returns the recorded edits, skipping fields already locked to the value.
+pub fn edit_media_metadata(conn: &Connection, media_id: i64, changes: &BTreeMap<String, Value>) -> Result<Vec<MetadataEdit>> {
+    let media = load_media(conn, media_id)?;
+    // Bulk edits run inside their own transaction
+    let tx = if conn.is_autocommit() { Some(conn.unchecked_transaction()?) } else { None };
+
+    let mut edits = Vec::new();
+    for (field, value) in changes {
+        let current = media_field_value(&media, field);
+        let existing = get_override(conn, media_id, field)?;
+        if existing.is_some() && current == *value {
+            continue;
+        }
+        // While unlocked, the current value is what the scan produced
+        let scanned = existing.as_ref().map(|o| o.scanned.clone()).unwrap_or_else(|| current.clone());
+        put_override(conn, media_id, field, value, &scanned)?;
+        edits.push(record_edit(conn, media_id, field, "edit", (&current, value), existing.is_some())?);
+    }
+    set_media_fields(conn, media_id, changes)?;
+    sync_lock_flag(conn, media_id)?;
+    if let Some(tx) = tx {
+        tx.commit()?;
+    }
+    Ok(edits)
+}
+
+/// Lock a field at its current value, or unlock it, keeping the value until
+/// the next scan or refresh. Returns None when the field is already so.
+pub fn set_metadata_field_lock(conn: &Connection, media_id: i64, field: &str, locked: bool) -> Result<Option<MetadataEdit>> {
+    let current = media_field_value(&load_media(conn, media_id)?, field);
+    let was_locked = get_override(conn, media_id, field)?.is_some();
+    if was_locked == locked {
+        return Ok(None);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    if locked {
+        put_override(&tx, media_id, field, &current, &current)?;
+    } else {
+        remove_override(&tx, media_id, field)?;
+    }
+    let action = if locked { "lock" } else { "unlock" };
+    let edit = record_edit(&tx, media_id, field, action, (&current, &current), was_locked)?;
+    sync_lock_flag(&tx, media_id)?;
+    tx.commit()?;
+    Ok(Some(edit))
+}
+
+/// Unlock a field and restore the value scans and providers last produced.
+/// Returns None when the field isn't locked.
+pub fn revert_metadata_field(conn: &Connection, media_id: i64, field: &str) -> Result<Option<MetadataEdit>> {
+    let current = media_field_value(&load_media(conn, media_id)?, field);
+    let Some(existing) = get_override(conn, media_id, field)? else {
+        return Ok(None);
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    remove_override(&tx, media_id, field)?;
+    set_media_fields(&tx, media_id, &BTreeMap::from([(field.to_string(), existing.scanned.clone())]))?;
+    let edit = record_edit(&tx, media_id, field, "revert", (&current, &existing.scanned), true)?;
+    sync_lock_flag(&tx, media_id)?;
+    tx.commit()?;
+    Ok(Some(edit))
+}
+
+/// Undo the latest change to a field that hasn't been undone yet, restoring
+/// its value and lock. A field that was unlocked before the change gets the
+/// latest scanned value back. Returns None when there's nothing to undo.
+pub fn undo_metadata_edit(conn: &Connection, media_id: i64, field: &str) -> Result<Option<MetadataEdit>> {
+    let current = media_field_value(&load_media(conn, media_id)?, field);
+    let latest = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM metadata_edits
+                 WHERE media_id = ?1 AND field = ?2 AND action != 'undo' AND undone = 0
+                 ORDER BY id DESC LIMIT 1",
+                EDIT_COLUMNS
+            ),
+            params![media_id, field],
+            edit_from_row,
+        )
+        .optional()?;
+    let Some(latest) = latest else {
+        return Ok(None);
+    };
+    let existing = get_override(conn, media_id, field)?;
+    let was_locked = existing.is_some();
+
+    let tx = conn.unchecked_transaction()?;
+    let restored = if latest.was_locked {
+        let scanned = existing.as_ref().map(|o| o.scanned.clone()).unwrap_or_else(|| current.clone());
+        put_override(&tx, media_id, field, &latest.old_value, &scanned)?;
+        latest.old_value.clone()
+    } else {
+        remove_override(&tx, media_id, field)?;
+        existing.map(|o| o.scanned).unwrap_or_else(|| latest.old_value.clone())
+    };
+    set_media_fields(&tx, media_id, &BTreeMap::from([(field.to_string(), restored.clone())]))?;
+    tx.execute("UPDATE metadata_edits SET undone = 1 WHERE id = ?1", params![latest.id])?;
+    let edit = record_edit(&tx, media_id, field, "undo", (&current, &restored), was_locked)?;
+    sync_lock_flag(&tx, media_id)?;
+    tx.commit()?;
+    Ok(Some(edit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use crate::db::operations::upsert_media_file;
+
+    fn field(conn: &Connection, field: &str) -> Value {
+        media_field_value(&load_media(conn, 1).unwrap(), field)
+    }
+
+    #[test]
+    fn test_locked_fields_survive_rescans_and_revert() {
+        let conn = init_db().unwrap();
+        conn.execute(
+            "INSERT INTO media_files (file_path, file_hash, file_name, file_size, media_type, title, year, metadata_json, last_modified)
+             VALUES ('/m/alien.mkv', 'hash', 'alien.mkv', 1, 'movie', 'alien', 1979, '{\"overview\":\"Scanned.\"}', datetime('now'))",
+            [],
+        )
+        .unwrap();
+
+        // Only the given fields change
+        let changes = BTreeMap::from([("title".to_string(), json!("Alien")), ("overview".to_string(), json!("Edited."))]);
+        assert_eq!(edit_media_metadata(&conn, 1, &changes).unwrap().len(), 2);
+        assert_eq!((field(&conn, "title"), field(&conn, "year")), (json!("Alien"), json!(1979)));
+        assert!(load_media(&conn, 1).unwrap().is_locked);
+        assert!(edit_media_metadata(&conn, 1, &changes).unwrap().is_empty());
+
+        // A rescan updates unlocked fields and the scanned values of locked ones
+        let mut scanned = load_media(&conn, 1).unwrap();
+        scanned.title = Some("alien.1979".to_string());
+        scanned.year = Some(1980);
+        scanned.metadata_json = Some("{\"overview\":\"Rescanned.\"}".to_string());
+        scanned.media_type = crate::db::models::MediaType::Video;
+        upsert_media_file(&conn, &scanned).unwrap();
+        assert_eq!((field(&conn, "title"), field(&conn, "year")), (json!("Alien"), json!(1980)));
+        assert_eq!(load_media(&conn, 1).unwrap().media_type.as_str(), "video");
+        assert_eq!(field(&conn, "overview"), json!("Edited."));
+        let overrides = get_metadata_overrides(&conn, 1).unwrap();
+        assert_eq!(overrides[1].field, "title");
+        assert_eq!(overrides[1].scanned, json!("alien.1979"));
+
+        let edit = revert_metadata_field(&conn, 1, "overview").unwrap().unwrap();
+        assert_eq!(edit.new_value, json!("Rescanned."));
+        assert_eq!(field(&conn, "overview"), json!("Rescanned."));
+        assert!(revert_metadata_field(&conn, 1, "overview").unwrap().is_none());
+
+        // Unlocking keeps the value; the file stays locked while title is
+        set_metadata_field_lock(&conn, 1, "year", true).unwrap().unwrap();
+        set_metadata_field_lock(&conn, 1, "year", false).unwrap().unwrap();
+        assert_eq!(field(&conn, "year"), json!(1980));
+        assert!(load_media(&conn, 1).unwrap().is_locked);
+
+        let actions: Vec<String> = get_metadata_edits(&conn, 1).unwrap().into_iter().map(|e| e.action).collect();
+        assert_eq!(actions, vec!["unlock", "lock", "revert", "edit", "edit"]);
+    }
+
+    #[test]
+    fn test_undo_steps_back_through_edits() {
+        let conn = init_db().unwrap();
+        conn.execute(
+            "INSERT INTO media_files (file_path, file_hash, file_name, file_size, media_type, title, last_modified)
+             VALUES ('/m/heat.mkv', 'hash', 'heat.mkv', 1, 'movie', 'heat', datetime('now'))",
+            [],
+        )
+        .unwrap();
+        let edit = |title: &str| {
+            edit_media_metadata(&conn, 1, &BTreeMap::from([("title".to_string(), json!(title))])).unwrap();
+        };
+        edit("Heat");
+        edit("Heat (1995)");
+        revert_metadata_field(&conn, 1, "title").unwrap();
+        assert_eq!(field(&conn, "title"), json!("heat"));
+
+        undo_metadata_edit(&conn, 1, "title").unwrap().unwrap();
+        assert_eq!(field(&conn, "title"), json!("Heat (1995)"));
+        undo_metadata_edit(&conn, 1, "title").unwrap().unwrap();
+        assert_eq!(field(&conn, "title"), json!("Heat"));
+        undo_metadata_edit(&conn, 1, "title").unwrap().unwrap();
+        assert_eq!(field(&conn, "title"), json!("heat"));
+        assert!(!load_media(&conn, 1).unwrap().is_locked);
+        assert!(undo_metadata_edit(&conn, 1, "title").unwrap().is_none());
+    }
+}
@@ -1,5 +1,14 @@
 use rusqlite::{Connection, Result};
-use super::schema::{CORE_SCHEMA, TMDB_SCHEMA};
+use super::schema::{
+    CORE_SCHEMA, TMDB_SCHEMA, SEARCH_SCHEMA, SEARCH_BACKFILL, LISTING_SCHEMA,
+    PLAYLIST_RULE_GROUPS_SCHEMA, SMART_PLAYLIST_SETTINGS_SCHEMA, PLAY_QUEUE_SCHEMA,
+    ITEM_ORDERING_SCHEMA, AUTO_COLLECTIONS_SCHEMA, TMDB_MATCH_CANDIDATES_SCHEMA,
+    TMDB_HTTP_CACHE_SCHEMA, TMDB_FACETS_SCHEMA, TMDB_FACETS_BACKFILL, RECOMMENDATIONS_SCHEMA,
+    TMDB_EPISODE_LINKS_SCHEMA, METADATA_PROVENANCE_SCHEMA,
+    METADATA_OVERRIDES_SCHEMA, METADATA_OVERRIDES_BACKFILL, SUBTITLE_TIMING_SCHEMA,
+    SUBTITLE_FLAGS_SCHEMA, AUTO_COLLECTION_SOURCES_SCHEMA,
+};
+use super::ordering::backfill_sort_keys;
 
 /// Run all database migrations
 pub fn run_migrations(conn: &Connection) -> Result<()> {
@@ -15,6 +24,74 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
     if current_version < 2 {
         migrate_v2(conn)?;
     }
+
+    if current_version < 3 {
+        migrate_v3(conn)?;
+    }
+
+    if current_version < 4 {
+        migrate_v4(conn)?;
+    }
+
+    if current_version < 5 {
+        migrate_v5(conn)?;
+    }
+
+    if current_version < 6 {
+        migrate_v6(conn)?;
+    }
+
+    if current_version < 7 {
+        migrate_v7(conn)?;
+    }
+
+    if current_version < 8 {
+        migrate_v8(conn)?;
+    }
+
+    if current_version < 9 {
+        migrate_v9(conn)?;
+    }
+
+    if current_version < 10 {
+        migrate_v10(conn)?;
+    }
+
+    if current_version < 11 {
+        migrate_v11(conn)?;
+    }
+
+    if current_version < 12 {
+        migrate_v12(conn)?;
+    }
+
+    if current_version < 13 {
+        migrate_v13(conn)?;
+    }
+
+    if current_version < 14 {
+        migrate_v14(conn)?;
+    }
+
+    if current_version < 15 {
+        migrate_v15(conn)?;
+    }
+
+    if current_version < 16 {
+        migrate_v16(conn)?;
+    }
+
+    if current_version < 17 {
+        migrate_v17(conn)?;
+    }
+
+    if current_version < 18 {
+        migrate_v18(conn)?;
+    }
+
+    if current_version < 19 {
+        migrate_v19(conn)?;
+    }
     
     Ok(())
 }
@@ -89,6 +166,217 @@ fn migrate_v2(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
+/// Migration v3: Full-text search index
+fn migrate_v3(conn: &Connection) -> Result<()> {
+    println!("Running migration: v3 - Full-text search index");
+
+    conn.execute_batch(SEARCH_SCHEMA)?;
+    conn.execute_batch(SEARCH_BACKFILL)?;
+
+    set_schema_version(conn, 3)?;
+
+    println!("Migration v3 completed successfully");
+    Ok(())
+}
+
+/// Migration v4: Width/height columns and sort indexes for listings
+fn migrate_v4(conn: &Connection) -> Result<()> {
+    println!("Running migration: v4 - Resolution dimensions and sort indexes");
+
+    conn.execute_batch(LISTING_SCHEMA)?;
+
+    set_schema_version(conn, 4)?;
+
+    println!("Migration v4 completed successfully");
+    Ok(())
+}
+
+/// Migration v5: Nested smart playlist rule groups
+fn migrate_v5(conn: &Connection) -> Result<()> {
+    println!("Running migration: v5 - Smart playlist rule groups");
+
+    conn.execute_batch(PLAYLIST_RULE_GROUPS_SCHEMA)?;
+
+    set_schema_version(conn, 5)?;
+
+    println!("Migration v5 completed successfully");
+    Ok(())
+}
+
+/// Migration v6: Smart playlist sort order, limit and shuffle seed
+fn migrate_v6(conn: &Connection) -> Result<()> {
+    println!("Running migration: v6 - Smart playlist sorting and limits");
+
+    conn.execute_batch(SMART_PLAYLIST_SETTINGS_SCHEMA)?;
+
+    set_schema_version(conn, 6)?;
+
+    println!("Migration v6 completed successfully");
+    Ok(())
+}
+
+/// Migration v7: Persistent play queue
+fn migrate_v7(conn: &Connection) -> Result<()> {
+    println!("Running migration: v7 - Play queue");
+
+    conn.execute_batch(PLAY_QUEUE_SCHEMA)?;
+
+    set_schema_version(conn, 7)?;
+
+    println!("Migration v7 completed successfully");
+    Ok(())
+}
+
+/// Migration v8: Sort keys for playlist and collection items
+fn migrate_v8(conn: &Connection) -> Result<()> {
+    println!("Running migration: v8 - Playlist and collection item ordering");
+
+    conn.execute_batch(ITEM_ORDERING_SCHEMA)?;
+    backfill_sort_keys(conn)?;
+
+    set_schema_version(conn, 8)?;
+
+    println!("Migration v8 completed successfully");
+    Ok(())
+}
+
+/// Migration v9: Auto-generated collections
+fn migrate_v9(conn: &Connection) -> Result<()> {
+    println!("Running migration: v9 - Auto-generated collections");
+
+    conn.execute_batch(AUTO_COLLECTIONS_SCHEMA)?;
+
+    set_schema_version(conn, 9)?;
+
+    println!("Migration v9 completed successfully");
+    Ok(())
+}
+
+/// Migration v10: TMDB match review queue
+fn migrate_v10(conn: &Connection) -> Result<()> {
+    println!("Running migration: v10 - TMDB match candidates");
+
+    conn.execute_batch(TMDB_MATCH_CANDIDATES_SCHEMA)?;
+
+    set_schema_version(conn, 10)?;
+
+    println!("Migration v10 completed successfully");
+    Ok(())
+}
+
+/// Migration v11: TMDB response cache, and rebuilt TMDB tables with working foreign keys
+fn migrate_v11(conn: &Connection) -> Result<()> {
+    println!("Running migration: v11 - TMDB HTTP cache and foreign key fixes");
+
+    conn.execute_batch(TMDB_HTTP_CACHE_SCHEMA)?;
+
+    set_schema_version(conn, 11)?;
+
+    println!("Migration v11 completed successfully");
+    Ok(())
+}
+
+/// Migration v12: genre, keyword, country and language facet tables
+fn migrate_v12(conn: &Connection) -> Result<()> {
+    println!("Running migration: v12 - TMDB facets");
+
+    conn.execute_batch(TMDB_FACETS_SCHEMA)?;
+    conn.execute_batch(TMDB_FACETS_BACKFILL)?;
+
+    set_schema_version(conn, 12)?;
+
+    println!("Migration v12 completed successfully");
+    Ok(())
+}
+
+/// Migration v13: stored recommendation ranking
+fn migrate_v13(conn: &Connection) -> Result<()> {
+    println!("Running migration: v13 - Recommendations");
+
+    conn.execute_batch(RECOMMENDATIONS_SCHEMA)?;
+
+    set_schema_version(conn, 13)?;
+
+    println!("Migration v13 completed successfully");
+    Ok(())
+}
+
+/// Migration v14: episode files linked to TMDB episodes
+fn migrate_v14(conn: &Connection) -> Result<()> {
+    println!("Running migration: v14 - TMDB episode links");
+
+    conn.execute_batch(TMDB_EPISODE_LINKS_SCHEMA)?;
+
+    set_schema_version(conn, 14)?;
+
+    println!("Migration v14 completed successfully");
+    Ok(())
+}
+
+/// Migration v15: which metadata provider supplied each stored field
+fn migrate_v15(conn: &Connection) -> Result<()> {
+    println!("Running migration: v15 - Metadata provenance");
+
+    conn.execute_batch(METADATA_PROVENANCE_SCHEMA)?;
+
+    set_schema_version(conn, 15)?;
+
+    println!("Migration v15 completed successfully");
+    Ok(())
+}
+
+/// Migration v16: per-field metadata locks and edit history, replacing the
+/// whole-file lock
+fn migrate_v16(conn: &Connection) -> Result<()> {
+    println!("Running migration: v16 - Metadata overrides and edit history");
+
+    conn.execute_batch(METADATA_OVERRIDES_SCHEMA)?;
+    conn.execute_batch(METADATA_OVERRIDES_BACKFILL)?;
+
+    set_schema_version(conn, 16)?;
+
+    println!("Migration v16 completed successfully");
+    Ok(())
+}
+
+/// Migration v17: per-track subtitle timing corrections
+fn migrate_v17(conn: &Connection) -> Result<()> {
+    println!("Running migration: v17 - Subtitle timing");
+
+    conn.execute_batch(SUBTITLE_TIMING_SCHEMA)?;
+
+    set_schema_version(conn, 17)?;
+
+    println!("Migration v17 completed successfully");
+    Ok(())
+}
+
+/// Migration v18: subtitle forced/SDH/default flags, and dropping duplicate
+/// external tracks left by earlier rescans
+fn migrate_v18(conn: &Connection) -> Result<()> {
+    println!("Running migration: v18 - Subtitle flags");
+
+    conn.execute_batch(SUBTITLE_FLAGS_SCHEMA)?;
+
+    set_schema_version(conn, 18)?;
+
+    println!("Migration v18 completed successfully");
+    Ok(())
+}
+
+/// Migration v19: remember which collection items and deleted collections
+/// belong to the franchise sync
+fn migrate_v19(conn: &Connection) -> Result<()> {
+    println!("Running migration: v19 - Auto collection sources");
+
+    conn.execute_batch(AUTO_COLLECTION_SOURCES_SCHEMA)?;
+
+    set_schema_version(conn, 19)?;
+
+    println!("Migration v19 completed successfully");
+    Ok(())
+}
+
 /// Insert default application settings
 fn insert_default_settings(conn: &Connection) -> Result<()> {
     let default_settings = vec![
@@ -119,6 +407,7 @@ fn insert_default_settings(conn: &Connection) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::super::schema::SCHEMA_VERSION;
     use rusqlite::Connection;
     
     #[test]
@@ -133,7 +422,7 @@ mod tests {
             |row| row.get(0),
         ).unwrap();
         
-        assert_eq!(version, 1);
+        assert_eq!(version, SCHEMA_VERSION);
         
         // Verify some tables exist
         let tables = vec![
@@ -169,4 +458,30 @@ mod tests {
         
         assert_eq!(theme, "dark");
     }
+
+    #[test]
+    fn test_whole_file_locks_become_field_locks() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO media_files (file_path, file_hash, file_name, file_size, media_type, title, year, metadata_json, is_locked, last_modified)
+             VALUES ('/m/alien.mkv', 'hash', 'alien.mkv', 1, 'movie', 'Alien', 1979,
+                     '{\"overview\":\"Edited.\",\"genres\":[\"Horror\"]}', 1, datetime('now'))",
+            [],
+        ).unwrap();
+        conn.execute_batch(METADATA_OVERRIDES_BACKFILL).unwrap();
+
+        let mut stmt = conn.prepare("SELECT field, value_json FROM metadata_overrides ORDER BY field").unwrap();
+        let overrides: Vec<(String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(overrides, vec![
+            ("genres".to_string(), "[\"Horror\"]".to_string()),
+            ("overview".to_string(), "\"Edited.\"".to_string()),
+            ("title".to_string(), "\"Alien\"".to_string()),
+            ("year".to_string(), "1979".to_string()),
+        ]);
+    }
 }
@@ -8,9 +8,22 @@ pub mod playlists;
 pub mod collections;
 pub mod audio_tracks;
 pub mod subtitles;
+pub mod search;
+pub mod query_parser;
+pub mod smart_rules;
+pub mod queue;
+pub mod ordering;
+pub mod auto_collections;
+pub mod people;
+pub mod facets;
+pub mod recommendations;
+pub mod metadata_overrides;
+pub mod bulk_edit;
 
 #[cfg(test)]
 mod tests;
+#[cfg(test)]
+pub mod test_support;
 
 pub use connection::Database;
 pub use models::*;
@@ -19,3 +32,12 @@ pub use playback::*;
 pub use playlists::*;
 pub use collections::*;
 pub use subtitles::*;
+pub use search::*;
+pub use queue::*;
+pub use auto_collections::*;
+pub use people::*;
+pub use facets::{FacetCount, MediaFacets, get_media_facets};
+pub use recommendations::*;
+pub use metadata_overrides::*;
+pub use bulk_edit::*;
+pub use smart_rules::RuleError;
@@ -50,6 +50,21 @@ pub struct PlaylistRule {
     pub rule_type: String,
     pub operator: String,
     pub value: String,
+    /// Group rule this rule belongs to; None for top-level rules
+    #[serde(default)]
+    pub parent_id: Option<i64>,
+}
+
+/// Ordering and size of a smart playlist
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SmartPlaylistSettings {
+    /// Defaults to title A-Z
+    pub sort: Option<SortKey>,
+    pub descending: Option<bool>,
+    /// Keep only the first N items after sorting
+    pub limit: Option<u32>,
+    /// When set, items are shuffled in an order that stays stable for this seed
+    pub random_seed: Option<i64>,
 }
 
 /// Media file record
@@ -207,7 +222,7 @@ pub struct Setting {
 }
 
 /// Filter criteria for media search
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FilterCriteria {
     pub min_year: Option<i32>,
     pub max_year: Option<i32>,
@@ -216,4 +231,80 @@ pub struct FilterCriteria {
     pub resolutions: Option<Vec<String>>, // "4k", "1080p", "720p", "sd"
     pub codecs: Option<Vec<String>>,
     pub media_types: Option<Vec<String>>,
+    /// TMDB facets of the matched title; a file matches any of the listed values
+    pub genres: Option<Vec<String>>,
+    pub keywords: Option<Vec<String>>,
+    /// ISO 3166-1 production country codes
+    pub countries: Option<Vec<String>>,
+    /// ISO 639-1 spoken language codes
+    pub languages: Option<Vec<String>>,
+    /// TMDB rating range (0-10)
+    pub min_rating: Option<f64>,
+    pub max_rating: Option<f64>,
+    /// Search query language expression, see `db::query_parser`
+    pub query: Option<String>,
+}
+
+/// Sort order for media listings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    /// Search rank; behaves like `Added` outside of search
+    Relevance,
+    Title,
+    Year,
+    #[default]
+    Added,
+    Size,
+    Duration,
+    LastPlayed,
+    /// Best first in the last computed recommendation ranking
+    Recommended,
+}
+
+impl SortKey {
+    /// Title sorts A-Z by default, everything else newest/largest first
+    pub fn default_descending(&self) -> bool {
+        !matches!(self, SortKey::Title)
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            SortKey::Relevance => "relevance",
+            SortKey::Title => "title",
+            SortKey::Year => "year",
+            SortKey::Added => "added",
+            SortKey::Size => "size",
+            SortKey::Duration => "duration",
+            SortKey::LastPlayed => "last_played",
+            SortKey::Recommended => "recommended",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "relevance" => Some(SortKey::Relevance),
+            "title" => Some(SortKey::Title),
+            "year" => Some(SortKey::Year),
+            "added" => Some(SortKey::Added),
+            "size" => Some(SortKey::Size),
+            "duration" => Some(SortKey::Duration),
+            "last_played" => Some(SortKey::LastPlayed),
+            "recommended" => Some(SortKey::Recommended),
+            _ => None,
+        }
+    }
+}
+
+/// Sorting and keyset pagination for listing commands.
+///
+/// To fetch the next page, pass the id of the last item received as
+/// `after_id` with the same sort settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PageRequest {
+    pub sort: Option<SortKey>,
+    pub descending: Option<bool>,
+    pub limit: Option<usize>,
+    pub after_id: Option<i64>,
 }
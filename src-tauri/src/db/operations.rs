@@ -1,5 +1,48 @@
-use rusqlite::{Connection, Result, params};
-use crate::db::models::{MediaFile, MediaType};
+use rusqlite::{Connection, Result, Row, ToSql, params};
+use crate::db::models::{MediaFile, MediaType, PageRequest, SortKey};
+use crate::db::metadata_overrides::{apply_metadata_overrides, edit_media_metadata, set_media_fields};
+use crate::db::recommendations::RECOMMENDATION_SCORE_SQL;
+use std::collections::BTreeMap;
+
+/// Column list matching `media_file_from_row`, for queries that alias media_files as `m`
+pub(crate) const MEDIA_COLUMNS: &str = "
+    m.id, m.file_path, m.file_hash, m.file_name, m.file_size, m.media_type,
+    m.duration, m.codec, m.resolution, m.bitrate, m.framerate,
+    m.audio_codec, m.audio_channels,
+    m.title, m.year, m.season_number, m.episode_number,
+    m.indexed_at, m.last_modified, m.is_deleted, m.metadata_json, m.is_locked";
+
+/// Map a row selected with `MEDIA_COLUMNS` to a MediaFile
+pub(crate) fn media_file_from_row(row: &Row) -> Result<MediaFile> {
+    let media_type_str: String = row.get(5)?;
+    let media_type = MediaType::from_str(&media_type_str)
+        .unwrap_or(MediaType::Video);
+
+    Ok(MediaFile {
+        id: Some(row.get(0)?),
+        file_path: row.get(1)?,
+        file_hash: row.get(2)?,
+        file_name: row.get(3)?,
+        file_size: row.get(4)?,
+        media_type,
+        duration: row.get(6)?,
+        codec: row.get(7)?,
+        resolution: row.get(8)?,
+        bitrate: row.get(9)?,
+        framerate: row.get(10)?,
+        audio_codec: row.get(11)?,
+        audio_channels: row.get(12)?,
+        title: row.get(13)?,
+        year: row.get(14)?,
+        season_number: row.get(15)?,
+        episode_number: row.get(16)?,
+        indexed_at: row.get(17)?,
+        last_modified: row.get(18)?,
+        is_deleted: row.get::<_, i32>(19)? != 0,
+        metadata_json: row.get(20)?,
+        is_locked: row.get::<_, bool>(21).unwrap_or(false),
+    })
+}
 
 /// Insert or update a media file in the database
 pub fn upsert_media_file(conn: &Connection, media: &MediaFile) -> Result<i64> {
@@ -24,6 +67,7 @@ pub fn upsert_media_file(conn: &Connection, media: &MediaFile) -> Result<i64> {
         ON CONFLICT(file_path) DO UPDATE SET
             file_hash = excluded.file_hash,
             file_size = excluded.file_size,
+            media_type = excluded.media_type,
             duration = excluded.duration,
             codec = excluded.codec,
             resolution = excluded.resolution,
@@ -31,13 +75,13 @@ pub fn upsert_media_file(conn: &Connection, media: &MediaFile) -> Result<i64> {
             framerate = excluded.framerate,
             audio_codec = excluded.audio_codec,
             audio_channels = excluded.audio_channels,
-            title = CASE WHEN media_files.is_locked = 1 THEN media_files.title ELSE excluded.title END,
-            year = CASE WHEN media_files.is_locked = 1 THEN media_files.year ELSE excluded.year END,
-            season_number = CASE WHEN media_files.is_locked = 1 THEN media_files.season_number ELSE excluded.season_number END,
-            episode_number = CASE WHEN media_files.is_locked = 1 THEN media_files.episode_number ELSE excluded.episode_number END,
+            title = excluded.title,
+            year = excluded.year,
+            season_number = excluded.season_number,
+            episode_number = excluded.episode_number,
             last_modified = excluded.last_modified,
             is_deleted = 0,
-            metadata_json = CASE WHEN media_files.is_locked = 1 THEN media_files.metadata_json ELSE excluded.metadata_json END",
+            metadata_json = excluded.metadata_json",
         params![
             &media.file_path,
             &media.file_hash,
@@ -62,55 +106,59 @@ pub fn upsert_media_file(conn: &Connection, media: &MediaFile) -> Result<i64> {
         ],
     )?;
 
-    Ok(conn.last_insert_rowid())
+    let (media_id, is_locked): (i64, bool) = conn.query_row(
+        "SELECT id, is_locked FROM media_files WHERE file_path = ?1",
+        params![&media.file_path],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    restore_provider_fields(conn, media_id)?;
+    // Fields the user locked get their values back over the scanned ones
+    if is_locked {
+        apply_metadata_overrides(conn, media_id)?;
+    }
+
+    Ok(media_id)
+}
+
+/// Write back the values the metadata provider chain last stored, so a
+/// rescan's filename-based title and empty metadata don't replace them
+fn restore_provider_fields(conn: &Connection, media_id: i64) -> Result<()> {
+    let mut stmt = conn.prepare("SELECT field, value_json FROM metadata_provenance WHERE media_id = ?1")?;
+    let values = stmt
+        .query_map(params![media_id], |row| {
+            let value: String = row.get(1)?;
+            Ok((row.get(0)?, serde_json::from_str(&value).unwrap_or(serde_json::Value::Null)))
+        })?
+        .collect::<Result<BTreeMap<String, serde_json::Value>>>()?;
+    if !values.is_empty() {
+        set_media_fields(conn, media_id, &values)?;
+    }
+    Ok(())
 }
 
 /// Get all media files
 pub fn get_all_media_files(conn: &Connection) -> Result<Vec<MediaFile>> {
-    let mut stmt = conn.prepare(
-        "SELECT 
-            id, file_path, file_hash, file_name, file_size, media_type,
-            duration, codec, resolution, bitrate, framerate,
-            audio_codec, audio_channels,
-            title, year, season_number, episode_number,
-            indexed_at, last_modified, is_deleted, metadata_json, is_locked
-        FROM media_files
-        WHERE is_deleted = 0
-        ORDER BY indexed_at DESC"
-    )?;
+    list_media(conn, &PageRequest::default())
+}
 
-    let media_iter = stmt.query_map([], |row| {
-        let media_type_str: String = row.get(5)?;
-        let media_type = MediaType::from_str(&media_type_str)
-            .unwrap_or(MediaType::Video);
-        
-        Ok(MediaFile {
-            id: Some(row.get(0)?),
-            file_path: row.get(1)?,
-            file_hash: row.get(2)?,
-            file_name: row.get(3)?,
-            file_size: row.get(4)?,
-            media_type,
-            duration: row.get(6)?,
-            codec: row.get(7)?,
-            resolution: row.get(8)?,
-            bitrate: row.get(9)?,
-            framerate: row.get(10)?,
-            audio_codec: row.get(11)?,
-            audio_channels: row.get(12)?,
-            title: row.get(13)?,
-            year: row.get(14)?,
-            season_number: row.get(15)?,
-            episode_number: row.get(16)?,
-            indexed_at: row.get(17)?,
-            last_modified: row.get(18)?,
-            is_deleted: row.get::<_, i32>(19)? != 0,
-            metadata_json: row.get(20)?,
-            is_locked: row.get::<_, bool>(21).unwrap_or(false),
-        })
-    })?;
+/// Get one page of media files
+pub fn list_media(conn: &Connection, page: &PageRequest) -> Result<Vec<MediaFile>> {
+    query_media_page(conn, "", Vec::new(), page)
+}
 
-    media_iter.collect()
+/// Get a single media file by ID
+pub fn get_media_file_by_id(conn: &Connection, media_id: i64) -> Result<Option<MediaFile>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM media_files m WHERE m.id = ?1",
+        MEDIA_COLUMNS
+    ))?;
+
+    let mut rows = stmt.query(params![media_id])?;
+
+    match rows.next()? {
+        Some(row) => Ok(Some(media_file_from_row(row)?)),
+        None => Ok(None),
+    }
 }
 
 #[cfg(test)]
@@ -120,137 +168,171 @@ pub fn add_media_file(conn: &Connection, media: &MediaFile) -> Result<i64> {
 }
 
 /// Get media files by type
-pub fn get_media_by_type(conn: &Connection, media_type: MediaType) -> Result<Vec<MediaFile>> {
-    let media_type_str = media_type.as_str();
-    
-    let mut stmt = conn.prepare(
-        "SELECT 
-            id, file_path, file_hash, file_name, file_size, media_type,
-            duration, codec, resolution, bitrate, framerate,
-            audio_codec, audio_channels,
-            title, year, season_number, episode_number,
-            indexed_at, last_modified, is_deleted, metadata_json, is_locked
-        FROM media_files
-        WHERE is_deleted = 0 AND media_type = ?1
-        ORDER BY indexed_at DESC"
-    )?;
+pub fn get_media_by_type(conn: &Connection, media_type: MediaType, page: &PageRequest) -> Result<Vec<MediaFile>> {
+    let params: Vec<Box<dyn ToSql>> = vec![Box::new(media_type.as_str().to_string())];
+    query_media_page(conn, " AND m.media_type = ?1", params, page)
+}
+
+/// SQL expression for a sort key over media_files aliased as `m`
+pub(crate) fn sort_expression(key: SortKey) -> &'static str {
+    match key {
+        SortKey::Relevance | SortKey::Added => "m.indexed_at",
+        SortKey::Title => "COALESCE(m.title, m.file_name) COLLATE NOCASE",
+        SortKey::Year => "COALESCE(m.year, 0)",
+        SortKey::Size => "m.file_size",
+        SortKey::Duration => "COALESCE(m.duration, 0)",
+        SortKey::LastPlayed => {
+            "COALESCE((SELECT ps.last_played_at FROM playback_state ps WHERE ps.media_id = m.id), '')"
+        }
+        SortKey::Recommended => RECOMMENDATION_SCORE_SQL,
+    }
+}
 
-    let media_iter = stmt.query_map([media_type_str], |row| {
-        Ok(MediaFile {
-            id: Some(row.get(0)?),
-            file_path: row.get(1)?,
-            file_hash: row.get(2)?,
-            file_name: row.get(3)?,
-            file_size: row.get(4)?,
-            media_type: media_type.clone(),
-            duration: row.get(6)?,
-            codec: row.get(7)?,
-            resolution: row.get(8)?,
-            bitrate: row.get(9)?,
-            framerate: row.get(10)?,
-            audio_codec: row.get(11)?,
-            audio_channels: row.get(12)?,
-            title: row.get(13)?,
-            year: row.get(14)?,
-            season_number: row.get(15)?,
-            episode_number: row.get(16)?,
-            indexed_at: row.get(17)?,
-            last_modified: row.get(18)?,
-            is_deleted: row.get::<_, i32>(19)? != 0,
-            metadata_json: row.get(20)?,
-            is_locked: row.get::<_, bool>(21).unwrap_or(false),
-        })
-    })?;
+/// Append the keyset condition, ORDER BY and LIMIT for `page` to a query
+/// whose WHERE clause is still open.
+///
+/// `cursor_value` builds SQL yielding the sort value of the row whose id is
+/// bound to the given placeholder. Ties are broken by id so pages never
+/// overlap or skip rows.
+pub(crate) fn push_page_clause(
+    query: &mut String,
+    params: &mut Vec<Box<dyn ToSql>>,
+    page: &PageRequest,
+    sort_expr: &str,
+    cursor_value: impl Fn(&str) -> String,
+) {
+    let key = page.sort.unwrap_or_default();
+    let descending = page.descending.unwrap_or(key.default_descending());
+    let (comparison, direction) = if descending { ("<", "DESC") } else { (">", "ASC") };
+
+    if let Some(after_id) = page.after_id {
+        let placeholder = format!("?{}", params.len() + 1);
+        query.push_str(&format!(
+            " AND ({}, m.id) {} ({}, {})",
+            sort_expr,
+            comparison,
+            cursor_value(&placeholder),
+            placeholder
+        ));
+        params.push(Box::new(after_id));
+    }
 
-    media_iter.collect()
+    query.push_str(&format!(" ORDER BY {} {}, m.id {}", sort_expr, direction, direction));
+
+    if let Some(limit) = page.limit {
+        query.push_str(&format!(" LIMIT ?{}", params.len() + 1));
+        params.push(Box::new(limit as i64));
+    }
 }
 
-/// Search media files by title
-pub fn search_media(conn: &Connection, query: &str) -> Result<Vec<MediaFile>> {
-    let search_query = format!("%{}%", query);
-    
-    let mut stmt = conn.prepare(
-        "SELECT 
-            id, file_path, file_hash, file_name, file_size, media_type,
-            duration, codec, resolution, bitrate, framerate,
-            audio_codec, audio_channels,
-            title, year, season_number, episode_number,
-            indexed_at, last_modified, is_deleted, metadata_json, is_locked
-        FROM media_files
-        WHERE is_deleted = 0 AND (title LIKE ?1 OR file_name LIKE ?1)
-        ORDER BY indexed_at DESC"
-    )?;
+/// Run a media listing with extra WHERE `conditions` (starting with " AND")
+fn query_media_page(
+    conn: &Connection,
+    conditions: &str,
+    mut params: Vec<Box<dyn ToSql>>,
+    page: &PageRequest,
+) -> Result<Vec<MediaFile>> {
+    let mut query = format!(
+        "SELECT {} FROM media_files m WHERE m.is_deleted = 0{}",
+        MEDIA_COLUMNS, conditions
+    );
 
-    let media_iter = stmt.query_map([&search_query], |row| {
-        let media_type_str: String = row.get(5)?;
-        let media_type = MediaType::from_str(&media_type_str)
-            .unwrap_or(MediaType::Video);
-        
-        Ok(MediaFile {
-            id: Some(row.get(0)?),
-            file_path: row.get(1)?,
-            file_hash: row.get(2)?,
-            file_name: row.get(3)?,
-            file_size: row.get(4)?,
-            media_type,
-            duration: row.get(6)?,
-            codec: row.get(7)?,
-            resolution: row.get(8)?,
-            bitrate: row.get(9)?,
-            framerate: row.get(10)?,
-            audio_codec: row.get(11)?,
-            audio_channels: row.get(12)?,
-            title: row.get(13)?,
-            year: row.get(14)?,
-            season_number: row.get(15)?,
-            episode_number: row.get(16)?,
-            indexed_at: row.get(17)?,
-            last_modified: row.get(18)?,
-            is_deleted: row.get::<_, i32>(19)? != 0,
-            metadata_json: row.get(20)?,
-            is_locked: row.get::<_, bool>(21).unwrap_or(false),
-        })
-    })?;
+    let sort_expr = sort_expression(page.sort.unwrap_or_default());
+    push_page_clause(&mut query, &mut params, page, sort_expr, |cursor| {
+        format!("(SELECT {} FROM media_files m WHERE m.id = {})", sort_expr, cursor)
+    });
+
+    let mut stmt = conn.prepare(&query)?;
+    let params_refs: Vec<&dyn ToSql> = params.iter().map(|p| p.as_ref()).collect();
+    let media_iter = stmt.query_map(params_refs.as_slice(), media_file_from_row)?;
 
     media_iter.collect()
 }
 
+/// Resolution bucket of a media row, as its nominal height (2160 for 4K).
+///
+/// Width counts as much as height so that scope (3840x1600) and 4:3 encodes
+/// land in the expected bucket. NULL when the resolution is unknown.
+pub(crate) fn resolution_class_sql(alias: &str) -> String {
+    format!(
+        "CASE
+            WHEN {a}.width IS NULL OR {a}.height IS NULL THEN NULL
+            WHEN {a}.width >= 6000 OR {a}.height >= 3400 THEN 4320
+            WHEN {a}.width >= 3200 OR {a}.height >= 1800 THEN 2160
+            WHEN {a}.width >= 2200 OR {a}.height >= 1300 THEN 1440
+            WHEN {a}.width >= 1600 OR {a}.height >= 900 THEN 1080
+            WHEN {a}.width >= 1100 OR {a}.height >= 600 THEN 720
+            ELSE 480
+        END",
+        a = alias
+    )
+}
+
+/// Nominal height of a resolution bucket name ("4k", "1080p", "720p", "sd", ...)
+pub fn resolution_class(bucket: &str) -> Option<i64> {
+    let bucket = bucket.trim().to_lowercase();
+    let height = match bucket.as_str() {
+        "8k" | "4320p" => 4320,
+        "4k" | "uhd" | "2160p" => 2160,
+        "2k" | "qhd" | "1440p" => 1440,
+        "fhd" | "fullhd" | "1080p" | "1080i" => 1080,
+        "hd" | "720p" => 720,
+        "sd" | "480p" | "576p" => 480,
+        _ => bucket.trim_end_matches(['p', 'i']).parse::<i64>().ok()?,
+    };
+    Some(nominal_height(height))
+}
+
+/// Round a frame height down to its bucket's nominal height
+pub(crate) fn nominal_height(height: i64) -> i64 {
+    match height {
+        h if h >= 4320 => 4320,
+        h if h >= 2160 => 2160,
+        h if h >= 1440 => 1440,
+        h if h >= 1080 => 1080,
+        h if h >= 720 => 720,
+        _ => 480,
+    }
+}
+
 /// Filter media files by criteria
-pub fn filter_media(conn: &Connection, criteria: &crate::db::models::FilterCriteria) -> Result<Vec<MediaFile>> {
-    let mut query = String::from(
-        "SELECT
-            id, file_path, file_hash, file_name, file_size, media_type,
-            duration, codec, resolution, bitrate, framerate,
-            audio_codec, audio_channels,
-            title, year, season_number, episode_number,
-            indexed_at, last_modified, is_deleted, metadata_json, is_locked
-        FROM media_files
-        WHERE is_deleted = 0"
-    );
+pub fn filter_media(
+    conn: &Connection,
+    criteria: &crate::db::models::FilterCriteria,
+    page: &PageRequest,
+) -> std::result::Result<Vec<MediaFile>, crate::db::query_parser::FilterError> {
+    let (conditions, params) = filter_conditions(criteria)?;
+    Ok(query_media_page(conn, &conditions, params, page)?)
+}
 
+/// WHERE conditions (each starting with " AND") and parameters for `criteria`
+pub(crate) fn filter_conditions(
+    criteria: &crate::db::models::FilterCriteria,
+) -> std::result::Result<(String, Vec<Box<dyn rusqlite::ToSql>>), crate::db::query_parser::FilterError> {
+    let mut query = String::new();
     let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
     let mut param_idx = 1;
 
     // Year range
     if let Some(min_year) = criteria.min_year {
-        query.push_str(&format!(" AND year >= ?{}", param_idx));
+        query.push_str(&format!(" AND m.year >= ?{}", param_idx));
         params.push(Box::new(min_year));
         param_idx += 1;
     }
     if let Some(max_year) = criteria.max_year {
-        query.push_str(&format!(" AND year <= ?{}", param_idx));
+        query.push_str(&format!(" AND m.year <= ?{}", param_idx));
         params.push(Box::new(max_year));
         param_idx += 1;
     }
 
     // Duration range
     if let Some(min_duration) = criteria.min_duration {
-        query.push_str(&format!(" AND duration >= ?{}", param_idx));
+        query.push_str(&format!(" AND m.duration >= ?{}", param_idx));
         params.push(Box::new(min_duration));
         param_idx += 1;
     }
     if let Some(max_duration) = criteria.max_duration {
-        query.push_str(&format!(" AND duration <= ?{}", param_idx));
+        query.push_str(&format!(" AND m.duration <= ?{}", param_idx));
         params.push(Box::new(max_duration));
         param_idx += 1;
     }
@@ -258,7 +340,7 @@ pub fn filter_media(conn: &Connection, criteria: &crate::db::models::FilterCrite
     // Codecs
     if let Some(codecs) = &criteria.codecs {
         if !codecs.is_empty() {
-            query.push_str(" AND codec IN (");
+            query.push_str(" AND m.codec IN (");
             for (i, codec) in codecs.iter().enumerate() {
                 if i > 0 {
                     query.push_str(", ");
@@ -274,7 +356,7 @@ pub fn filter_media(conn: &Connection, criteria: &crate::db::models::FilterCrite
     // Media Types
     if let Some(types) = &criteria.media_types {
         if !types.is_empty() {
-            query.push_str(" AND media_type IN (");
+            query.push_str(" AND m.media_type IN (");
             for (i, mtype) in types.iter().enumerate() {
                 if i > 0 {
                     query.push_str(", ");
@@ -303,92 +385,64 @@ pub fn filter_media(conn: &Connection, criteria: &crate::db::models::FilterCrite
         }
     }
 
-    // Resolutions (this is trickier as it's text search usually)
+    // Resolution buckets, computed from the stored width/height
     if let Some(resolutions) = &criteria.resolutions {
-        if !resolutions.is_empty() {
-            query.push_str(" AND (");
-            for (i, res) in resolutions.iter().enumerate() {
+        let classes: Vec<i64> = resolutions.iter().filter_map(|r| resolution_class(r)).collect();
+        if !classes.is_empty() {
+            query.push_str(&format!(" AND {} IN (", resolution_class_sql("m")));
+            for (i, class) in classes.into_iter().enumerate() {
                 if i > 0 {
-                    query.push_str(" OR ");
-                }
-                match res.as_str() {
-                    "4k" => {
-                        query.push_str(&format!("(resolution LIKE ?{} OR resolution LIKE ?{})", param_idx, param_idx + 1));
-                        params.push(Box::new("%2160p%".to_string()));
-                        params.push(Box::new("%4K%".to_string())); // Some might say 4K
-                        param_idx += 2;
-                    },
-                    "1080p" => {
-                        query.push_str(&format!("resolution LIKE ?{}", param_idx));
-                        params.push(Box::new("%1080p%".to_string()));
-                        param_idx += 1;
-                    },
-                    "720p" => {
-                        query.push_str(&format!("resolution LIKE ?{}", param_idx));
-                        params.push(Box::new("%720p%".to_string()));
-                        param_idx += 1;
-                    },
-                    "sd" => {
-                         // Simplify SD as anything not 720p/1080p/2160p? Or explicit 480p/576p
-                         query.push_str(&format!("(resolution LIKE ?{} OR resolution LIKE ?{})", param_idx, param_idx + 1));
-                         params.push(Box::new("%480p%".to_string()));
-                         params.push(Box::new("%576p%".to_string()));
-                         param_idx += 2;
-                    },
-                    _ => {
-                        // Generic match
-                         query.push_str(&format!("resolution LIKE ?{}", param_idx));
-                         params.push(Box::new(format!("%{}%", res)));
-                         param_idx += 1;
-                    }
+                    query.push_str(", ");
                 }
+                query.push_str(&format!("?{}", param_idx));
+                params.push(Box::new(class));
+                param_idx += 1;
             }
             query.push(')');
         }
     }
 
-    query.push_str(" ORDER BY indexed_at DESC");
+    // TMDB facets and rating
+    {
+        use crate::db::facets::{facet_condition, Facet, RATING_SQL};
 
-    let mut stmt = conn.prepare(&query)?;
+        let mut bind = |value: Box<dyn rusqlite::ToSql>| {
+            params.push(value);
+            format!("?{}", params.len())
+        };
+        let facets = [
+            (Facet::Genre, &criteria.genres),
+            (Facet::Keyword, &criteria.keywords),
+            (Facet::Country, &criteria.countries),
+            (Facet::Language, &criteria.languages),
+        ];
+        for (facet, values) in facets {
+            if let Some(condition) = values.as_deref().and_then(|v| facet_condition(facet, v, |s| bind(Box::new(s)))) {
+                query.push_str(&format!(" AND {}", condition));
+            }
+        }
+        if let Some(min_rating) = criteria.min_rating {
+            query.push_str(&format!(" AND {} >= {}", RATING_SQL, bind(Box::new(min_rating))));
+        }
+        if let Some(max_rating) = criteria.max_rating {
+            query.push_str(&format!(" AND {} <= {}", RATING_SQL, bind(Box::new(max_rating))));
+        }
+        param_idx = params.len() + 1;
+    }
 
-    // We need to convert our Vec<Box<dyn ToSql>> to a slice of references
-    let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
-
-    let media_iter = stmt.query_map(params_refs.as_slice(), |row| {
-        let media_type_str: String = row.get(5)?;
-        let media_type = MediaType::from_str(&media_type_str)
-            .unwrap_or(MediaType::Video);
-
-        Ok(MediaFile {
-            id: Some(row.get(0)?),
-            file_path: row.get(1)?,
-            file_hash: row.get(2)?,
-            file_name: row.get(3)?,
-            file_size: row.get(4)?,
-            media_type,
-            duration: row.get(6)?,
-            codec: row.get(7)?,
-            resolution: row.get(8)?,
-            bitrate: row.get(9)?,
-            framerate: row.get(10)?,
-            audio_codec: row.get(11)?,
-            audio_channels: row.get(12)?,
-            title: row.get(13)?,
-            year: row.get(14)?,
-            season_number: row.get(15)?,
-            episode_number: row.get(16)?,
-            indexed_at: row.get(17)?,
-            last_modified: row.get(18)?,
-            is_deleted: row.get::<_, i32>(19)? != 0,
-            metadata_json: row.get(20)?,
-            is_locked: row.get::<_, bool>(21).unwrap_or(false),
-        })
-    })?;
+    // Query language expression
+    if let Some(expression) = &criteria.query {
+        if let Some(filter) = crate::db::query_parser::compile_query(expression, param_idx)? {
+            query.push_str(&format!(" AND {}", filter.sql));
+            params.extend(filter.params.into_iter().map(|p| Box::new(p) as Box<dyn rusqlite::ToSql>));
+        }
+    }
 
-    media_iter.collect()
+    Ok((query, params))
 }
 
-/// Update media metadata (manual override)
+/// Update media metadata by hand. Only the given fields change, and each
+/// one is locked against later scans (see `metadata_overrides`).
 pub fn update_media_metadata(
     conn: &Connection,
     media_id: i64,
@@ -399,45 +453,19 @@ pub fn update_media_metadata(
     description: Option<String>,
     poster_url: Option<String>,
 ) -> Result<()> {
-    // We need to fetch current metadata_json to preserve other fields if needed,
-    // or just construct a new one. For simplicity, we'll merge or create new.
-    // However, SQLite doesn't have easy JSON merge in older versions.
-    // Let's read the current one first.
-
-    let current_metadata: Option<String> = conn.query_row(
-        "SELECT metadata_json FROM media_files WHERE id = ?1",
-        [media_id],
-        |row| row.get(0),
-    )?;
-
-    let mut metadata_map: serde_json::Map<String, serde_json::Value> =
-        if let Some(json_str) = current_metadata {
-            serde_json::from_str(&json_str).unwrap_or_default()
-        } else {
-            serde_json::Map::new()
-        };
-
-    if let Some(desc) = description {
-        metadata_map.insert("overview".to_string(), serde_json::Value::String(desc));
-    }
-    if let Some(url) = poster_url {
-        metadata_map.insert("poster_path".to_string(), serde_json::Value::String(url));
-    }
-
-    let new_metadata_json = serde_json::to_string(&metadata_map).unwrap_or("{}".to_string());
-
-    conn.execute(
-        "UPDATE media_files SET
-            title = ?2,
-            year = ?3,
-            season_number = ?4,
-            episode_number = ?5,
-            metadata_json = ?6,
-            is_locked = 1
-         WHERE id = ?1",
-        params![media_id, title, year, season, episode, new_metadata_json],
-    )?;
-
+    let changes: BTreeMap<String, serde_json::Value> = [
+        ("title", title.map(Into::into)),
+        ("year", year.map(Into::into)),
+        ("season", season.map(Into::into)),
+        ("episode", episode.map(Into::into)),
+        ("overview", description.map(Into::into)),
+        ("poster_path", poster_url.map(Into::into)),
+    ]
+    .into_iter()
+    .filter_map(|(field, value)| Some((field.to_string(), value?)))
+    .collect();
+
+    edit_media_metadata(conn, media_id, &changes)?;
     Ok(())
 }
 
@@ -0,0 +1,369 @@
+//! Lexicographic ordering keys shared by playlists and collections.
+//!
+//! Each item carries a `sort_key` string and lists are ordered by it, so
+//! moving an item only rewrites that item's key: a new key is generated
+//! between its new neighbours. Keys are base-62 with a variable length
+//! integer part followed by an optional fraction, which keeps keys short
+//! when appending and lets them grow only where items are squeezed together.
+
+use rusqlite::{Connection, Result, params};
+
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const BASE: usize = 62;
+/// Key of the first item in an empty list
+const ZERO_KEY: &str = "a0";
+/// Lowest representable integer part
+const SMALLEST_INTEGER: &str = "A00000000000000000000000000";
+
+/// Lists ordered with sort keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OrderedList {
+    Playlist,
+    Collection,
+}
+
+impl OrderedList {
+    fn table(&self) -> &str {
+        match self {
+            OrderedList::Playlist => "playlist_items",
+            OrderedList::Collection => "collection_items",
+        }
+    }
+
+    fn owner_column(&self) -> &str {
+        match self {
+            OrderedList::Playlist => "playlist_id",
+            OrderedList::Collection => "collection_id",
+        }
+    }
+}
+
+fn digit_value(c: u8) -> usize {
+    DIGITS.iter().position(|d| *d == c).unwrap_or(0)
+}
+
+/// Length of the integer part for a head character
+fn integer_length(head: u8) -> usize {
+    match head {
+        b'a'..=b'z' => (head - b'a') as usize + 2,
+        b'A'..=b'Z' => (b'Z' - head) as usize + 2,
+        _ => 2,
+    }
+}
+
+fn split_key(key: &str) -> (&str, &str) {
+    let length = integer_length(key.as_bytes()[0]).min(key.len());
+    key.split_at(length)
+}
+
+fn increment_integer(integer: &str) -> Option<String> {
+    let (head, digits) = (integer.as_bytes()[0], &integer[1..]);
+    let mut digits: Vec<u8> = digits.bytes().collect();
+
+    let mut carry = true;
+    for d in digits.iter_mut().rev() {
+        let value = digit_value(*d) + 1;
+        if value == BASE {
+            *d = b'0';
+        } else {
+            *d = DIGITS[value];
+            carry = false;
+            break;
+        }
+    }
+
+    if carry {
+        if head == b'Z' {
+            return Some(ZERO_KEY.to_string());
+        }
+        if head == b'z' {
+            return None;
+        }
+        let head = head + 1;
+        if head > b'a' {
+            digits.push(b'0');
+        } else {
+            digits.pop();
+        }
+        return Some(format!("{}{}", head as char, String::from_utf8_lossy(&digits)));
+    }
+
+    Some(format!("{}{}", head as char, String::from_utf8_lossy(&digits)))
+}
+
+fn decrement_integer(integer: &str) -> Option<String> {
+    let (head, digits) = (integer.as_bytes()[0], &integer[1..]);
+    let mut digits: Vec<u8> = digits.bytes().collect();
+
+    let mut borrow = true;
+    for d in digits.iter_mut().rev() {
+        let value = digit_value(*d);
+        if value == 0 {
+            *d = DIGITS[BASE - 1];
+        } else {
+            *d = DIGITS[value - 1];
+            borrow = false;
+            break;
+        }
+    }
+
+    if borrow {
+        if head == b'a' {
+            return Some(format!("Z{}", DIGITS[BASE - 1] as char));
+        }
+        if head == b'A' {
+            return None;
+        }
+        let head = head - 1;
+        if head < b'Z' {
+            digits.push(DIGITS[BASE - 1]);
+        } else {
+            digits.pop();
+        }
+        return Some(format!("{}{}", head as char, String::from_utf8_lossy(&digits)));
+    }
+
+    Some(format!("{}{}", head as char, String::from_utf8_lossy(&digits)))
+}
+
+/// A fraction strictly between `a` and `b` (None = 1). Neither may end in '0'.
+fn midpoint(a: &str, b: Option<&str>) -> String {
+    if let Some(b) = b {
+        let (ab, bb) = (a.as_bytes(), b.as_bytes());
+        let mut n = 0;
+        while n < bb.len() && ab.get(n).copied().unwrap_or(b'0') == bb[n] {
+            n += 1;
+        }
+        if n > 0 {
+            let rest_a = if n < a.len() { &a[n..] } else { "" };
+            return format!("{}{}", &b[..n], midpoint(rest_a, Some(&b[n..])));
+        }
+    }
+
+    let da = a.bytes().next().map(digit_value).unwrap_or(0);
+    let db = b.and_then(|b| b.bytes().next()).map(digit_value).unwrap_or(BASE);
+
+    if db.saturating_sub(da) > 1 {
+        (DIGITS[(da + db) / 2] as char).to_string()
+    } else if let Some(b) = b.filter(|b| b.len() > 1) {
+        b[..1].to_string()
+    } else {
+        let rest = if a.len() > 1 { &a[1..] } else { "" };
+        format!("{}{}", DIGITS[da] as char, midpoint(rest, None))
+    }
+}
+
+/// A key sorting strictly between `before` and `after` (either end may be open).
+///
+/// `before` must sort before `after`; both must be keys produced here.
+pub fn key_between(before: Option<&str>, after: Option<&str>) -> String {
+    match (before, after) {
+        (None, None) => ZERO_KEY.to_string(),
+        (None, Some(b)) => {
+            let (integer, fraction) = split_key(b);
+            if integer == SMALLEST_INTEGER {
+                return format!("{}{}", integer, midpoint("", Some(fraction)));
+            }
+            if !fraction.is_empty() {
+                return integer.to_string();
+            }
+            decrement_integer(integer).unwrap_or_else(|| format!("{}{}", integer, midpoint("", Some(fraction))))
+        }
+        (Some(a), None) => {
+            let (integer, fraction) = split_key(a);
+            increment_integer(integer).unwrap_or_else(|| format!("{}{}", integer, midpoint(fraction, None)))
+        }
+        (Some(a), Some(b)) => {
+            let (ia, fa) = split_key(a);
+            let (ib, fb) = split_key(b);
+            if ia == ib {
+                return format!("{}{}", ia, midpoint(fa, Some(fb)));
+            }
+            match increment_integer(ia) {
+                Some(next) if next.as_str() < b => next,
+                _ => format!("{}{}", ia, midpoint(fa, None)),
+            }
+        }
+    }
+}
+
+/// `count` ascending keys between `before` and `after`, spread so their lengths stay balanced
+pub fn keys_between(before: Option<&str>, after: Option<&str>, count: usize) -> Vec<String> {
+    match count {
+        0 => Vec::new(),
+        1 => vec![key_between(before, after)],
+        _ if after.is_none() => {
+            let mut keys = Vec::with_capacity(count);
+            let mut last = key_between(before, None);
+            for _ in 1..count {
+                let next = key_between(Some(&last), None);
+                keys.push(std::mem::replace(&mut last, next));
+            }
+            keys.push(last);
+            keys
+        }
+        _ if before.is_none() => {
+            let mut keys = Vec::with_capacity(count);
+            let mut first = key_between(None, after);
+            for _ in 1..count {
+                let previous = key_between(None, Some(&first));
+                keys.push(std::mem::replace(&mut first, previous));
+            }
+            keys.push(first);
+            keys.reverse();
+            keys
+        }
+        _ => {
+            let mid = count / 2;
+            let middle = key_between(before, after);
+            let mut keys = keys_between(before, Some(&middle), mid);
+            keys.push(middle.clone());
+            keys.extend(keys_between(Some(&middle), after, count - mid - 1));
+            keys
+        }
+    }
+}
+
+/// Sort key for appending to the end of a list
+pub(crate) fn append_key(conn: &Connection, list: OrderedList, owner_id: i64) -> Result<String> {
+    let last: Option<String> = conn.query_row(
+        &format!(
+            "SELECT MAX(sort_key) FROM {} WHERE {} = ?1",
+            list.table(),
+            list.owner_column()
+        ),
+        params![owner_id],
+        |row| row.get(0),
+    )?;
+
+    Ok(key_between(last.as_deref(), None))
+}
+
+/// Move `media_ids` (in the given order) so the first lands at `to_index` of the resulting list.
+///
+/// Only the moved rows are rewritten. Ids that aren't in the list are ignored,
+/// and so are items whose media was deleted, since lists don't show them.
+pub(crate) fn move_items(
+    conn: &Connection,
+    list: OrderedList,
+    owner_id: i64,
+    media_ids: &[i64],
+    to_index: usize,
+) -> Result<usize> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT i.media_id, i.sort_key FROM {} i
+         JOIN media_files m ON i.media_id = m.id
+         WHERE i.{} = ?1 AND m.is_deleted = 0
+         ORDER BY i.sort_key, i.id",
+        list.table(),
+        list.owner_column()
+    ))?;
+    let items = stmt
+        .query_map(params![owner_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut moving: Vec<i64> = Vec::new();
+    for id in media_ids {
+        if !moving.contains(id) && items.iter().any(|(media_id, _)| media_id == id) {
+            moving.push(*id);
+        }
+    }
+    if moving.is_empty() {
+        return Ok(0);
+    }
+
+    let remaining: Vec<&(i64, String)> = items.iter().filter(|(id, _)| !moving.contains(id)).collect();
+    let to_index = to_index.min(remaining.len());
+    let before = to_index.checked_sub(1).map(|i| remaining[i].1.as_str());
+    let after = remaining.get(to_index).map(|(_, key)| key.as_str());
+
+    let mut update = conn.prepare(&format!(
+        "UPDATE {} SET sort_key = ?1 WHERE {} = ?2 AND media_id = ?3",
+        list.table(),
+        list.owner_column()
+    ))?;
+    for (media_id, key) in moving.iter().zip(keys_between(before, after, moving.len())) {
+        update.execute(params![key, owner_id, media_id])?;
+    }
+
+    Ok(moving.len())
+}
+
+/// Give every playlist and collection item a sort key, keeping the current order
+pub(crate) fn backfill_sort_keys(conn: &Connection) -> Result<()> {
+    let lists = [
+        (OrderedList::Playlist, "position, id"),
+        // Collections used to be shown newest first, with `position` rarely set
+        (OrderedList::Collection, "position IS NULL, position, added_at DESC, id"),
+    ];
+
+    for (list, order) in lists {
+        let mut owners = conn.prepare(&format!(
+            "SELECT DISTINCT {} FROM {} WHERE sort_key IS NULL",
+            list.owner_column(),
+            list.table()
+        ))?;
+        let owner_ids = owners.query_map([], |row| row.get::<_, i64>(0))?.collect::<Result<Vec<_>>>()?;
+
+        for owner_id in owner_ids {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT id FROM {} WHERE {} = ?1 ORDER BY {}",
+                list.table(),
+                list.owner_column(),
+                order
+            ))?;
+            let ids = stmt.query_map(params![owner_id], |row| row.get::<_, i64>(0))?.collect::<Result<Vec<_>>>()?;
+
+            let mut update = conn.prepare(&format!("UPDATE {} SET sort_key = ?1 WHERE id = ?2", list.table()))?;
+            for (id, key) in ids.iter().zip(keys_between(None, None, ids.len())) {
+                update.execute(params![key, id])?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_between() {
+        assert_eq!(key_between(None, None), "a0");
+        assert_eq!(key_between(Some("a0"), None), "a1");
+        assert_eq!(key_between(None, Some("a0")), "Zz");
+        assert_eq!(key_between(Some("a0"), Some("a1")), "a0V");
+        assert_eq!(key_between(Some("a0V"), Some("a1")), "a0k");
+        assert_eq!(key_between(Some("az"), None), "b00");
+        assert_eq!(key_between(Some("Zz"), Some("a0")), "ZzV");
+        assert_eq!(key_between(None, Some("a0V")), "a0");
+    }
+
+    #[test]
+    fn test_keys_stay_ordered_and_short() {
+        // Appending stays compact
+        let keys = keys_between(None, None, 10_000);
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        assert!(keys.iter().all(|k| k.len() <= 4));
+
+        // Repeatedly inserting at the front and in the same gap keeps strict order
+        let mut list = vec![key_between(None, None)];
+        for i in 0..500 {
+            let key = if i % 2 == 0 {
+                key_between(None, Some(&list[0]))
+            } else {
+                key_between(Some(&list[0]), Some(&list[1]))
+            };
+            let index = if i % 2 == 0 { 0 } else { 1 };
+            list.insert(index, key);
+        }
+        assert!(list.windows(2).all(|w| w[0] < w[1]));
+        assert!(list.iter().all(|k| !split_key(k).1.ends_with('0')));
+
+        let between = keys_between(Some("a0"), Some("a1"), 50);
+        assert_eq!(between.len(), 50);
+        assert!(between.windows(2).all(|w| w[0] < w[1]));
+        assert!(between[0].as_str() > "a0" && between[49].as_str() < "a1");
+    }
+}
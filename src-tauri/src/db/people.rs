@@ -0,0 +1,278 @@
+use rusqlite::{Connection, Result, params};
+use std::collections::BTreeMap;
+
+use super::models::MediaFile;
+use super::operations::{MEDIA_COLUMNS, media_file_from_row};
+
+/// Joins a file's TMDB match to its credits
+const CREDITS_JOIN: &str = "
+    FROM tmdb_cast c
+    JOIN tmdb_media t ON t.tmdb_id = c.tmdb_media_id AND t.media_type = c.media_type
+    JOIN media_files m ON m.id = t.media_id AND m.is_deleted = 0";
+
+/// Someone credited on at least one file in the library
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Person {
+    pub person_id: i64,
+    pub name: String,
+    pub profile_path: Option<String>,
+    /// "cast", "director", "writer", "producer"
+    pub roles: Vec<String>,
+    /// Movies and shows (not files) they're credited on
+    pub title_count: usize,
+    pub file_count: usize,
+    pub watched_count: usize,
+}
+
+/// One movie or show in a person's filmography, limited to files we own
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FilmographyEntry {
+    pub tmdb_id: i64,
+    pub media_type: String,
+    pub title: Option<String>,
+    pub release_date: Option<String>,
+    pub roles: Vec<String>,
+    pub characters: Vec<String>,
+    /// The owned files: the movie, or the show's episodes
+    pub media: Vec<MediaFile>,
+    pub watched_count: usize,
+    /// Every owned file has been watched to the end
+    pub watched: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PersonDetails {
+    pub person: Person,
+    /// Newest first
+    pub filmography: Vec<FilmographyEntry>,
+}
+
+fn split_list(value: Option<String>) -> Vec<String> {
+    let mut items: Vec<String> = value
+        .unwrap_or_default()
+        .split(',')
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect();
+    items.sort();
+    items.dedup();
+    items
+}
+
+/// People credited in the library, most prolific first. `role` limits to one
+/// kind of credit and `query` matches names (case-insensitive substring).
+pub fn list_people(
+    conn: &Connection,
+    role: Option<&str>,
+    query: Option<&str>,
+    limit: usize,
+    offset: usize,
+) -> Result<Vec<Person>> {
+    let pattern = query.map(|q| format!("%{}%", q.trim()));
+    let mut stmt = conn.prepare(&format!(
+        "SELECT c.tmdb_person_id, MAX(c.name), MAX(c.profile_path), GROUP_CONCAT(DISTINCT c.role),
+                COUNT(DISTINCT c.tmdb_media_id || ':' || c.media_type),
+                COUNT(DISTINCT m.id),
+                COUNT(DISTINCT CASE WHEN ps.completed = 1 THEN m.id END)
+         {}
+         LEFT JOIN playback_state ps ON ps.media_id = m.id
+         WHERE (?1 IS NULL OR c.role = ?1) AND (?2 IS NULL OR c.name LIKE ?2)
+         GROUP BY c.tmdb_person_id
+         ORDER BY COUNT(DISTINCT c.tmdb_media_id || ':' || c.media_type) DESC, MAX(c.name) COLLATE NOCASE
+         LIMIT ?3 OFFSET ?4",
+        CREDITS_JOIN
+    ))?;
+
+    let people = stmt
+        .query_map(params![role, pattern, limit as i64, offset as i64], |row| {
+            Ok(Person {
+                person_id: row.get(0)?,
+                name: row.get(1)?,
+                profile_path: row.get(2)?,
+                roles: split_list(row.get(3)?),
+                title_count: row.get::<_, i64>(4)? as usize,
+                file_count: row.get::<_, i64>(5)? as usize,
+                watched_count: row.get::<_, i64>(6)? as usize,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(people)
+}
+
+/// A person and the titles we own that they're credited on; None if they
+/// aren't credited on anything in the library
+pub fn get_person(conn: &Connection, person_id: i64) -> Result<Option<PersonDetails>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, c.tmdb_media_id, c.media_type, c.name, c.profile_path, c.role, c.character,
+                md.title, md.release_date, COALESCE(ps.completed, 0)
+         {}
+         LEFT JOIN tmdb_metadata md ON md.tmdb_id = c.tmdb_media_id AND md.media_type = c.media_type
+         LEFT JOIN playback_state ps ON ps.media_id = m.id
+         WHERE c.tmdb_person_id = ?1
+         ORDER BY m.season_number, m.episode_number, m.id",
+        MEDIA_COLUMNS, CREDITS_JOIN
+    ))?;
+
+    let mut name = None;
+    let mut profile_path = None;
+    let mut entries: BTreeMap<(i64, String), FilmographyEntry> = BTreeMap::new();
+    let mut watched_files: BTreeMap<(i64, String), Vec<(i64, bool)>> = BTreeMap::new();
+
+    let mut rows = stmt.query(params![person_id])?;
+    while let Some(row) = rows.next()? {
+        // Credit columns follow the 22 media columns
+        let media = media_file_from_row(row)?;
+        let key: (i64, String) = (row.get(22)?, row.get(23)?);
+        name = Some(row.get::<_, String>(24)?);
+        profile_path = profile_path.or(row.get::<_, Option<String>>(25)?);
+        let role: String = row.get(26)?;
+        let character: Option<String> = row.get(27)?;
+        let completed: bool = row.get::<_, i64>(30)? == 1;
+
+        let entry = entries.entry(key.clone()).or_insert_with(|| FilmographyEntry {
+            tmdb_id: key.0,
+            media_type: key.1.clone(),
+            title: row.get(28).ok().flatten(),
+            release_date: row.get(29).ok().flatten(),
+            roles: Vec::new(),
+            characters: Vec::new(),
+            media: Vec::new(),
+            watched_count: 0,
+            watched: false,
+        });
+        if !entry.roles.contains(&role) {
+            entry.roles.push(role);
+        }
+        if let Some(character) = character.filter(|c| !c.is_empty()) {
+            if !entry.characters.contains(&character) {
+                entry.characters.push(character);
+            }
+        }
+
+        // Someone credited twice (e.g. writer and director) joins each file twice
+        let files = watched_files.entry(key).or_default();
+        if let Some(id) = media.id.filter(|id| !files.iter().any(|(seen, _)| seen == id)) {
+            files.push((id, completed));
+            entry.media.push(media);
+        }
+    }
+
+    let Some(name) = name else {
+        return Ok(None);
+    };
+
+    let mut filmography: Vec<FilmographyEntry> = entries
+        .into_iter()
+        .map(|(key, mut entry)| {
+            let files = &watched_files[&key];
+            entry.watched_count = files.iter().filter(|(_, done)| *done).count();
+            entry.watched = !files.is_empty() && entry.watched_count == files.len();
+            entry.roles.sort();
+            entry
+        })
+        .collect();
+    filmography.sort_by(|a, b| b.release_date.cmp(&a.release_date).then_with(|| a.title.cmp(&b.title)));
+
+    let mut roles: Vec<String> = filmography.iter().flat_map(|e| e.roles.clone()).collect();
+    roles.sort();
+    roles.dedup();
+
+    let person = Person {
+        person_id,
+        name,
+        profile_path,
+        roles,
+        title_count: filmography.len(),
+        file_count: filmography.iter().map(|e| e.media.len()).sum(),
+        watched_count: filmography.iter().map(|e| e.watched_count).sum(),
+    };
+
+    Ok(Some(PersonDetails { person, filmography }))
+}
+
+/// Files a person is credited on, optionally only for one role
+pub fn get_media_by_person(conn: &Connection, person_id: i64, role: Option<&str>) -> Result<Vec<MediaFile>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}
+         FROM media_files m
+         WHERE m.is_deleted = 0 AND m.id IN (
+            SELECT m.id {} WHERE c.tmdb_person_id = ?1 AND (?2 IS NULL OR c.role = ?2)
+         )
+         ORDER BY m.year DESC, m.title COLLATE NOCASE, m.season_number, m.episode_number",
+        MEDIA_COLUMNS, CREDITS_JOIN
+    ))?;
+
+    let media = stmt
+        .query_map(params![person_id, role], media_file_from_row)?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(media)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use crate::db::test_support::{self, TestMedia};
+
+    fn add_media(conn: &Connection, path: &str, media_type: &str, title: &str, season: Option<i32>) -> i64 {
+        let episode = season.map(|_| 1);
+        test_support::add_media(conn, &TestMedia { path, media_type, title: Some(title), season, episode, ..TestMedia::default() })
+    }
+
+    #[test]
+    fn test_people_and_filmography() -> Result<()> {
+        let conn = init_db()?;
+        conn.execute_batch(
+            "INSERT INTO tmdb_metadata (tmdb_id, media_type, title, release_date) VALUES
+                (1, 'movie', 'Heat', '1995-12-15'),
+                (2, 'movie', 'Collateral', '2004-08-06'),
+                (3, 'tv', 'Miami Vice', '1984-09-16'),
+                (4, 'movie', 'Ali', '2001-12-25');
+             INSERT INTO tmdb_cast (tmdb_media_id, media_type, tmdb_person_id, name, character, role) VALUES
+                (1, 'movie', 100, 'Michael Mann', NULL, 'director'),
+                (1, 'movie', 100, 'Michael Mann', NULL, 'writer'),
+                (1, 'movie', 200, 'Al Pacino', 'Vincent Hanna', 'cast'),
+                (2, 'movie', 100, 'Michael Mann', NULL, 'director'),
+                (3, 'tv', 100, 'Michael Mann', NULL, 'producer'),
+                (4, 'movie', 100, 'Michael Mann', NULL, 'director');",
+        )?;
+        let heat = add_media(&conn, "/m/heat.mkv", "movie", "Heat", None);
+        let collateral = add_media(&conn, "/m/collateral.mkv", "movie", "Collateral", None);
+        let e1 = add_media(&conn, "/tv/vice/s01e01.mkv", "tv_episode", "Pilot", Some(1));
+        let e2 = add_media(&conn, "/tv/vice/s02e01.mkv", "tv_episode", "Return", Some(2));
+        for (media_id, tmdb_id, media_type) in [(heat, 1, "movie"), (collateral, 2, "movie"), (e1, 3, "tv"), (e2, 3, "tv")] {
+            conn.execute(
+                "INSERT INTO tmdb_media (media_id, tmdb_id, media_type) VALUES (?1, ?2, ?3)",
+                params![media_id, tmdb_id, media_type],
+            )?;
+        }
+        conn.execute("INSERT INTO playback_state (media_id, completed) VALUES (?1, 1)", params![heat])?;
+        conn.execute("INSERT INTO playback_state (media_id, completed) VALUES (?1, 1)", params![e1])?;
+
+        let people = list_people(&conn, None, None, 10, 0)?;
+        assert_eq!(people.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), vec!["Michael Mann", "Al Pacino"]);
+        // Ali isn't in the library, so it doesn't count
+        assert_eq!((people[0].title_count, people[0].file_count, people[0].watched_count), (3, 4, 2));
+        assert_eq!(people[0].roles, vec!["director", "producer", "writer"]);
+        assert_eq!(list_people(&conn, Some("cast"), None, 10, 0)?.len(), 1);
+        assert_eq!(list_people(&conn, None, Some("pacino"), 10, 0)?[0].person_id, 200);
+
+        let mann = get_person(&conn, 100)?.unwrap();
+        let titles: Vec<_> = mann.filmography.iter().map(|e| e.title.clone().unwrap()).collect();
+        assert_eq!(titles, vec!["Collateral", "Heat", "Miami Vice"]);
+        assert_eq!(mann.filmography[1].roles, vec!["director", "writer"]);
+        assert_eq!(mann.filmography[1].media.len(), 1);
+        assert!(mann.filmography[1].watched);
+        let vice = &mann.filmography[2];
+        assert_eq!((vice.media.len(), vice.watched_count, vice.watched), (2, 1, false));
+        assert_eq!(get_person(&conn, 200)?.unwrap().filmography[0].characters, vec!["Vincent Hanna"]);
+        assert!(get_person(&conn, 999)?.is_none());
+
+        let directed: Vec<i64> = get_media_by_person(&conn, 100, Some("director"))?.iter().filter_map(|m| m.id).collect();
+        assert_eq!(directed, vec![collateral, heat]);
+        assert_eq!(get_media_by_person(&conn, 100, None)?.len(), 4);
+        Ok(())
+    }
+}
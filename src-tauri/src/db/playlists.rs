@@ -1,5 +1,8 @@
-use rusqlite::{Connection, Result, params};
-use crate::db::models::{Playlist, PlaylistType, PlaylistRule};
+use rusqlite::{Connection, Result, ToSql, params};
+use crate::db::models::{Playlist, PlaylistType, PlaylistRule, SmartPlaylistSettings, SortKey};
+use crate::db::operations::sort_expression;
+use crate::db::ordering::{append_key, move_items, OrderedList};
+use crate::db::smart_rules::{compile_rules, normalize_operator, validate_rule, RuleError, GROUP_RULE};
 use chrono::Utc;
 
 /// Create a new playlist
@@ -131,11 +134,12 @@ pub fn add_media_to_playlist(
     ).unwrap_or(None);
     
     let new_position = max_position.unwrap_or(-1) + 1;
+    let sort_key = append_key(conn, OrderedList::Playlist, playlist_id)?;
     
     conn.execute(
-        "INSERT INTO playlist_items (playlist_id, media_id, position, added_at)
-         VALUES (?1, ?2, ?3, ?4)",
-        params![playlist_id, media_id, new_position, &now],
+        "INSERT INTO playlist_items (playlist_id, media_id, position, added_at, sort_key)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![playlist_id, media_id, new_position, &now, sort_key],
     )?;
     
     // Update playlist updated_at
@@ -182,7 +186,10 @@ pub struct PlaylistMediaItem {
     pub position: i32,
 }
 
-pub fn get_playlist_media(conn: &Connection, playlist_id: i64) -> Result<Vec<PlaylistMediaItem>> {
+pub fn get_playlist_media(
+    conn: &Connection,
+    playlist_id: i64,
+) -> std::result::Result<Vec<PlaylistMediaItem>, RuleError> {
     // First check the playlist type
     let playlist_type: String = conn.query_row(
         "SELECT playlist_type FROM playlists WHERE id = ?1",
@@ -201,10 +208,10 @@ pub fn get_playlist_media(conn: &Connection, playlist_id: i64) -> Result<Vec<Pla
          FROM playlist_items pi
          JOIN media_files m ON pi.media_id = m.id
          WHERE pi.playlist_id = ?1 AND m.is_deleted = 0
-         ORDER BY pi.position ASC"
+         ORDER BY pi.sort_key, pi.id"
     )?;
     
-    let items = stmt.query_map(params![playlist_id], |row| {
+    let mut items = stmt.query_map(params![playlist_id], |row| {
         Ok(PlaylistMediaItem {
             id: row.get(0)?,
             file_path: row.get(1)?,
@@ -217,129 +224,50 @@ pub fn get_playlist_media(conn: &Connection, playlist_id: i64) -> Result<Vec<Pla
         })
     })?
     .collect::<Result<Vec<_>>>()?;
+
+    // Report the index in the sorted list; stored positions are only an insertion counter
+    for (index, item) in items.iter_mut().enumerate() {
+        item.position = index as i32;
+    }
     
     Ok(items)
 }
 
-/// Calculate smart playlist media based on rules
-fn get_smart_playlist_media(conn: &Connection, playlist_id: i64) -> Result<Vec<PlaylistMediaItem>> {
-    // 1. Fetch rules
-    let mut stmt = conn.prepare(
-        "SELECT id, playlist_id, rule_type, operator, value FROM playlist_rules WHERE playlist_id = ?1"
-    )?;
-
-    let rules = stmt.query_map(params![playlist_id], |row| {
-        Ok(PlaylistRule {
-            id: Some(row.get(0)?),
-            playlist_id: row.get(1)?,
-            rule_type: row.get(2)?,
-            operator: row.get(3)?,
-            value: row.get(4)?,
-        })
-    })?
-    .collect::<Result<Vec<_>>>()?;
+/// Calculate smart playlist media based on rules, sorted and limited per the playlist's settings
+fn get_smart_playlist_media(
+    conn: &Connection,
+    playlist_id: i64,
+) -> std::result::Result<Vec<PlaylistMediaItem>, RuleError> {
+    let rules = get_playlist_rules(conn, playlist_id)?;
+    let settings = get_smart_playlist_settings(conn, playlist_id)?;
 
-    if rules.is_empty() {
+    let mut params: Vec<Box<dyn ToSql>> = Vec::new();
+    let Some(condition) = compile_rules(&rules, &mut params)? else {
         return Ok(Vec::new());
-    }
+    };
 
-    // 2. Build Query
-    let mut query = String::from(
+    let mut query = format!(
         "SELECT m.id, m.file_path, m.file_name, m.title, m.year, m.media_type, m.duration
          FROM media_files m
-         WHERE m.is_deleted = 0"
+         WHERE m.is_deleted = 0 AND {}",
+        condition
     );
 
-    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-    let mut param_index = 1;
-
-    // currently we assume AND logic for all rules
-    for rule in rules {
-        query.push_str(" AND ");
-
-        match rule.rule_type.as_str() {
-            "media_type" => {
-                query.push_str("m.media_type");
-                match rule.operator.as_str() {
-                    "equals" => {
-                        query.push_str(" = ?");
-                        params.push(Box::new(rule.value));
-                    },
-                    "notequals" => {
-                        query.push_str(" != ?");
-                        params.push(Box::new(rule.value));
-                    }
-                    _ => { /* invalid operator for type */ query.push_str(" = ?"); params.push(Box::new(rule.value)); }
-                }
-            },
-            "year" => {
-                query.push_str("m.year");
-                match rule.operator.as_str() {
-                    "equals" => query.push_str(" = ?"),
-                    "gt" => query.push_str(" > ?"),
-                    "lt" => query.push_str(" < ?"),
-                    "gte" => query.push_str(" >= ?"),
-                    "lte" => query.push_str(" <= ?"),
-                    _ => query.push_str(" = ?"),
-                }
-                // parse value as int
-                let val = rule.value.parse::<i32>().unwrap_or(0);
-                params.push(Box::new(val));
-            },
-            "duration" => { // duration in database is seconds, value might be minutes? Assuming seconds for now
-                 query.push_str("m.duration");
-                match rule.operator.as_str() {
-                    "gt" => query.push_str(" > ?"),
-                    "lt" => query.push_str(" < ?"),
-                    _ => query.push_str(" = ?"),
-                }
-                let val = rule.value.parse::<i64>().unwrap_or(0);
-                params.push(Box::new(val));
-            },
-            "title" | "file_name" => {
-                let col = if rule.rule_type == "title" { "m.title" } else { "m.file_name" };
-                query.push_str(col);
-                match rule.operator.as_str() {
-                    "contains" => {
-                        query.push_str(" LIKE ?");
-                        params.push(Box::new(format!("%{}%", rule.value)));
-                    },
-                    "starts_with" => {
-                        query.push_str(" LIKE ?");
-                        params.push(Box::new(format!("{}%", rule.value)));
-                    },
-                    "ends_with" => {
-                         query.push_str(" LIKE ?");
-                        params.push(Box::new(format!("%{}", rule.value)));
-                    },
-                    "equals" => {
-                        query.push_str(" = ?");
-                        params.push(Box::new(rule.value));
-                    },
-                     _ => { query.push_str(" = ?"); params.push(Box::new(rule.value)); }
-                }
-                param_index += 0; // handled by logic above
-            },
-             _ => {
-                // Ignore unknown rules for now, or make them always true/false
-                query.push_str("1=1");
-             }
-        }
+    // Shuffled playlists are ordered and limited after fetching
+    if settings.random_seed.is_none() {
+        let key = settings.sort.unwrap_or(SortKey::Title);
+        let direction = if settings.descending.unwrap_or(key.default_descending()) { "DESC" } else { "ASC" };
+        query.push_str(&format!(" ORDER BY {} {}, m.id {}", sort_expression(key), direction, direction));
 
-        query.push_str(&format!("{}", param_index).replace(char::is_numeric, "")); // Hack to avoid unsed variable warning? No.
-        param_index += 1;
+        if let Some(limit) = settings.limit {
+            query.push_str(&format!(" LIMIT {}", limit));
+        }
     }
 
-    // Add sorting (default by title)
-    query.push_str(" ORDER BY m.title ASC");
-
-    // Execute
     let mut stmt = conn.prepare(&query)?;
+    let params_refs: Vec<&dyn ToSql> = params.iter().map(|p| p.as_ref()).collect();
 
-    // Convert params to slice of references
-    let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
-
-    let items = stmt.query_map(&*params_refs, |row| {
+    let mut items = stmt.query_map(&*params_refs, |row| {
          Ok(PlaylistMediaItem {
             id: row.get(0)?,
             file_path: row.get(1)?,
@@ -348,26 +276,125 @@ fn get_smart_playlist_media(conn: &Connection, playlist_id: i64) -> Result<Vec<P
             year: row.get(4)?,
             media_type: row.get(5)?,
             duration: row.get(6)?,
-            position: 0, // Smart playlists don't have manual position
+            position: 0,
         })
     })?
     .collect::<Result<Vec<_>>>()?;
 
+    if let Some(seed) = settings.random_seed {
+        items.sort_by_key(|item| shuffle_key(seed, item.id));
+        if let Some(limit) = settings.limit {
+            items.truncate(limit as usize);
+        }
+    }
+
+    // Smart playlists have no stored positions; report the computed order
+    for (index, item) in items.iter_mut().enumerate() {
+        item.position = index as i32;
+    }
+
     Ok(items)
 }
 
-/// Add a rule to a playlist
+/// Stable pseudo-random rank of an item for a shuffle seed (SplitMix64).
+///
+/// Each item's rank depends only on the seed and its id, so adding or
+/// removing media doesn't reshuffle the rest of the playlist.
+pub(crate) fn shuffle_key(seed: i64, media_id: i64) -> u64 {
+    let mut z = (seed as u64) ^ (media_id as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// A fresh shuffle seed from the current time
+pub(crate) fn time_seed() -> i64 {
+    let now = Utc::now();
+    now.timestamp() ^ ((now.timestamp_subsec_nanos() as i64) << 20)
+}
+
+/// Get a smart playlist's sort order, limit and shuffle seed
+pub fn get_smart_playlist_settings(conn: &Connection, playlist_id: i64) -> Result<SmartPlaylistSettings> {
+    conn.query_row(
+        "SELECT sort_key, sort_descending, item_limit, random_seed FROM playlists WHERE id = ?1",
+        params![playlist_id],
+        |row| {
+            let sort_key: Option<String> = row.get(0)?;
+            Ok(SmartPlaylistSettings {
+                sort: sort_key.as_deref().and_then(SortKey::from_str),
+                descending: row.get::<_, Option<i32>>(1)?.map(|d| d != 0),
+                limit: row.get(2)?,
+                random_seed: row.get(3)?,
+            })
+        },
+    )
+}
+
+/// Set a smart playlist's sort order, limit and shuffle seed
+pub fn set_smart_playlist_settings(
+    conn: &Connection,
+    playlist_id: i64,
+    settings: &SmartPlaylistSettings,
+) -> Result<()> {
+    let now = Utc::now().to_rfc3339();
+
+    conn.execute(
+        "UPDATE playlists
+         SET sort_key = ?1, sort_descending = ?2, item_limit = ?3, random_seed = ?4, updated_at = ?5
+         WHERE id = ?6",
+        params![
+            settings.sort.map(|key| key.as_str().to_string()),
+            settings.descending.map(|d| d as i32),
+            settings.limit,
+            settings.random_seed,
+            &now,
+            playlist_id,
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Shuffle a smart playlist with a fresh seed; returns the new seed
+pub fn reshuffle_smart_playlist(conn: &Connection, playlist_id: i64) -> Result<i64> {
+    let seed = time_seed();
+
+    let mut settings = get_smart_playlist_settings(conn, playlist_id)?;
+    settings.random_seed = Some(seed);
+    set_smart_playlist_settings(conn, playlist_id, &settings)?;
+
+    Ok(seed)
+}
+
+/// Add a rule to a playlist.
+///
+/// The rule is validated first; pass the id of a `group` rule as `parent_id`
+/// to nest it inside that group.
 pub fn add_playlist_rule(
     conn: &Connection,
     playlist_id: i64,
     rule_type: &str,
     operator: &str,
     value: &str,
-) -> Result<i64> {
-     conn.execute(
-        "INSERT INTO playlist_rules (playlist_id, rule_type, operator, value)
-         VALUES (?1, ?2, ?3, ?4)",
-        params![playlist_id, rule_type, operator, value],
+    parent_id: Option<i64>,
+) -> std::result::Result<i64, RuleError> {
+    validate_rule(rule_type, operator, value)?;
+
+    if let Some(parent_id) = parent_id {
+        let is_group: bool = conn.query_row(
+            "SELECT COUNT(*) FROM playlist_rules WHERE id = ?1 AND playlist_id = ?2 AND rule_type = ?3",
+            params![parent_id, playlist_id, GROUP_RULE],
+            |row| row.get::<_, i32>(0).map(|count| count > 0),
+        )?;
+        if !is_group {
+            return Err(RuleError::InvalidParent(parent_id));
+        }
+    }
+
+    conn.execute(
+        "INSERT INTO playlist_rules (playlist_id, rule_type, operator, value, parent_id)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![playlist_id, rule_type, normalize_operator(operator), value.trim(), parent_id],
     )?;
     Ok(conn.last_insert_rowid())
 }
@@ -375,7 +402,10 @@ pub fn add_playlist_rule(
 /// Get rules for a playlist
 pub fn get_playlist_rules(conn: &Connection, playlist_id: i64) -> Result<Vec<PlaylistRule>> {
     let mut stmt = conn.prepare(
-        "SELECT id, playlist_id, rule_type, operator, value FROM playlist_rules WHERE playlist_id = ?1"
+        "SELECT id, playlist_id, rule_type, operator, value, parent_id
+         FROM playlist_rules
+         WHERE playlist_id = ?1
+         ORDER BY id"
     )?;
 
     let rules = stmt.query_map(params![playlist_id], |row| {
@@ -385,6 +415,7 @@ pub fn get_playlist_rules(conn: &Connection, playlist_id: i64) -> Result<Vec<Pla
             rule_type: row.get(2)?,
             operator: row.get(3)?,
             value: row.get(4)?,
+            parent_id: row.get(5)?,
         })
     })?
     .collect::<Result<Vec<_>>>()?;
@@ -392,7 +423,7 @@ pub fn get_playlist_rules(conn: &Connection, playlist_id: i64) -> Result<Vec<Pla
     Ok(rules)
 }
 
-/// Delete a playlist rule
+/// Delete a playlist rule (for a group, the rules inside it go too)
 pub fn delete_playlist_rule(conn: &Connection, rule_id: i64) -> Result<()> {
     conn.execute(
         "DELETE FROM playlist_rules WHERE id = ?1",
@@ -401,30 +432,34 @@ pub fn delete_playlist_rule(conn: &Connection, rule_id: i64) -> Result<()> {
     Ok(())
 }
 
-/// Reorder a playlist item
-#[allow(dead_code)]
+/// Move a playlist item to `new_position` (0-based)
 pub fn reorder_playlist_item(
     conn: &Connection,
     playlist_id: i64,
     media_id: i64,
     new_position: i32,
 ) -> Result<()> {
-    let now = Utc::now().to_rfc3339();
-    
-    conn.execute(
-        "UPDATE playlist_items 
-         SET position = ?1
-         WHERE playlist_id = ?2 AND media_id = ?3",
-        params![new_position, playlist_id, media_id],
-    )?;
-    
+    move_playlist_items(conn, playlist_id, &[media_id], new_position.max(0) as usize)?;
+    Ok(())
+}
+
+/// Move several items of a manual playlist as a block so the first one ends
+/// up at `to_index`; returns how many items moved
+pub fn move_playlist_items(
+    conn: &Connection,
+    playlist_id: i64,
+    media_ids: &[i64],
+    to_index: usize,
+) -> Result<usize> {
+    let moved = move_items(conn, OrderedList::Playlist, playlist_id, media_ids, to_index)?;
+
     // Update playlist updated_at
     conn.execute(
         "UPDATE playlists SET updated_at = ?1 WHERE id = ?2",
-        params![&now, playlist_id],
+        params![Utc::now().to_rfc3339(), playlist_id],
     )?;
-    
-    Ok(())
+
+    Ok(moved)
 }
 
 /// Get playlist with item count
@@ -437,19 +472,25 @@ pub struct PlaylistWithCount {
     pub created_at: String,
     pub updated_at: String,
     pub item_count: i32,
+    /// Sum of item durations in seconds
+    pub total_duration: i64,
+    /// Set when a smart playlist's rules can't be evaluated
+    pub rule_error: Option<String>,
 }
 
 pub fn get_playlists_with_counts(conn: &Connection) -> Result<Vec<PlaylistWithCount>> {
     let mut stmt = conn.prepare(
         "SELECT p.id, p.name, p.description, p.playlist_type, p.created_at, p.updated_at,
-                COUNT(pi.media_id) as item_count
+                COUNT(m.id) as item_count,
+                COALESCE(SUM(m.duration), 0) as total_duration
          FROM playlists p
          LEFT JOIN playlist_items pi ON p.id = pi.playlist_id
+         LEFT JOIN media_files m ON m.id = pi.media_id AND m.is_deleted = 0
          GROUP BY p.id
          ORDER BY p.updated_at DESC"
     )?;
     
-    let playlists = stmt.query_map([], |row| {
+    let mut playlists = stmt.query_map([], |row| {
         Ok(PlaylistWithCount {
             id: row.get(0)?,
             name: row.get(1)?,
@@ -458,9 +499,27 @@ pub fn get_playlists_with_counts(conn: &Connection) -> Result<Vec<PlaylistWithCo
             created_at: row.get(4)?,
             updated_at: row.get(5)?,
             item_count: row.get(6)?,
+            total_duration: row.get(7)?,
+            rule_error: None,
         })
     })?
     .collect::<Result<Vec<_>>>()?;
+
+    // Smart playlist membership is computed, so count what the rules select
+    for playlist in playlists.iter_mut().filter(|p| p.playlist_type == PlaylistType::Smart.as_str()) {
+        match get_smart_playlist_media(conn, playlist.id) {
+            Ok(items) => {
+                playlist.item_count = items.len() as i32;
+                playlist.total_duration = items.iter().filter_map(|item| item.duration).sum();
+            }
+            Err(RuleError::Database(e)) => return Err(e),
+            Err(e) => {
+                playlist.item_count = 0;
+                playlist.total_duration = 0;
+                playlist.rule_error = Some(e.to_string());
+            }
+        }
+    }
     
     Ok(playlists)
 }
@@ -495,6 +554,7 @@ mod tests {
             last_modified: Utc::now().to_rfc3339(),
             is_deleted: false,
             metadata_json: None,
+            is_locked: false,
         };
         add_media_file(conn, &media)
     }
@@ -532,7 +592,7 @@ mod tests {
     }
 
     #[test]
-    fn test_playlist_items() -> Result<()> {
+    fn test_playlist_items() -> std::result::Result<(), RuleError> {
         let conn = init_db()?;
         
         // Create test data
@@ -561,7 +621,7 @@ mod tests {
     }
 
     #[test]
-    fn test_smart_playlist() -> Result<()> {
+    fn test_smart_playlist() -> std::result::Result<(), RuleError> {
         let conn = init_db()?;
 
         // Create test media
@@ -575,7 +635,7 @@ mod tests {
             duration: Some(100),
             codec: None, resolution: None, bitrate: None, framerate: None, audio_codec: None, audio_channels: None,
             year: Some(2023), season_number: None, episode_number: None,
-            indexed_at: Utc::now().to_rfc3339(), last_modified: Utc::now().to_rfc3339(), is_deleted: false, metadata_json: None,
+            indexed_at: Utc::now().to_rfc3339(), last_modified: Utc::now().to_rfc3339(), is_deleted: false, metadata_json: None, is_locked: false,
             id: None,
         };
         add_media_file(&conn, &media1)?;
@@ -590,7 +650,7 @@ mod tests {
             duration: Some(100),
             codec: None, resolution: None, bitrate: None, framerate: None, audio_codec: None, audio_channels: None,
             year: Some(2020), season_number: None, episode_number: None,
-            indexed_at: Utc::now().to_rfc3339(), last_modified: Utc::now().to_rfc3339(), is_deleted: false, metadata_json: None,
+            indexed_at: Utc::now().to_rfc3339(), last_modified: Utc::now().to_rfc3339(), is_deleted: false, metadata_json: None, is_locked: false,
             id: None,
         };
         add_media_file(&conn, &media2)?;
@@ -604,7 +664,7 @@ mod tests {
         )?;
 
         // Add Rule: Title contains "Action"
-        add_playlist_rule(&conn, playlist_id, "title", "contains", "Action")?;
+        add_playlist_rule(&conn, playlist_id, "title", "contains", "Action", None)?;
 
         // Get items
         let items = get_playlist_media(&conn, playlist_id)?;
@@ -612,7 +672,7 @@ mod tests {
         assert_eq!(items[0].title.as_ref().unwrap(), "Action Movie");
 
         // Add Rule: Year > 2022 (AND logic)
-        add_playlist_rule(&conn, playlist_id, "year", "gt", "2022")?;
+        add_playlist_rule(&conn, playlist_id, "year", "gt", "2022", None)?;
 
         let items2 = get_playlist_media(&conn, playlist_id)?;
         assert_eq!(items2.len(), 1);
@@ -625,7 +685,7 @@ mod tests {
     }
 
     #[test]
-    fn test_smart_playlist_operators() -> Result<()> {
+    fn test_smart_playlist_operators() -> std::result::Result<(), RuleError> {
         let conn = init_db()?;
 
         // Add media with different years
@@ -635,7 +695,7 @@ mod tests {
             media_type: MediaType::Movie,
             file_path: "/test/1.mp4".to_string(), file_hash: "h1".to_string(), file_name: "1.mp4".to_string(), file_size: 1, duration: Some(100),
             codec: None, resolution: None, bitrate: None, framerate: None, audio_codec: None, audio_channels: None,
-            season_number: None, episode_number: None, indexed_at: Utc::now().to_rfc3339(), last_modified: Utc::now().to_rfc3339(), is_deleted: false, metadata_json: None, id: None,
+            season_number: None, episode_number: None, indexed_at: Utc::now().to_rfc3339(), last_modified: Utc::now().to_rfc3339(), is_deleted: false, metadata_json: None, id: None, is_locked: false,
         };
         add_media_file(&conn, &m1)?;
 
@@ -645,14 +705,14 @@ mod tests {
             media_type: MediaType::Movie,
             file_path: "/test/2.mp4".to_string(), file_hash: "h2".to_string(), file_name: "2.mp4".to_string(), file_size: 1, duration: Some(100),
             codec: None, resolution: None, bitrate: None, framerate: None, audio_codec: None, audio_channels: None,
-            season_number: None, episode_number: None, indexed_at: Utc::now().to_rfc3339(), last_modified: Utc::now().to_rfc3339(), is_deleted: false, metadata_json: None, id: None,
+            season_number: None, episode_number: None, indexed_at: Utc::now().to_rfc3339(), last_modified: Utc::now().to_rfc3339(), is_deleted: false, metadata_json: None, id: None, is_locked: false,
         };
         add_media_file(&conn, &m2)?;
 
         let pid = create_playlist(&conn, "Year Test", None, PlaylistType::Smart)?;
 
         // Test Greater Than
-        add_playlist_rule(&conn, pid, "year", "gt", "2000")?;
+        add_playlist_rule(&conn, pid, "year", "gt", "2000", None)?;
         let items = get_playlist_media(&conn, pid)?;
         assert_eq!(items.len(), 1);
         assert_eq!(items[0].title.as_ref().unwrap(), "New Movie");
@@ -662,11 +722,242 @@ mod tests {
         // Actually since we don't know ID, let's delete all rules for playlist
         conn.execute("DELETE FROM playlist_rules WHERE playlist_id = ?1", params![pid])?;
 
-        add_playlist_rule(&conn, pid, "year", "lt", "2000")?;
+        add_playlist_rule(&conn, pid, "year", "lt", "2000", None)?;
         let items2 = get_playlist_media(&conn, pid)?;
         assert_eq!(items2.len(), 1);
         assert_eq!(items2[0].title.as_ref().unwrap(), "Old Movie");
 
         Ok(())
     }
+
+    fn add_rule_media(conn: &Connection, title: &str, year: i32, codec: &str, resolution: &str) -> Result<i64> {
+        let media = MediaFile {
+            id: None,
+            file_path: format!("/test/{}.mkv", title),
+            file_hash: title.to_string(),
+            file_name: format!("{}.mkv", title),
+            file_size: 1000,
+            media_type: MediaType::Movie,
+            duration: Some(6000),
+            codec: Some(codec.to_string()), resolution: Some(resolution.to_string()),
+            bitrate: None, framerate: None, audio_codec: None, audio_channels: None,
+            title: Some(title.to_string()), year: Some(year), season_number: None, episode_number: None,
+            indexed_at: Utc::now().to_rfc3339(), last_modified: Utc::now().to_rfc3339(), is_deleted: false,
+            metadata_json: None, is_locked: false,
+        };
+        add_media_file(conn, &media)
+    }
+
+    fn smart_titles(conn: &Connection, playlist_id: i64) -> Vec<String> {
+        let mut titles: Vec<String> = get_playlist_media(conn, playlist_id)
+            .unwrap()
+            .into_iter()
+            .filter_map(|item| item.title)
+            .collect();
+        titles.sort();
+        titles
+    }
+
+    #[test]
+    fn test_smart_playlist_nested_groups() -> std::result::Result<(), RuleError> {
+        let conn = init_db()?;
+        add_rule_media(&conn, "Alien", 1979, "h264", "1920x1040")?;
+        add_rule_media(&conn, "Arrival", 2016, "hevc", "3840x2160")?;
+        add_rule_media(&conn, "Blade Runner", 1982, "hevc", "1920x800")?;
+        add_rule_media(&conn, "Heat", 1995, "h264", "720x480")?;
+
+        // year < 1990 AND (codec = hevc OR resolution >= 4k)
+        let pid = create_playlist(&conn, "Nested", None, PlaylistType::Smart)?;
+        add_playlist_rule(&conn, pid, "year", "lt", "1990", None)?;
+        let group = add_playlist_rule(&conn, pid, "group", "or", "", None)?;
+        add_playlist_rule(&conn, pid, "codec", "equals", "HEVC", Some(group))?;
+        add_playlist_rule(&conn, pid, "resolution", "gte", "4k", Some(group))?;
+        assert_eq!(smart_titles(&conn, pid), vec!["Blade Runner"]);
+
+        // A nested AND group inside the OR group
+        let inner = add_playlist_rule(&conn, pid, "group", "and", "", Some(group))?;
+        add_playlist_rule(&conn, pid, "title", "starts_with", "Al", Some(inner))?;
+        add_playlist_rule(&conn, pid, "resolution", "equals", "1080p", Some(inner))?;
+        assert_eq!(smart_titles(&conn, pid), vec!["Alien", "Blade Runner"]);
+
+        // Deleting a group removes the rules inside it
+        delete_playlist_rule(&conn, group)?;
+        assert_eq!(get_playlist_rules(&conn, pid)?.len(), 1);
+        assert_eq!(smart_titles(&conn, pid), vec!["Alien", "Blade Runner"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_smart_playlist_new_fields() -> std::result::Result<(), RuleError> {
+        let conn = init_db()?;
+        let alien = add_rule_media(&conn, "Alien", 1979, "h264", "1920x1040")?;
+        let arrival = add_rule_media(&conn, "Arrival", 2016, "hevc", "3840x2160")?;
+        let heat = add_rule_media(&conn, "Heat", 1995, "h264", "720x480")?;
+
+        conn.execute(
+            "INSERT INTO playback_state (media_id, last_position, completed, watch_count, last_played_at)
+             VALUES (?1, 0, 1, 3, datetime('now', '-2 days')), (?2, 600, 0, 1, '2001-01-01 00:00:00')",
+            params![alien, heat],
+        )?;
+        conn.execute("INSERT INTO collections (name) VALUES ('Sci-Fi')", [])?;
+        let collection = conn.last_insert_rowid();
+        conn.execute(
+            "INSERT INTO collection_items (collection_id, media_id) VALUES (?1, ?2), (?1, ?3)",
+            params![collection, alien, arrival],
+        )?;
+        conn.execute(
+            "INSERT INTO audio_tracks (media_id, file_path, language) VALUES (?1, '/a', 'fra')",
+            params![arrival],
+        )?;
+
+        let check = |rule_type: &str, operator: &str, value: &str| -> std::result::Result<Vec<String>, RuleError> {
+            let pid = create_playlist(&conn, rule_type, None, PlaylistType::Smart)?;
+            add_playlist_rule(&conn, pid, rule_type, operator, value, None)?;
+            Ok(smart_titles(&conn, pid))
+        };
+
+        assert_eq!(check("watch_status", "equals", "unwatched")?, vec!["Arrival", "Heat"]);
+        assert_eq!(check("watch_status", "equals", "in_progress")?, vec!["Heat"]);
+        assert_eq!(check("play_count", "gte", "1")?, vec!["Alien", "Heat"]);
+        assert_eq!(check("last_played", "in_last", "1w")?, vec!["Alien"]);
+        assert_eq!(check("last_played", "not_in_last", "30")?, vec!["Arrival", "Heat"]);
+        assert_eq!(check("date_added", "after", "2001-01-01")?, vec!["Alien", "Arrival", "Heat"]);
+        assert_eq!(check("file_size", "between", "500..2000")?, vec!["Alien", "Arrival", "Heat"]);
+        assert_eq!(check("collection", "not_in", &collection.to_string())?, vec!["Heat"]);
+        assert_eq!(check("audio_language", "equals", "FRA")?, vec!["Arrival"]);
+        assert_eq!(check("resolution", "equals", "sd")?, vec!["Heat"]);
+        assert_eq!(check("duration", "greater_than", "3600")?, vec!["Alien", "Arrival", "Heat"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_playlist_rule_validation() -> Result<()> {
+        let conn = init_db()?;
+        let pid = create_playlist(&conn, "Invalid", None, PlaylistType::Smart)?;
+
+        let err = |rule_type: &str, operator: &str, value: &str| {
+            add_playlist_rule(&conn, pid, rule_type, operator, value, None).unwrap_err().to_string()
+        };
+
+        assert_eq!(err("mood", "gt", "5"), "Unknown rule field 'mood'");
+        assert_eq!(
+            err("year", "contains", "19"),
+            "Operator 'contains' is not supported for 'year' (expected one of: equals, notequals, gt, gte, lt, lte, between)"
+        );
+        assert!(err("year", "gt", "last year").starts_with("Invalid value 'last year' for 'year'"));
+        assert!(err("media_type", "equals", "book").starts_with("Invalid value 'book' for 'media_type'"));
+        assert!(err("last_played", "before", "yesterday").starts_with("Invalid value"));
+        assert!(err("group", "xor", "").starts_with("Operator 'xor' is not supported for 'group'"));
+
+        // Parents must be groups of the same playlist
+        let rule = add_playlist_rule(&conn, pid, "year", "gt", "2000", None).unwrap();
+        assert!(matches!(
+            add_playlist_rule(&conn, pid, "year", "lt", "2010", Some(rule)),
+            Err(RuleError::InvalidParent(id)) if id == rule
+        ));
+        assert_eq!(get_playlist_rules(&conn, pid)?.len(), 1);
+
+        // Legacy rows that bypassed validation are reported instead of ignored
+        conn.execute(
+            "INSERT INTO playlist_rules (playlist_id, rule_type, operator, value) VALUES (?1, 'mood', 'gt', '5')",
+            params![pid],
+        )?;
+        assert!(matches!(get_playlist_media(&conn, pid), Err(RuleError::UnknownField(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_smart_playlist_sort_and_limit() -> std::result::Result<(), RuleError> {
+        let conn = init_db()?;
+        for (title, year) in [("Alien", 1979), ("Heat", 1995), ("Arrival", 2016), ("Dune", 2021)] {
+            add_rule_media(&conn, title, year, "h264", "1920x1080")?;
+        }
+        let watched: i64 = conn.query_row("SELECT id FROM media_files WHERE title = 'Dune'", [], |row| row.get(0))?;
+        conn.execute("INSERT INTO playback_state (media_id, completed) VALUES (?1, 1)", params![watched])?;
+
+        // "2 newest unwatched movies"
+        let pid = create_playlist(&conn, "Newest unwatched", None, PlaylistType::Smart)?;
+        add_playlist_rule(&conn, pid, "media_type", "equals", "movie", None)?;
+        add_playlist_rule(&conn, pid, "watch_status", "equals", "unwatched", None)?;
+        let settings = SmartPlaylistSettings { sort: Some(SortKey::Year), limit: Some(2), ..Default::default() };
+        set_smart_playlist_settings(&conn, pid, &settings)?;
+        assert_eq!(get_smart_playlist_settings(&conn, pid)?, settings);
+
+        let items = get_playlist_media(&conn, pid)?;
+        let titles: Vec<_> = items.iter().map(|i| i.title.clone().unwrap()).collect();
+        assert_eq!(titles, vec!["Arrival", "Heat"]);
+        assert_eq!(items.iter().map(|i| i.position).collect::<Vec<_>>(), vec![0, 1]);
+
+        // Default stays title A-Z
+        set_smart_playlist_settings(&conn, pid, &SmartPlaylistSettings::default())?;
+        let titles: Vec<_> = get_playlist_media(&conn, pid)?.into_iter().map(|i| i.title.unwrap()).collect();
+        assert_eq!(titles, vec!["Alien", "Arrival", "Heat"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_smart_playlist_stable_shuffle() -> std::result::Result<(), RuleError> {
+        let conn = init_db()?;
+        for i in 0..20 {
+            add_rule_media(&conn, &format!("Movie {:02}", i), 2000 + i, "h264", "1920x1080")?;
+        }
+
+        let pid = create_playlist(&conn, "Shuffle", None, PlaylistType::Smart)?;
+        add_playlist_rule(&conn, pid, "year", "gte", "2000", None)?;
+        let settings = SmartPlaylistSettings { random_seed: Some(42), limit: Some(10), ..Default::default() };
+        set_smart_playlist_settings(&conn, pid, &settings)?;
+
+        let ids = |conn: &Connection| -> Vec<i64> { get_playlist_media(conn, pid).unwrap().into_iter().map(|i| i.id).collect() };
+        let first = ids(&conn);
+        assert_eq!(first.len(), 10);
+        assert_eq!(ids(&conn), first, "same seed gives the same order");
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_ne!(sorted, first, "items are actually shuffled");
+
+        let seed = reshuffle_smart_playlist(&conn, pid)?;
+        assert_eq!(get_smart_playlist_settings(&conn, pid)?.random_seed, Some(seed));
+        assert_eq!(get_smart_playlist_settings(&conn, pid)?.limit, Some(10));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_playlist_counts_include_smart_playlists() -> std::result::Result<(), RuleError> {
+        let conn = init_db()?;
+        let alien = add_rule_media(&conn, "Alien", 1979, "h264", "1920x1080")?;
+        add_rule_media(&conn, "Heat", 1995, "h264", "1920x1080")?;
+        add_rule_media(&conn, "Arrival", 2016, "hevc", "3840x2160")?;
+
+        let manual = create_playlist(&conn, "Manual", None, PlaylistType::Manual)?;
+        add_media_to_playlist(&conn, manual, alien)?;
+
+        let smart = create_playlist(&conn, "Smart", None, PlaylistType::Smart)?;
+        add_playlist_rule(&conn, smart, "codec", "equals", "h264", None)?;
+
+        let limited = create_playlist(&conn, "Limited", None, PlaylistType::Smart)?;
+        set_smart_playlist_settings(&conn, limited, &SmartPlaylistSettings { limit: Some(1), ..Default::default() })?;
+        add_playlist_rule(&conn, limited, "year", "gt", "1900", None)?;
+
+        let broken = create_playlist(&conn, "Broken", None, PlaylistType::Smart)?;
+        conn.execute(
+            "INSERT INTO playlist_rules (playlist_id, rule_type, operator, value) VALUES (?1, 'mood', 'gt', '5')",
+            params![broken],
+        )?;
+
+        let counts = get_playlists_with_counts(&conn)?;
+        let find = |id: i64| counts.iter().find(|p| p.id == id).unwrap();
+
+        assert_eq!((find(manual).item_count, find(manual).total_duration), (1, 6000));
+        assert_eq!((find(smart).item_count, find(smart).total_duration), (2, 12000));
+        assert_eq!(find(limited).item_count, 1);
+        assert_eq!(find(broken).item_count, 0);
+        assert!(find(broken).rule_error.as_deref().unwrap().contains("mood"));
+
+        Ok(())
+    }
 }
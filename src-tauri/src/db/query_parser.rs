@@ -0,0 +1,888 @@
+//! Search query language for the library search box.
+//!
+//! Queries combine free text with `field:value` filters, e.g.
+//! `codec:hevc year:>2015 res:4k unwatched "director:nolan"`.
+//!
+//! - Terms separated by spaces are ANDed; `OR` (or `|`) between terms and
+//!   parentheses build groups: `(genre:horror OR genre:thriller) year:1980..1989`
+//! - `-term`, `!term` or `NOT term` negates
+//! - Numeric fields accept `>`, `>=`, `<`, `<=`, `=` and `a..b` ranges
+//! - Quoted text is a phrase; `"field:value with spaces"` and
+//!   `field:"value with spaces"` are both accepted
+//! - Bare words search the full-text index; bare `watched`, `unwatched` and
+//!   `inprogress` are playback filters
+//!
+//! Queries compile to a parameterised SQL condition over `media_files m`.
+
+use rusqlite::types::Value;
+use super::facets::{facet_condition, Facet, RATING_SQL};
+use super::operations::{resolution_class, resolution_class_sql};
+
+/// Error produced for a malformed query
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{message} (at position {})", .position + 1)]
+pub struct QueryError {
+    pub message: String,
+    /// Character offset into the query (0-based)
+    pub position: usize,
+}
+
+impl QueryError {
+    fn new(message: impl Into<String>, position: usize) -> Self {
+        QueryError { message: message.into(), position }
+    }
+}
+
+/// Error from running a filter that includes a query expression
+#[derive(Debug, thiserror::Error)]
+pub enum FilterError {
+    #[error("Invalid search query: {0}")]
+    Query(#[from] QueryError),
+
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+/// A compiled query: an SQL condition using `?N` placeholders and its values
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlFilter {
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+/// Parse a query and compile it into an SQL condition whose placeholders start at `?{first_param}`
+pub fn compile_query(query: &str, first_param: usize) -> Result<Option<SqlFilter>, QueryError> {
+    let Some(expr) = parse_query(query)? else {
+        return Ok(None);
+    };
+
+    let mut compiler = Compiler { params: Vec::new(), next_param: first_param };
+    let sql = compiler.compile(&expr)?;
+
+    Ok(Some(SqlFilter { sql, params: compiler.params }))
+}
+
+/// Parsed query expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Term(Term),
+}
+
+/// A single search term
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub field: Option<String>,
+    pub value: String,
+    pub quoted: bool,
+    /// Position of the term in the query
+    pub position: usize,
+    /// Position of the value (after `field:`)
+    pub value_position: usize,
+}
+
+/// Parse a query into an expression tree; returns None for an empty query
+pub fn parse_query(query: &str) -> Result<Option<Expr>, QueryError> {
+    let tokens = tokenize(query)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+
+    let mut parser = Parser { tokens, pos: 0, end: query.chars().count() };
+    let expr = parser.parse_or()?;
+
+    if let Some(token) = parser.peek() {
+        // parse_or only stops early at an unmatched ')'
+        return Err(QueryError::new("Unmatched ')'", token.position));
+    }
+
+    Ok(Some(expr))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    Or,
+    And,
+    Not,
+    Term(Term),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, QueryError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        match c {
+            '(' => {
+                tokens.push(Token { kind: TokenKind::LParen, position: i });
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token { kind: TokenKind::RParen, position: i });
+                i += 1;
+            }
+            '|' => {
+                tokens.push(Token { kind: TokenKind::Or, position: i });
+                i += 1;
+            }
+            // Negation prefix binds to the following term or group
+            '-' | '!' if i + 1 < chars.len() && !chars[i + 1].is_whitespace() => {
+                tokens.push(Token { kind: TokenKind::Not, position: i });
+                i += 1;
+            }
+            '"' => {
+                let (text, next) = read_quoted(&chars, i)?;
+                let term = quoted_term(&text, i);
+                if term.field.is_some() || is_searchable(&term.value) {
+                    tokens.push(Token { kind: TokenKind::Term(term), position: i });
+                } else {
+                    skip_term(&mut tokens);
+                }
+                i = next;
+            }
+            _ => {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && !matches!(chars[i], '(' | ')' | ':' | '"') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+
+                if i < chars.len() && chars[i] == ':' {
+                    // field:value or field:"quoted value"
+                    let field = word.to_lowercase();
+                    i += 1;
+                    let value_position = i;
+
+                    let (value, quoted) = if i < chars.len() && chars[i] == '"' {
+                        let (text, next) = read_quoted(&chars, i)?;
+                        i = next;
+                        (text, true)
+                    } else {
+                        let value_start = i;
+                        while i < chars.len() && !chars[i].is_whitespace() && !matches!(chars[i], '(' | ')') {
+                            i += 1;
+                        }
+                        (chars[value_start..i].iter().collect::<String>(), false)
+                    };
+
+                    if field.is_empty() {
+                        return Err(QueryError::new("Expected a field name before ':'", start));
+                    }
+                    if value.is_empty() {
+                        return Err(QueryError::new(format!("Expected a value after '{}:'", field), value_position));
+                    }
+
+                    tokens.push(Token {
+                        kind: TokenKind::Term(Term {
+                            field: Some(field),
+                            value,
+                            quoted,
+                            position: start,
+                            value_position,
+                        }),
+                        position: start,
+                    });
+                } else {
+                    let kind = match word.as_str() {
+                        "OR" => TokenKind::Or,
+                        "AND" => TokenKind::And,
+                        "NOT" => TokenKind::Not,
+                        _ if !is_searchable(&word) => {
+                            skip_term(&mut tokens);
+                            continue;
+                        }
+                        _ => TokenKind::Term(Term {
+                            field: None,
+                            value: word,
+                            quoted: false,
+                            position: start,
+                            value_position: start,
+                        }),
+                    };
+                    tokens.push(Token { kind, position: start });
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Free text without a letter or digit would be an empty full-text phrase
+fn is_searchable(text: &str) -> bool {
+    text.chars().any(char::is_alphanumeric)
+}
+
+/// Drop a term that searches for nothing, with the negation in front of it
+fn skip_term(tokens: &mut Vec<Token>) {
+    if matches!(tokens.last(), Some(Token { kind: TokenKind::Not, .. })) {
+        tokens.pop();
+    }
+}
+
+/// Read a double-quoted string starting at `start`; returns the text and the index after the closing quote
+fn read_quoted(chars: &[char], start: usize) -> Result<(String, usize), QueryError> {
+    let mut i = start + 1;
+    let mut text = String::new();
+
+    while i < chars.len() {
+        match chars[i] {
+            '"' => return Ok((text, i + 1)),
+            '\\' if i + 1 < chars.len() => {
+                text.push(chars[i + 1]);
+                i += 2;
+            }
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    Err(QueryError::new("Unterminated quote", start))
+}
+
+/// A quoted token is a phrase, unless it looks like `"field:value"` for a known field
+fn quoted_term(text: &str, position: usize) -> Term {
+    if let Some((field, value)) = text.split_once(':') {
+        let field = field.trim().to_lowercase();
+        if is_known_field(&field) && !value.trim().is_empty() {
+            return Term {
+                value_position: position + 1 + text.find(':').unwrap_or(0) + 1,
+                field: Some(field),
+                value: value.trim().to_string(),
+                quoted: true,
+                position,
+            };
+        }
+    }
+
+    Term { field: None, value: text.to_string(), quoted: true, position, value_position: position + 1 }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn position(&self) -> usize {
+        self.peek().map(|t| t.position).unwrap_or(self.end)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut branches = vec![self.parse_and()?];
+
+        while let Some(Token { kind: TokenKind::Or, .. }) = self.peek() {
+            self.pos += 1;
+            branches.push(self.parse_and()?);
+        }
+
+        Ok(if branches.len() == 1 { branches.remove(0) } else { Expr::Or(branches) })
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut terms = Vec::new();
+
+        loop {
+            match self.peek().map(|t| &t.kind) {
+                None | Some(TokenKind::Or) | Some(TokenKind::RParen) => break,
+                Some(TokenKind::And) => {
+                    if terms.is_empty() {
+                        return Err(QueryError::new("'AND' needs a term on its left", self.position()));
+                    }
+                    self.pos += 1;
+                    if matches!(self.peek().map(|t| &t.kind), None | Some(TokenKind::Or) | Some(TokenKind::RParen)) {
+                        return Err(QueryError::new("'AND' needs a term on its right", self.position()));
+                    }
+                }
+                Some(_) => terms.push(self.parse_unary()?),
+            }
+        }
+
+        match terms.len() {
+            0 => {
+                let message = match self.peek().map(|t| &t.kind) {
+                    Some(TokenKind::Or) => "'OR' needs a term on its left",
+                    Some(TokenKind::RParen) => "Unmatched ')'",
+                    _ => "Expected a search term",
+                };
+                Err(QueryError::new(message, self.position()))
+            }
+            1 => Ok(terms.remove(0)),
+            _ => Ok(Expr::And(terms)),
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, QueryError> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(QueryError::new("Expected a search term", self.end));
+        };
+
+        match token.kind {
+            TokenKind::Not => {
+                self.pos += 1;
+                if matches!(self.peek().map(|t| &t.kind), None | Some(TokenKind::Or) | Some(TokenKind::RParen)) {
+                    return Err(QueryError::new("Nothing to negate", token.position));
+                }
+                Ok(Expr::Not(Box::new(self.parse_unary()?)))
+            }
+            TokenKind::LParen => {
+                self.pos += 1;
+                if let Some(Token { kind: TokenKind::RParen, position }) = self.peek() {
+                    return Err(QueryError::new("Empty group", *position));
+                }
+                let expr = self.parse_or()?;
+                match self.peek() {
+                    Some(Token { kind: TokenKind::RParen, .. }) => {
+                        self.pos += 1;
+                        Ok(expr)
+                    }
+                    _ => Err(QueryError::new("Missing ')' for this '('", token.position)),
+                }
+            }
+            TokenKind::Term(term) => {
+                self.pos += 1;
+                Ok(Expr::Term(term))
+            }
+            TokenKind::RParen => Err(QueryError::new("Unmatched ')'", token.position)),
+            TokenKind::Or | TokenKind::And => Err(QueryError::new("Expected a search term", token.position)),
+        }
+    }
+}
+
+/// Field names (and aliases) understood by the compiler
+const FIELDS: &[&str] = &[
+    "title", "file", "filename", "path", "year", "codec", "vcodec", "audio", "acodec", "channels",
+    "res", "resolution", "type", "duration", "runtime", "size", "season", "episode",
+    "genre", "keyword", "country", "lang", "language", "rating",
+    "cast", "actor", "director", "writer", "is",
+];
+
+fn is_known_field(field: &str) -> bool {
+    FIELDS.contains(&field)
+}
+
+/// Comparison parsed from a numeric field value
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Eq(f64),
+    Gt(f64),
+    Gte(f64),
+    Lt(f64),
+    Lte(f64),
+    Between(f64, f64),
+}
+
+struct Compiler {
+    params: Vec<Value>,
+    next_param: usize,
+}
+
+impl Compiler {
+    fn param(&mut self, value: Value) -> String {
+        self.params.push(value);
+        let placeholder = format!("?{}", self.next_param);
+        self.next_param += 1;
+        placeholder
+    }
+
+    fn compile(&mut self, expr: &Expr) -> Result<String, QueryError> {
+        match expr {
+            Expr::And(items) => self.join(items, " AND "),
+            Expr::Or(items) => self.join(items, " OR "),
+            Expr::Not(inner) => Ok(format!("NOT ({})", self.compile(inner)?)),
+            Expr::Term(term) => self.compile_term(term),
+        }
+    }
+
+    fn join(&mut self, items: &[Expr], separator: &str) -> Result<String, QueryError> {
+        let parts = items
+            .iter()
+            .map(|item| self.compile(item))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("({})", parts.join(separator)))
+    }
+
+    fn compile_term(&mut self, term: &Term) -> Result<String, QueryError> {
+        let Some(field) = term.field.as_deref() else {
+            return Ok(self.compile_text(term));
+        };
+
+        let value = term.value.as_str();
+        let lower = value.to_lowercase();
+
+        match field {
+            "title" => Ok(format!("m.title LIKE {} ESCAPE '\\'", self.param(like_pattern(value)))),
+            "file" | "filename" => Ok(format!("m.file_name LIKE {} ESCAPE '\\'", self.param(like_pattern(value)))),
+            "path" => Ok(format!("m.file_path LIKE {} ESCAPE '\\'", self.param(like_pattern(value)))),
+            "year" => self.compile_number("m.year", term, 1.0),
+            "season" => self.compile_number("m.season_number", term, 1.0),
+            "episode" => self.compile_number("m.episode_number", term, 1.0),
+            "channels" => self.compile_number("m.audio_channels", term, 1.0),
+            "duration" | "runtime" => self.compile_duration(term),
+            "size" => self.compile_size(term),
+            "codec" | "vcodec" => Ok(self.compile_in("LOWER(m.codec)", &codec_aliases(&lower))),
+            "audio" | "acodec" => Ok(self.compile_in("LOWER(m.audio_codec)", &audio_codec_aliases(&lower))),
+            "res" | "resolution" => self.compile_resolution(term),
+            "type" => {
+                let media_type = match lower.as_str() {
+                    "movie" | "movies" | "film" => "movie",
+                    "tv" | "show" | "episode" | "tv_episode" => "tv_episode",
+                    "music" | "song" => "music",
+                    "video" => "video",
+                    "audio" => "audio",
+                    _ => {
+                        return Err(QueryError::new(
+                            format!("Unknown media type '{}' (use movie, tv, music, video or audio)", value),
+                            term.value_position,
+                        ))
+                    }
+                };
+                Ok(format!("m.media_type = {}", self.param(Value::Text(media_type.to_string()))))
+            }
+            "genre" => {
+                let from_metadata = self.compile_json_list("genres", None, value);
+                Ok(format!("({} OR {})", from_metadata, self.compile_facet(Facet::Genre, value)))
+            }
+            "keyword" => Ok(self.compile_facet(Facet::Keyword, value)),
+            "country" => Ok(self.compile_facet(Facet::Country, value)),
+            "lang" | "language" => Ok(self.compile_facet(Facet::Language, value)),
+            "rating" => self.compile_number(RATING_SQL, term, 1.0),
+            "cast" | "actor" => Ok(self.compile_credit("cast", Some("name"), "cast", value)),
+            "director" => Ok(self.compile_credit("directors", None, "director", value)),
+            "writer" => Ok(self.compile_credit("writers", None, "writer", value)),
+            "is" => playback_flag(&lower).ok_or_else(|| {
+                QueryError::new(
+                    format!("Unknown flag 'is:{}' (use watched, unwatched, inprogress or locked)", value),
+                    term.value_position,
+                )
+            }),
+            _ => Err(QueryError::new(format!("Unknown field '{}'", field), term.position)),
+        }
+    }
+
+    /// Bare words and phrases search the full-text index
+    fn compile_text(&mut self, term: &Term) -> String {
+        if !term.quoted {
+            if let Some(flag) = playback_flag(&term.value.to_lowercase()) {
+                return flag;
+            }
+        }
+
+        let escaped = term.value.replace('"', "\"\"");
+        let fts = if term.quoted { format!("\"{}\"", escaped) } else { format!("\"{}\"*", escaped) };
+        format!(
+            "m.id IN (SELECT rowid FROM media_search WHERE media_search MATCH {})",
+            self.param(Value::Text(fts))
+        )
+    }
+
+    fn compile_number(&mut self, column: &str, term: &Term, scale: f64) -> Result<String, QueryError> {
+        let comparison = parse_comparison(&term.value, term.value_position, |s| s.trim().parse::<f64>().ok())?;
+        Ok(self.compile_comparison(column, comparison, scale))
+    }
+
+    fn compile_duration(&mut self, term: &Term) -> Result<String, QueryError> {
+        let comparison = parse_comparison(&term.value, term.value_position, parse_minutes)?;
+        Ok(self.compile_comparison("m.duration", comparison, 60.0))
+    }
+
+    fn compile_size(&mut self, term: &Term) -> Result<String, QueryError> {
+        let comparison = parse_comparison(&term.value, term.value_position, parse_megabytes)?;
+        Ok(self.compile_comparison("m.file_size", comparison, 1024.0 * 1024.0))
+    }
+
+    fn compile_comparison(&mut self, column: &str, comparison: Comparison, scale: f64) -> String {
+        let mut value = |v: f64| {
+            let scaled = v * scale;
+            if scaled.fract() == 0.0 {
+                self.param(Value::Integer(scaled as i64))
+            } else {
+                self.param(Value::Real(scaled))
+            }
+        };
+
+        match comparison {
+            Comparison::Eq(v) => format!("{} = {}", column, value(v)),
+            Comparison::Gt(v) => format!("{} > {}", column, value(v)),
+            Comparison::Gte(v) => format!("{} >= {}", column, value(v)),
+            Comparison::Lt(v) => format!("{} < {}", column, value(v)),
+            Comparison::Lte(v) => format!("{} <= {}", column, value(v)),
+            Comparison::Between(a, b) => {
+                let low = value(a);
+                let high = value(b);
+                format!("{} BETWEEN {} AND {}", column, low, high)
+            }
+        }
+    }
+
+    /// Resolution compares buckets, so `res:1080p` matches 1920x800 and `res:>=4k` matches 3840x1600
+    fn compile_resolution(&mut self, term: &Term) -> Result<String, QueryError> {
+        let comparison = parse_comparison(&term.value, term.value_position, |v| {
+            resolution_class(v).map(|class| class as f64)
+        })?;
+        Ok(self.compile_comparison(&resolution_class_sql("m"), comparison, 1.0))
+    }
+
+    fn compile_in(&mut self, column: &str, values: &[String]) -> String {
+        let placeholders = values
+            .iter()
+            .map(|v| self.param(Value::Text(v.clone())))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{} IN ({})", column, placeholders)
+    }
+
+    /// Match against a JSON array in metadata_json (optionally an object key of each element)
+    fn compile_json_list(&mut self, key: &str, object_key: Option<&str>, value: &str) -> String {
+        let item = match object_key {
+            Some(k) => format!("json_extract(j.value, '$.{}')", k),
+            None => "j.value".to_string(),
+        };
+        format!(
+            "EXISTS (SELECT 1 FROM json_each(CASE WHEN json_valid(m.metadata_json) THEN m.metadata_json END, '$.{}') j WHERE {} LIKE {} ESCAPE '\\')",
+            key,
+            item,
+            self.param(like_pattern(value))
+        )
+    }
+
+    /// A TMDB facet of the matched title
+    fn compile_facet(&mut self, facet: Facet, value: &str) -> String {
+        facet_condition(facet, &[value.to_string()], |s| self.param(Value::Text(s))).unwrap_or_else(|| "0".to_string())
+    }
+
+    /// A person credit, from NFO metadata or the matched title's TMDB credits
+    fn compile_credit(&mut self, key: &str, object_key: Option<&str>, role: &str, value: &str) -> String {
+        let from_metadata = self.compile_json_list(key, object_key, value);
+        format!(
+            "({} OR EXISTS (SELECT 1 FROM tmdb_media t JOIN tmdb_cast c ON c.tmdb_media_id = t.tmdb_id AND c.media_type = t.media_type \
+             WHERE t.media_id = m.id AND c.role = {} AND c.name LIKE {} ESCAPE '\\'))",
+            from_metadata,
+            self.param(Value::Text(role.to_string())),
+            self.param(like_pattern(value))
+        )
+    }
+}
+
+/// `%value%` with LIKE wildcards in the value matched literally (`ESCAPE '\'`)
+fn like_pattern(value: &str) -> Value {
+    Value::Text(format!("%{}%", escape_like(value)))
+}
+
+/// Escape LIKE wildcards so a pattern used with `ESCAPE '\'` matches them literally
+pub(crate) fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+fn playback_flag(flag: &str) -> Option<String> {
+    let sql = match flag {
+        "watched" | "seen" => "EXISTS (SELECT 1 FROM playback_state ps WHERE ps.media_id = m.id AND ps.completed = 1)",
+        "unwatched" | "unseen" => "NOT EXISTS (SELECT 1 FROM playback_state ps WHERE ps.media_id = m.id AND ps.completed = 1)",
+        "inprogress" | "in-progress" | "started" => {
+            "EXISTS (SELECT 1 FROM playback_state ps WHERE ps.media_id = m.id AND ps.completed = 0 AND ps.last_position > 0)"
+        }
+        "locked" => "m.is_locked = 1",
+        _ => return None,
+    };
+    Some(sql.to_string())
+}
+
+/// Parse a numeric filter value: `N`, `=N`, `>N`, `>=N`, `<N`, `<=N` or `A..B`
+fn parse_comparison(
+    value: &str,
+    position: usize,
+    parse: impl Fn(&str) -> Option<f64>,
+) -> Result<Comparison, QueryError> {
+    let invalid = |offset: usize, text: &str| {
+        QueryError::new(format!("Invalid value '{}'", text), position + offset)
+    };
+    let parse_at = |text: &str, offset: usize| parse(text).ok_or_else(|| invalid(offset, text));
+
+    if let Some((low, high)) = value.split_once("..") {
+        let low_value = parse_at(low, 0)?;
+        let high_value = parse_at(high, low.chars().count() + 2)?;
+        if low_value > high_value {
+            return Err(QueryError::new(format!("Range '{}' is reversed", value), position));
+        }
+        return Ok(Comparison::Between(low_value, high_value));
+    }
+
+    for (prefix, make) in [
+        (">=", Comparison::Gte as fn(f64) -> Comparison),
+        ("<=", Comparison::Lte),
+        (">", Comparison::Gt),
+        ("<", Comparison::Lt),
+        ("=", Comparison::Eq),
+    ] {
+        if let Some(rest) = value.strip_prefix(prefix) {
+            return Ok(make(parse_at(rest, prefix.len())?));
+        }
+    }
+
+    Ok(Comparison::Eq(parse_at(value, 0)?))
+}
+
+/// Durations in minutes: `90`, `90m`, `2h`, `1h30m`, `1.5h`
+fn parse_minutes(value: &str) -> Option<f64> {
+    let value = value.trim().to_lowercase();
+    if let Ok(minutes) = value.parse::<f64>() {
+        return Some(minutes);
+    }
+
+    let (hours, rest) = match value.split_once('h') {
+        Some((h, rest)) => (h.parse::<f64>().ok()?, rest),
+        None => (0.0, value.as_str()),
+    };
+    let rest = rest.trim_end_matches("min").trim_end_matches('m');
+    let minutes = if rest.is_empty() { 0.0 } else { rest.parse::<f64>().ok()? };
+
+    Some(hours * 60.0 + minutes)
+}
+
+/// Sizes in megabytes: `700`, `700mb`, `4gb`, `1.5g`, `500k`
+fn parse_megabytes(value: &str) -> Option<f64> {
+    let value = value.trim().to_lowercase();
+    let split = value.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let factor = match unit.trim_end_matches('b').trim_end_matches('i') {
+        "" | "m" => 1.0,
+        "k" => 1.0 / 1024.0,
+        "g" => 1024.0,
+        "t" => 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some(number * factor)
+}
+
+fn codec_aliases(codec: &str) -> Vec<String> {
+    let aliases: &[&str] = match codec {
+        "hevc" | "h265" | "h.265" | "x265" => &["hevc", "h265"],
+        "h264" | "h.264" | "avc" | "x264" => &["h264", "avc"],
+        "av1" => &["av1"],
+        "vp9" => &["vp9"],
+        "mpeg2" | "mpeg-2" | "mpeg2video" => &["mpeg2video", "mpeg2"],
+        "xvid" | "divx" | "mpeg4" => &["mpeg4"],
+        _ => return vec![codec.to_string()],
+    };
+    aliases.iter().map(|s| s.to_string()).collect()
+}
+
+fn audio_codec_aliases(codec: &str) -> Vec<String> {
+    let aliases: &[&str] = match codec {
+        "dd" | "ac3" => &["ac3"],
+        "ddp" | "eac3" | "dd+" => &["eac3"],
+        "dts" | "dts-hd" => &["dts"],
+        "truehd" | "atmos" => &["truehd"],
+        _ => return vec![codec.to_string()],
+    };
+    aliases.iter().map(|s| s.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use crate::db::models::{FilterCriteria, MediaFile, MediaType, PageRequest};
+    use crate::db::operations::{add_media_file, filter_media};
+    use chrono::Utc;
+    use rusqlite::Connection;
+
+    fn err(query: &str) -> QueryError {
+        compile_query(query, 1).unwrap_err()
+    }
+
+    fn titles(conn: &Connection, query: &str) -> Vec<String> {
+        let criteria = FilterCriteria {
+            query: Some(query.to_string()),
+            ..FilterCriteria::default()
+        };
+        let mut titles: Vec<String> = filter_media(conn, &criteria, &PageRequest::default())
+            .unwrap()
+            .into_iter()
+            .filter_map(|m| m.title)
+            .collect();
+        titles.sort();
+        titles
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn add(
+        conn: &Connection,
+        title: &str,
+        year: i32,
+        codec: &str,
+        resolution: &str,
+        duration: i64,
+        size: i64,
+        metadata_json: &str,
+    ) -> i64 {
+        let media = MediaFile {
+            id: None,
+            file_path: format!("/lib/{}.mkv", title),
+            file_hash: "hash".to_string(),
+            file_name: format!("{}.mkv", title),
+            file_size: size,
+            media_type: MediaType::Movie,
+            duration: Some(duration),
+            codec: Some(codec.to_string()),
+            resolution: Some(resolution.to_string()),
+            bitrate: None, framerate: None, audio_codec: Some("eac3".to_string()), audio_channels: Some(6),
+            title: Some(title.to_string()), year: Some(year), season_number: None, episode_number: None,
+            indexed_at: Utc::now().to_rfc3339(), last_modified: Utc::now().to_rfc3339(), is_deleted: false,
+            metadata_json: Some(metadata_json.to_string()),
+            is_locked: false,
+        };
+        add_media_file(conn, &media).unwrap()
+    }
+
+    fn library() -> Connection {
+        let conn = init_db().unwrap();
+        let gb = 1024 * 1024 * 1024;
+        add(&conn, "Interstellar", 2014, "hevc", "3840x2160", 10140, 40 * gb,
+            r#"{"directors": ["Christopher Nolan"], "genres": ["Science Fiction"], "cast": [{"name": "Matthew McConaughey"}]}"#);
+        let tenet = add(&conn, "Tenet", 2020, "hevc", "3840x1600", 9000, 30 * gb,
+            r#"{"directors": ["Christopher Nolan"], "genres": ["Action"], "cast": [{"name": "John David Washington"}]}"#);
+        add(&conn, "Dune", 2021, "h264", "1920x1080", 9300, 8 * gb,
+            r#"{"directors": ["Denis Villeneuve"], "genres": ["Science Fiction"]}"#);
+        add(&conn, "Alien", 1979, "h264", "1280x720", 7020, 4 * gb,
+            r#"{"directors": ["Ridley Scott"], "genres": ["Horror", "Science Fiction"]}"#);
+        conn.execute(
+            "INSERT INTO playback_state (media_id, last_position, completed, watch_count) VALUES (?1, 0, 1, 1)",
+            [tenet],
+        ).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_example_query() {
+        let conn = library();
+        assert_eq!(titles(&conn, r#"codec:hevc year:>2015 res:4k unwatched "director:nolan""#), Vec::<String>::new());
+        assert_eq!(titles(&conn, r#"codec:hevc year:>2010 res:4k unwatched "director:nolan""#), vec!["Interstellar"]);
+        assert_eq!(titles(&conn, "codec:x265 is:watched"), vec!["Tenet"]);
+    }
+
+    #[test]
+    fn test_ranges_and_units() {
+        let conn = library();
+        assert_eq!(titles(&conn, "year:1970..1999"), vec!["Alien"]);
+        assert_eq!(titles(&conn, "year:>=2020"), vec!["Dune", "Tenet"]);
+        assert_eq!(titles(&conn, "duration:>2h30m"), vec!["Dune", "Interstellar"]);
+        assert_eq!(titles(&conn, "size:<10gb"), vec!["Alien", "Dune"]);
+        assert_eq!(titles(&conn, "res:>=1080p"), vec!["Dune", "Interstellar", "Tenet"]);
+        assert_eq!(titles(&conn, "res:720p"), vec!["Alien"]);
+    }
+
+    #[test]
+    fn test_boolean_logic() {
+        let conn = library();
+        assert_eq!(titles(&conn, "genre:horror OR genre:action"), vec!["Alien", "Tenet"]);
+        assert_eq!(titles(&conn, "genre:\"science fiction\" -director:nolan"), vec!["Alien", "Dune"]);
+        assert_eq!(titles(&conn, "NOT (year:<2000 | codec:hevc)"), vec!["Dune"]);
+        assert_eq!(titles(&conn, "(cast:mcconaughey OR cast:washington) AND year:2020"), vec!["Tenet"]);
+        assert_eq!(titles(&conn, "interst"), vec!["Interstellar"]);
+        assert_eq!(titles(&conn, "type:movie !unwatched"), vec!["Tenet"]);
+    }
+
+    #[test]
+    fn test_parse_tree() {
+        let expr = parse_query("a b OR -c").unwrap().unwrap();
+        match expr {
+            Expr::Or(branches) => {
+                assert!(matches!(&branches[0], Expr::And(terms) if terms.len() == 2));
+                assert!(matches!(&branches[1], Expr::Not(_)));
+            }
+            other => panic!("unexpected tree {:?}", other),
+        }
+        assert_eq!(parse_query("   ").unwrap(), None);
+    }
+
+    #[test]
+    fn test_wildcards_and_punctuation_are_literal() {
+        let conn = library();
+        assert_eq!(titles(&conn, "title:%"), Vec::<String>::new());
+        assert_eq!(titles(&conn, "title:D_ne"), Vec::<String>::new());
+        assert_eq!(titles(&conn, "director:%nolan"), Vec::<String>::new());
+
+        // Punctuation alone searches for nothing, so it doesn't narrow the query
+        assert_eq!(titles(&conn, "dune & -- \"...\""), vec!["Dune"]);
+        assert_eq!(parse_query("- & \"!\"").unwrap(), None);
+        assert_eq!(compile_query("tenet -&", 1).unwrap().unwrap().params.len(), 1);
+    }
+
+    #[test]
+    fn test_tmdb_fields() {
+        let conn = library();
+        let dune: i64 = conn.query_row("SELECT id FROM media_files WHERE title = 'Dune'", [], |row| row.get(0)).unwrap();
+        conn.execute_batch(
+            "INSERT INTO tmdb_metadata (tmdb_id, media_type, title) VALUES (438631, 'movie', 'Dune');
+             INSERT INTO tmdb_cast (tmdb_media_id, media_type, tmdb_person_id, name, role) VALUES
+                (438631, 'movie', 1, 'Timothée Chalamet', 'cast'),
+                (438631, 'movie', 2, 'Jon Spaihts', 'writer');",
+        ).unwrap();
+        conn.execute("INSERT INTO tmdb_media (media_id, tmdb_id, media_type) VALUES (?1, 438631, 'movie')", [dune]).unwrap();
+
+        assert_eq!(titles(&conn, "cast:chalamet"), vec!["Dune"]);
+        assert_eq!(titles(&conn, "writer:spaihts"), vec!["Dune"]);
+        assert_eq!(titles(&conn, "director:spaihts"), Vec::<String>::new());
+        assert_eq!(titles(&conn, "director:villeneuve OR director:scott"), vec!["Alien", "Dune"]);
+
+        conn.execute(
+            "UPDATE tmdb_metadata SET genres_json = '[\"Adventure\"]', production_countries_json = '[\"US\"]', vote_average = 7.8",
+            [],
+        ).unwrap();
+        assert_eq!(titles(&conn, "genre:adventure rating:>7 country:us"), vec!["Dune"]);
+        assert_eq!(titles(&conn, "rating:<7"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_compiled_sql_is_parameterised() {
+        let filter = compile_query("title:\"'; DROP TABLE media_files; --\" year:2000", 3).unwrap().unwrap();
+        assert_eq!(filter.sql, "(m.title LIKE ?3 ESCAPE '\\' AND m.year = ?4)");
+        assert_eq!(filter.params[0], Value::Text("%'; DROP TABLE media\\_files; --%".to_string()));
+        assert_eq!(filter.params[1], Value::Integer(2000));
+    }
+
+    #[test]
+    fn test_error_positions() {
+        assert_eq!(err("year:>abc"), QueryError::new("Invalid value 'abc'", 6));
+        assert_eq!(err("year:2000..19x"), QueryError::new("Invalid value '19x'", 11));
+        assert_eq!(err("codec:hevc foo:bar"), QueryError::new("Unknown field 'foo'", 11));
+        assert_eq!(err("title:\"open"), QueryError::new("Unterminated quote", 6));
+        assert_eq!(err("(year:2000 OR codec:av1"), QueryError::new("Missing ')' for this '('", 0));
+        assert_eq!(err("year:2000)"), QueryError::new("Unmatched ')'", 9));
+        assert_eq!(err("year:2000 OR"), QueryError::new("Expected a search term", 12));
+        assert_eq!(err("OR year:2000"), QueryError::new("'OR' needs a term on its left", 0));
+        assert_eq!(err("()"), QueryError::new("Empty group", 1));
+        assert_eq!(err("type:book"), QueryError::new("Unknown media type 'book' (use movie, tv, music, video or audio)", 5));
+        assert_eq!(err("year: 2000"), QueryError::new("Expected a value after 'year:'", 5));
+        assert_eq!(err("year:2000..1990"), QueryError::new("Range '2000..1990' is reversed", 5));
+        assert_eq!(err("year:>abc").to_string(), "Invalid value 'abc' (at position 7)");
+    }
+}
@@ -0,0 +1,771 @@
+use std::collections::HashSet;
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::models::{MediaFile, PageRequest, SortKey};
+use super::operations::{media_file_from_row, MEDIA_COLUMNS};
+use super::playlists::{get_playlist_media, shuffle_key, time_seed};
+use super::collections::get_collection_media;
+use super::search::search_media_page;
+use super::smart_rules::RuleError;
+
+/// Number of search results queued by a search source
+const SEARCH_QUEUE_LIMIT: usize = 500;
+
+#[derive(Debug, thiserror::Error)]
+pub enum QueueError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("{0}")]
+    Rules(#[from] RuleError),
+    #[error("Nothing to play: {0}")]
+    EmptySource(String),
+    #[error("Queue item {0} not found")]
+    ItemNotFound(i64),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    /// Replay the current item when it finishes
+    One,
+    /// Start over from the top after the last item
+    All,
+}
+
+impl RepeatMode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            RepeatMode::Off => "off",
+            RepeatMode::One => "one",
+            RepeatMode::All => "all",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "off" => Some(RepeatMode::Off),
+            "one" => Some(RepeatMode::One),
+            "all" => Some(RepeatMode::All),
+            _ => None,
+        }
+    }
+}
+
+/// What a queue is started from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QueueSource {
+    Playlist { id: i64 },
+    Collection { id: i64 },
+    /// Episodes of one season, matched on the NFO show title or a folder named after the show
+    Season { show: String, season: i32 },
+    Search { query: String },
+    Media { ids: Vec<i64> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueEntry {
+    pub queue_item_id: i64,
+    pub position: i64,
+    pub media: MediaFile,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayQueue {
+    pub current: Option<QueueEntry>,
+    /// Items after the current one, in play order
+    pub upcoming: Vec<QueueEntry>,
+    /// Items before the current one, in play order
+    pub history: Vec<QueueEntry>,
+    pub shuffle: bool,
+    pub repeat_mode: RepeatMode,
+    pub source: Option<QueueSource>,
+    pub source_label: Option<String>,
+}
+
+struct QueueState {
+    current_item_id: Option<i64>,
+    shuffle: bool,
+    repeat_mode: RepeatMode,
+}
+
+fn load_state(conn: &Connection) -> Result<QueueState> {
+    conn.query_row(
+        "SELECT current_item_id, shuffle, repeat_mode FROM play_queue_state WHERE id = 1",
+        [],
+        |row| {
+            Ok(QueueState {
+                current_item_id: row.get(0)?,
+                shuffle: row.get::<_, i32>(1)? != 0,
+                repeat_mode: RepeatMode::from_str(&row.get::<_, String>(2)?).unwrap_or_default(),
+            })
+        },
+    )
+}
+
+fn set_current(conn: &Connection, item_id: Option<i64>) -> Result<()> {
+    conn.execute(
+        "UPDATE play_queue_state SET current_item_id = ?1, updated_at = ?2 WHERE id = 1",
+        params![item_id, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// Queue item ids in play order
+fn load_order(conn: &Connection) -> Result<Vec<i64>> {
+    let mut stmt = conn.prepare("SELECT id FROM play_queue_items ORDER BY position, id")?;
+    let ids = stmt.query_map([], |row| row.get(0))?.collect::<Result<Vec<i64>>>()?;
+    Ok(ids)
+}
+
+/// Queue items whose media is still in the library
+fn live_items(conn: &Connection) -> Result<HashSet<i64>> {
+    let mut stmt = conn.prepare(
+        "SELECT q.id FROM play_queue_items q
+         JOIN media_files m ON q.media_id = m.id
+         WHERE m.is_deleted = 0",
+    )?;
+    let ids = stmt.query_map([], |row| row.get(0))?.collect::<Result<HashSet<i64>>>()?;
+    Ok(ids)
+}
+
+/// Store a new play order
+fn write_order(conn: &Connection, order: &[i64]) -> Result<()> {
+    // Queue mutations run inside their own transaction
+    let tx = if conn.is_autocommit() { Some(conn.unchecked_transaction()?) } else { None };
+    {
+        let mut stmt = conn.prepare("UPDATE play_queue_items SET position = ?1 WHERE id = ?2")?;
+        for (position, id) in order.iter().enumerate() {
+            stmt.execute(params![position as i64, id])?;
+        }
+    }
+    if let Some(tx) = tx {
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// The ids that name media still in the library, in their given order
+fn live_media_ids(conn: &Connection, media_ids: &[i64]) -> Result<Vec<i64>> {
+    let mut stmt = conn.prepare("SELECT 1 FROM media_files WHERE id = ?1 AND is_deleted = 0")?;
+    let mut live = Vec::with_capacity(media_ids.len());
+    for id in media_ids {
+        if stmt.exists(params![id])? {
+            live.push(*id);
+        }
+    }
+    Ok(live)
+}
+
+fn shuffled(mut items: Vec<i64>, seed: i64) -> Vec<i64> {
+    items.sort_by_key(|id| shuffle_key(seed, *id));
+    items
+}
+
+fn load_entry(conn: &Connection, item_id: i64) -> Result<Option<QueueEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, q.id, q.position
+         FROM play_queue_items q
+         JOIN media_files m ON q.media_id = m.id
+         WHERE q.id = ?1 AND m.is_deleted = 0",
+        MEDIA_COLUMNS
+    ))?;
+
+    stmt.query_row(params![item_id], entry_from_row).optional()
+}
+
+fn entry_from_row(row: &rusqlite::Row) -> Result<QueueEntry> {
+    Ok(QueueEntry {
+        media: media_file_from_row(row)?,
+        queue_item_id: row.get(22)?,
+        position: row.get(23)?,
+    })
+}
+
+/// Get the persisted play queue
+pub fn get_queue(conn: &Connection) -> Result<PlayQueue> {
+    let state = load_state(conn)?;
+    let (source_json, source_label): (Option<String>, Option<String>) = conn.query_row(
+        "SELECT source_json, source_label FROM play_queue_state WHERE id = 1",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, q.id, q.position
+         FROM play_queue_items q
+         JOIN media_files m ON q.media_id = m.id
+         WHERE m.is_deleted = 0
+         ORDER BY q.position, q.id",
+        MEDIA_COLUMNS
+    ))?;
+    let entries = stmt.query_map([], entry_from_row)?.collect::<Result<Vec<_>>>()?;
+
+    let mut queue = PlayQueue {
+        current: None,
+        upcoming: Vec::new(),
+        history: Vec::new(),
+        shuffle: state.shuffle,
+        repeat_mode: state.repeat_mode,
+        source: source_json.and_then(|json| serde_json::from_str(&json).ok()),
+        source_label,
+    };
+
+    // Split around the current item's slot, which holds even when its media was deleted
+    let current_key = match state.current_item_id {
+        Some(id) => conn
+            .query_row("SELECT position FROM play_queue_items WHERE id = ?1", params![id], |row| row.get::<_, i64>(0))
+            .optional()?
+            .map(|position| (position, id)),
+        None => None,
+    };
+
+    for entry in entries {
+        match current_key.map(|key| (entry.position, entry.queue_item_id).cmp(&key)) {
+            Some(std::cmp::Ordering::Less) => queue.history.push(entry),
+            Some(std::cmp::Ordering::Equal) => queue.current = Some(entry),
+            _ => queue.upcoming.push(entry),
+        }
+    }
+
+    Ok(queue)
+}
+
+/// Media ids and a display label for a queue source
+fn resolve_source(conn: &Connection, source: &QueueSource) -> std::result::Result<(Vec<i64>, Option<String>), QueueError> {
+    Ok(match source {
+        QueueSource::Playlist { id } => {
+            let name: String = conn.query_row("SELECT name FROM playlists WHERE id = ?1", params![id], |row| row.get(0))?;
+            let ids = get_playlist_media(conn, *id)?.into_iter().map(|item| item.id).collect();
+            (ids, Some(name))
+        }
+        QueueSource::Collection { id } => {
+            let name: String = conn.query_row("SELECT name FROM collections WHERE id = ?1", params![id], |row| row.get(0))?;
+            let ids = get_collection_media(conn, *id)?.into_iter().map(|item| item.id).collect();
+            (ids, Some(name))
+        }
+        QueueSource::Season { show, season } => {
+            let mut stmt = conn.prepare(
+                "SELECT id FROM media_files
+                 WHERE is_deleted = 0 AND media_type = 'tv_episode' AND season_number = ?2
+                   AND CASE
+                       WHEN json_extract(metadata_json, '$.show_title') IS NOT NULL
+                           THEN json_extract(metadata_json, '$.show_title') = ?1 COLLATE NOCASE
+                       ELSE instr(lower(replace(file_path, '\\', '/')), '/' || lower(?1) || '/') > 0
+                   END
+                 ORDER BY episode_number, file_name COLLATE NOCASE",
+            )?;
+            let ids = stmt.query_map(params![show, season], |row| row.get(0))?.collect::<Result<Vec<i64>>>()?;
+            (ids, Some(format!("{} - Season {}", show, season)))
+        }
+        QueueSource::Search { query } => {
+            let page = PageRequest {
+                sort: Some(SortKey::Relevance),
+                limit: Some(SEARCH_QUEUE_LIMIT),
+                ..PageRequest::default()
+            };
+            let ids = search_media_page(conn, query, &page)?
+                .into_iter()
+                .filter_map(|result| result.media.id)
+                .collect();
+            (ids, Some(format!("Search: {}", query)))
+        }
+        QueueSource::Media { ids } => (ids.clone(), None),
+    })
+}
+
+/// Replace the queue with the items of `source`.
+///
+/// Playback starts at `start_media_id` if it is part of the source, otherwise
+/// at the first item (a random one when shuffling). The repeat mode carries over.
+pub fn start_queue(
+    conn: &Connection,
+    source: &QueueSource,
+    start_media_id: Option<i64>,
+    shuffle: bool,
+) -> std::result::Result<PlayQueue, QueueError> {
+    let (media_ids, label) = resolve_source(conn, source)?;
+    // Checked before touching the current queue, which stays as it was if nothing is playable
+    let media_ids = live_media_ids(conn, &media_ids)?;
+    if media_ids.is_empty() {
+        return Err(QueueError::EmptySource(label.unwrap_or_else(|| "no media selected".to_string())));
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    conn.execute("DELETE FROM play_queue_items", [])?;
+
+    let now = Utc::now().to_rfc3339();
+    let mut item_ids = Vec::with_capacity(media_ids.len());
+    {
+        let mut stmt = conn.prepare(
+            "INSERT INTO play_queue_items (media_id, position, original_position, added_at)
+             VALUES (?1, ?2, ?2, ?3)",
+        )?;
+        for (position, media_id) in media_ids.iter().enumerate() {
+            stmt.execute(params![media_id, position as i64, now])?;
+            item_ids.push(conn.last_insert_rowid());
+        }
+    }
+
+    let start = start_media_id
+        .and_then(|id| media_ids.iter().position(|m| *m == id))
+        .map(|index| item_ids[index]);
+
+    let order = match (shuffle, start) {
+        (false, _) => item_ids.clone(),
+        (true, Some(start)) => {
+            let rest = item_ids.iter().copied().filter(|id| *id != start).collect();
+            std::iter::once(start).chain(shuffled(rest, time_seed())).collect()
+        }
+        (true, None) => shuffled(item_ids.clone(), time_seed()),
+    };
+    if shuffle {
+        write_order(conn, &order)?;
+    }
+
+    conn.execute(
+        "UPDATE play_queue_state
+         SET current_item_id = ?1, shuffle = ?2, source_json = ?3, source_label = ?4, updated_at = ?5
+         WHERE id = 1",
+        params![
+            start.unwrap_or(order[0]),
+            shuffle as i32,
+            serde_json::to_string(source).ok(),
+            label,
+            now,
+        ],
+    )?;
+    tx.commit()?;
+
+    Ok(get_queue(conn)?)
+}
+
+/// Queue media after the current item (`play_next`) or at the end; ids of
+/// media no longer in the library are skipped
+pub fn enqueue_media(conn: &Connection, media_ids: &[i64], play_next: bool) -> Result<PlayQueue> {
+    let media_ids = live_media_ids(conn, media_ids)?;
+    if media_ids.is_empty() {
+        return get_queue(conn);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let state = load_state(conn)?;
+    let mut order = load_order(conn)?;
+    let current_index = state
+        .current_item_id
+        .and_then(|id| order.iter().position(|item| *item == id));
+    let count = media_ids.len() as i64;
+
+    // Slot in the as-queued order; play next goes right after the current item there too
+    let first_original: i64 = match (play_next, state.current_item_id) {
+        (true, Some(current)) => {
+            let current_original: i64 = conn.query_row(
+                "SELECT original_position FROM play_queue_items WHERE id = ?1",
+                params![current],
+                |row| row.get(0),
+            )?;
+            conn.execute(
+                "UPDATE play_queue_items SET original_position = original_position + ?1
+                 WHERE original_position > ?2",
+                params![count, current_original],
+            )?;
+            current_original + 1
+        }
+        _ => conn.query_row(
+            "SELECT COALESCE(MAX(original_position) + 1, 0) FROM play_queue_items",
+            [],
+            |row| row.get(0),
+        )?,
+    };
+
+    let now = Utc::now().to_rfc3339();
+    let mut new_items = Vec::with_capacity(media_ids.len());
+    {
+        let mut stmt = conn.prepare(
+            "INSERT INTO play_queue_items (media_id, position, original_position, added_at)
+             VALUES (?1, 0, ?2, ?3)",
+        )?;
+        for (offset, media_id) in media_ids.iter().enumerate() {
+            stmt.execute(params![media_id, first_original + offset as i64, now])?;
+            new_items.push(conn.last_insert_rowid());
+        }
+    }
+
+    let insert_at = match (play_next, current_index) {
+        (true, Some(index)) => index + 1,
+        _ => order.len(),
+    };
+    order.splice(insert_at..insert_at, new_items.iter().copied());
+    write_order(conn, &order)?;
+
+    if current_index.is_none() {
+        set_current(conn, Some(new_items[0]))?;
+    }
+    tx.commit()?;
+
+    get_queue(conn)
+}
+
+/// Move to the next item and return it.
+///
+/// `finished` means the current item played to the end (rather than the user
+/// skipping), which is when repeat-one replays it. Returns None at the end of
+/// the queue unless repeat-all is on; reshuffles each pass when shuffling.
+/// Items whose media was deleted after being queued are passed over.
+pub fn advance_queue(conn: &Connection, finished: bool) -> Result<Option<QueueEntry>> {
+    let tx = conn.unchecked_transaction()?;
+    let state = load_state(conn)?;
+    let mut order = load_order(conn)?;
+    let live = live_items(conn)?;
+    if live.is_empty() {
+        return Ok(None);
+    }
+
+    let current_index = state
+        .current_item_id
+        .and_then(|id| order.iter().position(|item| *item == id));
+    let first_live = |order: &[i64], from: usize| order[from..].iter().copied().find(|item| live.contains(item));
+
+    let next = match current_index {
+        None => first_live(&order, 0),
+        Some(index) if finished && state.repeat_mode == RepeatMode::One && live.contains(&order[index]) => {
+            Some(order[index])
+        }
+        Some(index) => first_live(&order, index + 1),
+    };
+
+    let next = match next {
+        Some(next) => next,
+        None if state.repeat_mode != RepeatMode::All => return Ok(None),
+        None => {
+            if state.shuffle {
+                order = shuffled(order, time_seed());
+                // Don't play the same item twice in a row across passes
+                let firsts: Vec<usize> = (0..order.len()).filter(|i| live.contains(&order[*i])).take(2).collect();
+                if let [first, second] = firsts[..] {
+                    if state.current_item_id == Some(order[first]) {
+                        order.swap(first, second);
+                    }
+                }
+                write_order(conn, &order)?;
+            }
+            match first_live(&order, 0) {
+                Some(next) => next,
+                None => return Ok(None),
+            }
+        }
+    };
+
+    set_current(conn, Some(next))?;
+    tx.commit()?;
+    load_entry(conn, next)
+}
+
+/// Move back to the previous item and return it; None at the start of the
+/// queue unless repeat-all wraps around to the end. Items whose media was
+/// deleted are passed over.
+pub fn previous_in_queue(conn: &Connection) -> Result<Option<QueueEntry>> {
+    let state = load_state(conn)?;
+    let order = load_order(conn)?;
+    let live = live_items(conn)?;
+    let Some(index) = state
+        .current_item_id
+        .and_then(|id| order.iter().position(|item| *item == id))
+    else {
+        return Ok(None);
+    };
+
+    let last_live = |items: &[i64]| items.iter().rev().copied().find(|item| live.contains(item));
+    let previous = match last_live(&order[..index]) {
+        Some(previous) => previous,
+        None if state.repeat_mode == RepeatMode::All => match last_live(&order) {
+            Some(previous) => previous,
+            None => return Ok(None),
+        },
+        None => return Ok(None),
+    };
+
+    set_current(conn, Some(previous))?;
+    load_entry(conn, previous)
+}
+
+/// Make a queued item the current one
+pub fn jump_to_queue_item(conn: &Connection, queue_item_id: i64) -> std::result::Result<QueueEntry, QueueError> {
+    let entry = load_entry(conn, queue_item_id)?.ok_or(QueueError::ItemNotFound(queue_item_id))?;
+    set_current(conn, Some(queue_item_id))?;
+    Ok(entry)
+}
+
+/// Remove an item from the queue; removing the current item moves on to the next one
+pub fn remove_from_queue(conn: &Connection, queue_item_id: i64) -> std::result::Result<PlayQueue, QueueError> {
+    let state = load_state(conn)?;
+    let mut order = load_order(conn)?;
+    let index = order
+        .iter()
+        .position(|item| *item == queue_item_id)
+        .ok_or(QueueError::ItemNotFound(queue_item_id))?;
+
+    order.remove(index);
+    let tx = conn.unchecked_transaction()?;
+    if state.current_item_id == Some(queue_item_id) {
+        let replacement = order.get(index).or_else(|| order.last()).copied();
+        set_current(conn, replacement)?;
+    }
+
+    conn.execute("DELETE FROM play_queue_items WHERE id = ?1", params![queue_item_id])?;
+    write_order(conn, &order)?;
+    tx.commit()?;
+
+    Ok(get_queue(conn)?)
+}
+
+/// Empty the queue (shuffle and repeat settings are kept)
+pub fn clear_queue(conn: &Connection) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    conn.execute("DELETE FROM play_queue_items", [])?;
+    conn.execute(
+        "UPDATE play_queue_state
+         SET current_item_id = NULL, source_json = NULL, source_label = NULL, updated_at = ?1
+         WHERE id = 1",
+        params![Utc::now().to_rfc3339()],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Turn shuffle on or off.
+///
+/// Turning it on shuffles only the upcoming items, so everything left plays
+/// exactly once before any repeat; turning it off restores the queued order.
+pub fn set_queue_shuffle(conn: &Connection, shuffle: bool) -> Result<PlayQueue> {
+    let state = load_state(conn)?;
+
+    if shuffle != state.shuffle {
+        let tx = conn.unchecked_transaction()?;
+        if shuffle {
+            let mut order = load_order(conn)?;
+            let split = state
+                .current_item_id
+                .and_then(|id| order.iter().position(|item| *item == id))
+                .map(|index| index + 1)
+                .unwrap_or(0);
+            let upcoming = order.split_off(split);
+            order.extend(shuffled(upcoming, time_seed()));
+            write_order(conn, &order)?;
+        } else {
+            conn.execute("UPDATE play_queue_items SET position = original_position", [])?;
+        }
+
+        conn.execute(
+            "UPDATE play_queue_state SET shuffle = ?1, updated_at = ?2 WHERE id = 1",
+            params![shuffle as i32, Utc::now().to_rfc3339()],
+        )?;
+        tx.commit()?;
+    }
+
+    get_queue(conn)
+}
+
+pub fn set_queue_repeat_mode(conn: &Connection, repeat_mode: RepeatMode) -> Result<()> {
+    conn.execute(
+        "UPDATE play_queue_state SET repeat_mode = ?1, updated_at = ?2 WHERE id = 1",
+        params![repeat_mode.as_str(), Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use crate::db::models::PlaylistType;
+    use crate::db::playlists::{add_media_to_playlist, create_playlist};
+    use crate::db::test_support::{self, TestMedia};
+
+    fn add_media(conn: &Connection, path: &str, season: Option<i32>, episode: Option<i32>) -> Result<i64> {
+        let media_type = if season.is_some() { "tv_episode" } else { "movie" };
+        Ok(test_support::add_media(conn, &TestMedia { path, media_type, season, episode, ..TestMedia::default() }))
+    }
+
+    fn media_ids(entries: &[QueueEntry]) -> Vec<i64> {
+        entries.iter().map(|e| e.media.id.unwrap()).collect()
+    }
+
+    fn current_media(queue: &PlayQueue) -> Option<i64> {
+        queue.current.as_ref().and_then(|e| e.media.id)
+    }
+
+    #[test]
+    fn test_queue_navigation_and_repeat() -> std::result::Result<(), QueueError> {
+        let conn = init_db()?;
+        let a = add_media(&conn, "/m/a.mkv", None, None)?;
+        let b = add_media(&conn, "/m/b.mkv", None, None)?;
+        let c = add_media(&conn, "/m/c.mkv", None, None)?;
+        let playlist = create_playlist(&conn, "Night", None, PlaylistType::Manual)?;
+        for id in [a, b, c] {
+            add_media_to_playlist(&conn, playlist, id)?;
+        }
+
+        let queue = start_queue(&conn, &QueueSource::Playlist { id: playlist }, Some(b), false)?;
+        assert_eq!(queue.source_label.as_deref(), Some("Night"));
+        assert_eq!(queue.source, Some(QueueSource::Playlist { id: playlist }));
+        assert_eq!(current_media(&queue), Some(b));
+        assert_eq!(media_ids(&queue.history), vec![a]);
+        assert_eq!(media_ids(&queue.upcoming), vec![c]);
+
+        assert_eq!(advance_queue(&conn, true)?.and_then(|e| e.media.id), Some(c));
+        assert!(advance_queue(&conn, true)?.is_none());
+        assert_eq!(current_media(&get_queue(&conn)?), Some(c));
+
+        set_queue_repeat_mode(&conn, RepeatMode::One)?;
+        assert_eq!(advance_queue(&conn, true)?.and_then(|e| e.media.id), Some(c));
+
+        set_queue_repeat_mode(&conn, RepeatMode::All)?;
+        assert_eq!(advance_queue(&conn, false)?.and_then(|e| e.media.id), Some(a));
+        assert_eq!(previous_in_queue(&conn)?.and_then(|e| e.media.id), Some(c));
+
+        // Repeat mode survives starting a new queue
+        let queue = start_queue(&conn, &QueueSource::Media { ids: vec![a] }, None, false)?;
+        assert_eq!(queue.repeat_mode, RepeatMode::All);
+        assert!(queue.source_label.is_none());
+
+        assert!(matches!(
+            start_queue(&conn, &QueueSource::Media { ids: vec![] }, None, false),
+            Err(QueueError::EmptySource(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_play_next_and_remove() -> std::result::Result<(), QueueError> {
+        let conn = init_db()?;
+        let ids = (0..5)
+            .map(|i| add_media(&conn, &format!("/m/{}.mkv", i), None, None))
+            .collect::<Result<Vec<_>>>()?;
+
+        start_queue(&conn, &QueueSource::Media { ids: ids[..3].to_vec() }, None, false)?;
+        enqueue_media(&conn, &[ids[3]], false)?;
+        let queue = enqueue_media(&conn, &[ids[4]], true)?;
+        assert_eq!(current_media(&queue), Some(ids[0]));
+        assert_eq!(media_ids(&queue.upcoming), vec![ids[4], ids[1], ids[2], ids[3]]);
+
+        // Removing the current item moves on to the next one
+        let current = queue.current.unwrap().queue_item_id;
+        let queue = remove_from_queue(&conn, current)?;
+        assert_eq!(current_media(&queue), Some(ids[4]));
+        assert_eq!(media_ids(&queue.upcoming), vec![ids[1], ids[2], ids[3]]);
+
+        let last = queue.upcoming.last().unwrap().queue_item_id;
+        assert_eq!(jump_to_queue_item(&conn, last)?.media.id, Some(ids[3]));
+        assert!(matches!(jump_to_queue_item(&conn, 999), Err(QueueError::ItemNotFound(999))));
+
+        // Deleted media can't be queued and drops out of the queue
+        conn.execute("UPDATE media_files SET is_deleted = 1 WHERE id = ?1", params![ids[1]])?;
+        let queue = enqueue_media(&conn, &[ids[1], 999], false)?;
+        assert_eq!(media_ids(&queue.history), vec![ids[4], ids[2]]);
+        assert!(queue.upcoming.is_empty());
+        assert!(matches!(
+            start_queue(&conn, &QueueSource::Media { ids: vec![ids[1]] }, None, false),
+            Err(QueueError::EmptySource(_))
+        ));
+        assert_eq!(current_media(&get_queue(&conn)?), Some(ids[3]));
+
+        clear_queue(&conn)?;
+        let queue = get_queue(&conn)?;
+        assert!(queue.current.is_none() && queue.upcoming.is_empty());
+
+        // Adding to an empty queue starts it
+        let queue = enqueue_media(&conn, &[ids[2]], false)?;
+        assert_eq!(current_media(&queue), Some(ids[2]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_skips_deleted_media() -> std::result::Result<(), QueueError> {
+        let conn = init_db()?;
+        let ids = (0..4)
+            .map(|i| add_media(&conn, &format!("/m/{}.mkv", i), None, None))
+            .collect::<Result<Vec<_>>>()?;
+        let delete = |id: i64| conn.execute("UPDATE media_files SET is_deleted = 1 WHERE id = ?1", params![id]);
+
+        start_queue(&conn, &QueueSource::Media { ids: ids.clone() }, None, false)?;
+        delete(ids[1])?;
+        assert_eq!(advance_queue(&conn, true)?.and_then(|e| e.media.id), Some(ids[2]));
+        assert_eq!(previous_in_queue(&conn)?.and_then(|e| e.media.id), Some(ids[0]));
+
+        // A deleted current item keeps its place between history and upcoming
+        advance_queue(&conn, true)?;
+        delete(ids[2])?;
+        let queue = get_queue(&conn)?;
+        assert!(queue.current.is_none());
+        assert_eq!(media_ids(&queue.history), vec![ids[0]]);
+        assert_eq!(media_ids(&queue.upcoming), vec![ids[3]]);
+        assert_eq!(advance_queue(&conn, true)?.and_then(|e| e.media.id), Some(ids[3]));
+
+        // Repeat-all wraps past deleted items in both directions
+        set_queue_repeat_mode(&conn, RepeatMode::All)?;
+        delete(ids[0])?;
+        assert_eq!(advance_queue(&conn, true)?.and_then(|e| e.media.id), Some(ids[3]));
+        assert_eq!(previous_in_queue(&conn)?.and_then(|e| e.media.id), Some(ids[3]));
+
+        delete(ids[3])?;
+        assert!(advance_queue(&conn, true)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_shuffle_without_repeats() -> std::result::Result<(), QueueError> {
+        let conn = init_db()?;
+        let ids = (0..20)
+            .map(|i| add_media(&conn, &format!("/m/{}.mkv", i), None, None))
+            .collect::<Result<Vec<_>>>()?;
+
+        start_queue(&conn, &QueueSource::Media { ids: ids.clone() }, None, false)?;
+        advance_queue(&conn, true)?;
+        let queue = set_queue_shuffle(&conn, true)?;
+        assert!(queue.shuffle);
+        assert_eq!(media_ids(&queue.history), vec![ids[0]]);
+        assert_eq!(current_media(&queue), Some(ids[1]));
+
+        let mut upcoming = media_ids(&queue.upcoming);
+        assert_ne!(upcoming, ids[2..].to_vec());
+        upcoming.sort();
+        assert_eq!(upcoming, ids[2..].to_vec());
+
+        // Every remaining item plays exactly once
+        let mut played = vec![ids[1]];
+        while let Some(entry) = advance_queue(&conn, true)? {
+            played.push(entry.media.id.unwrap());
+        }
+        played.sort();
+        assert_eq!(played, ids[1..].to_vec());
+
+        // Turning shuffle off restores the queued order around the current item
+        let queue = set_queue_shuffle(&conn, false)?;
+        let current = current_media(&queue).unwrap();
+        let index = ids.iter().position(|id| *id == current).unwrap();
+        assert_eq!(media_ids(&queue.upcoming), ids[index + 1..].to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_from_season() -> std::result::Result<(), QueueError> {
+        let conn = init_db()?;
+        let e2 = add_media(&conn, "/tv/Dark/Season 1/Dark.S01E02.mkv", Some(1), Some(2))?;
+        let e1 = add_media(&conn, "/tv/Dark/Season 1/Dark.S01E01.mkv", Some(1), Some(1))?;
+        add_media(&conn, "/tv/Dark/Season 2/Dark.S02E01.mkv", Some(2), Some(1))?;
+        add_media(&conn, "/tv/Darker/Season 1/Darker.S01E01.mkv", Some(1), Some(1))?;
+
+        let queue = start_queue(&conn, &QueueSource::Season { show: "dark".to_string(), season: 1 }, None, false)?;
+        assert_eq!(current_media(&queue), Some(e1));
+        assert_eq!(media_ids(&queue.upcoming), vec![e2]);
+        assert_eq!(queue.source_label.as_deref(), Some("dark - Season 1"));
+        Ok(())
+    }
+}
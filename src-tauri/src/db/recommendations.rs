@@ -0,0 +1,534 @@
+//! "Because you watched…" recommendations.
+//!
+//! What has been played forms a taste profile, each title weighted by how
+//! much of it was watched, how often and how recently. Unwatched titles are
+//! ranked by the directors, writers, top-billed cast, genres, keywords and
+//! release decade they share with that profile. Shows are recommended as a
+//! whole, through their first episode.
+//!
+//! The latest ranking is stored in `recommendations` so smart playlists can
+//! filter (`recommendation_rank`) and sort (`recommended`) by it. It is
+//! recomputed when something is finished, after scans and TMDB syncs, and
+//! whenever recommendations are asked for; reading playlists never does.
+
+use std::collections::HashMap;
+use rusqlite::{Connection, Result, params};
+
+use super::models::{MediaFile, MediaType};
+use super::operations::{MEDIA_COLUMNS, media_file_from_row};
+use crate::tmdb::show_identity;
+
+/// Rank (1 = best) of a file in the stored ranking, NULL when not recommended
+pub(crate) const RECOMMENDATION_RANK_SQL: &str = "(SELECT r.rank FROM recommendations r WHERE r.media_id = m.id)";
+
+/// Score of a file in the stored ranking, 0 when not recommended
+pub(crate) const RECOMMENDATION_SCORE_SQL: &str =
+    "COALESCE((SELECT r.score FROM recommendations r WHERE r.media_id = m.id), 0)";
+
+/// Played files below this share of their runtime neither shape the profile
+/// nor stop a title from being recommended
+const MIN_ENGAGEMENT: f64 = 0.2;
+/// Episodes watched before a show counts as fully liked
+const FULL_SHOW_EPISODES: f64 = 3.0;
+/// Days for a watched title's influence to halve
+const HALF_LIFE_DAYS: f64 = 180.0;
+/// Billing positions below this count as the title's stars
+const TOP_BILLED: i64 = 5;
+const MAX_REASONS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum FeatureKind {
+    Director,
+    Writer,
+    Cast,
+    Genre,
+    Keyword,
+    Era,
+}
+
+impl FeatureKind {
+    /// How strongly sharing one of these suggests a similar title
+    fn weight(&self) -> f64 {
+        match self {
+            FeatureKind::Director => 3.0,
+            FeatureKind::Writer => 2.0,
+            FeatureKind::Cast => 1.5,
+            FeatureKind::Genre => 1.0,
+            FeatureKind::Keyword => 0.75,
+            FeatureKind::Era => 0.5,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            FeatureKind::Director => "director",
+            FeatureKind::Writer => "writer",
+            FeatureKind::Cast => "cast",
+            FeatureKind::Genre => "genre",
+            FeatureKind::Keyword => "keyword",
+            FeatureKind::Era => "era",
+        }
+    }
+
+    fn from_role(role: &str) -> Option<Self> {
+        match role {
+            "director" => Some(FeatureKind::Director),
+            "writer" => Some(FeatureKind::Writer),
+            "cast" => Some(FeatureKind::Cast),
+            _ => None,
+        }
+    }
+}
+
+/// Something a title has; people are keyed by TMDB person id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Feature {
+    kind: FeatureKind,
+    key: String,
+}
+
+/// Why an item was recommended: what it shares with one watched title
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecommendationReason {
+    /// "director", "writer", "cast", "genre", "keyword" or "era"
+    pub kind: String,
+    /// The shared names, genres, keywords or decade
+    pub values: Vec<String>,
+    /// A file of the watched title
+    pub because_media_id: i64,
+    pub because_title: String,
+    /// e.g. "Shares director Ridley Scott with Alien"
+    pub text: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Recommendation {
+    /// The movie, or the first episode of a show
+    pub media: MediaFile,
+    pub title: String,
+    pub tmdb_id: Option<i64>,
+    pub score: f64,
+    /// Strongest first
+    pub reasons: Vec<RecommendationReason>,
+}
+
+/// Per (kind, watched title) behind a recommendation: the summed
+/// contribution, and each shared value's contribution and label
+type ReasonGroup<'a> = (f64, Vec<(f64, &'a str)>);
+
+/// A movie, a show, or an unmatched file
+struct Title {
+    name: String,
+    tmdb_id: Option<i64>,
+    files: Vec<PlayedFile>,
+    features: Vec<Feature>,
+}
+
+struct PlayedFile {
+    media: MediaFile,
+    /// Share of the runtime watched, 0-1
+    engagement: f64,
+    plays: i64,
+    days_since_played: Option<f64>,
+}
+
+impl Title {
+    /// How much this title says about taste; 0 for titles not (meaningfully) watched
+    fn profile_weight(&self) -> f64 {
+        let engaged: Vec<&PlayedFile> = self.files.iter().filter(|f| f.engagement >= MIN_ENGAGEMENT).collect();
+        if engaged.is_empty() {
+            return 0.0;
+        }
+
+        let engagement = if self.is_show() {
+            (engaged.iter().map(|f| f.engagement).sum::<f64>() / FULL_SHOW_EPISODES).min(1.0)
+        } else {
+            engaged.iter().map(|f| f.engagement).fold(0.0, f64::max)
+        };
+        let plays = engaged.iter().map(|f| f.plays).max().unwrap_or(1).max(1);
+        let rewatch = (1.0 + 0.25 * (plays - 1) as f64).min(2.0);
+        let days = engaged.iter().filter_map(|f| f.days_since_played).fold(f64::INFINITY, f64::min);
+        let recency = if days.is_finite() { 0.5f64.powf(days.max(0.0) / HALF_LIFE_DAYS) } else { 1.0 };
+
+        engagement * rewatch * recency
+    }
+
+    fn is_show(&self) -> bool {
+        self.files.iter().any(|f| matches!(f.media.media_type, MediaType::TvEpisode))
+    }
+
+    /// The file standing for the whole title: the movie, or the first regular episode
+    fn first_file(&self) -> &PlayedFile {
+        self.files
+            .iter()
+            .min_by_key(|f| (f.media.season_number == Some(0), f.media.season_number, f.media.episode_number, f.media.id))
+            .expect("titles have at least one file")
+    }
+
+    /// The file most recently played
+    fn last_played_file(&self) -> &PlayedFile {
+        self.files
+            .iter()
+            .filter(|f| f.engagement >= MIN_ENGAGEMENT)
+            .min_by(|a, b| {
+                let (a, b) = (a.days_since_played.unwrap_or(f64::INFINITY), b.days_since_played.unwrap_or(f64::INFINITY));
+                a.total_cmp(&b)
+            })
+            .unwrap_or_else(|| self.first_file())
+    }
+}
+
+/// Compute the ranking, store it and return the best `limit` items
+pub fn get_recommendations(conn: &Connection, limit: usize) -> Result<Vec<Recommendation>> {
+    let mut recommendations = compute_recommendations(conn)?;
+    store_recommendations(conn, &recommendations)?;
+    recommendations.truncate(limit);
+    Ok(recommendations)
+}
+
+/// Recompute the stored ranking; returns how many items are recommended
+pub fn refresh_recommendations(conn: &Connection) -> Result<usize> {
+    let recommendations = compute_recommendations(conn)?;
+    store_recommendations(conn, &recommendations)?;
+    Ok(recommendations.len())
+}
+
+fn store_recommendations(conn: &Connection, recommendations: &[Recommendation]) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM recommendations", [])?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO recommendations (media_id, rank, score, reasons_json) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for (index, recommendation) in recommendations.iter().enumerate() {
+            let reasons = serde_json::to_string(&recommendation.reasons).unwrap_or_else(|_| "[]".to_string());
+            insert.execute(params![recommendation.media.id, index as i64 + 1, recommendation.score, reasons])?;
+        }
+    }
+    tx.commit()
+}
+
+/// Rank every unwatched title in the library, best first. Empty until
+/// something has been watched.
+fn compute_recommendations(conn: &Connection) -> Result<Vec<Recommendation>> {
+    let (titles, labels) = load_titles(conn)?;
+
+    // Rare features say more than ones most of the library shares
+    let mut document_frequency: HashMap<&Feature, f64> = HashMap::new();
+    for title in &titles {
+        for feature in &title.features {
+            *document_frequency.entry(feature).or_default() += 1.0;
+        }
+    }
+    let title_count = titles.len() as f64;
+    let idf = |feature: &Feature| (1.0 + title_count / document_frequency.get(feature).copied().unwrap_or(1.0)).ln();
+
+    // The profile: each feature's share of watching, and the watched title weighing most for it
+    let weights: Vec<f64> = titles.iter().map(Title::profile_weight).collect();
+    let total: f64 = weights.iter().sum();
+    if total <= 0.0 {
+        return Ok(Vec::new());
+    }
+    let mut profile: HashMap<&Feature, (f64, usize)> = HashMap::new();
+    for (index, title) in titles.iter().enumerate().filter(|(i, _)| weights[*i] > 0.0) {
+        for feature in &title.features {
+            let entry = profile.entry(feature).or_insert((0.0, index));
+            entry.0 += weights[index];
+            if weights[index] > weights[entry.1] {
+                entry.1 = index;
+            }
+        }
+    }
+
+    let mut recommendations = Vec::new();
+    for (index, title) in titles.iter().enumerate() {
+        let started = title.files.iter().any(|f| f.engagement >= MIN_ENGAGEMENT);
+        if started || title.features.is_empty() {
+            continue;
+        }
+
+        let mut groups: HashMap<(FeatureKind, usize), ReasonGroup> = HashMap::new();
+        let mut score = 0.0;
+        for feature in &title.features {
+            let Some((share, source)) = profile.get(feature) else { continue };
+            let contribution = feature.kind.weight() * (share / total) * idf(feature);
+            score += contribution;
+            let group = groups.entry((feature.kind, *source)).or_default();
+            group.0 += contribution;
+            group.1.push((contribution, labels.get(feature).map(String::as_str).unwrap_or(&feature.key)));
+        }
+        if score <= 0.0 {
+            continue;
+        }
+        // Titles with long keyword lists shouldn't win by volume alone
+        score /= (title.features.len() as f64).sqrt();
+
+        let mut groups: Vec<_> = groups.into_iter().collect();
+        groups.sort_by(|a, b| b.1 .0.total_cmp(&a.1 .0).then(a.0 .1.cmp(&b.0 .1)));
+        let reasons = groups
+            .into_iter()
+            .take(MAX_REASONS)
+            .map(|((kind, source), (_, mut values))| {
+                values.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(b.1)));
+                let values: Vec<String> = values.into_iter().take(2).map(|(_, v)| v.to_string()).collect();
+                let source = &titles[source];
+                RecommendationReason {
+                    kind: kind.as_str().to_string(),
+                    text: explain(kind, &values, &source.name),
+                    values,
+                    because_media_id: source.last_played_file().media.id.unwrap_or_default(),
+                    because_title: source.name.clone(),
+                }
+            })
+            .collect();
+
+        recommendations.push((index, score, reasons));
+    }
+
+    recommendations.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| titles[a.0].name.cmp(&titles[b.0].name)));
+    Ok(recommendations
+        .into_iter()
+        .map(|(index, score, reasons)| {
+            let title = &titles[index];
+            Recommendation {
+                media: title.first_file().media.clone(),
+                title: title.name.clone(),
+                tmdb_id: title.tmdb_id,
+                score,
+                reasons,
+            }
+        })
+        .collect())
+}
+
+fn explain(kind: FeatureKind, values: &[String], because: &str) -> String {
+    let values = values.join(" and ");
+    match kind {
+        FeatureKind::Director => format!("Shares director {} with {}", values, because),
+        FeatureKind::Writer => format!("Shares writer {} with {}", values, because),
+        FeatureKind::Cast => format!("Stars {}, also in {}", values, because),
+        FeatureKind::Genre => format!("{}, like {}", values, because),
+        FeatureKind::Keyword => format!("About {}, like {}", values, because),
+        FeatureKind::Era => format!("From the {}, like {}", values, because),
+    }
+}
+
+/// Group the library's movies, episodes and videos into titles with their
+/// features; also returns display labels for features keyed by id
+fn load_titles(conn: &Connection) -> Result<(Vec<Title>, HashMap<Feature, String>)> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, t.tmdb_id, t.media_type, md.title, md.release_date,
+                COALESCE(ps.completed, 0) = 1
+                    OR EXISTS (SELECT 1 FROM playback_history h WHERE h.media_id = m.id AND h.completed = 1),
+                MAX(COALESCE(ps.last_position, 0),
+                    COALESCE((SELECT SUM(h.duration_watched) FROM playback_history h WHERE h.media_id = m.id), 0)),
+                COALESCE(m.duration, ps.duration),
+                MAX(COALESCE(ps.watch_count, 0),
+                    (SELECT COUNT(*) FROM playback_history h WHERE h.media_id = m.id AND h.completed = 1)),
+                julianday('now') - julianday(COALESCE(ps.last_played_at,
+                    (SELECT MAX(h.started_at) FROM playback_history h WHERE h.media_id = m.id)))
+         FROM media_files m
+         LEFT JOIN tmdb_media t ON t.media_id = m.id
+         LEFT JOIN tmdb_metadata md ON md.tmdb_id = t.tmdb_id AND md.media_type = t.media_type
+         LEFT JOIN playback_state ps ON ps.media_id = m.id
+         WHERE m.is_deleted = 0 AND m.media_type IN ('movie', 'tv_episode', 'video')
+         ORDER BY m.id",
+        MEDIA_COLUMNS
+    ))?;
+
+    let mut titles: Vec<Title> = Vec::new();
+    let mut by_key: HashMap<String, usize> = HashMap::new();
+    let mut by_tmdb: HashMap<(i64, String), usize> = HashMap::new();
+    let mut labels: HashMap<Feature, String> = HashMap::new();
+
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        // Playback columns follow the 22 media columns
+        let media = media_file_from_row(row)?;
+        let tmdb: Option<(i64, String)> = match (row.get::<_, Option<i64>>(22)?, row.get::<_, Option<String>>(23)?) {
+            (Some(id), Some(media_type)) => Some((id, media_type)),
+            _ => None,
+        };
+        let tmdb_title: Option<String> = row.get(24)?;
+        let release_date: Option<String> = row.get(25)?;
+        let completed: bool = row.get(26)?;
+        let watched_seconds: i64 = row.get(27)?;
+        let duration: Option<i64> = row.get(28)?;
+        let plays: i64 = row.get(29)?;
+        let days_since_played: Option<f64> = row.get(30)?;
+
+        let engagement = if completed {
+            1.0
+        } else {
+            match duration.filter(|d| *d > 0) {
+                Some(duration) => (watched_seconds as f64 / duration as f64).min(1.0),
+                None => 0.0,
+            }
+        };
+
+        let show = if matches!(media.media_type, MediaType::TvEpisode) && tmdb.is_none() {
+            show_identity(&media.file_path, media.metadata_json.as_deref())
+        } else {
+            None
+        };
+        let key = match (&tmdb, &show) {
+            (Some((id, media_type)), _) => format!("tmdb:{}:{}", media_type, id),
+            (None, Some((name, _))) => format!("show:{}", name.to_lowercase()),
+            (None, None) => format!("file:{}", media.id.unwrap_or_default()),
+        };
+
+        let index = *by_key.entry(key).or_insert_with(|| {
+            let name = tmdb_title
+                .clone()
+                .or_else(|| show.as_ref().map(|(name, _)| name.clone()))
+                .or_else(|| media.title.clone())
+                .unwrap_or_else(|| media.file_name.clone());
+            let year = release_date
+                .as_deref()
+                .and_then(|d| d.get(..4))
+                .and_then(|y| y.parse::<i32>().ok())
+                .or(media.year);
+            let mut features = Vec::new();
+            if let Some(year) = year {
+                let decade = (year / 10) * 10;
+                let feature = Feature { kind: FeatureKind::Era, key: decade.to_string() };
+                labels.insert(feature.clone(), format!("{}s", decade));
+                features.push(feature);
+            }
+            titles.push(Title { name, tmdb_id: tmdb.as_ref().map(|(id, _)| *id), files: Vec::new(), features });
+            if let Some(tmdb) = &tmdb {
+                by_tmdb.insert(tmdb.clone(), titles.len() - 1);
+            }
+            titles.len() - 1
+        });
+
+        titles[index].files.push(PlayedFile { media, engagement, plays, days_since_played });
+    }
+
+    let matched = "EXISTS (SELECT 1 FROM tmdb_media t WHERE t.tmdb_id = f.tmdb_id AND t.media_type = f.media_type)";
+    for (table, kind) in [("tmdb_genres", FeatureKind::Genre), ("tmdb_keywords", FeatureKind::Keyword)] {
+        let mut stmt = conn.prepare(&format!("SELECT f.tmdb_id, f.media_type, f.name FROM {} f WHERE {}", table, matched))?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            if let Some(&index) = by_tmdb.get(&(row.get(0)?, row.get(1)?)) {
+                titles[index].features.push(Feature { kind, key: row.get(2)? });
+            }
+        }
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT f.tmdb_media_id, f.media_type, f.tmdb_person_id, f.name, f.role
+         FROM tmdb_cast f
+         WHERE (f.role IN ('director', 'writer') OR (f.role = 'cast' AND COALESCE(f.order_position, 0) < ?1))
+           AND EXISTS (SELECT 1 FROM tmdb_media t WHERE t.tmdb_id = f.tmdb_media_id AND t.media_type = f.media_type)",
+    )?;
+    let mut rows = stmt.query(params![TOP_BILLED])?;
+    while let Some(row) = rows.next()? {
+        let role: String = row.get(4)?;
+        let (Some(kind), Some(&index)) = (FeatureKind::from_role(&role), by_tmdb.get(&(row.get(0)?, row.get(1)?))) else {
+            continue;
+        };
+        let feature = Feature { kind, key: row.get::<_, i64>(2)?.to_string() };
+        labels.insert(feature.clone(), row.get(3)?);
+        if !titles[index].features.contains(&feature) {
+            titles[index].features.push(feature);
+        }
+    }
+
+    Ok((titles, labels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use crate::db::models::{PlaylistType, SmartPlaylistSettings, SortKey};
+    use crate::db::playlists::{add_playlist_rule, create_playlist, get_playlist_media, set_smart_playlist_settings};
+    use crate::db::test_support::{self, TestMedia};
+
+    fn add_movie(conn: &Connection, tmdb_id: i64, title: &str, year: i32, genres: &str, people: &[(i64, &str, &str)]) -> i64 {
+        let path = format!("/m/{}.mkv", title);
+        let media = TestMedia { path: &path, title: Some(title), year: Some(year), duration: Some(6000), ..TestMedia::default() };
+        let media_id = test_support::add_media(conn, &media);
+        conn.execute(
+            "INSERT INTO tmdb_metadata (tmdb_id, media_type, title, release_date, genres_json) VALUES (?1, 'movie', ?2, ?3, ?4)",
+            params![tmdb_id, title, format!("{}-06-01", year), genres],
+        ).unwrap();
+        conn.execute("INSERT INTO tmdb_media (media_id, tmdb_id, media_type) VALUES (?1, ?2, 'movie')", params![media_id, tmdb_id])
+            .unwrap();
+        for (order, (person_id, name, role)) in people.iter().enumerate() {
+            conn.execute(
+                "INSERT INTO tmdb_cast (tmdb_media_id, media_type, tmdb_person_id, name, role, order_position)
+                 VALUES (?1, 'movie', ?2, ?3, ?4, ?5)",
+                params![tmdb_id, person_id, name, role, order as i64],
+            ).unwrap();
+        }
+        media_id
+    }
+
+    fn watch(conn: &Connection, media_id: i64, position: i64, completed: bool) {
+        conn.execute(
+            "INSERT INTO playback_state (media_id, last_position, duration, completed, watch_count, last_played_at)
+             VALUES (?1, ?2, 6000, ?3, ?4, datetime('now'))",
+            params![media_id, position, completed as i64, completed as i64],
+        ).unwrap();
+    }
+
+    #[test]
+    fn test_recommendations_follow_watched_titles() {
+        let conn = init_db().unwrap();
+        let scott = (1, "Ridley Scott", "director");
+        let alien = add_movie(&conn, 1, "Alien", 1979, r#"["Horror", "Science Fiction"]"#, &[scott, (2, "Sigourney Weaver", "cast")]);
+        let blade_runner = add_movie(&conn, 2, "Blade Runner", 1982, r#"["Science Fiction"]"#, &[scott]);
+        let aliens = add_movie(&conn, 3, "Aliens", 1986, r#"["Action", "Science Fiction"]"#, &[(2, "Sigourney Weaver", "cast")]);
+        let amelie = add_movie(&conn, 4, "Amélie", 2001, r#"["Comedy", "Romance"]"#, &[]);
+        let notting_hill = add_movie(&conn, 5, "Notting Hill", 1999, r#"["Comedy", "Romance"]"#, &[]);
+
+        // Nothing watched, nothing to go on
+        assert!(get_recommendations(&conn, 10).unwrap().is_empty());
+
+        watch(&conn, alien, 6000, true);
+        // Abandoned after a few minutes: says nothing about taste
+        watch(&conn, amelie, 300, false);
+
+        let recommendations = get_recommendations(&conn, 10).unwrap();
+        let ids: Vec<i64> = recommendations.iter().map(|r| r.media.id.unwrap()).collect();
+        assert_eq!(ids, vec![blade_runner, aliens]);
+        assert!(!ids.contains(&notting_hill) && !ids.contains(&amelie));
+
+        let reason = &recommendations[0].reasons[0];
+        assert_eq!(reason.kind, "director");
+        assert_eq!(reason.because_media_id, alien);
+        assert_eq!(reason.text, "Shares director Ridley Scott with Alien");
+        assert_eq!(recommendations[1].reasons[0].text, "Stars Sigourney Weaver, also in Alien");
+
+        // The ranking is stored for smart playlists
+        let rank: i64 = conn
+            .query_row("SELECT rank FROM recommendations WHERE media_id = ?1", params![aliens], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rank, 2);
+        assert_eq!(get_recommendations(&conn, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_recommendations_as_smart_playlist() {
+        let conn = init_db().unwrap();
+        let scott = (1, "Ridley Scott", "director");
+        let alien = add_movie(&conn, 1, "Alien", 1979, r#"["Science Fiction"]"#, &[scott]);
+        add_movie(&conn, 2, "Blade Runner", 1982, r#"["Science Fiction"]"#, &[scott]);
+        add_movie(&conn, 3, "Dune", 1984, r#"["Science Fiction"]"#, &[]);
+
+        let playlist_id = create_playlist(&conn, "Up next", None, PlaylistType::Smart).unwrap();
+        add_playlist_rule(&conn, playlist_id, "recommendation_rank", "lte", "5", None).unwrap();
+        let settings = SmartPlaylistSettings { sort: Some(SortKey::Recommended), ..SmartPlaylistSettings::default() };
+        set_smart_playlist_settings(&conn, playlist_id, &settings).unwrap();
+        assert!(get_playlist_media(&conn, playlist_id).unwrap().is_empty());
+
+        // The playlist reads the stored ranking, which changes once it's refreshed
+        watch(&conn, alien, 6000, true);
+        assert!(get_playlist_media(&conn, playlist_id).unwrap().is_empty());
+        assert_eq!(refresh_recommendations(&conn).unwrap(), 2);
+        let titles: Vec<String> = get_playlist_media(&conn, playlist_id).unwrap().into_iter().filter_map(|m| m.title).collect();
+        assert_eq!(titles, vec!["Blade Runner", "Dune"]);
+    }
+}
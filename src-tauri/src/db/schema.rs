@@ -4,7 +4,7 @@
 /// functionality and optional TMDB integration.
 /// Core schema version
 #[allow(dead_code)]
-pub const SCHEMA_VERSION: i32 = 1;
+pub const SCHEMA_VERSION: i32 = 19;
 
 /// Core tables for media management
 pub const CORE_SCHEMA: &str = r#"
@@ -365,3 +365,523 @@ CREATE TABLE IF NOT EXISTS tmdb_settings (
     updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
 );
 "#;
+
+/// Full-text search index over media titles, file names and metadata_json
+/// (overview, cast names, genres), kept in sync with media_files by triggers
+pub const SEARCH_SCHEMA: &str = r#"
+CREATE VIRTUAL TABLE IF NOT EXISTS media_search USING fts5(
+    title,
+    file_name,
+    overview,
+    cast_names,
+    genres,
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3 4'
+);
+
+CREATE TRIGGER IF NOT EXISTS media_search_ai AFTER INSERT ON media_files BEGIN
+    INSERT INTO media_search (rowid, title, file_name, overview, cast_names, genres)
+    VALUES (
+        NEW.id,
+        NEW.title,
+        NEW.file_name,
+        CASE WHEN json_valid(NEW.metadata_json) THEN json_extract(NEW.metadata_json, '$.overview') END,
+        (SELECT group_concat(json_extract(value, '$.name'), ' ')
+         FROM json_each(CASE WHEN json_valid(NEW.metadata_json) THEN NEW.metadata_json END, '$.cast')),
+        (SELECT group_concat(value, ' ')
+         FROM json_each(CASE WHEN json_valid(NEW.metadata_json) THEN NEW.metadata_json END, '$.genres'))
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS media_search_au AFTER UPDATE OF title, file_name, metadata_json ON media_files BEGIN
+    DELETE FROM media_search WHERE rowid = OLD.id;
+    INSERT INTO media_search (rowid, title, file_name, overview, cast_names, genres)
+    VALUES (
+        NEW.id,
+        NEW.title,
+        NEW.file_name,
+        CASE WHEN json_valid(NEW.metadata_json) THEN json_extract(NEW.metadata_json, '$.overview') END,
+        (SELECT group_concat(json_extract(value, '$.name'), ' ')
+         FROM json_each(CASE WHEN json_valid(NEW.metadata_json) THEN NEW.metadata_json END, '$.cast')),
+        (SELECT group_concat(value, ' ')
+         FROM json_each(CASE WHEN json_valid(NEW.metadata_json) THEN NEW.metadata_json END, '$.genres'))
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS media_search_ad AFTER DELETE ON media_files BEGIN
+    DELETE FROM media_search WHERE rowid = OLD.id;
+END;
+"#;
+
+/// Backfill the search index from existing media_files rows
+pub const SEARCH_BACKFILL: &str = r#"
+DELETE FROM media_search;
+INSERT INTO media_search (rowid, title, file_name, overview, cast_names, genres)
+SELECT
+    m.id,
+    m.title,
+    m.file_name,
+    CASE WHEN json_valid(m.metadata_json) THEN json_extract(m.metadata_json, '$.overview') END,
+    (SELECT group_concat(json_extract(value, '$.name'), ' ')
+     FROM json_each(CASE WHEN json_valid(m.metadata_json) THEN m.metadata_json END, '$.cast')),
+    (SELECT group_concat(value, ' ')
+     FROM json_each(CASE WHEN json_valid(m.metadata_json) THEN m.metadata_json END, '$.genres'))
+FROM media_files m;
+"#;
+
+/// Frame width/height derived from the stored "WxH" resolution, and indexes
+/// backing the listing sort keys
+pub const LISTING_SCHEMA: &str = r#"
+ALTER TABLE media_files ADD COLUMN width INTEGER GENERATED ALWAYS AS (
+    CASE WHEN instr(resolution, 'x') > 1
+         THEN CAST(substr(resolution, 1, instr(resolution, 'x') - 1) AS INTEGER) END
+) VIRTUAL;
+
+ALTER TABLE media_files ADD COLUMN height INTEGER GENERATED ALWAYS AS (
+    CASE WHEN instr(resolution, 'x') > 1
+         THEN CAST(substr(resolution, instr(resolution, 'x') + 1) AS INTEGER) END
+) VIRTUAL;
+
+CREATE INDEX IF NOT EXISTS idx_media_height ON media_files(height);
+CREATE INDEX IF NOT EXISTS idx_media_title_sort ON media_files(COALESCE(title, file_name) COLLATE NOCASE, id);
+CREATE INDEX IF NOT EXISTS idx_media_year ON media_files(year, id);
+CREATE INDEX IF NOT EXISTS idx_media_indexed_at ON media_files(indexed_at, id);
+CREATE INDEX IF NOT EXISTS idx_media_size ON media_files(file_size, id);
+"#;
+
+/// Nesting for smart playlist rules: rules point at the `group` rule containing them
+pub const PLAYLIST_RULE_GROUPS_SCHEMA: &str = r#"
+ALTER TABLE playlist_rules ADD COLUMN parent_id INTEGER REFERENCES playlist_rules(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS idx_playlist_rules_parent ON playlist_rules(parent_id);
+"#;
+
+/// Per-playlist ordering and limit for smart playlists
+pub const SMART_PLAYLIST_SETTINGS_SCHEMA: &str = r#"
+ALTER TABLE playlists ADD COLUMN sort_key TEXT;
+ALTER TABLE playlists ADD COLUMN sort_descending INTEGER;
+ALTER TABLE playlists ADD COLUMN item_limit INTEGER;
+ALTER TABLE playlists ADD COLUMN random_seed INTEGER;
+"#;
+
+/// Persistent play queue: queued items plus a single row of queue state
+pub const PLAY_QUEUE_SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS play_queue_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    media_id INTEGER NOT NULL REFERENCES media_files(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,           -- Play order (shuffled when shuffle is on)
+    original_position INTEGER NOT NULL,  -- Order the items were queued in
+    added_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_play_queue_position ON play_queue_items(position);
+
+CREATE TABLE IF NOT EXISTS play_queue_state (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    current_item_id INTEGER REFERENCES play_queue_items(id) ON DELETE SET NULL,
+    shuffle INTEGER NOT NULL DEFAULT 0,
+    repeat_mode TEXT NOT NULL DEFAULT 'off',  -- 'off', 'one', 'all'
+    source_json TEXT,                         -- What the queue was started from
+    source_label TEXT,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT OR IGNORE INTO play_queue_state (id) VALUES (1);
+"#;
+
+/// Lexicographic sort keys for playlist and collection items (see `db::ordering`)
+pub const ITEM_ORDERING_SCHEMA: &str = r#"
+ALTER TABLE playlist_items ADD COLUMN sort_key TEXT;
+ALTER TABLE collection_items ADD COLUMN sort_key TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_playlist_items_sort ON playlist_items(playlist_id, sort_key);
+CREATE INDEX IF NOT EXISTS idx_collection_items_sort ON collection_items(collection_id, sort_key);
+"#;
+
+/// Generated collections: `auto_source` identifies what a collection was built
+/// from (e.g. `tmdb:87096`, `folder:/movies/Alien Collection`); NULL for user collections
+pub const AUTO_COLLECTIONS_SCHEMA: &str = r#"
+ALTER TABLE collections ADD COLUMN auto_source TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_collections_auto_source ON collections(auto_source);
+"#;
+
+/// Low-confidence TMDB matches waiting for the user to confirm or reject
+pub const TMDB_MATCH_CANDIDATES_SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS tmdb_match_candidates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    media_id INTEGER NOT NULL,
+    tmdb_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL CHECK(media_type IN ('movie', 'tv')),
+    confidence REAL NOT NULL,     -- 0.0 to 1.0
+    title_score REAL NOT NULL,
+    year_score REAL NOT NULL,
+    runtime_score REAL NOT NULL,
+    episode_score REAL NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK(status IN ('pending', 'rejected')),
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (media_id) REFERENCES media_files(id) ON DELETE CASCADE,
+    UNIQUE(media_id, tmdb_id, media_type)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tmdb_match_candidates_media ON tmdb_match_candidates(media_id, status);
+"#;
+
+/// Raw TMDB responses keyed by request (without the API key), so repeat
+/// requests can be revalidated with If-None-Match.
+///
+/// Also rebuilds tmdb_cast, tmdb_images and tmdb_tv_shows: their foreign keys
+/// pointed at tmdb_metadata(tmdb_id), which isn't unique on its own, so every
+/// insert failed. Cast and images gain the media_type half of the key instead.
+pub const TMDB_HTTP_CACHE_SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS tmdb_http_cache (
+    request TEXT PRIMARY KEY,
+    etag TEXT,
+    body TEXT NOT NULL,
+    fetched_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE tmdb_cast_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tmdb_media_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL DEFAULT 'movie',
+    tmdb_person_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    character TEXT,
+    role TEXT NOT NULL CHECK(role IN ('cast', 'director', 'writer', 'producer')),
+    order_position INTEGER,
+    profile_path TEXT,
+
+    FOREIGN KEY (tmdb_media_id, media_type) REFERENCES tmdb_metadata(tmdb_id, media_type) ON DELETE CASCADE
+);
+INSERT INTO tmdb_cast_new (id, tmdb_media_id, tmdb_person_id, name, character, role, order_position, profile_path)
+    SELECT id, tmdb_media_id, tmdb_person_id, name, character, role, order_position, profile_path FROM tmdb_cast;
+DROP TABLE tmdb_cast;
+ALTER TABLE tmdb_cast_new RENAME TO tmdb_cast;
+
+CREATE INDEX IF NOT EXISTS idx_tmdb_cast_media ON tmdb_cast(tmdb_media_id, media_type);
+CREATE INDEX IF NOT EXISTS idx_tmdb_cast_person ON tmdb_cast(tmdb_person_id);
+CREATE INDEX IF NOT EXISTS idx_tmdb_cast_name ON tmdb_cast(name);
+
+CREATE TABLE tmdb_images_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tmdb_media_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL DEFAULT 'movie',
+    image_type TEXT NOT NULL CHECK(image_type IN ('poster', 'backdrop', 'still', 'profile')),
+    file_path TEXT,  -- Remote TMDB path
+    local_path TEXT, -- Local cached path
+    language TEXT,
+    width INTEGER,
+    height INTEGER,
+    vote_average REAL,
+    is_primary INTEGER NOT NULL DEFAULT 0,
+
+    FOREIGN KEY (tmdb_media_id, media_type) REFERENCES tmdb_metadata(tmdb_id, media_type) ON DELETE CASCADE
+);
+INSERT INTO tmdb_images_new (id, tmdb_media_id, image_type, file_path, local_path, language, width, height, vote_average, is_primary)
+    SELECT id, tmdb_media_id, image_type, file_path, local_path, language, width, height, vote_average, is_primary FROM tmdb_images;
+DROP TABLE tmdb_images;
+ALTER TABLE tmdb_images_new RENAME TO tmdb_images;
+
+CREATE INDEX IF NOT EXISTS idx_tmdb_images_media ON tmdb_images(tmdb_media_id, media_type);
+CREATE INDEX IF NOT EXISTS idx_tmdb_images_type ON tmdb_images(image_type);
+
+CREATE TABLE tmdb_tv_shows_new (
+    tmdb_id INTEGER PRIMARY KEY,
+    show_name TEXT NOT NULL,
+    first_air_date TEXT,
+    last_air_date TEXT,
+    in_production INTEGER NOT NULL DEFAULT 0,
+    next_episode_to_air_json TEXT,
+    networks_json TEXT
+);
+INSERT INTO tmdb_tv_shows_new SELECT tmdb_id, show_name, first_air_date, last_air_date, in_production,
+    next_episode_to_air_json, networks_json FROM tmdb_tv_shows;
+DROP TABLE tmdb_tv_shows;
+ALTER TABLE tmdb_tv_shows_new RENAME TO tmdb_tv_shows;
+"#;
+
+/// Genres, keywords, production countries and languages of cached TMDB
+/// titles as rows, kept in sync with the JSON columns of tmdb_metadata by
+/// triggers. Elements may be plain strings or TMDB objects.
+pub const TMDB_FACETS_SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS tmdb_genres (
+    tmdb_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL,
+    name TEXT NOT NULL,
+
+    PRIMARY KEY (tmdb_id, media_type, name),
+    FOREIGN KEY (tmdb_id, media_type) REFERENCES tmdb_metadata(tmdb_id, media_type) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_tmdb_genres_name ON tmdb_genres(name);
+
+CREATE TABLE IF NOT EXISTS tmdb_keywords (
+    tmdb_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL,
+    name TEXT NOT NULL,
+
+    PRIMARY KEY (tmdb_id, media_type, name),
+    FOREIGN KEY (tmdb_id, media_type) REFERENCES tmdb_metadata(tmdb_id, media_type) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_tmdb_keywords_name ON tmdb_keywords(name);
+
+CREATE TABLE IF NOT EXISTS tmdb_countries (
+    tmdb_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL,
+    code TEXT NOT NULL,
+
+    PRIMARY KEY (tmdb_id, media_type, code),
+    FOREIGN KEY (tmdb_id, media_type) REFERENCES tmdb_metadata(tmdb_id, media_type) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_tmdb_countries_code ON tmdb_countries(code);
+
+CREATE TABLE IF NOT EXISTS tmdb_languages (
+    tmdb_id INTEGER NOT NULL,
+    media_type TEXT NOT NULL,
+    code TEXT NOT NULL,
+
+    PRIMARY KEY (tmdb_id, media_type, code),
+    FOREIGN KEY (tmdb_id, media_type) REFERENCES tmdb_metadata(tmdb_id, media_type) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_tmdb_languages_code ON tmdb_languages(code);
+
+CREATE TRIGGER IF NOT EXISTS tmdb_facets_ai AFTER INSERT ON tmdb_metadata BEGIN
+    INSERT OR IGNORE INTO tmdb_genres (tmdb_id, media_type, name)
+    SELECT NEW.tmdb_id, NEW.media_type, v FROM (
+        SELECT TRIM(CASE j.type WHEN 'object' THEN json_extract(j.value, '$.name') ELSE j.value END) AS v
+        FROM json_each(CASE WHEN json_valid(NEW.genres_json) THEN NEW.genres_json END) j
+    ) WHERE v IS NOT NULL AND v != '';
+    INSERT OR IGNORE INTO tmdb_keywords (tmdb_id, media_type, name)
+    SELECT NEW.tmdb_id, NEW.media_type, v FROM (
+        SELECT TRIM(CASE j.type WHEN 'object' THEN json_extract(j.value, '$.name') ELSE j.value END) AS v
+        FROM json_each(CASE WHEN json_valid(NEW.keywords_json) THEN NEW.keywords_json END) j
+    ) WHERE v IS NOT NULL AND v != '';
+    INSERT OR IGNORE INTO tmdb_countries (tmdb_id, media_type, code)
+    SELECT NEW.tmdb_id, NEW.media_type, UPPER(v) FROM (
+        SELECT TRIM(CASE j.type WHEN 'object' THEN json_extract(j.value, '$.iso_3166_1') ELSE j.value END) AS v
+        FROM json_each(CASE WHEN json_valid(NEW.production_countries_json) THEN NEW.production_countries_json END) j
+    ) WHERE v IS NOT NULL AND v != '';
+    INSERT OR IGNORE INTO tmdb_languages (tmdb_id, media_type, code)
+    SELECT NEW.tmdb_id, NEW.media_type, LOWER(v) FROM (
+        SELECT TRIM(CASE j.type WHEN 'object' THEN json_extract(j.value, '$.iso_639_1') ELSE j.value END) AS v
+        FROM json_each(CASE WHEN json_valid(NEW.languages_json) THEN NEW.languages_json END) j
+    ) WHERE v IS NOT NULL AND v != '';
+END;
+
+CREATE TRIGGER IF NOT EXISTS tmdb_facets_au AFTER UPDATE OF genres_json, keywords_json, languages_json, production_countries_json ON tmdb_metadata BEGIN
+    DELETE FROM tmdb_genres WHERE tmdb_id = NEW.tmdb_id AND media_type = NEW.media_type;
+    INSERT OR IGNORE INTO tmdb_genres (tmdb_id, media_type, name)
+    SELECT NEW.tmdb_id, NEW.media_type, v FROM (
+        SELECT TRIM(CASE j.type WHEN 'object' THEN json_extract(j.value, '$.name') ELSE j.value END) AS v
+        FROM json_each(CASE WHEN json_valid(NEW.genres_json) THEN NEW.genres_json END) j
+    ) WHERE v IS NOT NULL AND v != '';
+    DELETE FROM tmdb_keywords WHERE tmdb_id = NEW.tmdb_id AND media_type = NEW.media_type;
+    INSERT OR IGNORE INTO tmdb_keywords (tmdb_id, media_type, name)
+    SELECT NEW.tmdb_id, NEW.media_type, v FROM (
+        SELECT TRIM(CASE j.type WHEN 'object' THEN json_extract(j.value, '$.name') ELSE j.value END) AS v
+        FROM json_each(CASE WHEN json_valid(NEW.keywords_json) THEN NEW.keywords_json END) j
+    ) WHERE v IS NOT NULL AND v != '';
+    DELETE FROM tmdb_countries WHERE tmdb_id = NEW.tmdb_id AND media_type = NEW.media_type;
+    INSERT OR IGNORE INTO tmdb_countries (tmdb_id, media_type, code)
+    SELECT NEW.tmdb_id, NEW.media_type, UPPER(v) FROM (
+        SELECT TRIM(CASE j.type WHEN 'object' THEN json_extract(j.value, '$.iso_3166_1') ELSE j.value END) AS v
+        FROM json_each(CASE WHEN json_valid(NEW.production_countries_json) THEN NEW.production_countries_json END) j
+    ) WHERE v IS NOT NULL AND v != '';
+    DELETE FROM tmdb_languages WHERE tmdb_id = NEW.tmdb_id AND media_type = NEW.media_type;
+    INSERT OR IGNORE INTO tmdb_languages (tmdb_id, media_type, code)
+    SELECT NEW.tmdb_id, NEW.media_type, LOWER(v) FROM (
+        SELECT TRIM(CASE j.type WHEN 'object' THEN json_extract(j.value, '$.iso_639_1') ELSE j.value END) AS v
+        FROM json_each(CASE WHEN json_valid(NEW.languages_json) THEN NEW.languages_json END) j
+    ) WHERE v IS NOT NULL AND v != '';
+END;
+
+"#;
+
+
+/// Fill the facet tables from existing tmdb_metadata rows
+pub const TMDB_FACETS_BACKFILL: &str = r#"
+INSERT OR IGNORE INTO tmdb_genres (tmdb_id, media_type, name)
+SELECT tmdb_id, media_type, v FROM (
+    SELECT md.tmdb_id, md.media_type, TRIM(CASE j.type WHEN 'object' THEN json_extract(j.value, '$.name') ELSE j.value END) AS v
+    FROM tmdb_metadata md, json_each(CASE WHEN json_valid(md.genres_json) THEN md.genres_json END) j
+) WHERE v IS NOT NULL AND v != '';
+
+INSERT OR IGNORE INTO tmdb_keywords (tmdb_id, media_type, name)
+SELECT tmdb_id, media_type, v FROM (
+    SELECT md.tmdb_id, md.media_type, TRIM(CASE j.type WHEN 'object' THEN json_extract(j.value, '$.name') ELSE j.value END) AS v
+    FROM tmdb_metadata md, json_each(CASE WHEN json_valid(md.keywords_json) THEN md.keywords_json END) j
+) WHERE v IS NOT NULL AND v != '';
+
+INSERT OR IGNORE INTO tmdb_countries (tmdb_id, media_type, code)
+SELECT tmdb_id, media_type, UPPER(v) FROM (
+    SELECT md.tmdb_id, md.media_type, TRIM(CASE j.type WHEN 'object' THEN json_extract(j.value, '$.iso_3166_1') ELSE j.value END) AS v
+    FROM tmdb_metadata md, json_each(CASE WHEN json_valid(md.production_countries_json) THEN md.production_countries_json END) j
+) WHERE v IS NOT NULL AND v != '';
+
+INSERT OR IGNORE INTO tmdb_languages (tmdb_id, media_type, code)
+SELECT tmdb_id, media_type, LOWER(v) FROM (
+    SELECT md.tmdb_id, md.media_type, TRIM(CASE j.type WHEN 'object' THEN json_extract(j.value, '$.iso_639_1') ELSE j.value END) AS v
+    FROM tmdb_metadata md, json_each(CASE WHEN json_valid(md.languages_json) THEN md.languages_json END) j
+) WHERE v IS NOT NULL AND v != '';
+
+"#;
+
+/// The last computed recommendation ranking, so smart playlists can filter
+/// and sort by it in SQL
+pub const RECOMMENDATIONS_SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS recommendations (
+    media_id INTEGER PRIMARY KEY,
+    rank INTEGER NOT NULL,  -- 1 is the best match
+    score REAL NOT NULL,
+    reasons_json TEXT NOT NULL DEFAULT '[]',
+    computed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (media_id) REFERENCES media_files(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_recommendations_rank ON recommendations(rank);
+"#;
+
+/// Episode files linked to the TMDB episodes they contain; a double episode
+/// file has a row for each
+pub const TMDB_EPISODE_LINKS_SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS tmdb_episode_links (
+    media_id INTEGER NOT NULL,
+    tmdb_show_id INTEGER NOT NULL,
+    season_number INTEGER NOT NULL,
+    episode_number INTEGER NOT NULL,
+    is_manual INTEGER NOT NULL DEFAULT 0,
+    linked_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (media_id, season_number, episode_number),
+    FOREIGN KEY (media_id) REFERENCES media_files(id) ON DELETE CASCADE,
+    FOREIGN KEY (tmdb_show_id, season_number, episode_number)
+        REFERENCES tmdb_episodes(tmdb_show_id, season_number, episode_number) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_tmdb_episode_links_episode
+    ON tmdb_episode_links(tmdb_show_id, season_number, episode_number);
+"#;
+
+/// The metadata provider each stored field of a file came from
+pub const METADATA_PROVENANCE_SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS metadata_provenance (
+    media_id INTEGER NOT NULL,
+    field TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    value_json TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (media_id, field),
+    FOREIGN KEY (media_id) REFERENCES media_files(id) ON DELETE CASCADE
+);
+"#;
+
+/// Manual edits kept apart from scanned data: the locked fields of each file
+/// with the user's value and the latest scanned one, and the history of
+/// every change
+pub const METADATA_OVERRIDES_SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS metadata_overrides (
+    media_id INTEGER NOT NULL,
+    field TEXT NOT NULL,
+    value_json TEXT NOT NULL,
+    scanned_json TEXT NOT NULL DEFAULT 'null',
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (media_id, field),
+    FOREIGN KEY (media_id) REFERENCES media_files(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS metadata_edits (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    media_id INTEGER NOT NULL,
+    field TEXT NOT NULL,
+    action TEXT NOT NULL CHECK(action IN ('edit', 'lock', 'unlock', 'revert', 'undo')),
+    old_value_json TEXT NOT NULL DEFAULT 'null',
+    new_value_json TEXT NOT NULL DEFAULT 'null',
+    was_locked INTEGER NOT NULL DEFAULT 0,
+    undone INTEGER NOT NULL DEFAULT 0,
+    edited_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (media_id) REFERENCES media_files(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_metadata_edits_field ON metadata_edits(media_id, field, id);
+"#;
+
+/// Files locked as a whole before per-field locks keep every value they had:
+/// the four columns and each metadata_json key become locked fields. Their
+/// scanned values are unknown until the next scan.
+pub const METADATA_OVERRIDES_BACKFILL: &str = r#"
+INSERT OR IGNORE INTO metadata_overrides (media_id, field, value_json)
+SELECT id, 'title', json_quote(title) FROM media_files WHERE is_locked = 1 AND title IS NOT NULL
+UNION ALL
+SELECT id, 'year', json_quote(year) FROM media_files WHERE is_locked = 1 AND year IS NOT NULL
+UNION ALL
+SELECT id, 'season', json_quote(season_number) FROM media_files WHERE is_locked = 1 AND season_number IS NOT NULL
+UNION ALL
+SELECT id, 'episode', json_quote(episode_number) FROM media_files WHERE is_locked = 1 AND episode_number IS NOT NULL
+UNION ALL
+SELECT m.id, j.key, CASE WHEN j.type IN ('object', 'array') THEN j.value
+                         WHEN j.type = 'true' THEN 'true'
+                         WHEN j.type = 'false' THEN 'false'
+                         ELSE json_quote(j.value) END
+FROM media_files m, json_each(m.metadata_json) j
+WHERE m.is_locked = 1 AND json_valid(m.metadata_json) AND json_type(m.metadata_json) = 'object';
+
+UPDATE media_files SET is_locked = EXISTS (SELECT 1 FROM metadata_overrides o WHERE o.media_id = media_files.id)
+WHERE is_locked = 1;
+"#;
+
+/// Timing corrections of external subtitle tracks, applied when a track is
+/// read: the times are scaled (resync stretch times the frame rate ratio),
+/// then shifted by the offset
+pub const SUBTITLE_TIMING_SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS subtitle_timing (
+    subtitle_id INTEGER PRIMARY KEY,
+    offset_ms INTEGER NOT NULL DEFAULT 0,
+    scale REAL NOT NULL DEFAULT 1.0,
+    source_framerate REAL,
+    target_framerate REAL,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (subtitle_id) REFERENCES subtitle_tracks(id) ON DELETE CASCADE
+);
+"#;
+
+/// Forced/SDH/default flags for subtitle tracks, and one row per external
+/// subtitle file so rescans don't add the same file twice
+pub const SUBTITLE_FLAGS_SCHEMA: &str = r#"
+ALTER TABLE subtitle_tracks ADD COLUMN is_forced INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE subtitle_tracks ADD COLUMN is_sdh INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE subtitle_tracks ADD COLUMN is_default INTEGER NOT NULL DEFAULT 0;
+
+DELETE FROM subtitle_tracks
+WHERE is_embedded = 0
+  AND id NOT IN (
+    SELECT MIN(id) FROM subtitle_tracks WHERE is_embedded = 0 GROUP BY media_id, file_path
+  );
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_subtitle_tracks_external
+    ON subtitle_tracks(media_id, file_path) WHERE is_embedded = 0;
+"#;
+
+/// Which collection items the franchise sync added (`source = 'auto'`), so it
+/// only ever removes its own, and the generated collections the user deleted,
+/// so they aren't created again
+pub const AUTO_COLLECTION_SOURCES_SCHEMA: &str = r#"
+ALTER TABLE collection_items ADD COLUMN source TEXT NOT NULL DEFAULT 'user';
+
+UPDATE collection_items SET source = 'auto'
+WHERE collection_id IN (SELECT id FROM collections WHERE auto_source IS NOT NULL);
+
+CREATE TABLE IF NOT EXISTS dismissed_auto_collections (
+    auto_source TEXT PRIMARY KEY,
+    dismissed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+"#;
@@ -0,0 +1,409 @@
+use rusqlite::{Connection, Result, ToSql};
+use unicode_normalization::UnicodeNormalization;
+use super::models::{MediaFile, PageRequest, SortKey};
+use super::operations::{
+    list_media, media_file_from_row, push_page_clause, sort_expression, MEDIA_COLUMNS,
+};
+
+/// Column weights for bm25(): title, file_name, overview, cast_names, genres
+const BM25_WEIGHTS: &str = "10.0, 4.0, 1.0, 3.0, 2.0";
+
+/// Markers used inside SQLite highlight()/snippet() before HTML escaping
+const MATCH_START: &str = "\u{1}";
+const MATCH_END: &str = "\u{2}";
+
+/// A ranked search hit.
+///
+/// Serializes as the media file's fields plus `score`, `title_highlight` and
+/// `snippet`. Highlights are HTML-escaped with matches wrapped in `<mark>`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    pub media: MediaFile,
+    /// Relevance score, higher is better
+    pub score: f64,
+    pub title_highlight: Option<String>,
+    pub snippet: Option<String>,
+}
+
+/// Full-text search over title, file name, overview, cast and genres.
+///
+/// Every word must match (as a prefix); quoted text matches as a phrase.
+/// Accents are folded, results are ranked by BM25, and when nothing matches
+/// a typo-tolerant fallback compares the query against titles.
+pub fn search_media(conn: &Connection, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
+    let page = PageRequest { sort: Some(SortKey::Relevance), limit: Some(limit), ..PageRequest::default() };
+    search_media_page(conn, query, &page)
+}
+
+/// Paged variant of `search_media`.
+///
+/// Sorts by relevance unless `page.sort` says otherwise. The typo-tolerant
+/// fallback only applies to the first page and is always ranked by distance.
+pub fn search_media_page(conn: &Connection, query: &str, page: &PageRequest) -> Result<Vec<SearchResult>> {
+    let terms = tokenize_query(query);
+    let limit = page.limit.unwrap_or(usize::MAX);
+
+    if terms.is_empty() {
+        let page = PageRequest { sort: page.sort.filter(|s| *s != SortKey::Relevance), ..page.clone() };
+        return Ok(list_media(conn, &page)?
+            .into_iter()
+            .map(|media| SearchResult { media, score: 0.0, title_highlight: None, snippet: None })
+            .collect());
+    }
+
+    let fts_query = terms
+        .iter()
+        .map(|term| match term {
+            QueryTerm::Word(w) => format!("\"{}\"*", w.replace('"', "\"\"")),
+            QueryTerm::Phrase(p) => format!("\"{}\"", p.replace('"', "\"\"")),
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let score_expr = format!("-bm25(media_search, {})", BM25_WEIGHTS);
+    let mut query = format!(
+        "SELECT {},
+                {} AS score,
+                highlight(media_search, 0, ?2, ?3),
+                snippet(media_search, 2, ?2, ?3, '…', 16)
+         FROM media_search
+         JOIN media_files m ON m.id = media_search.rowid
+         WHERE media_search MATCH ?1 AND m.is_deleted = 0",
+        MEDIA_COLUMNS, score_expr
+    );
+    let mut params: Vec<Box<dyn ToSql>> = vec![
+        Box::new(fts_query),
+        Box::new(MATCH_START),
+        Box::new(MATCH_END),
+    ];
+
+    let sort = page.sort.unwrap_or(SortKey::Relevance);
+    if sort == SortKey::Relevance {
+        let page = PageRequest { sort: Some(SortKey::Relevance), ..page.clone() };
+        push_page_clause(&mut query, &mut params, &page, &score_expr, |cursor| {
+            format!(
+                "(SELECT {} FROM media_search WHERE media_search MATCH ?1 AND rowid = {})",
+                score_expr, cursor
+            )
+        });
+    } else {
+        let sort_expr = sort_expression(sort);
+        push_page_clause(&mut query, &mut params, page, sort_expr, |cursor| {
+            format!("(SELECT {} FROM media_files m WHERE m.id = {})", sort_expr, cursor)
+        });
+    }
+
+    let mut stmt = conn.prepare(&query)?;
+    let params_refs: Vec<&dyn ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+    let results = stmt
+        .query_map(params_refs.as_slice(), |row| {
+            let title_highlight: Option<String> = row.get(23)?;
+            let snippet: Option<String> = row.get(24)?;
+
+            Ok(SearchResult {
+                media: media_file_from_row(row)?,
+                score: row.get(22)?,
+                title_highlight: title_highlight.filter(|h| h.contains(MATCH_START)).map(|h| render_highlight(&h)),
+                snippet: snippet.filter(|s| s.contains(MATCH_START)).map(|s| render_highlight(&s)),
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    if !results.is_empty() || page.after_id.is_some() {
+        return Ok(results);
+    }
+
+    fuzzy_title_search(conn, &terms, limit)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum QueryTerm {
+    Word(String),
+    Phrase(String),
+}
+
+/// Split user input into words and "quoted phrases", dropping FTS5 syntax characters
+fn tokenize_query(query: &str) -> Vec<QueryTerm> {
+    let mut terms = Vec::new();
+
+    for (i, part) in query.split('"').enumerate() {
+        let words: Vec<String> = part
+            .split(|c: char| !c.is_alphanumeric() && c != '\'')
+            .map(|w| w.trim_matches('\''))
+            .filter(|w| !w.is_empty())
+            .map(String::from)
+            .collect();
+
+        // Odd segments sit between a pair of quotes
+        if i % 2 == 1 && words.len() > 1 {
+            terms.push(QueryTerm::Phrase(words.join(" ")));
+        } else {
+            terms.extend(words.into_iter().map(QueryTerm::Word));
+        }
+    }
+
+    terms
+}
+
+/// HTML-escape highlighted text and turn the match markers into <mark> tags
+fn render_highlight(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace(MATCH_START, "<mark>")
+        .replace(MATCH_END, "</mark>")
+}
+
+/// Lowercase and strip diacritics (é -> e)
+pub(crate) fn fold_text(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !('\u{300}'..='\u{36f}').contains(c))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Edit distance between two strings, counting a swap of adjacent letters
+/// as one edit (optimal string alignment)
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut before_prev = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        curr[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            curr[j] = (prev[j] + 1).min(curr[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                curr[j] = curr[j].min(before_prev[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut before_prev, &mut prev);
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b.len()]
+}
+
+/// Typo-tolerant fallback: every query word must be within a small edit
+/// distance of (a prefix of) some word of the title or file name. Only
+/// titles with a word starting with one of each query word's first two
+/// letters are compared, so the search index narrows the candidates. That
+/// finds swapped first letters ("mtarix", "amtrix") but not a wrong or
+/// missing first letter ("natrix", "atrix").
+fn fuzzy_title_search(conn: &Connection, terms: &[QueryTerm], limit: usize) -> Result<Vec<SearchResult>> {
+    let words: Vec<String> = terms
+        .iter()
+        .flat_map(|t| match t {
+            QueryTerm::Word(w) => vec![fold_text(w)],
+            QueryTerm::Phrase(p) => p.split(' ').map(fold_text).collect(),
+        })
+        .collect();
+
+    let starts: Vec<String> = words
+        .iter()
+        .map(|w| {
+            let mut letters: Vec<char> = w.chars().filter(|c| c.is_alphanumeric()).take(2).collect();
+            letters.dedup();
+            letters.iter().map(|c| format!("\"{}\"*", c)).collect::<Vec<_>>().join(" OR ")
+        })
+        .filter(|s| !s.is_empty())
+        .map(|s| format!("({})", s))
+        .collect();
+    if starts.is_empty() {
+        return Ok(Vec::new());
+    }
+    let prefilter = format!("{{title file_name}} : ({})", starts.join(" AND "));
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}
+         FROM media_search
+         JOIN media_files m ON m.id = media_search.rowid
+         WHERE media_search MATCH ?1 AND m.is_deleted = 0
+         ORDER BY {}, m.id",
+        MEDIA_COLUMNS,
+        sort_expression(SortKey::Title)
+    ))?;
+    let candidates = stmt.query_map([prefilter], media_file_from_row)?.collect::<Result<Vec<_>>>()?;
+
+    let mut scored: Vec<(usize, MediaFile)> = candidates
+        .into_iter()
+        .filter_map(|media| {
+            let haystack = fold_text(&format!(
+                "{} {}",
+                media.title.as_deref().unwrap_or(""),
+                media.file_name
+            ));
+            let candidates: Vec<&str> = haystack
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|w| !w.is_empty())
+                .collect();
+
+            let mut total = 0;
+            for word in &words {
+                let max_distance = match word.chars().count() {
+                    0..=3 => 0,
+                    4..=6 => 1,
+                    _ => 2,
+                };
+                let best = candidates
+                    .iter()
+                    .map(|c| {
+                        // Compare against the candidate's prefix of similar length too
+                        let prefix: String = c.chars().take(word.chars().count()).collect();
+                        edit_distance(word, c).min(edit_distance(word, &prefix) + 1)
+                    })
+                    .min()?;
+                if best > max_distance {
+                    return None;
+                }
+                total += best;
+            }
+            Some((total, media))
+        })
+        .collect();
+
+    scored.sort_by_key(|(distance, _)| *distance);
+
+    Ok(scored
+        .into_iter()
+        .take(limit)
+        .map(|(distance, media)| SearchResult {
+            media,
+            score: 1.0 / (1.0 + distance as f64),
+            title_highlight: None,
+            snippet: None,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use crate::db::test_support::{self, TestMedia};
+
+    fn add_media(conn: &Connection, path: &str, title: &str, metadata_json: Option<&str>) -> Result<i64> {
+        Ok(test_support::add_media(conn, &TestMedia { path, title: Some(title), metadata_json, ..TestMedia::default() }))
+    }
+
+    #[test]
+    fn test_search_ranking_and_prefix() -> Result<()> {
+        let conn = init_db()?;
+        add_media(&conn, "/m/Amelie.2001.mkv", "Amélie", Some(r#"{"overview": "A shy waitress in Montmartre", "genres": ["Comedy", "Romance"]}"#))?;
+        add_media(&conn, "/m/Paris.mkv", "Paris, je t'aime", Some(r#"{"overview": "Stories set in Montmartre and beyond", "cast": [{"name": "Natalie Portman"}]}"#))?;
+        add_media(&conn, "/m/Heat.mkv", "Heat", None)?;
+
+        // Accent folding and prefix matching on the title
+        let results = search_media(&conn, "amel", 10)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].media.title.as_deref(), Some("Amélie"));
+        assert_eq!(results[0].title_highlight.as_deref(), Some("<mark>Amélie</mark>"));
+
+        // Title matches outrank overview matches
+        add_media(&conn, "/m/Montmartre.mkv", "Montmartre", None)?;
+        let results = search_media(&conn, "montmartre", 10)?;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].media.title.as_deref(), Some("Montmartre"));
+        assert!(results[1].snippet.as_deref().unwrap().contains("<mark>Montmartre</mark>"));
+
+        // Multiple words, cast and genres
+        assert_eq!(search_media(&conn, "portman paris", 10)?.len(), 1);
+        assert_eq!(search_media(&conn, "romance", 10)?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_index_follows_updates() -> Result<()> {
+        let conn = init_db()?;
+        let id = add_media(&conn, "/m/file.mkv", "Old Title", None)?;
+
+        conn.execute("UPDATE media_files SET title = 'New Title' WHERE id = ?1", [id])?;
+        assert!(search_media(&conn, "\"old title\"", 10)?.is_empty());
+        assert_eq!(search_media(&conn, "\"new title\"", 10)?.len(), 1);
+
+        conn.execute("UPDATE media_files SET is_deleted = 1 WHERE id = ?1", [id])?;
+        assert!(search_media(&conn, "new", 10)?.is_empty());
+
+        conn.execute("DELETE FROM media_files WHERE id = ?1", [id])?;
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM media_search", [], |row| row.get(0))?;
+        assert_eq!(count, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_typo_fallback() -> Result<()> {
+        let conn = init_db()?;
+        add_media(&conn, "/m/Interstellar.mkv", "Interstellar", None)?;
+        add_media(&conn, "/m/Inception.mkv", "Inception", None)?;
+        add_media(&conn, "/m/Amelie.mkv", "Amélie", None)?;
+        add_media(&conn, "/m/The.Matrix.mkv", "The Matrix", None)?;
+
+        let results = search_media(&conn, "intersteller", 10)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].media.title.as_deref(), Some("Interstellar"));
+        assert_eq!(search_media(&conn, "amelia", 10)?.len(), 1);
+        // Swapped first letters are found, a wrong or missing first letter isn't
+        for query in ["mtarix", "amtrix", "the amtrix"] {
+            assert_eq!(search_media(&conn, query, 10)?.len(), 1, "{}", query);
+        }
+        assert!(search_media(&conn, "natrix", 10)?.is_empty());
+        assert!(search_media(&conn, "nterstellar", 10)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_pagination() -> Result<()> {
+        let conn = init_db()?;
+        for i in 0..5 {
+            add_media(&conn, &format!("/m/Batman.{}.mkv", i), &format!("Batman {}", i), None)?;
+        }
+
+        let mut page = PageRequest { limit: Some(2), ..PageRequest::default() };
+        let mut seen = Vec::new();
+        loop {
+            let results = search_media_page(&conn, "batman", &page)?;
+            if results.is_empty() {
+                break;
+            }
+            assert!(results.len() <= 2);
+            page.after_id = results.last().unwrap().media.id;
+            seen.extend(results.into_iter().map(|r| r.media.title.unwrap()));
+        }
+        seen.sort();
+        assert_eq!(seen, vec!["Batman 0", "Batman 1", "Batman 2", "Batman 3", "Batman 4"]);
+
+        let page = PageRequest { sort: Some(SortKey::Title), descending: Some(true), limit: Some(1), ..PageRequest::default() };
+        let results = search_media_page(&conn, "batman", &page)?;
+        assert_eq!(results[0].media.title.as_deref(), Some("Batman 4"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_highlight_is_escaped() {
+        let rendered = render_highlight("Tom & \u{1}Jerry\u{2} <3");
+        assert_eq!(rendered, "Tom &amp; <mark>Jerry</mark> &lt;3");
+    }
+
+    #[test]
+    fn test_tokenize_query() {
+        assert_eq!(
+            tokenize_query("dark \"the knight\" -rises"),
+            vec![
+                QueryTerm::Word("dark".into()),
+                QueryTerm::Phrase("the knight".into()),
+                QueryTerm::Word("rises".into()),
+            ]
+        );
+        assert!(tokenize_query("  * ( ) ").is_empty());
+    }
+}
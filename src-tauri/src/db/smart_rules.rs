@@ -0,0 +1,409 @@
+//! Smart playlist rule engine.
+//!
+//! Rules are stored flat in `playlist_rules`. A rule whose `rule_type` is
+//! `group` holds other rules (those with `parent_id` pointing at it) and
+//! combines them with its operator, `and` or `or`. Top-level rules are ANDed.
+
+use std::collections::HashMap;
+use chrono::{DateTime, NaiveDate};
+use rusqlite::ToSql;
+use crate::db::models::{MediaType, PlaylistRule};
+use crate::db::facets::{facet_condition, Facet, POPULARITY_SQL, RATING_SQL};
+use crate::db::operations::{resolution_class, resolution_class_sql};
+use crate::db::query_parser::escape_like;
+use crate::db::recommendations::RECOMMENDATION_RANK_SQL;
+
+/// `rule_type` of a rule that groups other rules
+pub const GROUP_RULE: &str = "group";
+
+/// `rule_type` of a rule on the recommendation ranking
+pub const RECOMMENDATION_RANK_RULE: &str = "recommendation_rank";
+
+#[derive(Debug, thiserror::Error)]
+pub enum RuleError {
+    #[error("Unknown rule field '{0}'")]
+    UnknownField(String),
+
+    #[error("Operator '{operator}' is not supported for '{field}' (expected one of: {expected})")]
+    UnsupportedOperator { field: String, operator: String, expected: String },
+
+    #[error("Invalid value '{value}' for '{field}': {reason}")]
+    InvalidValue { field: String, value: String, reason: String },
+
+    #[error("Rule {0} is not a group in this playlist")]
+    InvalidParent(i64),
+
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+/// How a rule field is matched
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FieldKind {
+    Group,
+    Text(&'static str),
+    Number(&'static str),
+    MediaType,
+    Resolution,
+    WatchStatus,
+    Date(&'static str),
+    Collection,
+    AudioLanguage,
+    /// TMDB facet; the value may list several, comma-separated, any of which matches
+    Facet(Facet),
+}
+
+const TEXT_OPERATORS: &[&str] = &["equals", "notequals", "contains", "not_contains", "starts_with", "ends_with"];
+const NUMBER_OPERATORS: &[&str] = &["equals", "notequals", "gt", "gte", "lt", "lte", "between"];
+const EQUALITY_OPERATORS: &[&str] = &["equals", "notequals"];
+const DATE_OPERATORS: &[&str] = &["before", "after", "in_last", "not_in_last"];
+const MEMBERSHIP_OPERATORS: &[&str] = &["in", "not_in"];
+const GROUP_OPERATORS: &[&str] = &["and", "or"];
+
+const PLAY_COUNT_SQL: &str = "COALESCE((SELECT ps.watch_count FROM playback_state ps WHERE ps.media_id = m.id), 0)";
+const LAST_PLAYED_SQL: &str = "(SELECT ps.last_played_at FROM playback_state ps WHERE ps.media_id = m.id)";
+
+fn field_kind(rule_type: &str) -> Option<FieldKind> {
+    let kind = match rule_type {
+        GROUP_RULE => FieldKind::Group,
+        "title" => FieldKind::Text("m.title"),
+        "file_name" => FieldKind::Text("m.file_name"),
+        "codec" => FieldKind::Text("m.codec"),
+        "year" => FieldKind::Number("m.year"),
+        // Seconds
+        "duration" => FieldKind::Number("m.duration"),
+        // Bytes
+        "file_size" => FieldKind::Number("m.file_size"),
+        "play_count" => FieldKind::Number(PLAY_COUNT_SQL),
+        "media_type" => FieldKind::MediaType,
+        "resolution" => FieldKind::Resolution,
+        "watch_status" => FieldKind::WatchStatus,
+        "last_played" => FieldKind::Date(LAST_PLAYED_SQL),
+        "date_added" => FieldKind::Date("m.indexed_at"),
+        "collection" => FieldKind::Collection,
+        "audio_language" => FieldKind::AudioLanguage,
+        "genre" => FieldKind::Facet(Facet::Genre),
+        "keyword" => FieldKind::Facet(Facet::Keyword),
+        "country" => FieldKind::Facet(Facet::Country),
+        "language" => FieldKind::Facet(Facet::Language),
+        // TMDB rating, 0-10
+        "rating" => FieldKind::Number(RATING_SQL),
+        "popularity" => FieldKind::Number(POPULARITY_SQL),
+        // 1 is the best recommendation; unrecommended files never match
+        RECOMMENDATION_RANK_RULE => FieldKind::Number(RECOMMENDATION_RANK_SQL),
+        _ => return None,
+    };
+    Some(kind)
+}
+
+fn operators_for(kind: FieldKind) -> &'static [&'static str] {
+    match kind {
+        FieldKind::Group => GROUP_OPERATORS,
+        FieldKind::Text(_) => TEXT_OPERATORS,
+        FieldKind::Number(_) | FieldKind::Resolution => NUMBER_OPERATORS,
+        FieldKind::MediaType | FieldKind::WatchStatus | FieldKind::AudioLanguage | FieldKind::Facet(_) => EQUALITY_OPERATORS,
+        FieldKind::Date(_) => DATE_OPERATORS,
+        FieldKind::Collection => MEMBERSHIP_OPERATORS,
+    }
+}
+
+/// Map operator spellings used by older clients to their canonical names
+pub fn normalize_operator(operator: &str) -> String {
+    let operator = operator.trim().to_lowercase();
+    match operator.as_str() {
+        "=" | "==" | "is" => "equals",
+        "!=" | "not_equals" | "is_not" => "notequals",
+        ">" | "greater_than" => "gt",
+        ">=" => "gte",
+        "<" | "less_than" => "lt",
+        "<=" => "lte",
+        "all" => "and",
+        "any" => "or",
+        _ => return operator,
+    }
+    .to_string()
+}
+
+/// Check that a rule names a known field with an operator and value it supports
+pub fn validate_rule(rule_type: &str, operator: &str, value: &str) -> Result<(), RuleError> {
+    // Compiling into a scratch parameter list performs every check
+    compile_rule(rule_type, operator, value, &mut Vec::new()).map(|_| ())
+}
+
+/// Compile a playlist's rules into an SQL condition over `media_files m`.
+///
+/// Placeholders continue from `params.len() + 1`. Returns None when there
+/// are no rules (or only empty groups).
+pub fn compile_rules(rules: &[PlaylistRule], params: &mut Vec<Box<dyn ToSql>>) -> Result<Option<String>, RuleError> {
+    let mut children: HashMap<Option<i64>, Vec<&PlaylistRule>> = HashMap::new();
+    for rule in rules {
+        children.entry(rule.parent_id).or_default().push(rule);
+    }
+
+    compile_group(None, "and", &children, params)
+}
+
+fn compile_group(
+    parent: Option<i64>,
+    operator: &str,
+    children: &HashMap<Option<i64>, Vec<&PlaylistRule>>,
+    params: &mut Vec<Box<dyn ToSql>>,
+) -> Result<Option<String>, RuleError> {
+    let mut parts = Vec::new();
+
+    for rule in children.get(&parent).map(|c| c.as_slice()).unwrap_or_default() {
+        if rule.rule_type == GROUP_RULE {
+            let group_operator = normalize_operator(&rule.operator);
+            if !GROUP_OPERATORS.contains(&group_operator.as_str()) {
+                return Err(unsupported(GROUP_RULE, &rule.operator, GROUP_OPERATORS));
+            }
+            if let Some(sql) = compile_group(rule.id, &group_operator, children, params)? {
+                parts.push(sql);
+            }
+        } else {
+            parts.push(compile_rule(&rule.rule_type, &rule.operator, &rule.value, params)?);
+        }
+    }
+
+    Ok(match parts.len() {
+        0 => None,
+        1 => parts.pop(),
+        _ => {
+            let separator = if operator == "or" { " OR " } else { " AND " };
+            Some(format!("({})", parts.join(separator)))
+        }
+    })
+}
+
+fn compile_rule(
+    rule_type: &str,
+    operator: &str,
+    value: &str,
+    params: &mut Vec<Box<dyn ToSql>>,
+) -> Result<String, RuleError> {
+    let kind = field_kind(rule_type).ok_or_else(|| RuleError::UnknownField(rule_type.to_string()))?;
+    let operator = normalize_operator(operator);
+    let allowed = operators_for(kind);
+    if !allowed.contains(&operator.as_str()) {
+        return Err(unsupported(rule_type, &operator, allowed));
+    }
+
+    let invalid = |reason: &str| RuleError::InvalidValue {
+        field: rule_type.to_string(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    };
+    let value = value.trim();
+
+    let mut bind = |value: Box<dyn ToSql>| {
+        params.push(value);
+        format!("?{}", params.len())
+    };
+
+    let sql = match kind {
+        // Groups have no condition of their own
+        FieldKind::Group => "1=1".to_string(),
+        FieldKind::Text(column) => {
+            // Wildcards in the value are matched literally
+            let escaped = escape_like(value);
+            match operator.as_str() {
+                "equals" => format!("{} = {} COLLATE NOCASE", column, bind(Box::new(value.to_string()))),
+                "notequals" => format!("COALESCE({}, '') != {} COLLATE NOCASE", column, bind(Box::new(value.to_string()))),
+                "contains" => format!("{} LIKE {} ESCAPE '\\'", column, bind(Box::new(format!("%{}%", escaped)))),
+                "not_contains" => {
+                    format!("COALESCE({}, '') NOT LIKE {} ESCAPE '\\'", column, bind(Box::new(format!("%{}%", escaped))))
+                }
+                "starts_with" => format!("{} LIKE {} ESCAPE '\\'", column, bind(Box::new(format!("{}%", escaped)))),
+                _ => format!("{} LIKE {} ESCAPE '\\'", column, bind(Box::new(format!("%{}", escaped)))),
+            }
+        }
+        FieldKind::Number(column) => {
+            compare_numbers(column, &operator, value, |v| v.parse::<f64>().ok(), &mut bind)
+                .ok_or_else(|| invalid("expected a number, or a range like 10..20 for 'between'"))?
+        }
+        FieldKind::Resolution => {
+            let class = resolution_class_sql("m");
+            let column = if operator == "notequals" { format!("COALESCE({}, 0)", class) } else { class };
+            compare_numbers(&column, &operator, value, |v| resolution_class(v).map(|c| c as f64), &mut bind)
+                .ok_or_else(|| invalid("expected a resolution such as 4k, 1080p, 720p or sd"))?
+        }
+        FieldKind::MediaType => {
+            let media_type = MediaType::from_str(&value.to_lowercase())
+                .ok_or_else(|| invalid("expected movie, tv_episode, music, video or audio"))?;
+            let op = if operator == "equals" { "=" } else { "!=" };
+            format!("m.media_type {} {}", op, bind(Box::new(media_type.as_str().to_string())))
+        }
+        FieldKind::WatchStatus => {
+            let condition = match value.to_lowercase().as_str() {
+                "watched" => "EXISTS (SELECT 1 FROM playback_state ps WHERE ps.media_id = m.id AND ps.completed = 1)",
+                "unwatched" => "NOT EXISTS (SELECT 1 FROM playback_state ps WHERE ps.media_id = m.id AND ps.completed = 1)",
+                "in_progress" => {
+                    "EXISTS (SELECT 1 FROM playback_state ps WHERE ps.media_id = m.id AND ps.completed = 0 AND ps.last_position > 0)"
+                }
+                _ => return Err(invalid("expected watched, unwatched or in_progress")),
+            };
+            if operator == "equals" { condition.to_string() } else { format!("NOT ({})", condition) }
+        }
+        FieldKind::Date(column) => match operator.as_str() {
+            "before" | "after" => {
+                let date = parse_date(value).ok_or_else(|| invalid("expected a date like 2024-01-31"))?;
+                let op = if operator == "before" { "<" } else { ">" };
+                format!("julianday({}) {} julianday({})", column, op, bind(Box::new(date)))
+            }
+            _ => {
+                let days = parse_days(value).ok_or_else(|| invalid("expected a number of days, or weeks like 2w"))?;
+                let placeholder = bind(Box::new(days));
+                if operator == "in_last" {
+                    format!("julianday({}) >= julianday('now') - {}", column, placeholder)
+                } else {
+                    format!("({c} IS NULL OR julianday({c}) < julianday('now') - {p})", c = column, p = placeholder)
+                }
+            }
+        },
+        FieldKind::Collection => {
+            let collection_id: i64 = value.parse().map_err(|_| invalid("expected a collection id"))?;
+            let exists = format!(
+                "EXISTS (SELECT 1 FROM collection_items ci WHERE ci.collection_id = {} AND ci.media_id = m.id)",
+                bind(Box::new(collection_id))
+            );
+            if operator == "in" { exists } else { format!("NOT {}", exists) }
+        }
+        FieldKind::AudioLanguage => {
+            if value.is_empty() {
+                return Err(invalid("expected a language code"));
+            }
+            let exists = format!(
+                "EXISTS (SELECT 1 FROM audio_tracks a WHERE a.media_id = m.id AND LOWER(a.language) = LOWER({}))",
+                bind(Box::new(value.to_string()))
+            );
+            if operator == "equals" { exists } else { format!("NOT {}", exists) }
+        }
+        FieldKind::Facet(facet) => {
+            let values: Vec<String> = value.split(',').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect();
+            let exists = facet_condition(facet, &values, |s| bind(Box::new(s))).ok_or_else(|| invalid("expected a value"))?;
+            if operator == "equals" { exists } else { format!("NOT {}", exists) }
+        }
+    };
+
+    Ok(sql)
+}
+
+/// Build a numeric comparison; None when the value doesn't parse
+fn compare_numbers(
+    column: &str,
+    operator: &str,
+    value: &str,
+    parse: impl Fn(&str) -> Option<f64>,
+    bind: &mut impl FnMut(Box<dyn ToSql>) -> String,
+) -> Option<String> {
+    if operator == "between" {
+        let (low, high) = value.split_once("..")?;
+        let (low, high) = (parse(low.trim())?, parse(high.trim())?);
+        return Some(format!("{} BETWEEN {} AND {}", column, bind(Box::new(low)), bind(Box::new(high))));
+    }
+
+    let number = parse(value)?;
+    let op = match operator {
+        "equals" => "=",
+        "notequals" => "!=",
+        "gt" => ">",
+        "gte" => ">=",
+        "lt" => "<",
+        _ => "<=",
+    };
+    Some(format!("{} {} {}", column, op, bind(Box::new(number))))
+}
+
+/// Accept `YYYY-MM-DD` or RFC 3339, normalised for julianday()
+fn parse_date(value: &str) -> Option<String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(date.format("%Y-%m-%d").to_string());
+    }
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|dt| dt.naive_utc().format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+/// Days as `30`, `30d` or `2w`
+fn parse_days(value: &str) -> Option<f64> {
+    let value = value.to_lowercase();
+    let (number, factor) = match value.strip_suffix('w') {
+        Some(weeks) => (weeks, 7.0),
+        None => (value.strip_suffix('d').unwrap_or(&value), 1.0),
+    };
+    number.trim().parse::<f64>().ok().filter(|d| *d >= 0.0).map(|d| d * factor)
+}
+
+fn unsupported(field: &str, operator: &str, allowed: &[&str]) -> RuleError {
+    RuleError::UnsupportedOperator {
+        field: field.to_string(),
+        operator: operator.to_string(),
+        expected: allowed.join(", "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::types::{ToSqlOutput, Value};
+    use crate::db::connection::init_db;
+    use crate::db::test_support::{self, TestMedia};
+
+    fn compile(rule_type: &str, operator: &str, value: &str) -> Result<(String, Vec<Value>), RuleError> {
+        let mut params = Vec::new();
+        let sql = compile_rule(rule_type, operator, value, &mut params)?;
+        let values = params
+            .iter()
+            .map(|p| match p.to_sql().unwrap() {
+                ToSqlOutput::Borrowed(value) => value.into(),
+                ToSqlOutput::Owned(value) => value,
+                other => panic!("unexpected parameter {:?}", other),
+            })
+            .collect();
+        Ok((sql, values))
+    }
+
+    #[test]
+    fn test_operator_sql() -> Result<(), RuleError> {
+        let text = |s: &str| Value::Text(s.to_string());
+        assert_eq!(compile("title", "==", "Alien")?, ("m.title = ?1 COLLATE NOCASE".to_string(), vec![text("Alien")]));
+        assert_eq!(compile("title", "contains", " 100% ")?, ("m.title LIKE ?1 ESCAPE '\\'".to_string(), vec![text("%100\\%%")]));
+        assert_eq!(
+            compile("file_name", "not_contains", "a_b")?,
+            ("COALESCE(m.file_name, '') NOT LIKE ?1 ESCAPE '\\'".to_string(), vec![text("%a\\_b%")])
+        );
+        assert_eq!(compile("codec", "starts_with", "h\\")?.1, vec![text("h\\\\%")]);
+        assert_eq!(compile("title", "ends_with", "II")?.1, vec![text("%II")]);
+        assert_eq!(
+            compile("year", "between", "1990..1999")?,
+            ("m.year BETWEEN ?1 AND ?2".to_string(), vec![Value::Real(1990.0), Value::Real(1999.0)])
+        );
+        assert_eq!(compile("year", "<", "2000")?.0, "m.year < ?1");
+
+        assert!(matches!(compile("mood", "equals", "x"), Err(RuleError::UnknownField(_))));
+        assert!(matches!(compile("year", "contains", "19"), Err(RuleError::UnsupportedOperator { .. })));
+        assert!(matches!(compile("year", "gt", "soon"), Err(RuleError::InvalidValue { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_text_operators_match_wildcards_literally() -> Result<(), RuleError> {
+        let conn = init_db()?;
+        for (path, title) in [("/m/1.mkv", "100% Wolf"), ("/m/2.mkv", "1000 Ways"), ("/m/3.mkv", "A_B"), ("/m/4.mkv", "AxB")] {
+            test_support::add_media(&conn, &TestMedia { path, title: Some(title), ..TestMedia::default() });
+        }
+        let titles = |operator: &str, value: &str| -> Result<Vec<String>, RuleError> {
+            let mut params = Vec::new();
+            let sql = compile_rule("title", operator, value, &mut params)?;
+            let mut stmt = conn.prepare(&format!("SELECT m.title FROM media_files m WHERE {} ORDER BY m.id", sql))?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| row.get(0))?;
+            Ok(rows.collect::<rusqlite::Result<Vec<String>>>()?)
+        };
+
+        assert_eq!(titles("contains", "100%")?, vec!["100% Wolf"]);
+        assert_eq!(titles("starts_with", "a_")?, vec!["A_B"]);
+        assert_eq!(titles("ends_with", "_b")?, vec!["A_B"]);
+        assert_eq!(titles("not_contains", "%")?, vec!["1000 Ways", "A_B", "AxB"]);
+        Ok(())
+    }
+}
@@ -1,5 +1,9 @@
-use rusqlite::{Connection, Result, params};
+use rusqlite::{Connection, OptionalExtension, Result, params};
 use chrono::Utc;
+use std::path::{Path, PathBuf};
+use crate::indexer::languages::{language_name, normalize_language};
+use crate::indexer::metadata::SubtitleStreamMetadata;
+use crate::indexer::scanner::VIDEO_EXTENSIONS;
 
 /// Subtitle track model
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -13,9 +17,43 @@ pub struct SubtitleTrack {
     pub is_embedded: bool,
     pub track_index: Option<i32>,
     pub added_at: String,
+    pub is_forced: bool,
+    pub is_sdh: bool,
+    pub is_default: bool,
 }
 
-/// Add a subtitle track
+/// Forced, SDH and default markers of a subtitle track
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SubtitleFlags {
+    /// Only translates foreign dialogue and signs
+    pub is_forced: bool,
+    /// Includes sound descriptions for the deaf and hard of hearing (SDH/CC)
+    pub is_sdh: bool,
+    pub is_default: bool,
+}
+
+const SUBTITLE_COLUMNS: &str =
+    "id, media_id, file_path, language, label, codec, is_embedded, track_index, added_at, is_forced, is_sdh, is_default";
+
+fn subtitle_track_from_row(row: &rusqlite::Row) -> Result<SubtitleTrack> {
+    Ok(SubtitleTrack {
+        id: Some(row.get(0)?),
+        media_id: row.get(1)?,
+        file_path: row.get(2)?,
+        language: row.get(3)?,
+        label: row.get(4)?,
+        codec: row.get(5)?,
+        is_embedded: row.get(6)?,
+        track_index: row.get(7)?,
+        added_at: row.get(8)?,
+        is_forced: row.get(9)?,
+        is_sdh: row.get(10)?,
+        is_default: row.get(11)?,
+    })
+}
+
+/// Add a subtitle track. An external file already registered for the media
+/// is not added again; its existing id is returned.
 #[allow(clippy::too_many_arguments)]
 pub fn add_subtitle_track(
     conn: &Connection,
@@ -29,39 +67,45 @@ pub fn add_subtitle_track(
 ) -> Result<i64> {
     let now = Utc::now().to_rfc3339();
     
-    conn.execute(
+    let inserted = conn.execute(
         "INSERT INTO subtitle_tracks 
          (media_id, file_path, language, label, codec, is_embedded, track_index, added_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(media_id, file_path) WHERE is_embedded = 0 DO NOTHING",
         params![media_id, file_path, language, label, codec, is_embedded, track_index, &now],
     )?;
     
+    if inserted == 0 {
+        return conn.query_row(
+            "SELECT id FROM subtitle_tracks WHERE media_id = ?1 AND file_path = ?2 AND is_embedded = 0",
+            params![media_id, file_path],
+            |row| row.get(0),
+        );
+    }
     Ok(conn.last_insert_rowid())
 }
 
+/// Set the forced/SDH/default markers of a track
+pub fn set_subtitle_flags(conn: &Connection, subtitle_id: i64, flags: &SubtitleFlags) -> Result<()> {
+    conn.execute(
+        "UPDATE subtitle_tracks SET is_forced = ?1, is_sdh = ?2, is_default = ?3 WHERE id = ?4",
+        params![flags.is_forced, flags.is_sdh, flags.is_default, subtitle_id],
+    )?;
+
+    Ok(())
+}
+
 /// Get all subtitle tracks for a media file
 pub fn get_subtitle_tracks(conn: &Connection, media_id: i64) -> Result<Vec<SubtitleTrack>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, media_id, file_path, language, label, codec, is_embedded, track_index, added_at
-         FROM subtitle_tracks
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM subtitle_tracks
          WHERE media_id = ?1
-         ORDER BY track_index ASC, added_at ASC"
-    )?;
+         ORDER BY track_index ASC, added_at ASC",
+        SUBTITLE_COLUMNS
+    ))?;
     
-    let tracks = stmt.query_map(params![media_id], |row| {
-        Ok(SubtitleTrack {
-            id: Some(row.get(0)?),
-            media_id: row.get(1)?,
-            file_path: row.get(2)?,
-            language: row.get(3)?,
-            label: row.get(4)?,
-            codec: row.get(5)?,
-            is_embedded: row.get(6)?,
-            track_index: row.get(7)?,
-            added_at: row.get(8)?,
-        })
-    })?
-    .collect::<Result<Vec<_>>>()?;
+    let tracks = stmt.query_map(params![media_id], subtitle_track_from_row)?
+        .collect::<Result<Vec<_>>>()?;
     
     Ok(tracks)
 }
@@ -77,117 +121,259 @@ pub fn remove_subtitle_track(conn: &Connection, subtitle_id: i64) -> Result<()>
 }
 
 /// Get subtitle track by ID
-#[allow(dead_code)]
 pub fn get_subtitle_track_by_id(conn: &Connection, subtitle_id: i64) -> Result<Option<SubtitleTrack>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, media_id, file_path, language, label, codec, is_embedded, track_index, added_at
-         FROM subtitle_tracks
-         WHERE id = ?1"
-    )?;
-    
-    let mut rows = stmt.query(params![subtitle_id])?;
-    
-    if let Some(row) = rows.next()? {
-        Ok(Some(SubtitleTrack {
-            id: Some(row.get(0)?),
-            media_id: row.get(1)?,
-            file_path: row.get(2)?,
-            language: row.get(3)?,
-            label: row.get(4)?,
-            codec: row.get(5)?,
-            is_embedded: row.get(6)?,
-            track_index: row.get(7)?,
-            added_at: row.get(8)?,
-        }))
-    } else {
-        Ok(None)
+    conn.query_row(
+        &format!("SELECT {} FROM subtitle_tracks WHERE id = ?1", SUBTITLE_COLUMNS),
+        params![subtitle_id],
+        subtitle_track_from_row,
+    )
+    .optional()
+}
+
+/// "English Forced SDH (External)"
+fn track_label(name: &str, flags: &SubtitleFlags, source: &str) -> String {
+    let mut label = name.to_string();
+    if flags.is_forced {
+        label.push_str(" Forced");
     }
+    if flags.is_sdh {
+        label.push_str(" SDH");
+    }
+    format!("{} ({})", label, source)
+}
+
+/// Sync the embedded subtitle tracks of a media file with the streams
+/// ffprobe found. Tracks keep their ids across rescans (matched by stream
+/// index), streams that are gone are removed, and external tracks,
+/// including extracted ones, are kept.
+pub fn save_embedded_subtitle_tracks(
+    conn: &Connection,
+    media_id: i64,
+    file_path: &str,
+    streams: &[SubtitleStreamMetadata],
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+
+    let existing: Vec<(i64, Option<i32>)> = {
+        let mut stmt = tx.prepare("SELECT id, track_index FROM subtitle_tracks WHERE media_id = ?1 AND is_embedded = 1")?;
+        let rows = stmt.query_map(params![media_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<Vec<_>>>()?
+    };
+    for (id, index) in &existing {
+        if !streams.iter().any(|stream| Some(stream.index) == *index) {
+            tx.execute("DELETE FROM subtitle_tracks WHERE id = ?1", params![id])?;
+        }
+    }
+
+    for stream in streams {
+        let language = stream
+            .language
+            .as_deref()
+            .filter(|lang| *lang != "und")
+            .map(|lang| normalize_language(lang).map(String::from).unwrap_or_else(|| lang.to_lowercase()));
+        let flags = SubtitleFlags { is_forced: stream.is_forced, is_sdh: stream.is_sdh, is_default: stream.is_default };
+        let name = stream
+            .title
+            .clone()
+            .or_else(|| language.as_deref().map(|lang| language_name(lang).map(String::from).unwrap_or_else(|| lang.to_uppercase())))
+            .unwrap_or_else(|| format!("Track {}", stream.index));
+        let label = track_label(&name, &flags, "Embedded");
+        let subtitle_id = match existing.iter().find(|(_, index)| *index == Some(stream.index)) {
+            Some((id, _)) => {
+                tx.execute(
+                    "UPDATE subtitle_tracks SET file_path = ?1, language = ?2, label = ?3, codec = ?4 WHERE id = ?5",
+                    params![file_path, language, label, stream.codec, id],
+                )?;
+                *id
+            }
+            None => add_subtitle_track(
+                &tx,
+                media_id,
+                file_path,
+                language.as_deref(),
+                Some(&label),
+                Some(&stream.codec),
+                true,
+                Some(stream.index),
+            )?,
+        };
+        set_subtitle_flags(&tx, subtitle_id, &flags)?;
+    }
+
+    tx.commit()
+}
+
+const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "vtt", "ass", "ssa", "sub", "idx"];
+
+/// Folders next to a video that hold its subtitles, matched case-insensitively
+const SUBTITLE_FOLDERS: &[&str] = &["subs", "subtitles"];
+
+fn subtitle_files_in(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.extension()
+                .map(|ext| SUBTITLE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+/// What follows the media's name in a subtitle's file stem: `""` for
+/// `movie.srt`, `"en.forced"` for `movie.en.forced.srt`. `None` when the
+/// subtitle is named after something else (`movie 2.srt` isn't `movie`'s).
+fn suffix_after_stem<'a>(subtitle_stem: &'a str, media_stem: &str) -> Option<&'a str> {
+    let rest = subtitle_stem.get(..media_stem.len()).filter(|head| head.eq_ignore_ascii_case(media_stem))?;
+    let rest = &subtitle_stem[rest.len()..];
+    if rest.is_empty() {
+        return Some(rest);
+    }
+    rest.strip_prefix(['.', '_', '['])
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default()
 }
 
-/// Auto-discover subtitle files in the same directory as media file
+/// Auto-discover subtitle files for a media file: files named after it in
+/// its directory or in a `Subs`/`Subtitles` folder next to it, and every
+/// file in `Subs/<name>/`. When the video is the only one in its directory,
+/// every file in `Subs/` belongs to it.
 pub fn discover_subtitle_files(media_path: &str) -> Result<Vec<String>> {
-    use std::path::Path;
-    
     let media_path = Path::new(media_path);
     let parent_dir = match media_path.parent() {
         Some(dir) => dir,
         None => return Ok(vec![]),
     };
     
-    let file_stem = match media_path.file_stem() {
+    let media_stem = match media_path.file_stem() {
         Some(stem) => stem.to_string_lossy().to_string(),
         None => return Ok(vec![]),
     };
+    let named_after_media = |path: &PathBuf| suffix_after_stem(&file_stem(path), &media_stem).is_some();
     
-    let subtitle_extensions = ["srt", "vtt", "ass", "ssa", "sub", "idx"];
-    let mut subtitle_files = Vec::new();
-    
-    if let Ok(entries) = std::fs::read_dir(parent_dir) {
-        for entry in entries.flatten() {
-            if let Ok(path) = entry.path().canonicalize() {
-                if let Some(name) = path.file_name() {
-                    let name_str = name.to_string_lossy().to_string();
-                    
-                    // Check if filename starts with the same stem
-                    if name_str.starts_with(&file_stem) {
-                        if let Some(ext) = path.extension() {
-                            let ext_str = ext.to_string_lossy().to_lowercase();
-                            if subtitle_extensions.contains(&ext_str.as_str()) {
-                                subtitle_files.push(path.to_string_lossy().to_string());
-                            }
-                        }
-                    }
+    let mut candidates: Vec<PathBuf> = subtitle_files_in(parent_dir).into_iter().filter(named_after_media).collect();
+
+    let folders: Vec<PathBuf> = std::fs::read_dir(parent_dir)
+        .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    let only_video = folders
+        .iter()
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.extension()
+                .map(|ext| VIDEO_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+                .unwrap_or(false)
+        })
+        .count()
+        <= 1;
+
+    for folder in folders.iter().filter(|path| path.is_dir()) {
+        let name = folder.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+        if !SUBTITLE_FOLDERS.contains(&name.as_str()) {
+            continue;
+        }
+        candidates.extend(subtitle_files_in(folder).into_iter().filter(|path| only_video || named_after_media(path)));
+
+        // Subs/<name>/, as some releases of whole seasons ship them
+        if let Ok(entries) = std::fs::read_dir(folder) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let matches = path.is_dir()
+                    && path.file_name().map(|n| n.to_string_lossy().eq_ignore_ascii_case(&media_stem)).unwrap_or(false);
+                if matches {
+                    candidates.extend(subtitle_files_in(&path));
                 }
             }
         }
     }
+
+    let mut subtitle_files: Vec<String> = candidates
+        .into_iter()
+        .filter_map(|path| path.canonicalize().ok())
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    subtitle_files.sort();
+    subtitle_files.dedup();
     
     Ok(subtitle_files)
 }
 
-/// Parse language from subtitle filename (e.g., "movie.en.srt" -> "en")
-pub fn parse_language_from_filename(file_path: &str) -> Option<String> {
-    use std::path::Path;
-    
-    let path = Path::new(file_path);
-    let file_stem = path.file_stem()?.to_string_lossy();
-    
-    // Common language codes
-    let language_codes = vec![
-        "en", "eng", "english",
-        "es", "spa", "spanish",
-        "fr", "fre", "french",
-        "de", "ger", "german",
-        "it", "ita", "italian",
-        "pt", "por", "portuguese",
-        "ja", "jpn", "japanese",
-        "ko", "kor", "korean",
-        "zh", "chi", "chinese",
-        "ru", "rus", "russian",
-        "ar", "ara", "arabic",
-        "hi", "hin", "hindi",
-    ];
-    
-    // Split by dots and check each part
-    for part in file_stem.split('.') {
-        let lower_part = part.to_lowercase();
-        if language_codes.contains(&lower_part.as_str()) {
-            return Some(lower_part);
+/// Split a file stem into the words that may name a language or marker;
+/// `-` is kept so region tags like `pt-BR` stay whole
+fn filename_tokens(stem: &str) -> impl DoubleEndedIterator<Item = &str> {
+    stem.split(['.', '_', ' ', ',', '[', ']', '(', ')']).filter(|token| !token.is_empty())
+}
+
+/// The language and markers named in a subtitle file name; the last word
+/// naming a language wins, so `movie.en.fr.srt` is French. `after_media_name`
+/// says `stem` is only what follows the media's name.
+fn parse_subtitle_tokens(stem: &str, after_media_name: bool) -> (Option<String>, SubtitleFlags) {
+    let tokens: Vec<&str> = filename_tokens(stem).collect();
+    let mut flags = SubtitleFlags::default();
+    let mut language = None;
+    for token in &tokens {
+        match token.to_lowercase().as_str() {
+            "forced" | "foreign" => flags.is_forced = true,
+            "sdh" | "cc" => flags.is_sdh = true,
+            "default" => flags.is_default = true,
+            // `movie.en.hi.srt` marks hearing-impaired subtitles (as on
+            // OpenSubtitles); `movie.hi.srt` is Hindi
+            "hi" if language.is_some() || (after_media_name && tokens.len() > 1) => flags.is_sdh = true,
+            _ => {
+                if let Some(code) = normalize_language(token) {
+                    language = Some(code.to_string());
+                }
+            }
         }
     }
-    
-    None
+    (language, flags)
+}
+
+/// Parse language from subtitle filename as an ISO 639-1 code where one
+/// exists (e.g., "movie.en.srt", "movie.eng.srt" and "movie.English.srt" -> "en")
+pub fn parse_language_from_filename(file_path: &str) -> Option<String> {
+    let stem = file_stem(Path::new(file_path));
+    parse_subtitle_tokens(&stem, false).0
 }
 
-/// Auto-scan and add external subtitle files for a media file
+/// Auto-scan and add external subtitle files for a media file, returning
+/// the ids of tracks that weren't registered yet. The markers of tracks found
+/// again are refreshed from their file names.
 pub fn scan_and_add_subtitles(conn: &Connection, media_id: i64, media_path: &str) -> Result<Vec<i64>> {
     let subtitle_files = discover_subtitle_files(media_path)?;
+    let media_stem = file_stem(Path::new(media_path));
     let mut added_ids = Vec::new();
     
     for subtitle_path in subtitle_files {
-        let language = parse_language_from_filename(&subtitle_path);
-        let label = language.clone().map(|lang| format!("{} (External)", lang.to_uppercase()));
-        
+        // Only look past the media's name, so a title like "No Country for
+        // Old Men" isn't read as Norwegian
+        let subtitle_stem = file_stem(Path::new(&subtitle_path));
+        let (language, flags) = match suffix_after_stem(&subtitle_stem, &media_stem) {
+            Some(suffix) => parse_subtitle_tokens(suffix, true),
+            None => parse_subtitle_tokens(&subtitle_stem, false),
+        };
+
+        let existing: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM subtitle_tracks WHERE media_id = ?1 AND file_path = ?2 AND is_embedded = 0",
+                params![media_id, subtitle_path],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(existing) = existing {
+            set_subtitle_flags(conn, existing, &flags)?;
+            continue;
+        }
+
+        let label = language
+            .as_deref()
+            .map(|lang| track_label(language_name(lang).unwrap_or(lang), &flags, "External"));
         let subtitle_id = add_subtitle_track(
             conn,
             media_id,
@@ -198,6 +384,7 @@ pub fn scan_and_add_subtitles(conn: &Connection, media_id: i64, media_path: &str
             false, // external file
             None, // no track index for external
         )?;
+        set_subtitle_flags(conn, subtitle_id, &flags)?;
         
         added_ids.push(subtitle_id);
     }
@@ -235,10 +422,44 @@ mod tests {
             last_modified: Utc::now().to_rfc3339(),
             is_deleted: false,
             metadata_json: None,
+            is_locked: false,
         };
         add_media_file(conn, &media)
     }
 
+    #[test]
+    fn test_embedded_tracks_keep_ids_across_rescans() -> Result<()> {
+        let conn = init_db()?;
+        let media_id = create_test_media(&conn)?;
+        let stream = |index: i32, language: &str| SubtitleStreamMetadata {
+            index,
+            codec: "subrip".to_string(),
+            language: Some(language.to_string()),
+            ..Default::default()
+        };
+        let embedded = |conn: &Connection| -> Result<Vec<SubtitleTrack>> {
+            Ok(get_subtitle_tracks(conn, media_id)?.into_iter().filter(|track| track.is_embedded).collect())
+        };
+        let ids = |tracks: &[SubtitleTrack]| tracks.iter().map(|track| track.id.unwrap()).collect::<Vec<_>>();
+
+        save_embedded_subtitle_tracks(&conn, media_id, "/test/movie.mp4", &[stream(2, "eng"), stream(3, "fre")])?;
+        let first = embedded(&conn)?;
+        assert_eq!(first.len(), 2);
+
+        // Same streams: same ids. A changed language is updated in place
+        save_embedded_subtitle_tracks(&conn, media_id, "/test/movie.mp4", &[stream(2, "eng"), stream(3, "ger")])?;
+        let second = embedded(&conn)?;
+        assert_eq!(ids(&second), ids(&first));
+        assert_eq!(second[1].language.as_deref(), Some("de"));
+
+        // A stream that's gone is removed
+        save_embedded_subtitle_tracks(&conn, media_id, "/test/movie.mp4", &[stream(2, "eng")])?;
+        assert_eq!(ids(&embedded(&conn)?), ids(&first[..1]));
+        save_embedded_subtitle_tracks(&conn, media_id, "/test/movie.mp4", &[])?;
+        assert!(embedded(&conn)?.is_empty());
+        Ok(())
+    }
+
     #[test]
     fn test_subtitle_crud() -> Result<()> {
         let conn = init_db()?;
@@ -278,8 +499,63 @@ mod tests {
     #[test]
     fn test_parse_language() {
         assert_eq!(parse_language_from_filename("movie.en.srt"), Some("en".to_string()));
-        assert_eq!(parse_language_from_filename("movie.english.srt"), Some("english".to_string()));
+        assert_eq!(parse_language_from_filename("movie.english.srt"), Some("en".to_string()));
         assert_eq!(parse_language_from_filename("movie.es.vtt"), Some("es".to_string()));
         assert_eq!(parse_language_from_filename("movie.srt"), None);
+        assert_eq!(parse_language_from_filename("/m/Movie [ger].forced.srt"), Some("de".to_string()));
+        assert_eq!(parse_language_from_filename("movie.pt-BR.sdh.srt"), Some("pt".to_string()));
+        // "hi" after a language marks hearing-impaired subtitles
+        assert_eq!(parse_language_from_filename("movie.en.hi.srt"), Some("en".to_string()));
+        assert_eq!(parse_language_from_filename("movie.hi.srt"), Some("hi".to_string()));
+        assert_eq!(parse_subtitle_tokens("en.hi", true), (Some("en".to_string()), SubtitleFlags { is_sdh: true, ..Default::default() }));
+        assert_eq!(parse_subtitle_tokens("hi.forced", true), (None, SubtitleFlags { is_sdh: true, is_forced: true, ..Default::default() }));
+        assert_eq!(parse_subtitle_tokens("hi", true), (Some("hi".to_string()), SubtitleFlags::default()));
+    }
+
+    #[test]
+    fn test_scan_discovers_subs_folders_once() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in [
+            "Movie.mkv",
+            "Movie.en.srt",
+            "Movie.English.forced.srt",
+            "Movie 2.srt",
+            "Other.srt",
+            "Subs/Movie.fre.sdh.srt",
+            "Subs/Other.srt",
+            "Subs/Movie/3_Spanish.srt",
+            "subtitles/Movie.default.ass",
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let media = root.join("Movie.mkv").to_string_lossy().to_string();
+        // Another video in the directory, so Subs/Other.srt isn't Movie's
+        std::fs::write(root.join("Other.mkv"), "").unwrap();
+
+        let conn = init_db()?;
+        let media_id = create_test_media(&conn)?;
+        let added = scan_and_add_subtitles(&conn, media_id, &media)?;
+        assert_eq!(added.len(), 5);
+
+        let tracks = get_subtitle_tracks(&conn, media_id)?;
+        let track = |name: &str| tracks.iter().find(|t| t.file_path.ends_with(name)).unwrap();
+        assert_eq!(track("Movie.en.srt").label.as_deref(), Some("English (External)"));
+        let forced = track("Movie.English.forced.srt");
+        assert_eq!(forced.language.as_deref(), Some("en"));
+        assert!(forced.is_forced && !forced.is_sdh);
+        let sdh = track("Movie.fre.sdh.srt");
+        assert_eq!((sdh.language.as_deref(), sdh.is_sdh), (Some("fr"), true));
+        assert_eq!(sdh.label.as_deref(), Some("French SDH (External)"));
+        assert_eq!(track("3_Spanish.srt").language.as_deref(), Some("es"));
+        let default = track("Movie.default.ass");
+        assert!(default.is_default && default.language.is_none());
+
+        // Rescanning finds the same files and adds nothing
+        assert!(scan_and_add_subtitles(&conn, media_id, &media)?.is_empty());
+        assert_eq!(get_subtitle_tracks(&conn, media_id)?.len(), 5);
+        Ok(())
     }
 }
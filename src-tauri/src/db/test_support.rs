@@ -0,0 +1,55 @@
+//! Media rows for tests across the crate.
+
+use rusqlite::{Connection, params};
+
+/// A `media_files` row to insert; anything not set is left empty
+#[derive(Debug, Clone)]
+pub struct TestMedia<'a> {
+    pub path: &'a str,
+    pub media_type: &'a str,
+    pub title: Option<&'a str>,
+    pub year: Option<i32>,
+    pub duration: Option<i64>,
+    pub season: Option<i32>,
+    pub episode: Option<i32>,
+    pub metadata_json: Option<&'a str>,
+}
+
+impl Default for TestMedia<'_> {
+    fn default() -> Self {
+        TestMedia {
+            path: "/m/movie.mkv",
+            media_type: "movie",
+            title: None,
+            year: None,
+            duration: None,
+            season: None,
+            episode: None,
+            metadata_json: None,
+        }
+    }
+}
+
+/// Insert a media file, named after the last part of its path, and return its id
+pub fn add_media(conn: &Connection, media: &TestMedia) -> i64 {
+    let file_name = media.path.rsplit(['/', '\\']).next().unwrap_or(media.path);
+    conn.execute(
+        "INSERT INTO media_files
+            (file_path, file_hash, file_name, file_size, media_type, title, year, duration,
+             season_number, episode_number, metadata_json, last_modified)
+         VALUES (?1, ?1, ?2, 1, ?3, ?4, ?5, ?6, ?7, ?8, ?9, datetime('now'))",
+        params![
+            media.path,
+            file_name,
+            media.media_type,
+            media.title,
+            media.year,
+            media.duration,
+            media.season,
+            media.episode,
+            media.metadata_json,
+        ],
+    )
+    .unwrap();
+    conn.last_insert_rowid()
+}
@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests {
     use super::super::*;
+    use super::super::schema::SCHEMA_VERSION;
     use std::path::PathBuf;
 
     #[test]
@@ -18,7 +19,7 @@ mod tests {
             |row| row.get(0),
         ).unwrap();
         
-        assert_eq!(version, 1);
+        assert_eq!(version, SCHEMA_VERSION);
     }
 
     #[test]
@@ -184,4 +185,162 @@ mod tests {
         ).unwrap();
         assert_eq!(count, 0, "Playback state should be deleted via CASCADE");
     }
+
+    fn insert_listing_media(conn: &rusqlite::Connection, title: &str, year: i32, resolution: &str, size: i64) -> i64 {
+        conn.execute(
+            "INSERT INTO media_files (file_path, file_hash, file_name, file_size, media_type, resolution, title, year, last_modified)
+             VALUES (?1, 'hash', ?2, ?3, 'movie', ?4, ?5, ?6, datetime('now'))",
+            rusqlite::params![format!("/m/{}.mkv", title), format!("{}.mkv", title), size, resolution, title, year],
+        ).unwrap();
+        conn.last_insert_rowid()
+    }
+
+    fn listing_titles(media: &[MediaFile]) -> Vec<&str> {
+        media.iter().map(|m| m.title.as_deref().unwrap_or("")).collect()
+    }
+
+    #[test]
+    fn test_resolution_buckets() {
+        let conn = connection::init_db().unwrap();
+        insert_listing_media(&conn, "Scope 4K", 2020, "3840x1600", 1);
+        insert_listing_media(&conn, "Full HD", 2019, "1920x1080", 1);
+        insert_listing_media(&conn, "Scope HD", 2018, "1920x800", 1);
+        insert_listing_media(&conn, "DVD", 2001, "720x576", 1);
+        insert_listing_media(&conn, "Unknown", 2000, "", 1);
+
+        let (width, height): (Option<i64>, Option<i64>) = conn.query_row(
+            "SELECT width, height FROM media_files WHERE title = 'Scope 4K'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap();
+        assert_eq!((width, height), (Some(3840), Some(1600)));
+
+        let filter = |resolutions: &[&str]| {
+            let criteria = FilterCriteria {
+                resolutions: Some(resolutions.iter().map(|r| r.to_string()).collect()),
+                ..FilterCriteria::default()
+            };
+            let page = PageRequest { sort: Some(SortKey::Title), ..PageRequest::default() };
+            filter_media(&conn, &criteria, &page).unwrap()
+        };
+
+        assert_eq!(listing_titles(&filter(&["4k"])), vec!["Scope 4K"]);
+        assert_eq!(listing_titles(&filter(&["1080p"])), vec!["Full HD", "Scope HD"]);
+        assert_eq!(listing_titles(&filter(&["720p", "sd"])), vec!["DVD"]);
+    }
+
+    #[test]
+    fn test_sorting_and_keyset_pagination() {
+        let conn = connection::init_db().unwrap();
+        insert_listing_media(&conn, "Charlie", 2010, "1920x1080", 300);
+        insert_listing_media(&conn, "alpha", 2010, "1920x1080", 100);
+        insert_listing_media(&conn, "Bravo", 2020, "1920x1080", 200);
+        insert_listing_media(&conn, "Delta", 2005, "1920x1080", 200);
+
+        // Case-insensitive title order, paged two at a time
+        let mut page = PageRequest { sort: Some(SortKey::Title), limit: Some(2), ..PageRequest::default() };
+        let first = list_media(&conn, &page).unwrap();
+        assert_eq!(listing_titles(&first), vec!["alpha", "Bravo"]);
+        page.after_id = first.last().unwrap().id;
+        let second = list_media(&conn, &page).unwrap();
+        assert_eq!(listing_titles(&second), vec!["Charlie", "Delta"]);
+        page.after_id = second.last().unwrap().id;
+        assert!(list_media(&conn, &page).unwrap().is_empty());
+
+        // Ties on the sort key are broken by id without skipping rows
+        let mut page = PageRequest { sort: Some(SortKey::Size), limit: Some(2), ..PageRequest::default() };
+        let first = list_media(&conn, &page).unwrap();
+        assert_eq!(listing_titles(&first), vec!["Charlie", "Delta"]);
+        page.after_id = first.last().unwrap().id;
+        assert_eq!(listing_titles(&list_media(&conn, &page).unwrap()), vec!["Bravo", "alpha"]);
+
+        // Explicit direction
+        let page = PageRequest { sort: Some(SortKey::Year), descending: Some(false), ..PageRequest::default() };
+        assert_eq!(listing_titles(&list_media(&conn, &page).unwrap()), vec!["Delta", "Charlie", "alpha", "Bravo"]);
+
+        // Last played puts never-played items last
+        let bravo = list_media(&conn, &PageRequest::default()).unwrap().into_iter().find(|m| m.title.as_deref() == Some("Bravo")).unwrap();
+        conn.execute(
+            "INSERT INTO playback_state (media_id, last_position, last_played_at) VALUES (?1, 10, '2030-01-01T00:00:00Z')",
+            [bravo.id],
+        ).unwrap();
+        let page = PageRequest { sort: Some(SortKey::LastPlayed), limit: Some(1), ..PageRequest::default() };
+        assert_eq!(listing_titles(&list_media(&conn, &page).unwrap()), vec!["Bravo"]);
+    }
+
+    #[test]
+    fn test_playlist_and_collection_ordering() {
+        let conn = connection::init_db().unwrap();
+        let ids: Vec<i64> = ["A", "B", "C", "D", "E"]
+            .iter()
+            .map(|t| insert_listing_media(&conn, t, 2000, "1920x1080", 1))
+            .collect();
+
+        let playlist_id = create_playlist(&conn, "Order", None, PlaylistType::Manual).unwrap();
+        let collection_id = create_collection(&conn, "Order", None).unwrap();
+        for id in &ids {
+            add_media_to_playlist(&conn, playlist_id, *id).unwrap();
+            add_media_to_collection(&conn, collection_id, *id).unwrap();
+        }
+
+        let playlist_order = |conn: &rusqlite::Connection| -> Vec<i64> {
+            get_playlist_media(conn, playlist_id).unwrap().iter().map(|i| i.id).collect()
+        };
+        let collection_order = |conn: &rusqlite::Connection| -> Vec<i64> {
+            get_collection_media(conn, collection_id).unwrap().iter().map(|i| i.id).collect()
+        };
+        assert_eq!(playlist_order(&conn), ids);
+        assert_eq!(collection_order(&conn), ids);
+
+        // Moving one item only rewrites that item's key
+        let keys_before: Vec<String> = conn
+            .prepare("SELECT sort_key FROM playlist_items ORDER BY media_id").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        reorder_playlist_item(&conn, playlist_id, ids[4], 1).unwrap();
+        assert_eq!(playlist_order(&conn), vec![ids[0], ids[4], ids[1], ids[2], ids[3]]);
+        let changed: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM playlist_items WHERE sort_key NOT IN (?1, ?2, ?3, ?4)",
+            rusqlite::params![keys_before[0], keys_before[1], keys_before[2], keys_before[3]],
+            |row| row.get(0),
+        ).unwrap();
+        assert_eq!(changed, 1);
+
+        // Bulk moves keep the given order; positions reflect the new order
+        assert_eq!(move_collection_items(&conn, collection_id, &[ids[3], ids[0], 999], 0).unwrap(), 2);
+        assert_eq!(collection_order(&conn), vec![ids[3], ids[0], ids[1], ids[2], ids[4]]);
+        move_collection_items(&conn, collection_id, &[ids[1], ids[2]], 10).unwrap();
+        assert_eq!(collection_order(&conn), vec![ids[3], ids[0], ids[4], ids[1], ids[2]]);
+        let positions: Vec<i32> = get_collection_media(&conn, collection_id).unwrap().iter().map(|i| i.position).collect();
+        assert_eq!(positions, vec![0, 1, 2, 3, 4]);
+
+        // Rows from before sort keys existed are backfilled in their old order
+        conn.execute("UPDATE playlist_items SET sort_key = NULL, position = 5 - media_id", []).unwrap();
+        conn.execute("UPDATE collection_items SET sort_key = NULL, position = NULL", []).unwrap();
+        conn.execute("UPDATE collection_items SET added_at = '2020-01-0' || media_id", []).unwrap();
+        ordering::backfill_sort_keys(&conn).unwrap();
+        let mut reversed = ids.clone();
+        reversed.reverse();
+        assert_eq!(playlist_order(&conn), reversed);
+        assert_eq!(collection_order(&conn), reversed);
+    }
+
+    #[test]
+    fn test_moves_count_positions_among_shown_items() {
+        let conn = connection::init_db().unwrap();
+        let ids: Vec<i64> = ["A", "B", "C", "D"]
+            .iter()
+            .map(|t| insert_listing_media(&conn, t, 2000, "1920x1080", 1))
+            .collect();
+        let playlist_id = create_playlist(&conn, "Order", None, PlaylistType::Manual).unwrap();
+        for id in &ids {
+            add_media_to_playlist(&conn, playlist_id, *id).unwrap();
+        }
+        conn.execute("UPDATE media_files SET is_deleted = 1 WHERE id = ?1", [ids[0]]).unwrap();
+
+        // Position 1 is the second item the user sees, not counting the deleted one
+        move_playlist_items(&conn, playlist_id, &[ids[3]], 1).unwrap();
+        let order: Vec<i64> = get_playlist_media(&conn, playlist_id).unwrap().iter().map(|i| i.id).collect();
+        assert_eq!(order, vec![ids[1], ids[3], ids[2]]);
+    }
 }
@@ -0,0 +1,255 @@
+//! ISO 639 language codes and names, for recognising the language of a
+//! subtitle from tokens like `en`, `eng`, `fre`, `English` or `Français`.
+
+/// ISO 639-1 code (empty when the language has none), ISO 639-2 codes
+/// (bibliographic first, then terminology when it differs) and names, the
+/// English one first
+const LANGUAGES: &[(&str, &[&str], &[&str])] = &[
+    ("aa", &["aar"], &["Afar"]),
+    ("ab", &["abk"], &["Abkhazian"]),
+    ("ae", &["ave"], &["Avestan"]),
+    ("af", &["afr"], &["Afrikaans"]),
+    ("ak", &["aka"], &["Akan"]),
+    ("am", &["amh"], &["Amharic"]),
+    ("an", &["arg"], &["Aragonese"]),
+    ("ar", &["ara"], &["Arabic", "العربية"]),
+    ("as", &["asm"], &["Assamese"]),
+    ("av", &["ava"], &["Avaric"]),
+    ("ay", &["aym"], &["Aymara"]),
+    ("az", &["aze"], &["Azerbaijani", "Azərbaycan"]),
+    ("ba", &["bak"], &["Bashkir"]),
+    ("be", &["bel"], &["Belarusian", "Беларуская"]),
+    ("bg", &["bul"], &["Bulgarian", "Български"]),
+    ("bh", &["bih"], &["Bihari"]),
+    ("bi", &["bis"], &["Bislama"]),
+    ("bm", &["bam"], &["Bambara"]),
+    ("bn", &["ben"], &["Bengali", "Bangla", "বাংলা"]),
+    ("bo", &["tib", "bod"], &["Tibetan"]),
+    ("br", &["bre"], &["Breton"]),
+    ("bs", &["bos"], &["Bosnian", "Bosanski"]),
+    ("ca", &["cat"], &["Catalan", "Català"]),
+    ("ce", &["che"], &["Chechen"]),
+    ("ch", &["cha"], &["Chamorro"]),
+    ("co", &["cos"], &["Corsican"]),
+    ("cr", &["cre"], &["Cree"]),
+    ("cs", &["cze", "ces"], &["Czech", "Čeština"]),
+    ("cu", &["chu"], &["Church Slavic"]),
+    ("cv", &["chv"], &["Chuvash"]),
+    ("cy", &["wel", "cym"], &["Welsh", "Cymraeg"]),
+    ("da", &["dan"], &["Danish", "Dansk"]),
+    ("de", &["ger", "deu"], &["German", "Deutsch"]),
+    ("dv", &["div"], &["Divehi"]),
+    ("dz", &["dzo"], &["Dzongkha"]),
+    ("ee", &["ewe"], &["Ewe"]),
+    ("el", &["gre", "ell"], &["Greek", "Ελληνικά"]),
+    ("en", &["eng"], &["English"]),
+    ("eo", &["epo"], &["Esperanto"]),
+    ("es", &["spa"], &["Spanish", "Español", "Castellano", "Latino"]),
+    ("et", &["est"], &["Estonian", "Eesti"]),
+    ("eu", &["baq", "eus"], &["Basque", "Euskara"]),
+    ("fa", &["per", "fas"], &["Persian", "Farsi", "فارسی"]),
+    ("ff", &["ful"], &["Fulah"]),
+    ("fi", &["fin"], &["Finnish", "Suomi"]),
+    ("fj", &["fij"], &["Fijian"]),
+    ("fo", &["fao"], &["Faroese", "Føroyskt"]),
+    ("fr", &["fre", "fra"], &["French", "Français"]),
+    ("fy", &["fry"], &["Western Frisian", "Frisian"]),
+    ("ga", &["gle"], &["Irish", "Gaeilge"]),
+    ("gd", &["gla"], &["Scottish Gaelic", "Gaelic"]),
+    ("gl", &["glg"], &["Galician", "Galego"]),
+    ("gn", &["grn"], &["Guarani"]),
+    ("gu", &["guj"], &["Gujarati"]),
+    ("gv", &["glv"], &["Manx"]),
+    ("ha", &["hau"], &["Hausa"]),
+    ("he", &["heb"], &["Hebrew", "עברית"]),
+    ("hi", &["hin"], &["Hindi", "हिन्दी"]),
+    ("ho", &["hmo"], &["Hiri Motu"]),
+    ("hr", &["hrv"], &["Croatian", "Hrvatski"]),
+    ("ht", &["hat"], &["Haitian", "Haitian Creole"]),
+    ("hu", &["hun"], &["Hungarian", "Magyar"]),
+    ("hy", &["arm", "hye"], &["Armenian"]),
+    ("hz", &["her"], &["Herero"]),
+    ("ia", &["ina"], &["Interlingua"]),
+    ("id", &["ind"], &["Indonesian", "Bahasa Indonesia"]),
+    ("ie", &["ile"], &["Interlingue"]),
+    ("ig", &["ibo"], &["Igbo"]),
+    ("ii", &["iii"], &["Sichuan Yi"]),
+    ("ik", &["ipk"], &["Inupiaq"]),
+    ("io", &["ido"], &["Ido"]),
+    ("is", &["ice", "isl"], &["Icelandic", "Íslenska"]),
+    ("it", &["ita"], &["Italian", "Italiano"]),
+    ("iu", &["iku"], &["Inuktitut"]),
+    ("ja", &["jpn"], &["Japanese", "日本語"]),
+    ("jv", &["jav"], &["Javanese"]),
+    ("ka", &["geo", "kat"], &["Georgian"]),
+    ("kg", &["kon"], &["Kongo"]),
+    ("ki", &["kik"], &["Kikuyu"]),
+    ("kj", &["kua"], &["Kuanyama"]),
+    ("kk", &["kaz"], &["Kazakh"]),
+    ("kl", &["kal"], &["Kalaallisut", "Greenlandic"]),
+    ("km", &["khm"], &["Khmer"]),
+    ("kn", &["kan"], &["Kannada"]),
+    ("ko", &["kor"], &["Korean", "한국어"]),
+    ("kr", &["kau"], &["Kanuri"]),
+    ("ks", &["kas"], &["Kashmiri"]),
+    ("ku", &["kur"], &["Kurdish"]),
+    ("kv", &["kom"], &["Komi"]),
+    ("kw", &["cor"], &["Cornish"]),
+    ("ky", &["kir"], &["Kyrgyz"]),
+    ("la", &["lat"], &["Latin"]),
+    ("lb", &["ltz"], &["Luxembourgish"]),
+    ("lg", &["lug"], &["Ganda"]),
+    ("li", &["lim"], &["Limburgish"]),
+    ("ln", &["lin"], &["Lingala"]),
+    ("lo", &["lao"], &["Lao"]),
+    ("lt", &["lit"], &["Lithuanian", "Lietuvių"]),
+    ("lu", &["lub"], &["Luba-Katanga"]),
+    ("lv", &["lav"], &["Latvian", "Latviešu"]),
+    ("mg", &["mlg"], &["Malagasy"]),
+    ("mh", &["mah"], &["Marshallese"]),
+    ("mi", &["mao", "mri"], &["Maori"]),
+    ("mk", &["mac", "mkd"], &["Macedonian", "Македонски"]),
+    ("ml", &["mal"], &["Malayalam"]),
+    ("mn", &["mon"], &["Mongolian"]),
+    ("mr", &["mar"], &["Marathi"]),
+    ("ms", &["may", "msa"], &["Malay", "Bahasa Melayu"]),
+    ("mt", &["mlt"], &["Maltese"]),
+    ("my", &["bur", "mya"], &["Burmese"]),
+    ("na", &["nau"], &["Nauru"]),
+    ("nb", &["nob"], &["Norwegian Bokmål", "Bokmål"]),
+    ("nd", &["nde"], &["North Ndebele"]),
+    ("ne", &["nep"], &["Nepali"]),
+    ("ng", &["ndo"], &["Ndonga"]),
+    ("nl", &["dut", "nld"], &["Dutch", "Nederlands", "Flemish"]),
+    ("nn", &["nno"], &["Norwegian Nynorsk", "Nynorsk"]),
+    ("no", &["nor"], &["Norwegian", "Norsk"]),
+    ("nr", &["nbl"], &["South Ndebele"]),
+    ("nv", &["nav"], &["Navajo"]),
+    ("ny", &["nya"], &["Chichewa"]),
+    ("oc", &["oci"], &["Occitan"]),
+    ("oj", &["oji"], &["Ojibwa"]),
+    ("om", &["orm"], &["Oromo"]),
+    ("or", &["ori"], &["Oriya", "Odia"]),
+    ("os", &["oss"], &["Ossetian"]),
+    ("pa", &["pan"], &["Punjabi", "Panjabi"]),
+    ("pi", &["pli"], &["Pali"]),
+    ("pl", &["pol"], &["Polish", "Polski"]),
+    ("ps", &["pus"], &["Pashto"]),
+    ("pt", &["por"], &["Portuguese", "Português", "Brazilian"]),
+    ("qu", &["que"], &["Quechua"]),
+    ("rm", &["roh"], &["Romansh"]),
+    ("rn", &["run"], &["Rundi"]),
+    ("ro", &["rum", "ron"], &["Romanian", "Română"]),
+    ("ru", &["rus"], &["Russian", "Русский"]),
+    ("rw", &["kin"], &["Kinyarwanda"]),
+    ("sa", &["san"], &["Sanskrit"]),
+    ("sc", &["srd"], &["Sardinian"]),
+    ("sd", &["snd"], &["Sindhi"]),
+    ("se", &["sme"], &["Northern Sami"]),
+    ("sg", &["sag"], &["Sango"]),
+    ("si", &["sin"], &["Sinhala"]),
+    ("sk", &["slo", "slk"], &["Slovak", "Slovenčina"]),
+    ("sl", &["slv"], &["Slovenian", "Slovenščina"]),
+    ("sm", &["smo"], &["Samoan"]),
+    ("sn", &["sna"], &["Shona"]),
+    ("so", &["som"], &["Somali"]),
+    ("sq", &["alb", "sqi"], &["Albanian", "Shqip"]),
+    ("sr", &["srp"], &["Serbian", "Српски", "Srpski"]),
+    ("ss", &["ssw"], &["Swati"]),
+    ("st", &["sot"], &["Southern Sotho"]),
+    ("su", &["sun"], &["Sundanese"]),
+    ("sv", &["swe"], &["Swedish", "Svenska"]),
+    ("sw", &["swa"], &["Swahili", "Kiswahili"]),
+    ("ta", &["tam"], &["Tamil", "தமிழ்"]),
+    ("te", &["tel"], &["Telugu"]),
+    ("tg", &["tgk"], &["Tajik"]),
+    ("th", &["tha"], &["Thai", "ไทย"]),
+    ("ti", &["tir"], &["Tigrinya"]),
+    ("tk", &["tuk"], &["Turkmen"]),
+    ("tl", &["tgl"], &["Tagalog"]),
+    ("tn", &["tsn"], &["Tswana"]),
+    ("to", &["ton"], &["Tongan"]),
+    ("tr", &["tur"], &["Turkish", "Türkçe"]),
+    ("ts", &["tso"], &["Tsonga"]),
+    ("tt", &["tat"], &["Tatar"]),
+    ("tw", &["twi"], &["Twi"]),
+    ("ty", &["tah"], &["Tahitian"]),
+    ("ug", &["uig"], &["Uyghur"]),
+    ("uk", &["ukr"], &["Ukrainian", "Українська"]),
+    ("ur", &["urd"], &["Urdu", "اردو"]),
+    ("uz", &["uzb"], &["Uzbek"]),
+    ("ve", &["ven"], &["Venda"]),
+    ("vi", &["vie"], &["Vietnamese", "Tiếng Việt"]),
+    ("vo", &["vol"], &["Volapük"]),
+    ("wa", &["wln"], &["Walloon"]),
+    ("wo", &["wol"], &["Wolof"]),
+    ("xh", &["xho"], &["Xhosa"]),
+    ("yi", &["yid"], &["Yiddish"]),
+    ("yo", &["yor"], &["Yoruba"]),
+    ("za", &["zha"], &["Zhuang"]),
+    ("zh", &["chi", "zho", "chs", "cht"], &["Chinese", "中文", "Mandarin", "Cantonese"]),
+    ("zu", &["zul"], &["Zulu"]),
+    // Without an ISO 639-1 code
+    ("", &["fil"], &["Filipino"]),
+    ("", &["haw"], &["Hawaiian"]),
+    ("", &["ceb"], &["Cebuano"]),
+    ("", &["hmn"], &["Hmong"]),
+    ("", &["kok"], &["Konkani"]),
+    ("", &["mai"], &["Maithili"]),
+    ("", &["mni"], &["Manipuri"]),
+    ("", &["sat"], &["Santali"]),
+    ("", &["tlh"], &["Klingon"]),
+];
+
+/// The ISO 639-1 code (or ISO 639-2 code, for languages without one) a
+/// code or name stands for. Region subtags are ignored: `pt-BR` is `pt`.
+pub fn normalize_language(token: &str) -> Option<&'static str> {
+    let token = token.trim().to_lowercase();
+    if token.is_empty() {
+        return None;
+    }
+    let find = |token: &str| {
+        LANGUAGES
+            .iter()
+            .find(|(iso1, iso2, names)| {
+                *iso1 == token || iso2.contains(&token) || names.iter().any(|name| name.to_lowercase() == token)
+            })
+            .map(|(iso1, iso2, _)| if iso1.is_empty() { iso2[0] } else { *iso1 })
+    };
+    find(&token).or_else(|| {
+        let (language, region) = token.split_once('-')?;
+        (region.len() <= 4).then(|| find(language)).flatten()
+    })
+}
+
+/// The English name of a language code
+pub fn language_name(code: &str) -> Option<&'static str> {
+    normalize_language(code).and_then(|code| {
+        LANGUAGES
+            .iter()
+            .find(|(iso1, iso2, _)| *iso1 == code || iso2.first() == Some(&code))
+            .map(|(_, _, names)| names[0])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_language() {
+        for token in ["en", "ENG", "English", "english"] {
+            assert_eq!(normalize_language(token), Some("en"), "{}", token);
+        }
+        assert_eq!(normalize_language("fre"), Some("fr"));
+        assert_eq!(normalize_language("fra"), Some("fr"));
+        assert_eq!(normalize_language("Français"), Some("fr"));
+        assert_eq!(normalize_language("pt-BR"), Some("pt"));
+        assert_eq!(normalize_language("chs"), Some("zh"));
+        assert_eq!(normalize_language("fil"), Some("fil"));
+        assert_eq!(normalize_language("movie"), None);
+        assert_eq!(normalize_language("forced"), None);
+        assert_eq!(language_name("ger"), Some("German"));
+        assert_eq!(language_name("xx"), None);
+    }
+}
@@ -16,6 +16,7 @@ pub struct MediaMetadata {
     pub audio_channels: Option<u32>, // Number of audio channels
     pub sample_rate: Option<u32>,    // Audio sample rate
     pub audio_tracks: Vec<AudioTrackMetadata>, // Audio tracks
+    pub subtitle_tracks: Vec<SubtitleStreamMetadata>, // Embedded subtitle streams
 }
 
 /// Audio track metadata
@@ -28,6 +29,18 @@ pub struct AudioTrackMetadata {
     pub is_default: bool,
 }
 
+/// Embedded subtitle stream metadata
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SubtitleStreamMetadata {
+    pub index: i32,
+    pub codec: String,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub is_default: bool,
+    pub is_forced: bool,
+    pub is_sdh: bool,
+}
+
 impl MediaMetadata {
     /// Extract metadata from a media file using ffprobe
     /// Requires ffprobe to be installed and available in PATH
@@ -147,6 +160,19 @@ impl MediaMetadata {
                         is_default: stream.disposition.and_then(|d| d.default).unwrap_or(0) == 1,
                     });
                 }
+                Some("subtitle") => {
+                    let tags = stream.tags.unwrap_or_default();
+                    let disposition = stream.disposition.unwrap_or_default();
+                    metadata.subtitle_tracks.push(SubtitleStreamMetadata {
+                        index: stream.index,
+                        codec: stream.codec_name.unwrap_or_default(),
+                        language: tags.language,
+                        title: tags.title,
+                        is_default: disposition.default.unwrap_or(0) == 1,
+                        is_forced: disposition.forced.unwrap_or(0) == 1,
+                        is_sdh: disposition.hearing_impaired.unwrap_or(0) == 1,
+                    });
+                }
                 _ => {}
             }
         }
@@ -254,14 +280,17 @@ struct FFProbeStream {
     disposition: Option<FFProbeDisposition>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Default)]
 struct FFProbeTags {
     language: Option<String>,
+    title: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Default)]
 struct FFProbeDisposition {
     default: Option<i32>,
+    forced: Option<i32>,
+    hearing_impaired: Option<i32>,
 }
 
 /// FFProbe format information
@@ -1,6 +1,8 @@
 pub mod scanner;
 pub mod metadata;
 pub mod hash;
+pub mod nfo;
+pub mod languages;
 
 pub use scanner::{MediaScanner, ScanProgress};
 // MediaMetadata is used internally but not needed in public API
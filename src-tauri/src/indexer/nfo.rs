@@ -0,0 +1,757 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::db::models::{MediaFile, MediaType};
+use crate::indexer::scanner::VIDEO_EXTENSIONS;
+
+/// Kind of NFO document, taken from its root element
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NfoKind {
+    Movie,
+    TvShow,
+    Episode,
+}
+
+impl NfoKind {
+    pub fn root_element(&self) -> &str {
+        match self {
+            NfoKind::Movie => "movie",
+            NfoKind::TvShow => "tvshow",
+            NfoKind::Episode => "episodedetails",
+        }
+    }
+
+    fn from_root(name: &str) -> Option<Self> {
+        match name {
+            "movie" => Some(NfoKind::Movie),
+            "tvshow" => Some(NfoKind::TvShow),
+            "episodedetails" => Some(NfoKind::Episode),
+            _ => None,
+        }
+    }
+}
+
+/// A single rating entry (e.g. imdb, tmdb, themoviedb)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NfoRating {
+    pub source: String,
+    pub value: f64,
+    pub votes: Option<i64>,
+}
+
+/// A cast member entry
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NfoActor {
+    pub name: String,
+    pub role: Option<String>,
+    pub order: Option<i32>,
+    pub thumb: Option<String>,
+}
+
+/// Metadata read from (or written to) a Kodi/Jellyfin style NFO sidecar
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NfoMetadata {
+    pub kind: NfoKind,
+    pub title: Option<String>,
+    pub original_title: Option<String>,
+    pub show_title: Option<String>,
+    pub year: Option<i32>,
+    pub premiered: Option<String>,
+    pub plot: Option<String>,
+    pub tagline: Option<String>,
+    pub runtime: Option<i32>, // Minutes
+    pub season: Option<i32>,
+    pub episode: Option<i32>,
+    pub genres: Vec<String>,
+    pub ratings: Vec<NfoRating>,
+    pub imdb_id: Option<String>,
+    pub tmdb_id: Option<i64>,
+    pub directors: Vec<String>,
+    pub writers: Vec<String>,
+    pub cast: Vec<NfoActor>,
+}
+
+impl NfoMetadata {
+    pub fn new(kind: NfoKind) -> Self {
+        NfoMetadata {
+            kind,
+            title: None,
+            original_title: None,
+            show_title: None,
+            year: None,
+            premiered: None,
+            plot: None,
+            tagline: None,
+            runtime: None,
+            season: None,
+            episode: None,
+            genres: Vec::new(),
+            ratings: Vec::new(),
+            imdb_id: None,
+            tmdb_id: None,
+            directors: Vec::new(),
+            writers: Vec::new(),
+            cast: Vec::new(),
+        }
+    }
+
+    /// Read and parse an NFO file from disk
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> Result<Self, NfoError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| NfoError::Io(format!("{}: {}", path.display(), e)))?;
+        Self::parse(&content)
+    }
+
+    /// Parse NFO XML content
+    pub fn parse(content: &str) -> Result<Self, NfoError> {
+        let mut reader = Reader::from_str(content);
+        reader.trim_text(true);
+
+        let mut nfo: Option<NfoMetadata> = None;
+        // Element names from the root down to the current element
+        let mut stack: Vec<String> = Vec::new();
+        let mut current_actor: Option<NfoActor> = None;
+        let mut current_rating: Option<NfoRating> = None;
+        let mut uniqueid_type: Option<String> = None;
+
+        loop {
+            let event = reader
+                .read_event()
+                .map_err(|e| NfoError::Parse(format!("at byte {}: {}", reader.buffer_position(), e)))?;
+
+            match event {
+                Event::Start(e) => {
+                    let name = element_name(&e);
+
+                    if stack.is_empty() {
+                        let kind = NfoKind::from_root(&name)
+                            .ok_or_else(|| NfoError::UnsupportedRoot(name.clone()))?;
+                        nfo = Some(NfoMetadata::new(kind));
+                    } else if stack.len() == 1 {
+                        match name.as_str() {
+                            "actor" => current_actor = Some(NfoActor::default()),
+                            "uniqueid" => uniqueid_type = attribute(&e, "type"),
+                            _ => {}
+                        }
+                    } else if name == "rating" && stack.last().map(String::as_str) == Some("ratings") {
+                        current_rating = Some(NfoRating {
+                            source: attribute(&e, "name").unwrap_or_else(|| "default".to_string()),
+                            ..Default::default()
+                        });
+                    }
+
+                    stack.push(name);
+                }
+                Event::Text(t) => {
+                    let Some(nfo) = nfo.as_mut() else { continue };
+                    let text = t
+                        .unescape()
+                        .map_err(|e| NfoError::Parse(e.to_string()))?
+                        .trim()
+                        .to_string();
+                    if text.is_empty() {
+                        continue;
+                    }
+
+                    let path: Vec<&str> = stack.iter().skip(1).map(String::as_str).collect();
+                    match path.as_slice() {
+                        ["title"] => nfo.title = Some(text),
+                        ["originaltitle"] => nfo.original_title = Some(text),
+                        ["showtitle"] => nfo.show_title = Some(text),
+                        ["year"] => nfo.year = text.parse().ok(),
+                        ["premiered"] | ["aired"] => nfo.premiered = Some(text),
+                        ["plot"] => nfo.plot = Some(text),
+                        ["outline"] if nfo.plot.is_none() => nfo.plot = Some(text),
+                        ["tagline"] => nfo.tagline = Some(text),
+                        ["runtime"] => nfo.runtime = text.parse().ok(),
+                        ["season"] => nfo.season = text.parse().ok(),
+                        ["episode"] => nfo.episode = text.parse().ok(),
+                        ["genre"] => push_unique(&mut nfo.genres, text),
+                        ["director"] => push_unique(&mut nfo.directors, text),
+                        ["credits"] | ["writer"] => push_unique(&mut nfo.writers, text),
+                        // Legacy single rating
+                        ["rating"] => {
+                            if let Ok(value) = text.parse() {
+                                nfo.ratings.push(NfoRating {
+                                    source: "default".to_string(),
+                                    value,
+                                    votes: None,
+                                });
+                            }
+                        }
+                        ["votes"] => {
+                            if let Some(rating) = nfo.ratings.iter_mut().find(|r| r.source == "default") {
+                                rating.votes = parse_votes(&text);
+                            }
+                        }
+                        ["ratings", "rating", "value"] => {
+                            if let Some(rating) = current_rating.as_mut() {
+                                rating.value = text.parse().unwrap_or(0.0);
+                            }
+                        }
+                        ["ratings", "rating", "votes"] => {
+                            if let Some(rating) = current_rating.as_mut() {
+                                rating.votes = parse_votes(&text);
+                            }
+                        }
+                        ["uniqueid"] => match uniqueid_type.as_deref() {
+                            Some("imdb") => nfo.imdb_id = Some(text),
+                            Some("tmdb") => nfo.tmdb_id = text.parse().ok(),
+                            _ => {}
+                        },
+                        ["id"] | ["imdbid"] if text.starts_with("tt") => {
+                            nfo.imdb_id.get_or_insert(text);
+                        }
+                        ["tmdbid"] if nfo.tmdb_id.is_none() => nfo.tmdb_id = text.parse().ok(),
+                        ["actor", field] => {
+                            if let Some(actor) = current_actor.as_mut() {
+                                match *field {
+                                    "name" => actor.name = text,
+                                    "role" => actor.role = Some(text),
+                                    "order" => actor.order = text.parse().ok(),
+                                    "thumb" => actor.thumb = Some(text),
+                                    _ => {}
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Event::End(_) => {
+                    let name = stack.pop().unwrap_or_default();
+
+                    if stack.is_empty() {
+                        // Anything after the root element (e.g. a scraper URL) is ignored
+                        break;
+                    }
+
+                    match name.as_str() {
+                        "actor" => {
+                            if let (Some(nfo), Some(actor)) = (nfo.as_mut(), current_actor.take()) {
+                                if !actor.name.is_empty() {
+                                    nfo.cast.push(actor);
+                                }
+                            }
+                        }
+                        "rating" => {
+                            if let (Some(nfo), Some(rating)) = (nfo.as_mut(), current_rating.take()) {
+                                nfo.ratings.push(rating);
+                            }
+                        }
+                        "uniqueid" => uniqueid_type = None,
+                        _ => {}
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        let mut nfo = nfo.ok_or(NfoError::NotXml)?;
+
+        // Fall back to the premiere date when <year> is missing
+        if nfo.year.is_none() {
+            nfo.year = nfo
+                .premiered
+                .as_deref()
+                .and_then(|d| d.get(0..4))
+                .and_then(|y| y.parse().ok());
+        }
+
+        Ok(nfo)
+    }
+
+    /// Build NFO metadata from a media file record and its metadata_json
+    pub fn from_media(media: &MediaFile) -> Self {
+        let kind = match media.media_type {
+            MediaType::TvEpisode => NfoKind::Episode,
+            _ => NfoKind::Movie,
+        };
+
+        let mut nfo = NfoMetadata::new(kind);
+        nfo.title = media.title.clone();
+        nfo.year = media.year;
+        nfo.season = media.season_number;
+        nfo.episode = media.episode_number;
+        nfo.runtime = media.duration.map(|d| ((d + 59) / 60) as i32);
+
+        let map: serde_json::Map<String, serde_json::Value> = media
+            .metadata_json
+            .as_deref()
+            .and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or_default();
+
+        let string = |key: &str| map.get(key).and_then(|v| v.as_str()).map(String::from);
+        let strings = |key: &str| -> Vec<String> {
+            map.get(key)
+                .and_then(|v| v.as_array())
+                .map(|arr| arr.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+                .unwrap_or_default()
+        };
+
+        nfo.original_title = string("original_title");
+        nfo.show_title = string("show_title");
+        nfo.premiered = string("premiered");
+        nfo.plot = string("overview");
+        nfo.tagline = string("tagline");
+        nfo.imdb_id = string("imdb_id");
+        nfo.tmdb_id = map.get("tmdb_id").and_then(|v| v.as_i64());
+        nfo.genres = strings("genres");
+        nfo.directors = strings("directors");
+        nfo.writers = strings("writers");
+        nfo.ratings = map
+            .get("ratings")
+            .cloned()
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        nfo.cast = map
+            .get("cast")
+            .cloned()
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        nfo
+    }
+
+    /// Merge this NFO's values into a media file record.
+    ///
+    /// Organization fields are overwritten and the remaining values are merged
+    /// into `metadata_json`. Locked records are left to `upsert_media_file`,
+    /// which keeps their stored values.
+    pub fn apply_to(&self, media: &mut MediaFile) {
+        match self.kind {
+            NfoKind::Movie => media.media_type = MediaType::Movie,
+            NfoKind::Episode => media.media_type = MediaType::TvEpisode,
+            NfoKind::TvShow => {}
+        }
+
+        if self.kind != NfoKind::TvShow {
+            if let Some(title) = &self.title {
+                media.title = Some(title.clone());
+            }
+            if self.year.is_some() {
+                media.year = self.year;
+            }
+        }
+        if self.season.is_some() {
+            media.season_number = self.season;
+        }
+        if self.episode.is_some() {
+            media.episode_number = self.episode;
+        }
+
+        let mut map: serde_json::Map<String, serde_json::Value> = media
+            .metadata_json
+            .as_deref()
+            .and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or_default();
+
+        if self.kind == NfoKind::TvShow {
+            if let Some(title) = &self.title {
+                map.insert("show_title".to_string(), title.clone().into());
+            }
+        } else {
+            if let Some(v) = &self.original_title {
+                map.insert("original_title".to_string(), v.clone().into());
+            }
+            if let Some(v) = &self.show_title {
+                map.insert("show_title".to_string(), v.clone().into());
+            }
+            if let Some(v) = &self.premiered {
+                map.insert("premiered".to_string(), v.clone().into());
+            }
+            if let Some(v) = &self.plot {
+                map.insert("overview".to_string(), v.clone().into());
+            }
+            if let Some(v) = &self.tagline {
+                map.insert("tagline".to_string(), v.clone().into());
+            }
+            if let Some(v) = &self.imdb_id {
+                map.insert("imdb_id".to_string(), v.clone().into());
+            }
+            if let Some(v) = self.tmdb_id {
+                map.insert("tmdb_id".to_string(), v.into());
+            }
+            if !self.ratings.is_empty() {
+                map.insert("ratings".to_string(), serde_json::to_value(&self.ratings).unwrap_or_default());
+            }
+            if !self.cast.is_empty() {
+                map.insert("cast".to_string(), serde_json::to_value(&self.cast).unwrap_or_default());
+            }
+            if !self.directors.is_empty() {
+                map.insert("directors".to_string(), serde_json::to_value(&self.directors).unwrap_or_default());
+            }
+            if !self.writers.is_empty() {
+                map.insert("writers".to_string(), serde_json::to_value(&self.writers).unwrap_or_default());
+            }
+        }
+        // Episode NFOs rarely carry genres, so show-level genres fill them in
+        if !self.genres.is_empty() && (self.kind != NfoKind::TvShow || !map.contains_key("genres")) {
+            map.insert("genres".to_string(), serde_json::to_value(&self.genres).unwrap_or_default());
+        }
+
+        media.metadata_json = serde_json::to_string(&map).ok();
+    }
+
+    /// Serialize this metadata as an NFO XML document
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+        let root = self.kind.root_element();
+        xml.push_str(&format!("<{}>\n", root));
+
+        write_element(&mut xml, 1, "title", self.title.as_deref());
+        write_element(&mut xml, 1, "originaltitle", self.original_title.as_deref());
+        write_element(&mut xml, 1, "showtitle", self.show_title.as_deref());
+        if self.kind == NfoKind::Episode {
+            write_element(&mut xml, 1, "season", self.season.map(|s| s.to_string()).as_deref());
+            write_element(&mut xml, 1, "episode", self.episode.map(|e| e.to_string()).as_deref());
+        }
+
+        if !self.ratings.is_empty() {
+            xml.push_str("  <ratings>\n");
+            for (i, rating) in self.ratings.iter().enumerate() {
+                xml.push_str(&format!(
+                    "    <rating name=\"{}\" max=\"10\" default=\"{}\">\n",
+                    escape(&rating.source),
+                    i == 0
+                ));
+                write_element(&mut xml, 3, "value", Some(&rating.value.to_string()));
+                write_element(&mut xml, 3, "votes", rating.votes.map(|v| v.to_string()).as_deref());
+                xml.push_str("    </rating>\n");
+            }
+            xml.push_str("  </ratings>\n");
+        }
+
+        write_element(&mut xml, 1, "year", self.year.map(|y| y.to_string()).as_deref());
+        write_element(&mut xml, 1, "plot", self.plot.as_deref());
+        write_element(&mut xml, 1, "tagline", self.tagline.as_deref());
+        write_element(&mut xml, 1, "runtime", self.runtime.map(|r| r.to_string()).as_deref());
+
+        if let Some(imdb_id) = &self.imdb_id {
+            xml.push_str(&format!(
+                "  <uniqueid type=\"imdb\" default=\"true\">{}</uniqueid>\n",
+                escape(imdb_id)
+            ));
+        }
+        if let Some(tmdb_id) = self.tmdb_id {
+            xml.push_str(&format!(
+                "  <uniqueid type=\"tmdb\" default=\"{}\">{}</uniqueid>\n",
+                self.imdb_id.is_none(),
+                tmdb_id
+            ));
+        }
+
+        for genre in &self.genres {
+            write_element(&mut xml, 1, "genre", Some(genre));
+        }
+        for writer in &self.writers {
+            write_element(&mut xml, 1, "credits", Some(writer));
+        }
+        for director in &self.directors {
+            write_element(&mut xml, 1, "director", Some(director));
+        }
+        write_element(
+            &mut xml,
+            1,
+            if self.kind == NfoKind::Episode { "aired" } else { "premiered" },
+            self.premiered.as_deref(),
+        );
+
+        for actor in &self.cast {
+            xml.push_str("  <actor>\n");
+            write_element(&mut xml, 2, "name", Some(&actor.name));
+            write_element(&mut xml, 2, "role", actor.role.as_deref());
+            write_element(&mut xml, 2, "order", actor.order.map(|o| o.to_string()).as_deref());
+            write_element(&mut xml, 2, "thumb", actor.thumb.as_deref());
+            xml.push_str("  </actor>\n");
+        }
+
+        xml.push_str(&format!("</{}>\n", root));
+        xml
+    }
+
+    /// Write this metadata as an NFO file
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), NfoError> {
+        let path = path.as_ref();
+        fs::write(path, self.to_xml())
+            .map_err(|e| NfoError::Io(format!("{}: {}", path.display(), e)))
+    }
+}
+
+/// Find the NFO sidecar for a media file.
+///
+/// Looks for `<stem>.nfo` first, then `movie.nfo` in the same directory when
+/// the file is the only video there; in a folder of several videos it would
+/// describe just one of them.
+pub fn find_nfo<P: AsRef<Path>>(media_path: P) -> Option<PathBuf> {
+    let media_path = media_path.as_ref();
+    let parent = media_path.parent()?;
+
+    let own = nfo_path_for(media_path)?;
+    if own.is_file() {
+        return Some(own);
+    }
+    let shared = parent.join("movie.nfo");
+    (shared.is_file() && video_count(parent) <= 1).then_some(shared)
+}
+
+fn video_count(dir: &Path) -> usize {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .filter(|path| {
+                    path.extension()
+                        .map(|ext| VIDEO_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+                        .unwrap_or(false)
+                })
+                .count()
+        })
+        .unwrap_or(0)
+}
+
+/// Find the `tvshow.nfo` for an episode, in its directory or the show directory above a season folder
+pub fn find_tvshow_nfo<P: AsRef<Path>>(media_path: P) -> Option<PathBuf> {
+    media_path
+        .as_ref()
+        .ancestors()
+        .skip(1)
+        .take(2)
+        .map(|dir| dir.join("tvshow.nfo"))
+        .find(|p| p.is_file())
+}
+
+/// The `<stem>.nfo` path next to a media file
+pub fn nfo_path_for<P: AsRef<Path>>(media_path: P) -> Option<PathBuf> {
+    let media_path = media_path.as_ref();
+    let stem = media_path.file_stem()?;
+    Some(media_path.with_file_name(format!("{}.nfo", stem.to_string_lossy())))
+}
+
+/// Read all NFO metadata that applies to a media file and merge it into the record.
+///
+/// Returns true if any NFO was found.
+pub fn apply_sidecar_nfo<P: AsRef<Path>>(media_path: P, media: &mut MediaFile) -> bool {
+    let media_path = media_path.as_ref();
+    let mut found = false;
+
+    let file_nfo = find_nfo(media_path).and_then(|p| match NfoMetadata::read_from_file(&p) {
+        Ok(nfo) => Some(nfo),
+        Err(e) => {
+            eprintln!("Skipping NFO {}: {}", p.display(), e);
+            None
+        }
+    });
+
+    let is_episode = matches!(media.media_type, MediaType::TvEpisode)
+        || file_nfo.as_ref().map(|n| n.kind == NfoKind::Episode).unwrap_or(false);
+
+    if is_episode {
+        if let Some(show_nfo) = find_tvshow_nfo(media_path).and_then(|p| NfoMetadata::read_from_file(p).ok()) {
+            show_nfo.apply_to(media);
+            found = true;
+        }
+    }
+
+    if let Some(nfo) = file_nfo {
+        nfo.apply_to(media);
+        found = true;
+    }
+
+    found
+}
+
+/// Errors that can occur while reading or writing NFO files
+#[derive(Debug, thiserror::Error)]
+pub enum NfoError {
+    #[error("IO error: {0}")]
+    Io(String),
+
+    #[error("Invalid NFO XML {0}")]
+    Parse(String),
+
+    #[error("Unsupported NFO root element: {0}")]
+    UnsupportedRoot(String),
+
+    #[error("File does not contain NFO XML")]
+    NotXml,
+}
+
+fn element_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.name().as_ref()).to_lowercase()
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Option<String> {
+    e.try_get_attribute(name)
+        .ok()
+        .flatten()
+        .and_then(|a| a.unescape_value().ok().map(|v| v.to_string()))
+}
+
+fn parse_votes(text: &str) -> Option<i64> {
+    text.replace([',', '.'], "").parse().ok()
+}
+
+fn push_unique(list: &mut Vec<String>, value: String) {
+    if !list.contains(&value) {
+        list.push(value);
+    }
+}
+
+fn escape(value: &str) -> String {
+    quick_xml::escape::escape(value).to_string()
+}
+
+fn write_element(xml: &mut String, depth: usize, name: &str, value: Option<&str>) {
+    if let Some(value) = value {
+        xml.push_str(&format!("{}<{}>{}</{}>\n", "  ".repeat(depth), name, escape(value), name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOVIE_NFO: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes" ?>
+<movie>
+    <title>Inception</title>
+    <originaltitle>Inception</originaltitle>
+    <ratings>
+        <rating name="imdb" max="10" default="true">
+            <value>8.8</value>
+            <votes>2,400,000</votes>
+        </rating>
+        <rating name="themoviedb" max="10">
+            <value>8.4</value>
+        </rating>
+    </ratings>
+    <year>2010</year>
+    <plot>A thief who steals corporate secrets &amp; more.</plot>
+    <runtime>148</runtime>
+    <uniqueid type="imdb" default="true">tt1375666</uniqueid>
+    <uniqueid type="tmdb">27205</uniqueid>
+    <genre>Action</genre>
+    <genre>Science Fiction</genre>
+    <director>Christopher Nolan</director>
+    <actor>
+        <name>Leonardo DiCaprio</name>
+        <role>Cobb</role>
+        <order>0</order>
+    </actor>
+    <actor>
+        <name>Elliot Page</name>
+        <role>Ariadne</role>
+        <order>1</order>
+    </actor>
+</movie>
+https://www.themoviedb.org/movie/27205
+"#;
+
+    fn test_media() -> MediaFile {
+        MediaFile {
+            id: None,
+            file_path: "/movies/Inception.mkv".to_string(),
+            file_hash: "hash".to_string(),
+            file_name: "Inception.mkv".to_string(),
+            file_size: 100,
+            media_type: MediaType::Video,
+            duration: Some(8880),
+            codec: None, resolution: None, bitrate: None, framerate: None, audio_codec: None, audio_channels: None,
+            title: Some("Inception".to_string()), year: None, season_number: None, episode_number: None,
+            indexed_at: String::new(), last_modified: String::new(), is_deleted: false, metadata_json: None,
+            is_locked: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_movie_nfo() {
+        let nfo = NfoMetadata::parse(MOVIE_NFO).unwrap();
+
+        assert_eq!(nfo.kind, NfoKind::Movie);
+        assert_eq!(nfo.title.as_deref(), Some("Inception"));
+        assert_eq!(nfo.year, Some(2010));
+        assert_eq!(nfo.plot.as_deref(), Some("A thief who steals corporate secrets & more."));
+        assert_eq!(nfo.runtime, Some(148));
+        assert_eq!(nfo.imdb_id.as_deref(), Some("tt1375666"));
+        assert_eq!(nfo.tmdb_id, Some(27205));
+        assert_eq!(nfo.genres, vec!["Action", "Science Fiction"]);
+        assert_eq!(nfo.directors, vec!["Christopher Nolan"]);
+        assert_eq!(nfo.ratings.len(), 2);
+        assert_eq!(nfo.ratings[0].source, "imdb");
+        assert_eq!(nfo.ratings[0].votes, Some(2_400_000));
+        assert_eq!(nfo.cast.len(), 2);
+        assert_eq!(nfo.cast[1].role.as_deref(), Some("Ariadne"));
+    }
+
+    #[test]
+    fn test_parse_episode_nfo() {
+        let xml = r#"<episodedetails>
+            <title>Pilot</title>
+            <showtitle>Breaking Bad</showtitle>
+            <season>1</season>
+            <episode>1</episode>
+            <aired>2008-01-20</aired>
+            <rating>8.2</rating>
+            <id>tt0959621</id>
+        </episodedetails>"#;
+        let nfo = NfoMetadata::parse(xml).unwrap();
+
+        assert_eq!(nfo.kind, NfoKind::Episode);
+        assert_eq!(nfo.season, Some(1));
+        assert_eq!(nfo.episode, Some(1));
+        assert_eq!(nfo.year, Some(2008));
+        assert_eq!(nfo.imdb_id.as_deref(), Some("tt0959621"));
+        assert_eq!(nfo.ratings[0].value, 8.2);
+    }
+
+    #[test]
+    fn test_rejects_non_nfo() {
+        assert!(matches!(NfoMetadata::parse("https://www.imdb.com/title/tt1375666/"), Err(NfoError::NotXml)));
+        assert!(matches!(NfoMetadata::parse("<artist><name>x</name></artist>"), Err(NfoError::UnsupportedRoot(_))));
+    }
+
+    #[test]
+    fn test_find_nfo_uses_movie_nfo_only_for_a_lone_video() {
+        let dir = tempfile::tempdir().unwrap();
+        let inception = dir.path().join("Inception.mkv");
+        fs::write(&inception, b"").unwrap();
+        fs::write(dir.path().join("movie.nfo"), MOVIE_NFO).unwrap();
+        assert_eq!(find_nfo(&inception), Some(dir.path().join("movie.nfo")));
+
+        // With a second video the shared NFO could be about either
+        let extras = dir.path().join("Inception.Featurette.mkv");
+        fs::write(&extras, b"").unwrap();
+        assert_eq!(find_nfo(&extras), None);
+        assert_eq!(find_nfo(&inception), None);
+
+        fs::write(dir.path().join("Inception.nfo"), MOVIE_NFO).unwrap();
+        assert_eq!(find_nfo(&inception), Some(dir.path().join("Inception.nfo")));
+    }
+
+    #[test]
+    fn test_apply_and_roundtrip() {
+        let nfo = NfoMetadata::parse(MOVIE_NFO).unwrap();
+        let mut media = test_media();
+        nfo.apply_to(&mut media);
+
+        assert!(matches!(media.media_type, MediaType::Movie));
+        assert_eq!(media.year, Some(2010));
+        let json: serde_json::Value = serde_json::from_str(media.metadata_json.as_deref().unwrap()).unwrap();
+        assert_eq!(json["overview"], "A thief who steals corporate secrets & more.");
+        assert_eq!(json["cast"][0]["name"], "Leonardo DiCaprio");
+
+        let exported = NfoMetadata::from_media(&media);
+        let reparsed = NfoMetadata::parse(&exported.to_xml()).unwrap();
+        assert_eq!(reparsed, exported);
+        assert_eq!(reparsed.genres, nfo.genres);
+        assert_eq!(reparsed.cast, nfo.cast);
+        assert_eq!(reparsed.ratings, nfo.ratings);
+    }
+}
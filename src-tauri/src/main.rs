@@ -5,6 +5,11 @@ mod db;
 mod indexer;
 mod player;
 mod backup;
+mod playlist_io;
+mod tmdb;
+mod metadata;
+mod organizer;
+mod subtitles;
 
 use std::sync::Mutex;
 use tauri::State;
@@ -95,7 +100,7 @@ async fn scan_directory(
             .ok();
         
         // Create media file record
-        let media = db::MediaFile {
+        let mut media = db::MediaFile {
             id: None,
             file_path: file.path.to_string_lossy().to_string(),
             file_hash,
@@ -122,7 +127,11 @@ async fn scan_directory(
                 .unwrap_or_else(|| Utc::now().to_rfc3339()),
             is_deleted: false,
             metadata_json: None,
+            is_locked: false,
         };
+
+        // Merge Kodi/Jellyfin NFO sidecars (locked records keep their values on upsert)
+        indexer::nfo::apply_sidecar_nfo(&file.path, &mut media);
         
         // Insert or update in database
         match db::upsert_media_file(&conn, &media) {
@@ -141,6 +150,14 @@ async fn scan_directory(
     }
     
     println!("Scan complete: {} added, {} updated, {} errors", added, updated, errors);
+
+    // Keep generated franchise collections in step with the library
+    if let Err(e) = db::sync_auto_collections(&conn) {
+        eprintln!("Error syncing auto collections: {}", e);
+    }
+    if let Err(e) = db::refresh_recommendations(&conn) {
+        eprintln!("Error refreshing recommendations: {}", e);
+    }
     
     Ok(ScanResult {
         total_found: files.len(),
@@ -151,17 +168,21 @@ async fn scan_directory(
 }
 
 #[tauri::command]
-fn get_all_media(state: State<AppState>) -> Result<Vec<db::MediaFile>, String> {
+fn get_all_media(
+    page: Option<db::PageRequest>,
+    state: State<AppState>,
+) -> Result<Vec<db::MediaFile>, String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
     
-    db::get_all_media_files(&conn).map_err(|e| e.to_string())
+    db::list_media(&conn, &page.unwrap_or_default()).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 fn get_media_by_type(
     media_type: String,
+    page: Option<db::PageRequest>,
     state: State<AppState>,
 ) -> Result<Vec<db::MediaFile>, String> {
     let db = state.db.lock().unwrap();
@@ -175,28 +196,44 @@ fn get_media_by_type(
         _ => return Err("Invalid media type".to_string()),
     };
     
-    db::get_media_by_type(&conn, media_type).map_err(|e| e.to_string())
+    db::get_media_by_type(&conn, media_type, &page.unwrap_or_default()).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 fn filter_media(
     criteria: db::FilterCriteria,
+    page: Option<db::PageRequest>,
     state: State<AppState>,
 ) -> Result<Vec<db::MediaFile>, String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
 
-    db::filter_media(&conn, &criteria).map_err(|e| e.to_string())
+    db::filter_media(&conn, &criteria, &page.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_media_facets(criteria: Option<db::FilterCriteria>, state: State<AppState>) -> Result<db::MediaFacets, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::get_media_facets(&conn, &criteria.unwrap_or_default()).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn search_media(query: String, state: State<AppState>) -> Result<Vec<db::MediaFile>, String> {
+fn search_media(
+    query: String,
+    page: Option<db::PageRequest>,
+    state: State<AppState>,
+) -> Result<Vec<db::SearchResult>, String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
     
-    db::search_media(&conn, &query).map_err(|e| e.to_string())
+    let mut page = page.unwrap_or_default();
+    page.limit = page.limit.or(Some(200));
+    db::search_media_page(&conn, &query, &page).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -225,7 +262,13 @@ fn mark_as_completed(
     let conn = conn.lock().unwrap();
     
     db::mark_as_completed(&conn, media_id, duration)
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+
+    // Finishing something changes what's recommended next
+    if let Err(e) = db::refresh_recommendations(&conn) {
+        eprintln!("Error refreshing recommendations: {}", e);
+    }
+    Ok(())
 }
 
 #[tauri::command]
@@ -340,6 +383,11 @@ async fn extract_metadata(
         db::audio_tracks::save_audio_tracks(&conn, media_id, &updated_media.file_path, &metadata.audio_tracks)
             .map_err(|e| e.to_string())?;
     }
+
+    // Register embedded subtitle streams so they can be extracted; with
+    // none found, tracks left from an earlier probe are removed
+    db::save_embedded_subtitle_tracks(&conn, media_id, &updated_media.file_path, &metadata.subtitle_tracks)
+        .map_err(|e| e.to_string())?;
     
     Ok(MetadataResult {
         duration: metadata.duration,
@@ -419,6 +467,100 @@ fn scan_subtitles(
         .map_err(|e| e.to_string())
 }
 
+/// The cues of an external subtitle track, in any supported format
+#[tauri::command]
+fn get_subtitle_cues(subtitle_id: i64, state: State<AppState>) -> Result<subtitles::SubtitleDocument, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    subtitles::load_subtitle_track(&conn, subtitle_id).map_err(|e| e.to_string())
+}
+
+/// An external subtitle track converted to WebVTT for the player
+#[tauri::command]
+fn get_subtitle_webvtt(subtitle_id: i64, state: State<AppState>) -> Result<String, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    subtitles::load_subtitle_track(&conn, subtitle_id)
+        .map(|document| document.to_webvtt())
+        .map_err(|e| e.to_string())
+}
+
+/// Extract an embedded text subtitle stream into the app cache as a new
+/// external track, returning its id
+#[tauri::command]
+async fn extract_embedded_subtitle(
+    subtitle_id: i64,
+    format: Option<subtitles::SubtitleFormat>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<i64, String> {
+    let cache_dir = tauri::api::path::app_cache_dir(&app_handle.config())
+        .ok_or("Failed to get app cache directory")?;
+
+    let conn = state.db.lock().unwrap().connection();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let extraction = subtitles::plan_extraction(&conn.lock().unwrap(), subtitle_id, &cache_dir, format)?;
+        // ffmpeg reads through the whole video, so it runs without the database
+        extraction.run()?;
+        subtitles::register_extraction(&conn.lock().unwrap(), &extraction)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_subtitle_timing(subtitle_id: i64, state: State<AppState>) -> Result<subtitles::SubtitleTiming, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    subtitles::get_subtitle_timing(&conn, subtitle_id).map_err(|e| e.to_string())
+}
+
+/// Store the offset and frame rate conversion applied whenever the track is read
+#[tauri::command]
+fn set_subtitle_timing(
+    subtitle_id: i64,
+    timing: subtitles::SubtitleTiming,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    subtitles::set_subtitle_timing(&conn, subtitle_id, &timing).map_err(|e| e.to_string())
+}
+
+/// Two-point resync, stored on the track or with `write` saved as a new track
+#[tauri::command]
+fn resync_subtitle_track(
+    subtitle_id: i64,
+    points: [subtitles::SyncPoint; 2],
+    write: Option<bool>,
+    state: State<AppState>,
+) -> Result<subtitles::ResyncResult, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    subtitles::resync_subtitle_track(&conn, subtitle_id, points, write.unwrap_or(false)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn save_adjusted_subtitle_track(subtitle_id: i64, state: State<AppState>) -> Result<i64, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    subtitles::save_adjusted_subtitle_track(&conn, subtitle_id).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 fn get_audio_tracks(
     media_id: i64,
@@ -496,230 +638,620 @@ fn remove_from_collection(
 }
 
 #[tauri::command]
-fn update_collection(
+fn reorder_collection_item(
     collection_id: i64,
-    name: String,
-    description: Option<String>,
+    media_id: i64,
+    new_position: i32,
     state: State<AppState>,
 ) -> Result<(), String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
-    
-    db::update_collection(&conn, collection_id, &name, description.as_deref())
+
+    db::reorder_collection_item(&conn, collection_id, media_id, new_position)
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn delete_collection(
+fn move_collection_items(
     collection_id: i64,
+    media_ids: Vec<i64>,
+    to_index: usize,
     state: State<AppState>,
-) -> Result<(), String> {
+) -> Result<usize, String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
-    
-    db::delete_collection(&conn, collection_id)
+
+    db::move_collection_items(&conn, collection_id, &media_ids, to_index)
         .map_err(|e| e.to_string())
 }
 
-// Playlist commands
 #[tauri::command]
-fn create_playlist(
-    name: String,
-    description: Option<String>,
-    playlist_type: String,
+fn sync_auto_collections(state: State<AppState>) -> Result<db::AutoCollectionReport, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::sync_auto_collections(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_recommendations(limit: Option<usize>, state: State<AppState>) -> Result<Vec<db::Recommendation>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::get_recommendations(&conn, limit.unwrap_or(20)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_people(
+    role: Option<String>,
+    query: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
     state: State<AppState>,
-) -> Result<i64, String> {
+) -> Result<Vec<db::Person>, String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
-    
-    let ptype = match playlist_type.as_str() {
-        "manual" => db::PlaylistType::Manual,
-        "smart" => db::PlaylistType::Smart,
-        "auto" => db::PlaylistType::Auto,
-        _ => return Err("Invalid playlist type".to_string()),
-    };
-    
-    db::create_playlist(&conn, &name, description.as_deref(), ptype)
+
+    db::list_people(&conn, role.as_deref(), query.as_deref(), limit.unwrap_or(100), offset.unwrap_or(0))
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn get_all_playlists(state: State<AppState>) -> Result<Vec<db::PlaylistWithCount>, String> {
+fn get_person(person_id: i64, state: State<AppState>) -> Result<Option<db::PersonDetails>, String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
-    
-    db::get_playlists_with_counts(&conn).map_err(|e| e.to_string())
+
+    db::get_person(&conn, person_id).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn get_playlist_media(
-    playlist_id: i64,
+fn get_media_by_person(
+    person_id: i64,
+    role: Option<String>,
     state: State<AppState>,
-) -> Result<Vec<db::PlaylistMediaItem>, String> {
+) -> Result<Vec<db::MediaFile>, String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
-    
-    db::get_playlist_media(&conn, playlist_id).map_err(|e| e.to_string())
+
+    db::get_media_by_person(&conn, person_id, role.as_deref()).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn add_to_playlist(
-    playlist_id: i64,
-    media_id: i64,
-    state: State<AppState>,
-) -> Result<(), String> {
+fn match_library(auto_accept: Option<f64>, state: State<AppState>) -> Result<tmdb::MatchReport, String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
-    
-    db::add_media_to_playlist(&conn, playlist_id, media_id)
-        .map_err(|e| e.to_string())
+
+    let report = tmdb::match_library(&conn, auto_accept.unwrap_or(tmdb::AUTO_ACCEPT_CONFIDENCE))
+        .map_err(|e| e.to_string())?;
+    tmdb::link_episodes(&conn, None).map_err(|e| e.to_string())?;
+    db::sync_auto_collections(&conn).map_err(|e| e.to_string())?;
+    Ok(report)
 }
 
 #[tauri::command]
-fn remove_from_playlist(
-    playlist_id: i64,
+fn get_match_candidates(media_id: Option<i64>, state: State<AppState>) -> Result<Vec<tmdb::MatchCandidate>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    tmdb::get_match_candidates(&conn, media_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn confirm_match(
     media_id: i64,
+    tmdb_id: i64,
+    media_type: String,
     state: State<AppState>,
-) -> Result<(), String> {
+) -> Result<usize, String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
-    
-    db::remove_media_from_playlist(&conn, playlist_id, media_id)
-        .map_err(|e| e.to_string())
+
+    let matched = tmdb::confirm_match(&conn, media_id, tmdb_id, &media_type).map_err(|e| e.to_string())?;
+    db::sync_auto_collections(&conn).map_err(|e| e.to_string())?;
+    Ok(matched)
 }
 
 #[tauri::command]
-fn update_playlist(
-    playlist_id: i64,
-    name: String,
-    description: Option<String>,
-    state: State<AppState>,
-) -> Result<(), String> {
+fn link_episodes(tmdb_show_id: Option<i64>, state: State<AppState>) -> Result<tmdb::EpisodeLinkReport, String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
-    
-    db::update_playlist(&conn, playlist_id, &name, description.as_deref())
-        .map_err(|e| e.to_string())
+
+    tmdb::link_episodes(&conn, tmdb_show_id).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn delete_playlist(
-    playlist_id: i64,
-    state: State<AppState>,
-) -> Result<(), String> {
+fn get_episode_info(media_id: i64, state: State<AppState>) -> Result<Vec<tmdb::EpisodeInfo>, String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
-    
-    db::delete_playlist(&conn, playlist_id)
-        .map_err(|e| e.to_string())
+
+    tmdb::get_episode_info(&conn, media_id).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn add_playlist_rule(
-    playlist_id: i64,
-    rule_type: String,
-    operator: String,
-    value: String,
-    state: State<AppState>,
-) -> Result<i64, String> {
+fn set_episode_links(media_id: i64, episodes: Vec<(i32, i32)>, state: State<AppState>) -> Result<(), String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
 
-    db::add_playlist_rule(&conn, playlist_id, &rule_type, &operator, &value)
-        .map_err(|e| e.to_string())
+    tmdb::set_episode_links(&conn, media_id, &episodes).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn get_playlist_rules(
-    playlist_id: i64,
-    state: State<AppState>,
-) -> Result<Vec<db::PlaylistRule>, String> {
+fn get_episode_report(tmdb_show_id: Option<i64>, state: State<AppState>) -> Result<Vec<tmdb::ShowEpisodeReport>, String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
 
-    db::get_playlist_rules(&conn, playlist_id).map_err(|e| e.to_string())
+    tmdb::get_episode_report(&conn, tmdb_show_id).map_err(|e| e.to_string())
 }
 
+/// Search, match and refresh metadata online; fails without touching the
+/// network when TMDB is disabled in settings
 #[tauri::command]
-fn delete_playlist_rule(
-    rule_id: i64,
+async fn sync_tmdb(max_age_days: Option<i64>, state: State<'_, AppState>) -> Result<tmdb::TmdbSyncReport, String> {
+    let conn = state.db.lock().unwrap().connection();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let client = tmdb::TmdbClient::from_settings(&conn.lock().unwrap(), tmdb::UreqTransport::default())?;
+        // The sync locks the database per statement, so the app stays usable
+        // during requests and rate-limit waits
+        let report = tmdb::sync_library(&*conn, &client, max_age_days.unwrap_or(30))?;
+        let conn = conn.lock().unwrap();
+        db::sync_auto_collections(&conn)?;
+        db::refresh_recommendations(&conn)?;
+        Ok::<_, tmdb::TmdbError>(report)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn export_tmdb_bundle(
+    output_path: String,
+    format: Option<tmdb::BundleFormat>,
     state: State<AppState>,
-) -> Result<(), String> {
+) -> Result<tmdb::BundleReport, String> {
     let db = state.db.lock().unwrap();
     let conn = db.connection();
     let conn = conn.lock().unwrap();
 
-    db::delete_playlist_rule(&conn, rule_id).map_err(|e| e.to_string())
+    tmdb::export_bundle(&conn, std::path::Path::new(&output_path), format).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn check_dependencies() -> DependencyStatus {
-    let ffmpeg = std::process::Command::new("ffmpeg")
-        .arg("-version")
-        .output()
-        .map(|o| o.status.success())
-        .unwrap_or(false);
-
-    let vlc = cfg!(feature = "vlc");
+fn import_tmdb_bundle(input_path: String, state: State<AppState>) -> Result<tmdb::BundleImportReport, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
 
-    DependencyStatus { ffmpeg, vlc }
+    let report = tmdb::import_bundle(&conn, std::path::Path::new(&input_path)).map_err(|e| e.to_string())?;
+    db::sync_auto_collections(&conn).map_err(|e| e.to_string())?;
+    Ok(report)
 }
 
 #[tauri::command]
-async fn generate_thumbnail(
-    file_path: String,
-    time: f64,
-) -> Result<String, String> {
-    let path = std::path::Path::new(&file_path);
-    let temp_dir = std::env::temp_dir();
-    let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
-    let output_path = temp_dir.join(format!("{}_thumb.jpg", file_stem));
+fn search_metadata(query: metadata::SearchQuery, state: State<AppState>) -> Result<Vec<metadata::SearchResult>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
 
-    indexer::metadata::generate_thumbnail(path, &output_path, time)
-        .map_err(|e| e.to_string())?;
+    let chain = metadata::ProviderChain::from_settings(&conn).map_err(|e| e.to_string())?;
+    Ok(chain.search(&conn, &query))
+}
 
-    Ok(output_path.to_string_lossy().to_string())
+/// What the provider chain would set for a file, per field with every
+/// provider's offer, without changing anything
+#[tauri::command]
+fn preview_metadata(media_id: i64, state: State<AppState>) -> Result<metadata::ResolvedMetadata, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    let chain = metadata::ProviderChain::from_settings(&conn).map_err(|e| e.to_string())?;
+    metadata::preview_metadata(&conn, &chain, media_id).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-#[allow(unused_variables)]
-fn init_vlc_player(state: State<AppState>) -> Result<bool, String> {
-    if cfg!(feature = "vlc") {
-        #[cfg(feature = "vlc")]
-        {
-            let mut player_lock = state.vlc_player.lock().map_err(|_| "Failed to lock state")?;
-            if player_lock.is_some() {
-                return Ok(true);
-            }
+fn refresh_metadata(media_ids: Option<Vec<i64>>, state: State<AppState>) -> Result<metadata::MetadataRefreshReport, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
 
-            match player::vlc::VlcPlayer::new() {
-                Some(player) => {
-                    *player_lock = Some(player);
-                    Ok(true)
-                }
-                None => Err("Failed to initialize VLC instance. LibVLC might be missing.".into()),
-            }
-        }
-        #[cfg(not(feature = "vlc"))]
-        Ok(false)
-    } else {
-        Err("VLC feature is not enabled in build.".into())
-    }
+    let chain = metadata::ProviderChain::from_settings(&conn).map_err(|e| e.to_string())?;
+    metadata::refresh_metadata(&conn, &chain, media_ids.as_deref()).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-#[allow(unused_variables)]
+fn get_metadata_provenance(media_id: i64, state: State<AppState>) -> Result<Vec<metadata::FieldProvenance>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    metadata::get_metadata_provenance(&conn, media_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_metadata_images(media_id: i64, state: State<AppState>) -> Result<Vec<metadata::ProviderImage>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    let media = db::get_media_file_by_id(&conn, media_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Media {} not found", media_id))?;
+    let chain = metadata::ProviderChain::from_settings(&conn).map_err(|e| e.to_string())?;
+    Ok(chain.fetch_images(&conn, &media))
+}
+
+#[tauri::command]
+fn get_metadata_config(state: State<AppState>) -> Result<metadata::MetadataConfig, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    metadata::get_metadata_config(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_metadata_config(config: metadata::MetadataConfig, state: State<AppState>) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    metadata::set_metadata_config(&conn, &config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn reject_match_candidate(media_id: i64, tmdb_id: i64, state: State<AppState>) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    tmdb::reject_match_candidate(&conn, media_id, tmdb_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn update_collection(
+    collection_id: i64,
+    name: String,
+    description: Option<String>,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+    
+    db::update_collection(&conn, collection_id, &name, description.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_collection(
+    collection_id: i64,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+    
+    db::delete_collection(&conn, collection_id)
+        .map_err(|e| e.to_string())
+}
+
+// Playlist commands
+#[tauri::command]
+fn create_playlist(
+    name: String,
+    description: Option<String>,
+    playlist_type: String,
+    state: State<AppState>,
+) -> Result<i64, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+    
+    let ptype = match playlist_type.as_str() {
+        "manual" => db::PlaylistType::Manual,
+        "smart" => db::PlaylistType::Smart,
+        "auto" => db::PlaylistType::Auto,
+        _ => return Err("Invalid playlist type".to_string()),
+    };
+    
+    db::create_playlist(&conn, &name, description.as_deref(), ptype)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_all_playlists(state: State<AppState>) -> Result<Vec<db::PlaylistWithCount>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+    
+    db::get_playlists_with_counts(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_playlist_media(
+    playlist_id: i64,
+    state: State<AppState>,
+) -> Result<Vec<db::PlaylistMediaItem>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+    
+    db::get_playlist_media(&conn, playlist_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn add_to_playlist(
+    playlist_id: i64,
+    media_id: i64,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+    
+    db::add_media_to_playlist(&conn, playlist_id, media_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn remove_from_playlist(
+    playlist_id: i64,
+    media_id: i64,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+    
+    db::remove_media_from_playlist(&conn, playlist_id, media_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn reorder_playlist_item(
+    playlist_id: i64,
+    media_id: i64,
+    new_position: i32,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::reorder_playlist_item(&conn, playlist_id, media_id, new_position)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn move_playlist_items(
+    playlist_id: i64,
+    media_ids: Vec<i64>,
+    to_index: usize,
+    state: State<AppState>,
+) -> Result<usize, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::move_playlist_items(&conn, playlist_id, &media_ids, to_index)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn update_playlist(
+    playlist_id: i64,
+    name: String,
+    description: Option<String>,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+    
+    db::update_playlist(&conn, playlist_id, &name, description.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_playlist(
+    playlist_id: i64,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+    
+    db::delete_playlist(&conn, playlist_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn add_playlist_rule(
+    playlist_id: i64,
+    rule_type: String,
+    operator: String,
+    value: String,
+    parent_id: Option<i64>,
+    state: State<AppState>,
+) -> Result<i64, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::add_playlist_rule(&conn, playlist_id, &rule_type, &operator, &value, parent_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_smart_playlist_settings(
+    playlist_id: i64,
+    state: State<AppState>,
+) -> Result<db::SmartPlaylistSettings, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::get_smart_playlist_settings(&conn, playlist_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_smart_playlist_settings(
+    playlist_id: i64,
+    settings: db::SmartPlaylistSettings,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::set_smart_playlist_settings(&conn, playlist_id, &settings).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn reshuffle_smart_playlist(
+    playlist_id: i64,
+    state: State<AppState>,
+) -> Result<i64, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::reshuffle_smart_playlist(&conn, playlist_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn export_playlist(
+    playlist_id: i64,
+    output_path: String,
+    format: Option<playlist_io::PlaylistFormat>,
+    relative_paths: Option<bool>,
+    state: State<AppState>,
+) -> Result<usize, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    playlist_io::export_playlist(
+        &conn,
+        playlist_id,
+        std::path::Path::new(&output_path),
+        format,
+        relative_paths.unwrap_or(false),
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn import_playlist(
+    input_path: String,
+    name: Option<String>,
+    state: State<AppState>,
+) -> Result<playlist_io::ImportReport, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    playlist_io::import_playlist(&conn, std::path::Path::new(&input_path), name.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_playlist_rules(
+    playlist_id: i64,
+    state: State<AppState>,
+) -> Result<Vec<db::PlaylistRule>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::get_playlist_rules(&conn, playlist_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_playlist_rule(
+    rule_id: i64,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::delete_playlist_rule(&conn, rule_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn check_dependencies() -> DependencyStatus {
+    let ffmpeg = std::process::Command::new("ffmpeg")
+        .arg("-version")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false);
+
+    let vlc = cfg!(feature = "vlc");
+
+    DependencyStatus { ffmpeg, vlc }
+}
+
+#[tauri::command]
+async fn generate_thumbnail(
+    file_path: String,
+    time: f64,
+) -> Result<String, String> {
+    let path = std::path::Path::new(&file_path);
+    let temp_dir = std::env::temp_dir();
+    let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let output_path = temp_dir.join(format!("{}_thumb.jpg", file_stem));
+
+    indexer::metadata::generate_thumbnail(path, &output_path, time)
+        .map_err(|e| e.to_string())?;
+
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+#[allow(unused_variables)]
+fn init_vlc_player(state: State<AppState>) -> Result<bool, String> {
+    if cfg!(feature = "vlc") {
+        #[cfg(feature = "vlc")]
+        {
+            let mut player_lock = state.vlc_player.lock().map_err(|_| "Failed to lock state")?;
+            if player_lock.is_some() {
+                return Ok(true);
+            }
+
+            match player::vlc::VlcPlayer::new() {
+                Some(player) => {
+                    *player_lock = Some(player);
+                    Ok(true)
+                }
+                None => Err("Failed to initialize VLC instance. LibVLC might be missing.".into()),
+            }
+        }
+        #[cfg(not(feature = "vlc"))]
+        Ok(false)
+    } else {
+        Err("VLC feature is not enabled in build.".into())
+    }
+}
+
+#[tauri::command]
+#[allow(unused_variables)]
 fn play_in_vlc(
     file_path: String,
     state: State<AppState>,
@@ -766,6 +1298,126 @@ fn set_audio_track(
     }
 }
 
+#[tauri::command]
+fn get_play_queue(
+    state: State<AppState>,
+) -> Result<db::PlayQueue, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::get_queue(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn start_play_queue(
+    source: db::QueueSource,
+    start_media_id: Option<i64>,
+    shuffle: Option<bool>,
+    state: State<AppState>,
+) -> Result<db::PlayQueue, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::start_queue(&conn, &source, start_media_id, shuffle.unwrap_or(false)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn enqueue_media(
+    media_ids: Vec<i64>,
+    play_next: Option<bool>,
+    state: State<AppState>,
+) -> Result<db::PlayQueue, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::enqueue_media(&conn, &media_ids, play_next.unwrap_or(false)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn queue_next(
+    finished: Option<bool>,
+    state: State<AppState>,
+) -> Result<Option<db::QueueEntry>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::advance_queue(&conn, finished.unwrap_or(false)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn queue_previous(
+    state: State<AppState>,
+) -> Result<Option<db::QueueEntry>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::previous_in_queue(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn jump_to_queue_item(
+    queue_item_id: i64,
+    state: State<AppState>,
+) -> Result<db::QueueEntry, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::jump_to_queue_item(&conn, queue_item_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn remove_from_queue(
+    queue_item_id: i64,
+    state: State<AppState>,
+) -> Result<db::PlayQueue, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::remove_from_queue(&conn, queue_item_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn clear_play_queue(
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::clear_queue(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_queue_shuffle(
+    shuffle: bool,
+    state: State<AppState>,
+) -> Result<db::PlayQueue, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::set_queue_shuffle(&conn, shuffle).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_queue_repeat_mode(
+    repeat_mode: db::RepeatMode,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::set_queue_repeat_mode(&conn, repeat_mode).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 fn export_database(
     output_path: String,
@@ -816,6 +1468,195 @@ fn update_media_metadata(
     ).map_err(|e| e.to_string())
 }
 
+/// Set fields by hand; each is locked against scans and providers. Null
+/// clears a field.
+#[tauri::command]
+fn edit_media_metadata(
+    media_id: i64,
+    changes: std::collections::BTreeMap<String, serde_json::Value>,
+    state: State<AppState>,
+) -> Result<Vec<db::MetadataEdit>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::edit_media_metadata(&conn, media_id, &changes).map_err(|e| e.to_string())
+}
+
+/// Apply a bulk edit to many files in one transaction; with `dry_run` only
+/// return the values it would produce
+#[tauri::command]
+fn bulk_edit_metadata(
+    edit: db::BulkEdit,
+    dry_run: Option<bool>,
+    state: State<AppState>,
+) -> Result<Vec<db::BulkEditResult>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::bulk_edit_metadata(&conn, &edit, dry_run.unwrap_or(false)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_metadata_overrides(media_id: i64, state: State<AppState>) -> Result<Vec<db::MetadataOverride>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::get_metadata_overrides(&conn, media_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_metadata_field_lock(
+    media_id: i64,
+    field: String,
+    locked: bool,
+    state: State<AppState>,
+) -> Result<Option<db::MetadataEdit>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::set_metadata_field_lock(&conn, media_id, &field, locked).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn revert_metadata_field(media_id: i64, field: String, state: State<AppState>) -> Result<Option<db::MetadataEdit>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::revert_metadata_field(&conn, media_id, &field).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn undo_metadata_edit(media_id: i64, field: String, state: State<AppState>) -> Result<Option<db::MetadataEdit>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::undo_metadata_edit(&conn, media_id, &field).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_metadata_edits(media_id: i64, state: State<AppState>) -> Result<Vec<db::MetadataEdit>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    db::get_metadata_edits(&conn, media_id).map_err(|e| e.to_string())
+}
+
+/// Plan moving files to naming templates and, unless `dry_run`, move them
+#[tauri::command]
+async fn organize_files(
+    request: organizer::OrganizeRequest,
+    dry_run: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<organizer::OrganizePlan, String> {
+    let conn = state.db.lock().unwrap().connection();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let plan = organizer::plan_organize(&conn.lock().unwrap(), &request)?;
+        if !dry_run.unwrap_or(false) {
+            // The database is free while the files move, which can take
+            // minutes when they are copied across drives
+            let journal = organizer::move_planned_files(&plan)?;
+            organizer::finish_organize(&conn.lock().unwrap(), &plan, journal)?;
+        }
+        Ok::<_, organizer::OrganizeError>(plan)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn import_nfo(
+    media_id: i64,
+    state: State<AppState>,
+) -> Result<bool, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    let mut media = db::get_media_file_by_id(&conn, media_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Media file not found".to_string())?;
+
+    // Locked fields keep the user's values
+    let media_path = std::path::PathBuf::from(&media.file_path);
+    if !indexer::nfo::apply_sidecar_nfo(&media_path, &mut media) {
+        return Ok(false);
+    }
+
+    db::upsert_media_file(&conn, &media).map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+#[tauri::command]
+fn export_nfo(
+    media_id: i64,
+    overwrite: bool,
+    state: State<AppState>,
+) -> Result<Option<String>, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    let media = db::get_media_file_by_id(&conn, media_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Media file not found".to_string())?;
+
+    write_nfo_sidecar(&media, overwrite)
+}
+
+#[tauri::command]
+fn export_all_nfo(
+    overwrite: bool,
+    state: State<AppState>,
+) -> Result<NfoExportResult, String> {
+    let db = state.db.lock().unwrap();
+    let conn = db.connection();
+    let conn = conn.lock().unwrap();
+
+    let media_files = db::get_all_media_files(&conn).map_err(|e| e.to_string())?;
+
+    let mut result = NfoExportResult { written: 0, skipped: 0, errors: 0 };
+    for media in media_files {
+        if matches!(media.media_type, db::MediaType::Music | db::MediaType::Audio) {
+            continue;
+        }
+        match write_nfo_sidecar(&media, overwrite) {
+            Ok(Some(_)) => result.written += 1,
+            Ok(None) => result.skipped += 1,
+            Err(e) => {
+                eprintln!("Error writing NFO for {}: {}", media.file_path, e);
+                result.errors += 1;
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Write `<stem>.nfo` next to a media file; returns None if one exists and overwrite is off
+fn write_nfo_sidecar(media: &db::MediaFile, overwrite: bool) -> Result<Option<String>, String> {
+    let nfo_path = indexer::nfo::nfo_path_for(&media.file_path)
+        .ok_or_else(|| "Invalid media path".to_string())?;
+
+    if nfo_path.exists() && !overwrite {
+        return Ok(None);
+    }
+
+    indexer::nfo::NfoMetadata::from_media(media)
+        .write_to_file(&nfo_path)
+        .map_err(|e| e.to_string())?;
+
+    Ok(Some(nfo_path.to_string_lossy().to_string()))
+}
+
 #[tauri::command]
 async fn extract_all_metadata(
     state: State<'_, AppState>,
@@ -913,6 +1754,13 @@ struct BatchMetadataResult {
     errors: usize,
 }
 
+#[derive(serde::Serialize)]
+struct NfoExportResult {
+    written: usize,
+    skipped: usize,
+    errors: usize,
+}
+
 #[derive(serde::Serialize, Clone)]
 struct MetadataProgress {
     current: usize,
@@ -975,6 +1823,7 @@ fn main() {
             get_all_media,
             get_media_by_type,
             filter_media,
+            get_media_facets,
             search_media,
             update_playback_position,
             mark_as_completed,
@@ -985,18 +1834,61 @@ fn main() {
             get_watch_history_chart,
             get_media_type_distribution,
             update_media_metadata,
+            edit_media_metadata,
+            bulk_edit_metadata,
+            get_metadata_overrides,
+            set_metadata_field_lock,
+            revert_metadata_field,
+            undo_metadata_edit,
+            get_metadata_edits,
+            organize_files,
+            import_nfo,
+            export_nfo,
+            export_all_nfo,
             extract_metadata,
             extract_all_metadata,
             add_subtitle_track,
             get_subtitle_tracks,
             remove_subtitle_track,
             scan_subtitles,
+            get_subtitle_cues,
+            get_subtitle_webvtt,
+            extract_embedded_subtitle,
+            get_subtitle_timing,
+            set_subtitle_timing,
+            resync_subtitle_track,
+            save_adjusted_subtitle_track,
             get_audio_tracks,
             create_collection,
             get_all_collections,
             get_collection_media,
             add_to_collection,
             remove_from_collection,
+            reorder_collection_item,
+            move_collection_items,
+            sync_auto_collections,
+            get_recommendations,
+            list_people,
+            get_person,
+            get_media_by_person,
+            match_library,
+            get_match_candidates,
+            confirm_match,
+            reject_match_candidate,
+            sync_tmdb,
+            link_episodes,
+            get_episode_info,
+            set_episode_links,
+            get_episode_report,
+            export_tmdb_bundle,
+            import_tmdb_bundle,
+            search_metadata,
+            preview_metadata,
+            refresh_metadata,
+            get_metadata_provenance,
+            get_metadata_images,
+            get_metadata_config,
+            set_metadata_config,
             update_collection,
             delete_collection,
             create_playlist,
@@ -1004,10 +1896,27 @@ fn main() {
             get_playlist_media,
             add_to_playlist,
             remove_from_playlist,
+            reorder_playlist_item,
+            move_playlist_items,
             update_playlist,
             delete_playlist,
             add_playlist_rule,
             get_playlist_rules,
+            get_smart_playlist_settings,
+            set_smart_playlist_settings,
+            reshuffle_smart_playlist,
+            export_playlist,
+            import_playlist,
+            get_play_queue,
+            start_play_queue,
+            enqueue_media,
+            queue_next,
+            queue_previous,
+            jump_to_queue_item,
+            remove_from_queue,
+            clear_play_queue,
+            set_queue_shuffle,
+            set_queue_repeat_mode,
             delete_playlist_rule,
             check_dependencies,
             generate_thumbnail,
@@ -0,0 +1,516 @@
+//! Metadata providers and the chain that combines them.
+//!
+//! Each provider (TMDB, NFO sidecars, the user's override file) offers
+//! search, details and images for a file. Details are a flat map of field
+//! name to JSON value; for every field the chain takes the first provider in
+//! that field's precedence order that has a value, writes it to the file and
+//! records the provider in `metadata_provenance`. Fields the user locked
+//! keep their values (see `db::metadata_overrides`).
+
+pub mod nfo;
+pub mod overrides;
+pub mod tmdb;
+
+pub use nfo::NfoProvider;
+pub use overrides::OverrideProvider;
+pub use tmdb::TmdbProvider;
+
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::db::metadata_overrides::{apply_metadata_overrides, get_metadata_overrides, set_media_fields};
+use crate::db::models::{MediaFile, MediaType};
+use crate::db::operations::{MEDIA_COLUMNS, media_file_from_row};
+
+/// Settings key holding the JSON `MetadataConfig`
+const CONFIG_SETTING: &str = "metadata_providers";
+
+/// Every field providers may fill; title, year, season and episode are
+/// media_files columns, the rest metadata_json keys
+pub const FIELDS: &[&str] = &[
+    "title",
+    "year",
+    "season",
+    "episode",
+    "original_title",
+    "show_title",
+    "premiered",
+    "overview",
+    "tagline",
+    "runtime",
+    "rating",
+    "genres",
+    "directors",
+    "writers",
+    "cast",
+    "imdb_id",
+    "tmdb_id",
+    "poster_path",
+    "backdrop_path",
+];
+
+/// Field name to value, as a provider returns them
+pub type FieldValues = BTreeMap<String, Value>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MetadataError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("TMDB error: {0}")]
+    Tmdb(#[from] crate::tmdb::TmdbError),
+    #[error("Invalid override file: {0}")]
+    Overrides(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Invalid input: {0}")]
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchQuery {
+    pub title: String,
+    pub year: Option<i32>,
+    pub media_type: MediaType,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub provider: String,
+    /// The provider's id for the title
+    pub id: String,
+    pub title: Option<String>,
+    pub year: Option<i32>,
+    pub overview: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderImage {
+    pub provider: String,
+    /// "poster", "backdrop", "still" or "logo"
+    pub kind: String,
+    /// A TMDB path, URL or local file path
+    pub path: String,
+    pub language: Option<String>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+}
+
+/// A source of metadata for library files
+pub trait MetadataProvider {
+    /// Stable name used in precedence settings and provenance, e.g. "tmdb"
+    fn name(&self) -> &'static str;
+
+    /// Titles matching a query, best first
+    fn search(&self, conn: &Connection, query: &SearchQuery) -> Result<Vec<SearchResult>, MetadataError>;
+
+    /// The provider's values for a file; fields it doesn't know are left out
+    fn fetch_details(&self, conn: &Connection, media: &MediaFile) -> Result<FieldValues, MetadataError>;
+
+    fn fetch_images(&self, conn: &Connection, media: &MediaFile) -> Result<Vec<ProviderImage>, MetadataError>;
+}
+
+/// Which provider wins, by default and per field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetadataConfig {
+    /// Provider names, highest precedence first
+    pub precedence: Vec<String>,
+    /// Per-field orders replacing `precedence` for that field
+    #[serde(default)]
+    pub fields: BTreeMap<String, Vec<String>>,
+    /// The user's JSON or YAML override file
+    #[serde(default)]
+    pub overrides_path: Option<String>,
+}
+
+impl Default for MetadataConfig {
+    fn default() -> Self {
+        Self {
+            precedence: vec!["override".to_string(), "nfo".to_string(), "tmdb".to_string()],
+            fields: BTreeMap::new(),
+            overrides_path: None,
+        }
+    }
+}
+
+impl MetadataConfig {
+    /// Providers for a field, highest precedence first
+    pub fn order_for(&self, field: &str) -> &[String] {
+        self.fields.get(field).unwrap_or(&self.precedence)
+    }
+}
+
+pub fn get_metadata_config(conn: &Connection) -> Result<MetadataConfig, MetadataError> {
+    let json: Option<String> = conn
+        .query_row("SELECT value FROM settings WHERE key = ?1", params![CONFIG_SETTING], |row| row.get(0))
+        .optional()?;
+    Ok(json.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default())
+}
+
+pub fn set_metadata_config(conn: &Connection, config: &MetadataConfig) -> Result<(), MetadataError> {
+    if let Some(field) = config.fields.keys().find(|f| !FIELDS.contains(&f.as_str())) {
+        return Err(MetadataError::Invalid(format!("unknown metadata field '{}'", field)));
+    }
+    let json = serde_json::to_string(config).map_err(|e| MetadataError::Invalid(e.to_string()))?;
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        params![CONFIG_SETTING, json],
+    )?;
+    Ok(())
+}
+
+/// A field's chosen value and every provider's offer
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedField {
+    pub value: Value,
+    pub provider: String,
+    pub candidates: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ResolvedMetadata {
+    pub media_id: i64,
+    pub fields: BTreeMap<String, ResolvedField>,
+    /// Providers that failed, with the error
+    pub errors: BTreeMap<String, String>,
+    /// Fields the user locked, which keep their values
+    pub locked: Vec<String>,
+}
+
+/// Where a stored value came from; "user" for locked fields
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldProvenance {
+    pub field: String,
+    pub provider: String,
+    pub value: Value,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MetadataRefreshReport {
+    pub updated: usize,
+    /// Locked fields, left as the user edited them
+    pub locked: usize,
+    /// Files a provider failed for, with the errors
+    pub errors: Vec<String>,
+}
+
+/// Providers in the order they're consulted
+pub struct ProviderChain {
+    providers: Vec<Box<dyn MetadataProvider>>,
+    config: MetadataConfig,
+}
+
+impl ProviderChain {
+    pub fn new(config: MetadataConfig) -> Self {
+        Self { providers: Vec::new(), config }
+    }
+
+    pub fn with_provider(mut self, provider: impl MetadataProvider + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    /// The configured chain: the override file (when set), NFO sidecars and
+    /// TMDB, online when TMDB is enabled and from the cache otherwise
+    pub fn from_settings(conn: &Connection) -> Result<Self, MetadataError> {
+        let config = get_metadata_config(conn)?;
+        let mut chain = Self::new(config.clone());
+        if let Some(path) = config.overrides_path.as_deref().filter(|p| !p.trim().is_empty()) {
+            chain = chain.with_provider(OverrideProvider::load(path)?);
+        }
+        let client = crate::tmdb::TmdbClient::from_settings(conn, crate::tmdb::UreqTransport::default()).ok();
+        Ok(chain.with_provider(NfoProvider).with_provider(TmdbProvider::new(client)))
+    }
+
+    /// Search every provider; a failing provider is skipped
+    pub fn search(&self, conn: &Connection, query: &SearchQuery) -> Vec<SearchResult> {
+        self.providers
+            .iter()
+            .filter_map(|p| p.search(conn, query).ok())
+            .flatten()
+            .collect()
+    }
+
+    /// Images from every provider, in precedence order
+    pub fn fetch_images(&self, conn: &Connection, media: &MediaFile) -> Vec<ProviderImage> {
+        let mut images: Vec<ProviderImage> = self
+            .providers
+            .iter()
+            .filter_map(|p| p.fetch_images(conn, media).ok())
+            .flatten()
+            .collect();
+        images.sort_by_key(|image| self.rank(&image.provider, "poster_path"));
+        images
+    }
+
+    fn rank(&self, provider: &str, field: &str) -> usize {
+        let order = self.config.order_for(field);
+        order.iter().position(|p| p == provider).unwrap_or(order.len())
+    }
+
+    /// Each provider's details for a file, and the winning value per field
+    pub fn resolve(&self, conn: &Connection, media: &MediaFile) -> ResolvedMetadata {
+        let mut resolved = ResolvedMetadata { media_id: media.id.unwrap_or_default(), ..Default::default() };
+        let mut offers: Vec<(&str, FieldValues)> = Vec::new();
+        for provider in &self.providers {
+            match provider.fetch_details(conn, media) {
+                Ok(values) => offers.push((provider.name(), values)),
+                Err(e) => {
+                    resolved.errors.insert(provider.name().to_string(), e.to_string());
+                }
+            }
+        }
+
+        for field in FIELDS {
+            let candidates: BTreeMap<String, Value> = offers
+                .iter()
+                .filter_map(|(name, values)| values.get(*field).filter(|v| has_value(v)).map(|v| (name.to_string(), v.clone())))
+                .collect();
+            // Providers missing from the order come last
+            let Some((provider, value)) = candidates.iter().min_by_key(|(name, _)| self.rank(name, field)) else {
+                continue;
+            };
+            // An order listing the field's providers explicitly excludes the rest
+            if self.config.fields.contains_key(*field) && !self.config.order_for(field).contains(provider) {
+                continue;
+            }
+            resolved.fields.insert(
+                field.to_string(),
+                ResolvedField { value: value.clone(), provider: provider.clone(), candidates: candidates.clone() },
+            );
+        }
+        resolved
+    }
+}
+
+fn has_value(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::String(s) => !s.trim().is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+        _ => true,
+    }
+}
+
+fn load_media(conn: &Connection, media_id: i64) -> Result<MediaFile, MetadataError> {
+    conn.query_row(
+        &format!("SELECT {} FROM media_files m WHERE m.id = ?1", MEDIA_COLUMNS),
+        params![media_id],
+        media_file_from_row,
+    )
+    .optional()?
+    .ok_or_else(|| MetadataError::NotFound(format!("media {}", media_id)))
+}
+
+fn locked_fields(conn: &Connection, media_id: i64) -> Result<Vec<String>, MetadataError> {
+    Ok(get_metadata_overrides(conn, media_id)?.into_iter().map(|o| o.field).collect())
+}
+
+/// What the chain would set for a file, without changing it
+pub fn preview_metadata(conn: &Connection, chain: &ProviderChain, media_id: i64) -> Result<ResolvedMetadata, MetadataError> {
+    let media = load_media(conn, media_id)?;
+    let mut resolved = chain.resolve(conn, &media);
+    resolved.locked = locked_fields(conn, media_id)?;
+    Ok(resolved)
+}
+
+/// Resolve a file's metadata through the chain, store it and record each
+/// field's provider. Locked fields keep the user's values; the chain's
+/// values become the ones they revert to.
+pub fn apply_metadata(conn: &Connection, chain: &ProviderChain, media_id: i64) -> Result<ResolvedMetadata, MetadataError> {
+    let media = load_media(conn, media_id)?;
+    let mut resolved = chain.resolve(conn, &media);
+    resolved.locked = locked_fields(conn, media_id)?;
+    let values: FieldValues = resolved.fields.iter().map(|(field, f)| (field.clone(), f.value.clone())).collect();
+
+    let tx = conn.unchecked_transaction()?;
+    set_media_fields(&tx, media_id, &values)?;
+    apply_metadata_overrides(&tx, media_id)?;
+    for (field, resolved_field) in resolved.fields.iter().filter(|(field, _)| !resolved.locked.contains(field)) {
+        tx.execute(
+            "INSERT INTO metadata_provenance (media_id, field, provider, value_json, updated_at)
+             VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP)
+             ON CONFLICT(media_id, field) DO UPDATE SET
+                provider = excluded.provider,
+                value_json = excluded.value_json,
+                updated_at = excluded.updated_at",
+            params![media_id, field, resolved_field.provider, resolved_field.value.to_string()],
+        )?;
+    }
+    tx.commit()?;
+
+    Ok(resolved)
+}
+
+/// Apply the chain to the given files, or to every file in the library
+pub fn refresh_metadata(
+    conn: &Connection,
+    chain: &ProviderChain,
+    media_ids: Option<&[i64]>,
+) -> Result<MetadataRefreshReport, MetadataError> {
+    let ids: Vec<i64> = match media_ids {
+        Some(ids) => ids.to_vec(),
+        None => {
+            let mut stmt = conn.prepare("SELECT id FROM media_files WHERE is_deleted = 0 ORDER BY id")?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    let mut report = MetadataRefreshReport::default();
+    for media_id in ids {
+        // One bad file doesn't stop the rest of the library
+        let resolved = match apply_metadata(conn, chain, media_id) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                report.errors.push(format!("media {}: {}", media_id, e));
+                continue;
+            }
+        };
+        report.updated += 1;
+        report.locked += resolved.locked.len();
+        report
+            .errors
+            .extend(resolved.errors.iter().map(|(provider, e)| format!("media {} ({}): {}", media_id, provider, e)));
+    }
+    Ok(report)
+}
+
+/// The provider behind each stored field of a file; locked fields are the
+/// user's
+pub fn get_metadata_provenance(conn: &Connection, media_id: i64) -> Result<Vec<FieldProvenance>, MetadataError> {
+    let mut stmt = conn.prepare(
+        "SELECT field, provider, value_json, updated_at FROM metadata_provenance WHERE media_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![media_id], |row| {
+        let value: String = row.get(2)?;
+        Ok(FieldProvenance {
+            field: row.get(0)?,
+            provider: row.get(1)?,
+            value: serde_json::from_str(&value).unwrap_or(Value::Null),
+            updated_at: row.get(3)?,
+        })
+    })?;
+    let mut provenance: BTreeMap<String, FieldProvenance> =
+        rows.map(|p| p.map(|p| (p.field.clone(), p))).collect::<Result<_, _>>()?;
+    for o in get_metadata_overrides(conn, media_id)? {
+        provenance.insert(
+            o.field.clone(),
+            FieldProvenance { field: o.field, provider: "user".to_string(), value: o.value, updated_at: o.updated_at },
+        );
+    }
+    Ok(provenance.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use serde_json::json;
+
+    /// Canned details under a given name
+    struct Fixed(&'static str, FieldValues);
+
+    impl MetadataProvider for Fixed {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn search(&self, _: &Connection, _: &SearchQuery) -> Result<Vec<SearchResult>, MetadataError> {
+            Ok(Vec::new())
+        }
+
+        fn fetch_details(&self, _: &Connection, _: &MediaFile) -> Result<FieldValues, MetadataError> {
+            Ok(self.1.clone())
+        }
+
+        fn fetch_images(&self, _: &Connection, _: &MediaFile) -> Result<Vec<ProviderImage>, MetadataError> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn values(pairs: &[(&str, Value)]) -> FieldValues {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    #[test]
+    fn test_chain_precedence_and_provenance() {
+        let conn = init_db().unwrap();
+        conn.execute(
+            "INSERT INTO media_files (file_path, file_hash, file_name, file_size, media_type, title, last_modified)
+             VALUES ('/m/alien.mkv', 'hash', 'alien.mkv', 1, 'movie', 'alien', datetime('now'))",
+            [],
+        )
+        .unwrap();
+
+        let mut config = MetadataConfig::default();
+        config.fields.insert("overview".to_string(), vec!["tmdb".to_string()]);
+        set_metadata_config(&conn, &config).unwrap();
+        let config = get_metadata_config(&conn).unwrap();
+
+        let chain = ProviderChain::new(config)
+            .with_provider(Fixed("tmdb", values(&[
+                ("title", json!("Alien")),
+                ("year", json!(1979)),
+                ("overview", json!("In space no one can hear you scream.")),
+                ("genres", json!(["Horror", "Science Fiction"])),
+            ])))
+            .with_provider(Fixed("nfo", values(&[
+                ("title", json!("Alien (Director's Cut)")),
+                ("overview", json!("From the NFO")),
+                ("genres", json!([])),
+            ])));
+
+        let resolved = apply_metadata(&conn, &chain, 1).unwrap();
+        // NFO outranks TMDB by default, but overview is TMDB's alone; empty values don't count
+        assert_eq!(resolved.fields["title"].provider, "nfo");
+        assert_eq!(resolved.fields["title"].candidates.len(), 2);
+        assert_eq!(resolved.fields["overview"].value, json!("In space no one can hear you scream."));
+        assert_eq!(resolved.fields["genres"].provider, "tmdb");
+
+        let media = load_media(&conn, 1).unwrap();
+        assert_eq!((media.title.as_deref(), media.year), (Some("Alien (Director's Cut)"), Some(1979)));
+        let stored: Value = serde_json::from_str(media.metadata_json.as_deref().unwrap()).unwrap();
+        assert_eq!(stored["genres"], json!(["Horror", "Science Fiction"]));
+
+        let provenance = get_metadata_provenance(&conn, 1).unwrap();
+        let sources: Vec<(&str, &str)> = provenance.iter().map(|p| (p.field.as_str(), p.provider.as_str())).collect();
+        assert_eq!(sources, vec![("genres", "tmdb"), ("overview", "tmdb"), ("title", "nfo"), ("year", "tmdb")]);
+
+        assert!(matches!(
+            set_metadata_config(&conn, &MetadataConfig { fields: [("mood".to_string(), vec![])].into(), ..MetadataConfig::default() }),
+            Err(MetadataError::Invalid(_))
+        ));
+
+        // A locked field keeps the user's value; the chain's becomes its scanned value
+        crate::db::edit_media_metadata(&conn, 1, &values(&[("title", json!("Alien: Special Edition"))])).unwrap();
+        let resolved = apply_metadata(&conn, &chain, 1).unwrap();
+        assert_eq!(resolved.locked, vec!["title"]);
+        assert_eq!(load_media(&conn, 1).unwrap().title.as_deref(), Some("Alien: Special Edition"));
+        let title = get_metadata_provenance(&conn, 1).unwrap().into_iter().find(|p| p.field == "title").unwrap();
+        assert_eq!(title.provider, "user");
+        assert_eq!(get_metadata_overrides(&conn, 1).unwrap()[0].scanned, json!("Alien (Director's Cut)"));
+
+        // A rescan's filename title and empty metadata don't replace what the chain stored
+        let mut rescanned = load_media(&conn, 1).unwrap();
+        rescanned.title = Some("alien".to_string());
+        rescanned.year = None;
+        rescanned.metadata_json = None;
+        crate::db::upsert_media_file(&conn, &rescanned).unwrap();
+        let media = load_media(&conn, 1).unwrap();
+        assert_eq!((media.title.as_deref(), media.year), (Some("Alien: Special Edition"), Some(1979)));
+        let stored: Value = serde_json::from_str(media.metadata_json.as_deref().unwrap()).unwrap();
+        assert_eq!(stored["overview"], json!("In space no one can hear you scream."));
+
+        // A file that fails is reported and the rest are still refreshed
+        let report = refresh_metadata(&conn, &chain, Some(&[404, 1])).unwrap();
+        assert_eq!(report.updated, 1);
+        assert!(report.errors[0].starts_with("media 404: "));
+    }
+}
@@ -0,0 +1,184 @@
+//! Kodi/Jellyfin NFO sidecars and the artwork saved next to them.
+
+use rusqlite::Connection;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use super::{FieldValues, MetadataError, MetadataProvider, ProviderImage, SearchQuery, SearchResult};
+use crate::db::models::{MediaFile, MediaType};
+use crate::indexer::nfo::{find_nfo, find_tvshow_nfo, NfoKind, NfoMetadata};
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+pub struct NfoProvider;
+
+impl NfoProvider {
+    fn insert(values: &mut FieldValues, field: &str, value: impl Into<Value>) {
+        values.insert(field.to_string(), value.into());
+    }
+
+    /// The NFO's values; a tvshow.nfo only contributes the show title and genres
+    fn fields(nfo: &NfoMetadata, values: &mut FieldValues) {
+        if nfo.kind == NfoKind::TvShow {
+            if let Some(title) = &nfo.title {
+                Self::insert(values, "show_title", title.clone());
+            }
+            if !nfo.genres.is_empty() {
+                Self::insert(values, "genres", nfo.genres.clone());
+            }
+            return;
+        }
+
+        let strings = [
+            ("title", &nfo.title),
+            ("original_title", &nfo.original_title),
+            ("show_title", &nfo.show_title),
+            ("premiered", &nfo.premiered),
+            ("overview", &nfo.plot),
+            ("tagline", &nfo.tagline),
+            ("imdb_id", &nfo.imdb_id),
+        ];
+        for (field, value) in strings {
+            if let Some(value) = value {
+                Self::insert(values, field, value.clone());
+            }
+        }
+        let numbers = [("year", nfo.year), ("season", nfo.season), ("episode", nfo.episode), ("runtime", nfo.runtime)];
+        for (field, value) in numbers {
+            if let Some(value) = value {
+                Self::insert(values, field, value);
+            }
+        }
+        if let Some(tmdb_id) = nfo.tmdb_id {
+            Self::insert(values, "tmdb_id", tmdb_id);
+        }
+        if let Some(rating) = nfo.ratings.first() {
+            Self::insert(values, "rating", rating.value);
+        }
+        let lists = [("genres", &nfo.genres), ("directors", &nfo.directors), ("writers", &nfo.writers)];
+        for (field, list) in lists {
+            if !list.is_empty() {
+                Self::insert(values, field, list.clone());
+            }
+        }
+        if !nfo.cast.is_empty() {
+            Self::insert(values, "cast", serde_json::to_value(&nfo.cast).unwrap_or_default());
+        }
+    }
+}
+
+/// The first existing `<name>.<ext>` in `dir`
+fn find_image(dir: &Path, names: &[String]) -> Option<PathBuf> {
+    names
+        .iter()
+        .flat_map(|name| IMAGE_EXTENSIONS.iter().map(move |ext| dir.join(format!("{}.{}", name, ext))))
+        .find(|path| path.is_file())
+}
+
+impl MetadataProvider for NfoProvider {
+    fn name(&self) -> &'static str {
+        "nfo"
+    }
+
+    /// NFO files describe one file each; there's nothing to search
+    fn search(&self, _conn: &Connection, _query: &SearchQuery) -> Result<Vec<SearchResult>, MetadataError> {
+        Ok(Vec::new())
+    }
+
+    fn fetch_details(&self, _conn: &Connection, media: &MediaFile) -> Result<FieldValues, MetadataError> {
+        let path = Path::new(&media.file_path);
+        let mut values = FieldValues::new();
+
+        // Show values first, so the episode's own NFO can override them
+        if matches!(media.media_type, MediaType::TvEpisode) {
+            if let Some(show) = find_tvshow_nfo(path).and_then(|p| NfoMetadata::read_from_file(p).ok()) {
+                Self::fields(&show, &mut values);
+            }
+        }
+        if let Some(nfo_path) = find_nfo(path) {
+            let nfo = NfoMetadata::read_from_file(&nfo_path)
+                .map_err(|e| MetadataError::Invalid(format!("{}: {}", nfo_path.display(), e)))?;
+            Self::fields(&nfo, &mut values);
+        }
+        Ok(values)
+    }
+
+    /// Artwork named the Kodi way: `<stem>-poster.jpg`, `poster.jpg`,
+    /// `folder.jpg`, `fanart.jpg` and, for episodes, `<stem>-thumb.jpg`
+    fn fetch_images(&self, _conn: &Connection, media: &MediaFile) -> Result<Vec<ProviderImage>, MetadataError> {
+        let path = Path::new(&media.file_path);
+        let (Some(dir), Some(stem)) = (path.parent(), path.file_stem().map(|s| s.to_string_lossy().to_string())) else {
+            return Ok(Vec::new());
+        };
+        let is_episode = matches!(media.media_type, MediaType::TvEpisode);
+        // Episodes share the show's artwork, a folder up from "Season 1"
+        let show_dir = if is_episode { dir.parent().filter(|_| find_tvshow_nfo(path).is_some()) } else { None };
+
+        let mut wanted = vec![
+            ("poster", vec![format!("{}-poster", stem), "poster".to_string(), "folder".to_string()]),
+            ("backdrop", vec![format!("{}-fanart", stem), "fanart".to_string(), "backdrop".to_string()]),
+        ];
+        if is_episode {
+            wanted.push(("still", vec![format!("{}-thumb", stem)]));
+        }
+
+        let images = wanted
+            .into_iter()
+            .filter_map(|(kind, names)| {
+                let found = find_image(dir, &names).or_else(|| show_dir.and_then(|show| find_image(show, &names)))?;
+                Some(ProviderImage {
+                    provider: self.name().to_string(),
+                    kind: kind.to_string(),
+                    path: found.to_string_lossy().to_string(),
+                    language: None,
+                    width: None,
+                    height: None,
+                })
+            })
+            .collect();
+        Ok(images)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn test_nfo_details_and_artwork() {
+        let dir = tempfile::tempdir().unwrap();
+        let show = dir.path().join("Firefly");
+        let season = show.join("Season 1");
+        fs::create_dir_all(&season).unwrap();
+        fs::write(show.join("tvshow.nfo"), "<tvshow><title>Firefly</title><genre>Western</genre></tvshow>").unwrap();
+        fs::write(
+            season.join("Firefly.S01E01.nfo"),
+            "<episodedetails><title>Serenity</title><season>1</season><episode>1</episode><plot>Pilot.</plot></episodedetails>",
+        )
+        .unwrap();
+        fs::write(show.join("poster.jpg"), b"jpg").unwrap();
+        fs::write(season.join("Firefly.S01E01-thumb.png"), b"png").unwrap();
+
+        let conn = init_db().unwrap();
+        conn.execute(
+            "INSERT INTO media_files (file_path, file_hash, file_name, file_size, media_type, last_modified)
+             VALUES (?1, 'hash', 'Firefly.S01E01.mkv', 1, 'tv_episode', datetime('now'))",
+            [season.join("Firefly.S01E01.mkv").to_string_lossy().to_string()],
+        )
+        .unwrap();
+        let media = super::super::load_media(&conn, conn.last_insert_rowid()).unwrap();
+        let values = NfoProvider.fetch_details(&conn, &media).unwrap();
+        assert_eq!(values["title"], json!("Serenity"));
+        assert_eq!(values["show_title"], json!("Firefly"));
+        assert_eq!(values["genres"], json!(["Western"]));
+        assert_eq!(values["overview"], json!("Pilot."));
+
+        let images = NfoProvider.fetch_images(&conn, &media).unwrap();
+        let kinds: Vec<&str> = images.iter().map(|i| i.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["poster", "still"]);
+        assert!(images[0].path.ends_with("poster.jpg"));
+    }
+}
@@ -0,0 +1,173 @@
+//! The user's hand-edited override file, JSON or YAML.
+//!
+//! Top-level keys name what an entry applies to: a file path, a directory
+//! (every file below it) or a bare file name. Values are field maps:
+//!
+//! ```yaml
+//! "/media/Movies/Alien (1979)":
+//!   genres: [Horror, Science Fiction]
+//! "Alien.Directors.Cut.mkv":
+//!   title: Alien (Director's Cut)
+//! ```
+//!
+//! When several entries apply, directories come first (outermost to
+//! innermost), then the file name, then the full path, each overriding the
+//! fields of the one before.
+
+use rusqlite::Connection;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{FieldValues, MetadataError, MetadataProvider, ProviderImage, SearchQuery, SearchResult, FIELDS};
+use crate::db::models::MediaFile;
+
+pub struct OverrideProvider {
+    entries: BTreeMap<String, FieldValues>,
+}
+
+impl OverrideProvider {
+    /// Read an override file; a file that doesn't exist yet has no overrides
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, MetadataError> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self { entries: BTreeMap::new() });
+        }
+        let content = fs::read_to_string(path)?;
+        let is_yaml = path
+            .extension()
+            .map(|ext| matches!(ext.to_string_lossy().to_lowercase().as_str(), "yaml" | "yml"))
+            .unwrap_or(false);
+        Self::parse(&content, is_yaml)
+    }
+
+    pub fn parse(content: &str, is_yaml: bool) -> Result<Self, MetadataError> {
+        let entries: BTreeMap<String, FieldValues> = if content.trim().is_empty() {
+            BTreeMap::new()
+        } else if is_yaml {
+            serde_yaml::from_str(content).map_err(|e| MetadataError::Overrides(e.to_string()))?
+        } else {
+            serde_json::from_str(content).map_err(|e| MetadataError::Overrides(e.to_string()))?
+        };
+
+        for (key, fields) in &entries {
+            if let Some(field) = fields.keys().find(|f| !FIELDS.contains(&f.as_str())) {
+                return Err(MetadataError::Overrides(format!("unknown field '{}' in '{}'", field, key)));
+            }
+        }
+        Ok(Self { entries })
+    }
+
+    /// The merged fields of every entry that applies to a file
+    fn values_for(&self, media: &MediaFile) -> FieldValues {
+        let path = PathBuf::from(media.file_path.replace('\\', "/"));
+
+        let mut directories: Vec<&String> = self
+            .entries
+            .keys()
+            .filter(|key| {
+                let dir = Path::new(key.trim_end_matches('/'));
+                key.contains('/') && path != dir && path.starts_with(dir)
+            })
+            .collect();
+        directories.sort_by_key(|key| key.trim_end_matches('/').len());
+
+        let mut values = FieldValues::new();
+        let keys = directories
+            .into_iter()
+            .chain(self.entries.keys().filter(|key| **key == media.file_name))
+            .chain(self.entries.keys().filter(|key| Path::new(key) == path));
+        for key in keys {
+            values.extend(self.entries[key].clone());
+        }
+        values
+    }
+}
+
+impl MetadataProvider for OverrideProvider {
+    fn name(&self) -> &'static str {
+        "override"
+    }
+
+    /// Entries whose title matches the query
+    fn search(&self, _conn: &Connection, query: &SearchQuery) -> Result<Vec<SearchResult>, MetadataError> {
+        let wanted = query.title.trim().to_lowercase();
+        let results = self
+            .entries
+            .iter()
+            .filter_map(|(key, fields)| {
+                let title = fields.get("title")?.as_str()?;
+                let year = fields.get("year").and_then(|v| v.as_i64()).map(|y| y as i32);
+                let matches = title.to_lowercase().contains(&wanted) && (query.year.is_none() || year.is_none() || year == query.year);
+                matches.then(|| SearchResult {
+                    provider: self.name().to_string(),
+                    id: key.clone(),
+                    title: Some(title.to_string()),
+                    year,
+                    overview: fields.get("overview").and_then(|v| v.as_str()).map(String::from),
+                })
+            })
+            .collect();
+        Ok(results)
+    }
+
+    fn fetch_details(&self, _conn: &Connection, media: &MediaFile) -> Result<FieldValues, MetadataError> {
+        Ok(self.values_for(media))
+    }
+
+    fn fetch_images(&self, _conn: &Connection, media: &MediaFile) -> Result<Vec<ProviderImage>, MetadataError> {
+        let values = self.values_for(media);
+        let images = [("poster", "poster_path"), ("backdrop", "backdrop_path")]
+            .into_iter()
+            .filter_map(|(kind, field)| {
+                Some(ProviderImage {
+                    provider: self.name().to_string(),
+                    kind: kind.to_string(),
+                    path: values.get(field)?.as_str()?.to_string(),
+                    language: None,
+                    width: None,
+                    height: None,
+                })
+            })
+            .collect();
+        Ok(images)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use serde_json::json;
+
+    #[test]
+    fn test_override_entries_layer() {
+        let yaml = r#"
+"/media/Movies":
+  genres: [Film]
+"/media/Movies/Alien (1979)/":
+  genres: [Horror, Science Fiction]
+  title: Alien
+"Alien.Directors.Cut.mkv":
+  title: "Alien (Director's Cut)"
+  poster_path: /art/alien.jpg
+"#;
+        let provider = OverrideProvider::parse(yaml, true).unwrap();
+        let conn = init_db().unwrap();
+        conn.execute(
+            "INSERT INTO media_files (file_path, file_hash, file_name, file_size, media_type, last_modified)
+             VALUES ('/media/Movies/Alien (1979)/Alien.Directors.Cut.mkv', 'hash', 'Alien.Directors.Cut.mkv', 1, 'movie', datetime('now'))",
+            [],
+        )
+        .unwrap();
+        let media = super::super::load_media(&conn, 1).unwrap();
+
+        let values = provider.fetch_details(&conn, &media).unwrap();
+        assert_eq!(values["title"], json!("Alien (Director's Cut)"));
+        assert_eq!(values["genres"], json!(["Horror", "Science Fiction"]));
+        assert_eq!(provider.fetch_images(&conn, &media).unwrap()[0].path, "/art/alien.jpg");
+
+        let json = r#"{"/media/Movies/Alien.mkv": {"mood": "tense"}}"#;
+        assert!(matches!(OverrideProvider::parse(json, false), Err(MetadataError::Overrides(_))));
+    }
+}
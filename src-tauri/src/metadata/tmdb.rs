@@ -0,0 +1,270 @@
+//! TMDB as a metadata provider. Details and images always come from the local
+//! cache (`tmdb_metadata` and friends); the client, when TMDB is enabled, is
+//! only used to search.
+
+use rusqlite::{Connection, OptionalExtension, params};
+use serde_json::{Value, json};
+
+use super::{FieldValues, MetadataError, MetadataProvider, ProviderImage, SearchQuery, SearchResult};
+use crate::db::models::{MediaFile, MediaType};
+use crate::tmdb::{TmdbClient, Transport, UreqTransport, get_episode_info};
+
+pub struct TmdbProvider<T: Transport = UreqTransport> {
+    client: Option<TmdbClient<T>>,
+}
+
+impl<T: Transport> TmdbProvider<T> {
+    /// Without a client the provider works offline from the cache
+    pub fn new(client: Option<TmdbClient<T>>) -> Self {
+        Self { client }
+    }
+}
+
+/// The cached TMDB title a file is matched to
+fn matched_title(conn: &Connection, media_id: i64) -> Result<Option<(i64, String)>, MetadataError> {
+    Ok(conn
+        .query_row(
+            "SELECT tmdb_id, media_type FROM tmdb_media WHERE media_id = ?1",
+            params![media_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?)
+}
+
+fn year_of(date: Option<&str>) -> Option<i32> {
+    date.and_then(|d| d.get(..4)).and_then(|y| y.parse().ok())
+}
+
+impl<T: Transport> MetadataProvider for TmdbProvider<T> {
+    fn name(&self) -> &'static str {
+        "tmdb"
+    }
+
+    /// Searches TMDB when online, then lists the cached titles matching the
+    /// query, best known first
+    fn search(&self, conn: &Connection, query: &SearchQuery) -> Result<Vec<SearchResult>, MetadataError> {
+        let media_type = if matches!(query.media_type, MediaType::TvEpisode) { "tv" } else { "movie" };
+        if let Some(client) = &self.client {
+            client.search(conn, media_type, &query.title, query.year)?;
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT tmdb_id, title, release_date, overview FROM tmdb_metadata
+             WHERE media_type = ?1 AND (title LIKE '%' || ?2 || '%' OR original_title LIKE '%' || ?2 || '%')
+             ORDER BY COALESCE(popularity, 0) DESC, tmdb_id
+             LIMIT 20",
+        )?;
+        let rows = stmt.query_map(params![media_type, query.title.trim()], |row| {
+            let release_date: Option<String> = row.get(2)?;
+            Ok(SearchResult {
+                provider: self.name().to_string(),
+                id: row.get::<_, i64>(0)?.to_string(),
+                title: row.get(1)?,
+                year: year_of(release_date.as_deref()),
+                overview: row.get(3)?,
+            })
+        })?;
+        let results = rows.collect::<Result<Vec<_>, _>>()?;
+        Ok(results
+            .into_iter()
+            .filter(|r| query.year.is_none() || r.year.is_none() || r.year == query.year)
+            .collect())
+    }
+
+    fn fetch_details(&self, conn: &Connection, media: &MediaFile) -> Result<FieldValues, MetadataError> {
+        let mut values = FieldValues::new();
+        let Some((tmdb_id, media_type)) = matched_title(conn, media.id.unwrap_or_default())? else {
+            return Ok(values);
+        };
+        let Some(row) = conn
+            .query_row(
+                "SELECT title, original_title, overview, tagline, release_date, runtime, vote_average, imdb_id
+                 FROM tmdb_metadata WHERE tmdb_id = ?1 AND media_type = ?2",
+                params![tmdb_id, media_type],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<String>>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, Option<String>>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                        row.get::<_, Option<i64>>(5)?,
+                        row.get::<_, Option<f64>>(6)?,
+                        row.get::<_, Option<String>>(7)?,
+                    ))
+                },
+            )
+            .optional()?
+        else {
+            return Ok(values);
+        };
+        let (title, original_title, overview, tagline, release_date, runtime, rating, imdb_id) = row;
+
+        let mut set = |field: &str, value: Value| {
+            if !value.is_null() {
+                values.insert(field.to_string(), value);
+            }
+        };
+        set("tmdb_id", json!(tmdb_id));
+        set("original_title", json!(original_title));
+        set("tagline", json!(tagline));
+        set("rating", json!(rating));
+        set("imdb_id", json!(imdb_id));
+
+        if media_type == "tv" {
+            // The show supplies show_title; the linked episode supplies the rest
+            let episodes = get_episode_info(conn, media.id.unwrap_or_default())?;
+            set("show_title", json!(title));
+            if let Some(episode) = episodes.first() {
+                let names: Vec<&str> = episodes.iter().filter_map(|e| e.name.as_deref()).collect();
+                set("title", if names.is_empty() { Value::Null } else { json!(names.join(" / ")) });
+                set("season", json!(episode.season_number));
+                set("episode", json!(episode.episode_number));
+                set("overview", json!(episode.overview));
+                set("premiered", json!(episode.air_date));
+                set("year", json!(year_of(episode.air_date.as_deref())));
+                set("runtime", json!(episode.runtime));
+            }
+        } else {
+            set("title", json!(title));
+            set("overview", json!(overview));
+            set("premiered", json!(release_date));
+            set("year", json!(year_of(release_date.as_deref())));
+            set("runtime", json!(runtime));
+        }
+
+        let mut stmt = conn.prepare("SELECT name FROM tmdb_genres WHERE tmdb_id = ?1 AND media_type = ?2 ORDER BY name")?;
+        let genres = stmt
+            .query_map(params![tmdb_id, media_type], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        if !genres.is_empty() {
+            values.insert("genres".to_string(), json!(genres));
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT name, character, role, order_position, profile_path FROM tmdb_cast
+             WHERE tmdb_media_id = ?1 AND media_type = ?2
+             ORDER BY COALESCE(order_position, 0), id",
+        )?;
+        let mut rows = stmt.query(params![tmdb_id, media_type])?;
+        let (mut directors, mut writers, mut cast) = (Vec::new(), Vec::new(), Vec::new());
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(0)?;
+            match row.get::<_, String>(2)?.as_str() {
+                "director" => directors.push(json!(name)),
+                "writer" => writers.push(json!(name)),
+                "cast" => cast.push(json!({
+                    "name": name,
+                    "role": row.get::<_, Option<String>>(1)?,
+                    "order": row.get::<_, Option<i64>>(3)?,
+                    "thumb": row.get::<_, Option<String>>(4)?,
+                })),
+                _ => {}
+            }
+        }
+        for (field, list) in [("directors", directors), ("writers", writers), ("cast", cast)] {
+            if !list.is_empty() {
+                values.insert(field.to_string(), Value::Array(list));
+            }
+        }
+
+        for (field, image_type) in [("poster_path", "poster"), ("backdrop_path", "backdrop")] {
+            let path: Option<String> = conn
+                .query_row(
+                    "SELECT COALESCE(local_path, file_path) FROM tmdb_images
+                     WHERE tmdb_media_id = ?1 AND media_type = ?2 AND image_type = ?3
+                     ORDER BY is_primary DESC, COALESCE(vote_average, 0) DESC, id
+                     LIMIT 1",
+                    params![tmdb_id, media_type, image_type],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .flatten();
+            if let Some(path) = path {
+                values.insert(field.to_string(), json!(path));
+            }
+        }
+        Ok(values)
+    }
+
+    /// Cached images of the matched title, primary first, and the still of
+    /// a linked episode
+    fn fetch_images(&self, conn: &Connection, media: &MediaFile) -> Result<Vec<ProviderImage>, MetadataError> {
+        let media_id = media.id.unwrap_or_default();
+        let Some((tmdb_id, media_type)) = matched_title(conn, media_id)? else {
+            return Ok(Vec::new());
+        };
+
+        let mut images: Vec<ProviderImage> = get_episode_info(conn, media_id)?
+            .into_iter()
+            .filter_map(|episode| episode.still_path)
+            .map(|path| ProviderImage {
+                provider: self.name().to_string(),
+                kind: "still".to_string(),
+                path,
+                language: None,
+                width: None,
+                height: None,
+            })
+            .collect();
+
+        let mut stmt = conn.prepare(
+            "SELECT image_type, COALESCE(local_path, file_path), language, width, height FROM tmdb_images
+             WHERE tmdb_media_id = ?1 AND media_type = ?2 AND image_type != 'profile'
+               AND COALESCE(local_path, file_path) IS NOT NULL
+             ORDER BY image_type, is_primary DESC, COALESCE(vote_average, 0) DESC, id",
+        )?;
+        let rows = stmt.query_map(params![tmdb_id, media_type], |row| {
+            Ok(ProviderImage {
+                provider: self.name().to_string(),
+                kind: row.get(0)?,
+                path: row.get(1)?,
+                language: row.get(2)?,
+                width: row.get(3)?,
+                height: row.get(4)?,
+            })
+        })?;
+        images.extend(rows.collect::<Result<Vec<_>, _>>()?);
+        Ok(images)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+
+    #[test]
+    fn test_cached_movie_details() {
+        let conn = init_db().unwrap();
+        conn.execute_batch(
+            "INSERT INTO media_files (file_path, file_hash, file_name, file_size, media_type, last_modified)
+                VALUES ('/m/alien.mkv', 'hash', 'alien.mkv', 1, 'movie', datetime('now'));
+             INSERT INTO tmdb_metadata (tmdb_id, media_type, title, overview, release_date, runtime, vote_average, genres_json)
+                VALUES (348, 'movie', 'Alien', 'In space no one can hear you scream.', '1979-05-25', 117, 8.1, '[\"Horror\"]');
+             INSERT INTO tmdb_media (media_id, tmdb_id, media_type) VALUES (1, 348, 'movie');
+             INSERT INTO tmdb_cast (tmdb_media_id, media_type, tmdb_person_id, name, character, role, order_position)
+                VALUES (348, 'movie', 10205, 'Sigourney Weaver', 'Ripley', 'cast', 0),
+                       (348, 'movie', 578, 'Ridley Scott', NULL, 'director', NULL);
+             INSERT INTO tmdb_images (tmdb_media_id, media_type, image_type, file_path, is_primary)
+                VALUES (348, 'movie', 'poster', '/other.jpg', 0), (348, 'movie', 'poster', '/alien.jpg', 1);",
+        )
+        .unwrap();
+        let media = super::super::load_media(&conn, 1).unwrap();
+        let provider: TmdbProvider = TmdbProvider::new(None);
+
+        let values = provider.fetch_details(&conn, &media).unwrap();
+        assert_eq!(values["title"], json!("Alien"));
+        assert_eq!(values["year"], json!(1979));
+        assert_eq!(values["genres"], json!(["Horror"]));
+        assert_eq!(values["directors"], json!(["Ridley Scott"]));
+        assert_eq!(values["cast"][0]["role"], json!("Ripley"));
+        assert_eq!(values["poster_path"], json!("/alien.jpg"));
+
+        let query = SearchQuery { title: "alien".to_string(), year: Some(1979), media_type: MediaType::Movie };
+        let results = provider.search(&conn, &query).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, "348");
+        assert_eq!(provider.fetch_images(&conn, &media).unwrap()[0].path, "/alien.jpg");
+    }
+}
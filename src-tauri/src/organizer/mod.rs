@@ -0,0 +1,681 @@
+//! Renaming and moving library files to a naming template.
+//!
+//! Templates are paths relative to a library root with `{field}`
+//! placeholders, optionally zero-padded as `{season:02}`:
+//!
+//! ```text
+//! {show}/Season {season:02}/{show} - S{season:02}E{episode:02} - {episode_title}.{ext}
+//! {title} ({year})/{title} ({year}).{ext}
+//! ```
+//!
+//! A plan lists every move without touching the disk. Applying it moves the
+//! files together with their sidecars (subtitles, NFOs, `<name>-poster.jpg`
+//! and, for a movie alone in its folder, the folder artwork), then updates
+//! the stored paths in one transaction. If a move or the update fails, the
+//! files already moved are put back.
+
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::db::models::{MediaFile, MediaType};
+use crate::db::operations::{MEDIA_COLUMNS, media_file_from_row};
+
+/// Placeholders a template may use
+const TEMPLATE_FIELDS: &[&str] = &[
+    "title",
+    "year",
+    "show",
+    "season",
+    "episode",
+    "episode_title",
+    "original_title",
+    "resolution",
+    "ext",
+];
+
+/// Files next to a video that belong to it when named after it
+const SIDECAR_EXTENSIONS: &[&str] = &[
+    "srt", "ass", "ssa", "vtt", "sub", "idx", "sup", "nfo", "jpg", "jpeg", "png", "webp", "tbn",
+];
+
+/// Folder artwork that moves with a movie that has its folder to itself
+const FOLDER_ART: &[&str] = &["poster", "folder", "fanart", "backdrop", "banner", "logo", "clearlogo", "clearart", "disc", "landscape", "thumb"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum OrganizeError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("TMDB error: {0}")]
+    Tmdb(#[from] crate::tmdb::TmdbError),
+    #[error("Invalid template: {0}")]
+    Template(String),
+    /// The disk no longer matches the plan
+    #[error("Plan is out of date: {0}")]
+    Stale(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Field { name: String, width: Option<usize> },
+}
+
+/// A parsed naming template
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamingTemplate {
+    segments: Vec<Segment>,
+}
+
+impl NamingTemplate {
+    pub fn parse(template: &str) -> Result<Self, OrganizeError> {
+        let template = template.trim().replace('\\', "/");
+        if template.starts_with('/') || template.split('/').any(|part| part.is_empty() || part == "." || part == "..") {
+            return Err(OrganizeError::Template("must be a relative path without empty, '.' or '..' parts".to_string()));
+        }
+
+        let mut segments = Vec::new();
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find(['{', '}']) {
+            if rest[start..].starts_with('}') {
+                return Err(OrganizeError::Template("unmatched '}'".to_string()));
+            }
+            let end = rest[start..].find('}').map(|i| start + i).ok_or_else(|| OrganizeError::Template("unclosed '{'".to_string()))?;
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let spec = &rest[start + 1..end];
+            let (name, width) = match spec.split_once(':') {
+                Some((name, width)) => {
+                    let width = width
+                        .parse()
+                        .map_err(|_| OrganizeError::Template(format!("invalid width in '{{{}}}'", spec)))?;
+                    (name, Some(width))
+                }
+                None => (spec, None),
+            };
+            if !TEMPLATE_FIELDS.contains(&name) {
+                return Err(OrganizeError::Template(format!("unknown field '{{{}}}'", name)));
+            }
+            segments.push(Segment::Field { name: name.to_string(), width });
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+
+        if !segments.iter().any(|s| matches!(s, Segment::Field { name, .. } if name == "ext")) {
+            return Err(OrganizeError::Template("must keep the extension with {ext}".to_string()));
+        }
+        Ok(Self { segments })
+    }
+
+    /// The relative path for a file's values, or the first missing field
+    fn render(&self, values: &HashMap<&str, String>) -> Result<PathBuf, String> {
+        let mut path = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => path.push_str(text),
+                Segment::Field { name, width } => {
+                    let value = values.get(name.as_str()).filter(|v| !v.is_empty()).ok_or_else(|| name.clone())?;
+                    match width {
+                        Some(width) => path.push_str(&format!("{:0>width$}", value, width = *width)),
+                        None => path.push_str(value),
+                    }
+                }
+            }
+        }
+        // Values can't be empty, but a part made of separators only can be
+        let parts: Vec<String> = path.split('/').map(|part| part.trim().trim_end_matches('.').trim().to_string()).collect();
+        if parts.iter().any(String::is_empty) {
+            return Err("a path part is empty".to_string());
+        }
+        Ok(parts.iter().collect())
+    }
+}
+
+/// A value made safe for a file name: no path separators or characters
+/// Windows forbids, and "Title: Subtitle" becomes "Title - Subtitle"
+fn sanitize(value: &str) -> String {
+    let replaced: String = value
+        .replace(": ", " - ")
+        .chars()
+        .filter_map(|c| match c {
+            ':' | '/' | '\\' | '|' => Some('-'),
+            '<' | '>' | '"' | '?' | '*' => None,
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect();
+    replaced.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OrganizeRequest {
+    /// Template for movies; movies are left alone without one
+    pub movie_template: Option<String>,
+    /// Template for TV episodes; episodes are left alone without one
+    pub episode_template: Option<String>,
+    /// Destination root; by default the library path each file is under
+    pub root: Option<String>,
+    /// Only these files; by default every file in the library
+    pub media_ids: Option<Vec<i64>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileMove {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedMove {
+    pub media_id: i64,
+    pub from: String,
+    pub to: String,
+    /// Subtitles, NFOs and artwork moving along
+    pub sidecars: Vec<FileMove>,
+    /// The library root the target is under; emptied folders below it are removed
+    pub root: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkippedFile {
+    pub media_id: i64,
+    pub file_path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OrganizePlan {
+    pub moves: Vec<PlannedMove>,
+    /// Files already where their template puts them
+    pub unchanged: usize,
+    pub skipped: Vec<SkippedFile>,
+}
+
+fn library_paths(conn: &Connection) -> Result<Vec<PathBuf>, OrganizeError> {
+    let json: Option<String> = conn
+        .query_row("SELECT value FROM settings WHERE key = 'library_paths'", [], |row| row.get(0))
+        .optional()?;
+    let paths: Vec<String> = json.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default();
+    Ok(paths.into_iter().map(PathBuf::from).collect())
+}
+
+/// Template values for a file; missing ones are left out
+fn template_values(conn: &Connection, media: &MediaFile) -> Result<HashMap<&'static str, String>, OrganizeError> {
+    let metadata: serde_json::Value = media
+        .metadata_json
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default();
+    let text = |key: &str| metadata.get(key).and_then(|v| v.as_str()).map(String::from);
+    let path = Path::new(&media.file_path);
+
+    let mut values = HashMap::new();
+    let mut insert = |name: &'static str, value: Option<String>| {
+        if let Some(value) = value.map(|v| sanitize(&v)).filter(|v| !v.is_empty()) {
+            values.insert(name, value);
+        }
+    };
+    insert("ext", path.extension().map(|e| e.to_string_lossy().to_string()));
+    insert("year", media.year.map(|y| y.to_string()));
+    insert("season", media.season_number.map(|s| s.to_string()));
+    insert("episode", media.episode_number.map(|e| e.to_string()));
+    insert("original_title", text("original_title"));
+    insert("resolution", media.resolution.clone());
+
+    if matches!(media.media_type, MediaType::TvEpisode) {
+        let show = crate::tmdb::show_identity(&media.file_path, media.metadata_json.as_deref()).map(|(show, _)| show);
+        // The linked TMDB episodes name it best; otherwise the title, unless
+        // it's just the show name parsed from the file name
+        let linked: Vec<String> = crate::tmdb::get_episode_info(conn, media.id.unwrap_or_default())?
+            .into_iter()
+            .filter_map(|e| e.name)
+            .collect();
+        let episode_title = if linked.is_empty() {
+            media
+                .title
+                .clone()
+                .filter(|title| show.as_deref().map(|show| !title.eq_ignore_ascii_case(show)).unwrap_or(true))
+        } else {
+            Some(linked.join(" / "))
+        };
+        insert("title", media.title.clone());
+        insert("episode_title", episode_title);
+        insert("show", show);
+    } else {
+        let title = media.title.clone().or_else(|| Some(crate::indexer::metadata::parse_filename(&media.file_name).0));
+        insert("title", title);
+    }
+    Ok(values)
+}
+
+/// Sidecars of a video: files in its folder named `<stem>.<...>` or
+/// `<stem>-<...>` with a sidecar extension, with the suffix after the stem
+fn find_sidecars(video: &Path) -> Vec<(PathBuf, String)> {
+    let (Some(dir), Some(stem)) = (video.parent(), video.file_stem().map(|s| s.to_string_lossy().to_string())) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut sidecars: Vec<(PathBuf, String)> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && path != video)
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().to_string();
+            let suffix = name.strip_prefix(&stem)?;
+            let extension = path.extension()?.to_string_lossy().to_lowercase();
+            (suffix.starts_with(['.', '-']) && SIDECAR_EXTENSIONS.contains(&extension.as_str())).then(|| (path, suffix.to_string()))
+        })
+        .collect();
+    sidecars.sort();
+    sidecars
+}
+
+fn folder_art(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut art: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            let stem = path.file_stem().map(|s| s.to_string_lossy().to_lowercase()).unwrap_or_default();
+            let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+            path.is_file() && FOLDER_ART.contains(&stem.as_str()) && ["jpg", "jpeg", "png", "webp"].contains(&extension.as_str())
+        })
+        .collect();
+    art.sort();
+    art
+}
+
+/// Whether two paths name the same file on disk, as `a.mkv` and `A.mkv` do
+/// on a case-insensitive file system. Compares file identity rather than
+/// names, so on Linux those are two files.
+fn same_file(a: &Path, b: &Path) -> bool {
+    same_file::is_same_file(a, b).unwrap_or(false)
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+/// Work out where each file goes, without touching the disk
+pub fn plan_organize(conn: &Connection, request: &OrganizeRequest) -> Result<OrganizePlan, OrganizeError> {
+    let movie_template = request.movie_template.as_deref().map(NamingTemplate::parse).transpose()?;
+    let episode_template = request.episode_template.as_deref().map(NamingTemplate::parse).transpose()?;
+    let roots = match &request.root {
+        Some(root) => vec![PathBuf::from(root)],
+        None => library_paths(conn)?,
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM media_files m WHERE m.is_deleted = 0 ORDER BY m.file_path",
+        MEDIA_COLUMNS
+    ))?;
+    let library = stmt.query_map([], media_file_from_row)?.collect::<Result<Vec<_>, _>>()?;
+    let known_paths: HashMap<String, i64> = library.iter().map(|m| (m.file_path.clone(), m.id.unwrap_or_default())).collect();
+    let mut per_dir: HashMap<PathBuf, usize> = HashMap::new();
+    for media in &library {
+        if let Some(dir) = Path::new(&media.file_path).parent() {
+            *per_dir.entry(dir.to_path_buf()).or_default() += 1;
+        }
+    }
+    let wanted: Option<HashSet<i64>> = request.media_ids.as_ref().map(|ids| ids.iter().copied().collect());
+
+    let mut plan = OrganizePlan::default();
+    let mut claimed: BTreeMap<String, i64> = BTreeMap::new();
+    for media in &library {
+        let media_id = media.id.unwrap_or_default();
+        if wanted.as_ref().is_some_and(|ids| !ids.contains(&media_id)) {
+            continue;
+        }
+        let template = match media.media_type {
+            MediaType::Movie => movie_template.as_ref(),
+            MediaType::TvEpisode => episode_template.as_ref(),
+            _ => None,
+        };
+        let Some(template) = template else { continue };
+        let mut skip = |reason: String| {
+            plan.skipped.push(SkippedFile { media_id, file_path: media.file_path.clone(), reason });
+        };
+
+        let source = PathBuf::from(&media.file_path);
+        let Some(root) = roots.iter().find(|root| request.root.is_some() || source.starts_with(root)) else {
+            skip("not under a library path".to_string());
+            continue;
+        };
+        let relative = match template.render(&template_values(conn, media)?) {
+            Ok(relative) => relative,
+            Err(field) => {
+                skip(format!("no value for {{{}}}", field));
+                continue;
+            }
+        };
+        let target = root.join(relative);
+        if target == source {
+            plan.unchanged += 1;
+            continue;
+        }
+
+        let target_dir = target.parent().unwrap_or(root).to_path_buf();
+        let target_stem = target.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let mut sidecars: Vec<FileMove> = find_sidecars(&source)
+            .into_iter()
+            .map(|(path, suffix)| FileMove { from: path_string(&path), to: path_string(&target_dir.join(format!("{}{}", target_stem, suffix))) })
+            .collect();
+        let source_dir = source.parent().unwrap_or(Path::new("")).to_path_buf();
+        if matches!(media.media_type, MediaType::Movie) && per_dir.get(&source_dir) == Some(&1) && source_dir != target_dir {
+            sidecars.extend(folder_art(&source_dir).into_iter().map(|path| FileMove {
+                to: path_string(&target_dir.join(path.file_name().unwrap_or_default())),
+                from: path_string(&path),
+            }));
+        }
+
+        let targets = std::iter::once((source.clone(), target.clone()))
+            .chain(sidecars.iter().map(|m| (PathBuf::from(&m.from), PathBuf::from(&m.to))));
+        let mut conflict = None;
+        for (from, to) in targets {
+            let to_string = path_string(&to);
+            if let Some(other) = claimed.get(&to_string) {
+                conflict = Some(format!("{} is also the target of media {}", to_string, other));
+            } else if to.exists() && !same_file(&from, &to) {
+                conflict = Some(format!("{} already exists", to_string));
+            } else if known_paths.get(&to_string).is_some_and(|id| *id != media_id) {
+                conflict = Some(format!("{} is already in the library", to_string));
+            }
+            if conflict.is_some() {
+                break;
+            }
+        }
+        if let Some(reason) = conflict {
+            skip(reason);
+            continue;
+        }
+
+        claimed.insert(path_string(&target), media_id);
+        claimed.extend(sidecars.iter().map(|m| (m.to.clone(), media_id)));
+        plan.moves.push(PlannedMove {
+            media_id,
+            from: media.file_path.clone(),
+            to: path_string(&target),
+            sidecars,
+            root: path_string(root),
+        });
+    }
+    Ok(plan)
+}
+
+/// Files moved by `move_planned_files`, to put back if the library can't be
+/// updated to match
+#[derive(Default)]
+pub struct Journal {
+    moved: Vec<(PathBuf, PathBuf)>,
+    created_dirs: Vec<PathBuf>,
+}
+
+impl Journal {
+    fn move_file(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        if let Some(parent) = to.parent() {
+            let missing: Vec<PathBuf> = parent.ancestors().take_while(|dir| !dir.exists()).map(Path::to_path_buf).collect();
+            fs::create_dir_all(parent)?;
+            self.created_dirs.extend(missing.into_iter().rev());
+        }
+        rename(from, to)?;
+        self.moved.push((from.to_path_buf(), to.to_path_buf()));
+        Ok(())
+    }
+
+    /// Put everything back, newest first; best effort
+    fn rollback(self) {
+        for (from, to) in self.moved.iter().rev() {
+            let _ = rename(to, from);
+        }
+        for dir in self.created_dirs.iter().rev() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+}
+
+/// Rename, falling back to copy and delete across file systems
+fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    if let Err(e) = fs::remove_file(from) {
+        let _ = fs::remove_file(to);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Move the files of a plan without touching the library, so the database
+/// needn't be held while large files are copied across drives. On failure
+/// nothing is moved.
+pub fn move_planned_files(plan: &OrganizePlan) -> Result<Journal, OrganizeError> {
+    // Check the whole plan before moving anything
+    for planned in &plan.moves {
+        let moves = std::iter::once((planned.from.as_str(), planned.to.as_str()))
+            .chain(planned.sidecars.iter().map(|m| (m.from.as_str(), m.to.as_str())));
+        for (from, to) in moves {
+            if !Path::new(from).exists() {
+                return Err(OrganizeError::Stale(format!("{} no longer exists", from)));
+            }
+            if Path::new(to).exists() && !same_file(Path::new(from), Path::new(to)) {
+                return Err(OrganizeError::Stale(format!("{} already exists", to)));
+            }
+        }
+    }
+
+    let mut journal = Journal::default();
+    for planned in &plan.moves {
+        let moves = std::iter::once((planned.from.as_str(), planned.to.as_str()))
+            .chain(planned.sidecars.iter().map(|m| (m.from.as_str(), m.to.as_str())));
+        for (from, to) in moves {
+            if let Err(e) = journal.move_file(Path::new(from), Path::new(to)) {
+                journal.rollback();
+                return Err(e.into());
+            }
+        }
+    }
+    Ok(journal)
+}
+
+/// Update the library to the files `move_planned_files` moved, returning the
+/// number of media files moved; if that fails the files are put back
+pub fn finish_organize(conn: &Connection, plan: &OrganizePlan, journal: Journal) -> Result<usize, OrganizeError> {
+    if let Err(e) = update_paths(conn, plan) {
+        journal.rollback();
+        return Err(e.into());
+    }
+
+    // Remove folders the moves emptied, up to the library root
+    for planned in &plan.moves {
+        let root = Path::new(&planned.root);
+        for dir in Path::new(&planned.from).ancestors().skip(1) {
+            if !dir.starts_with(root) || dir == root || fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(plan.moves.len())
+}
+
+fn update_paths(conn: &Connection, plan: &OrganizePlan) -> Result<(), rusqlite::Error> {
+    let tx = conn.unchecked_transaction()?;
+    for planned in &plan.moves {
+        let file_name = Path::new(&planned.to).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        tx.execute(
+            "UPDATE media_files SET file_path = ?2, file_name = ?3 WHERE id = ?1",
+            params![planned.media_id, planned.to, file_name],
+        )?;
+        for sidecar in &planned.sidecars {
+            for table in ["subtitle_tracks", "audio_tracks"] {
+                tx.execute(&format!("UPDATE {} SET file_path = ?2 WHERE file_path = ?1", table), params![sidecar.from, sidecar.to])?;
+            }
+            for key in ["$.poster_path", "$.backdrop_path"] {
+                tx.execute(
+                    "UPDATE media_files SET metadata_json = json_set(metadata_json, ?3, ?2)
+                     WHERE id = ?4 AND json_valid(metadata_json) AND json_extract(metadata_json, ?3) = ?1",
+                    params![sidecar.from, sidecar.to, key, planned.media_id],
+                )?;
+            }
+        }
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use crate::db::test_support::{self, TestMedia};
+
+    const MOVIES: &str = "{title} ({year})/{title} ({year}).{ext}";
+    const EPISODES: &str = "{show}/Season {season:02}/{show} - S{season:02}E{episode:02} - {episode_title}.{ext}";
+
+    fn add(conn: &Connection, path: &Path, media_type: &str, title: &str, year: Option<i32>, metadata: Option<&str>) -> i64 {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"video").unwrap();
+        let path = path_string(path);
+        let media = TestMedia {
+            path: &path,
+            media_type,
+            title: Some(title),
+            year,
+            season: Some(1),
+            episode: Some(2),
+            metadata_json: metadata,
+            ..TestMedia::default()
+        };
+        test_support::add_media(conn, &media)
+    }
+
+    fn apply_organize_plan(conn: &Connection, plan: &OrganizePlan) -> Result<usize, OrganizeError> {
+        let journal = move_planned_files(plan)?;
+        finish_organize(conn, plan, journal)
+    }
+
+    #[test]
+    fn test_template_parsing_and_rendering() {
+        let template = NamingTemplate::parse(EPISODES).unwrap();
+        let values = HashMap::from([
+            ("show", sanitize("Star Wars: Andor")),
+            ("season", "1".to_string()),
+            ("episode", "2".to_string()),
+            ("episode_title", sanitize("That Would Be Me?")),
+            ("ext", "mkv".to_string()),
+        ]);
+        assert_eq!(
+            template.render(&values).unwrap(),
+            PathBuf::from("Star Wars - Andor/Season 01/Star Wars - Andor - S01E02 - That Would Be Me.mkv")
+        );
+        let mut missing = values.clone();
+        missing.remove("episode_title");
+        assert_eq!(template.render(&missing), Err("episode_title".to_string()));
+
+        for bad in ["{title}.mkv", "../{title}.{ext}", "{rating}.{ext}", "{title.{ext}", "{season:x}.{ext}", "/abs/{title}.{ext}"] {
+            assert!(matches!(NamingTemplate::parse(bad), Err(OrganizeError::Template(_))), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_plan_and_apply_moves_sidecars() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let conn = init_db().unwrap();
+        let movie = root.join("incoming/alien.1979.mkv");
+        let alien = add(&conn, &movie, "movie", "Alien", Some(1979), None);
+        fs::write(root.join("incoming/alien.1979.en.srt"), b"1").unwrap();
+        fs::write(root.join("incoming/poster.jpg"), b"jpg").unwrap();
+        conn.execute(
+            "INSERT INTO subtitle_tracks (media_id, file_path, language) VALUES (?1, ?2, 'en')",
+            params![alien, path_string(&root.join("incoming/alien.1979.en.srt"))],
+        )
+        .unwrap();
+        let episode = root.join("tv/firefly/firefly.s01e02.mkv");
+        add(&conn, &episode, "tv_episode", "The Train Job", None, Some(r#"{"show_title": "Firefly"}"#));
+        // Nothing to call the file
+        add(&conn, &root.join("tv/firefly/firefly.s01e03.mkv"), "tv_episode", "firefly", None, None);
+
+        let request = OrganizeRequest {
+            movie_template: Some(MOVIES.to_string()),
+            episode_template: Some(EPISODES.to_string()),
+            root: Some(path_string(root)),
+            media_ids: None,
+        };
+        let plan = plan_organize(&conn, &request).unwrap();
+        assert_eq!(plan.moves.len(), 2);
+        assert_eq!(plan.skipped.len(), 1);
+        assert_eq!(plan.skipped[0].reason, "no value for {episode_title}");
+        assert!(movie.exists());
+
+        let alien_move = plan.moves.iter().find(|m| m.media_id == alien).unwrap();
+        let target_dir = root.join("Alien (1979)");
+        assert_eq!(alien_move.to, path_string(&target_dir.join("Alien (1979).mkv")));
+        let sidecars: Vec<&str> = alien_move.sidecars.iter().map(|m| m.to.as_str()).collect();
+        assert_eq!(sidecars, vec![path_string(&target_dir.join("Alien (1979).en.srt")), path_string(&target_dir.join("poster.jpg"))]);
+
+        assert_eq!(apply_organize_plan(&conn, &plan).unwrap(), 2);
+        assert!(target_dir.join("Alien (1979).mkv").exists());
+        assert!(target_dir.join("poster.jpg").exists());
+        assert!(root.join("Firefly/Season 01/Firefly - S01E02 - The Train Job.mkv").exists());
+        assert!(!root.join("incoming").exists());
+        let (path, name): (String, String) =
+            conn.query_row("SELECT file_path, file_name FROM media_files WHERE id = ?1", [alien], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        assert_eq!((path, name.as_str()), (path_string(&target_dir.join("Alien (1979).mkv")), "Alien (1979).mkv"));
+        let subtitle: String = conn.query_row("SELECT file_path FROM subtitle_tracks", [], |row| row.get(0)).unwrap();
+        assert!(subtitle.ends_with("Alien (1979).en.srt"));
+
+        // Everything is in place now
+        let plan = plan_organize(&conn, &request).unwrap();
+        assert_eq!((plan.moves.len(), plan.unchanged), (0, 2));
+    }
+
+    #[test]
+    fn test_failed_move_rolls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let conn = init_db().unwrap();
+        let first = root.join("a/alien.mkv");
+        let second = root.join("b/heat.mkv");
+        add(&conn, &first, "movie", "Alien", Some(1979), None);
+        add(&conn, &second, "movie", "Heat", Some(1995), None);
+        let request = OrganizeRequest { movie_template: Some(MOVIES.to_string()), root: Some(path_string(root)), ..Default::default() };
+        let plan = plan_organize(&conn, &request).unwrap();
+        assert_eq!(plan.moves.len(), 2);
+
+        // A file where Heat's folder should go makes its move fail
+        fs::write(root.join("Heat (1995)"), b"in the way").unwrap();
+        assert!(matches!(apply_organize_plan(&conn, &plan), Err(OrganizeError::Io(_))));
+        assert!(first.exists() && second.exists());
+        assert!(!root.join("Alien (1979)").exists());
+        let path: String = conn.query_row("SELECT file_path FROM media_files WHERE id = 1", [], |row| row.get(0)).unwrap();
+        assert_eq!(path, path_string(&first));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_names_differing_in_case_are_different_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let conn = init_db().unwrap();
+        let movie = root.join("Alien (1979)/alien (1979).mkv");
+        add(&conn, &movie, "movie", "Alien", Some(1979), None);
+        let other = root.join("Alien (1979)/Alien (1979).mkv");
+        fs::write(&other, b"another file").unwrap();
+
+        let request = OrganizeRequest { movie_template: Some(MOVIES.to_string()), root: Some(path_string(root)), ..Default::default() };
+        let plan = plan_organize(&conn, &request).unwrap();
+        assert!(plan.moves.is_empty());
+        assert_eq!(plan.skipped[0].reason, format!("{} already exists", path_string(&other)));
+        assert_eq!(fs::read(&other).unwrap(), b"another file");
+    }
+}
@@ -0,0 +1,60 @@
+//! Extended M3U (M3U8)
+
+use super::{PlaylistDocument, PlaylistEntry};
+
+pub fn parse(content: &str) -> PlaylistDocument {
+    let mut document = PlaylistDocument::default();
+    // #EXTINF info waiting for its location line
+    let mut pending: Option<(Option<i64>, Option<String>)> = None;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // #EXTINF:<seconds>[ key="value" ...],<title>
+            let (head, title) = info.split_once(',').unwrap_or((info, ""));
+            let duration = head
+                .split_whitespace()
+                .next()
+                .and_then(|d| d.parse::<f64>().ok())
+                .filter(|d| *d >= 0.0)
+                .map(|d| d.round() as i64);
+            let title = Some(title.trim().to_string()).filter(|t| !t.is_empty());
+            pending = Some((duration, title));
+        } else if let Some(title) = line.strip_prefix("#PLAYLIST:") {
+            document.title = Some(title.trim().to_string());
+        } else if line.starts_with('#') {
+            continue;
+        } else {
+            let (duration, title) = pending.take().unwrap_or((None, None));
+            document.entries.push(PlaylistEntry { location: line.to_string(), title, duration });
+        }
+    }
+
+    document
+}
+
+pub fn write(document: &PlaylistDocument) -> String {
+    let mut out = String::from("#EXTM3U\n");
+    if let Some(title) = &document.title {
+        out.push_str(&format!("#PLAYLIST:{}\n", single_line(title)));
+    }
+
+    for entry in &document.entries {
+        out.push_str(&format!(
+            "#EXTINF:{},{}\n{}\n",
+            entry.duration.unwrap_or(-1),
+            single_line(entry.title.as_deref().unwrap_or("")),
+            entry.location
+        ));
+    }
+
+    out
+}
+
+fn single_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
@@ -0,0 +1,592 @@
+//! Playlist files: export playlists as M3U8, PLS or XSPF and import them back,
+//! resolving entries against the library.
+
+mod m3u;
+mod pls;
+mod xspf;
+
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::db::{self, PlaylistType, RuleError};
+use crate::db::search::{edit_distance, fold_text};
+use crate::subtitles::encoding;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PlaylistIoError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("{0}")]
+    Rules(#[from] RuleError),
+    #[error("Parse error: {0}")]
+    Parse(String),
+    #[error("Unsupported playlist format: {0}")]
+    UnsupportedFormat(String),
+}
+
+/// Supported playlist file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistFormat {
+    M3u8,
+    Pls,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().trim_start_matches('.') {
+            "m3u8" | "m3u" => Some(PlaylistFormat::M3u8),
+            "pls" => Some(PlaylistFormat::Pls),
+            "xspf" => Some(PlaylistFormat::Xspf),
+            _ => None,
+        }
+    }
+
+    /// Detect the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension().and_then(|e| e.to_str()).and_then(Self::from_str)
+    }
+}
+
+/// One entry of a playlist file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistEntry {
+    /// Path or URI as written in the file
+    pub location: String,
+    pub title: Option<String>,
+    /// Seconds
+    pub duration: Option<i64>,
+}
+
+/// Parsed playlist file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistDocument {
+    pub title: Option<String>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+/// An entry that couldn't be matched to a library file
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UnresolvedEntry {
+    /// 1-based position in the playlist file
+    pub index: usize,
+    pub location: String,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ImportReport {
+    pub playlist_id: i64,
+    pub name: String,
+    pub imported: usize,
+    /// Entries matched by file name rather than exact path
+    pub fuzzy_matched: usize,
+    /// Entries pointing at a file already in the playlist
+    pub duplicates: usize,
+    pub unresolved: Vec<UnresolvedEntry>,
+}
+
+/// Parse playlist file content
+pub fn parse_playlist(content: &str, format: PlaylistFormat) -> Result<PlaylistDocument, PlaylistIoError> {
+    let content = content.trim_start_matches('\u{feff}');
+    match format {
+        PlaylistFormat::M3u8 => Ok(m3u::parse(content)),
+        PlaylistFormat::Pls => pls::parse(content),
+        PlaylistFormat::Xspf => xspf::parse(content),
+    }
+}
+
+/// Render a playlist file
+pub fn write_playlist(document: &PlaylistDocument, format: PlaylistFormat) -> String {
+    match format {
+        PlaylistFormat::M3u8 => m3u::write(document),
+        PlaylistFormat::Pls => pls::write(document),
+        PlaylistFormat::Xspf => xspf::write(document),
+    }
+}
+
+/// Export a playlist (smart playlists as a snapshot of their current items).
+///
+/// With `relative_paths`, locations are written relative to the output
+/// file's folder where possible. Returns the number of entries written.
+pub fn export_playlist(
+    conn: &Connection,
+    playlist_id: i64,
+    output_path: &Path,
+    format: Option<PlaylistFormat>,
+    relative_paths: bool,
+) -> Result<usize, PlaylistIoError> {
+    let format = match format {
+        Some(format) => format,
+        None => PlaylistFormat::from_path(output_path)
+            .ok_or_else(|| PlaylistIoError::UnsupportedFormat(output_path.display().to_string()))?,
+    };
+
+    let name: String = conn.query_row(
+        "SELECT name FROM playlists WHERE id = ?1",
+        params![playlist_id],
+        |row| row.get(0),
+    )?;
+    let items = db::get_playlist_media(conn, playlist_id)?;
+    let base_dir = output_path.parent().map(absolute_dir);
+
+    let entries = items
+        .into_iter()
+        .map(|item| {
+            let path = PathBuf::from(&item.file_path);
+            let relative = if relative_paths {
+                base_dir.as_deref().and_then(|base| relative_path(base, &path))
+            } else {
+                None
+            };
+            let location = match (relative, format) {
+                (Some(rel), PlaylistFormat::Xspf) => xspf::relative_uri(&rel),
+                (Some(rel), _) => rel.to_string_lossy().to_string(),
+                (None, PlaylistFormat::Xspf) => xspf::file_uri(&path),
+                (None, _) => item.file_path.clone(),
+            };
+
+            PlaylistEntry {
+                location,
+                title: item.title.or(Some(item.file_name)),
+                duration: item.duration,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let count = entries.len();
+    let document = PlaylistDocument { title: Some(name), entries };
+    fs::write(output_path, write_playlist(&document, format))?;
+
+    Ok(count)
+}
+
+/// Import a playlist file as a new manual playlist.
+///
+/// Entries are matched on exact path first, then by file name; entries that
+/// match nothing are listed in the report rather than failing the import.
+/// Files that aren't UTF-8 (M3U and PLS are often CP1252) are decoded from a
+/// guessed encoding. Any other failure leaves no playlist behind.
+pub fn import_playlist(
+    conn: &Connection,
+    input_path: &Path,
+    name: Option<&str>,
+) -> Result<ImportReport, PlaylistIoError> {
+    let format = PlaylistFormat::from_path(input_path)
+        .ok_or_else(|| PlaylistIoError::UnsupportedFormat(input_path.display().to_string()))?;
+    let bytes = fs::read(input_path)?;
+    let (content, _) = encoding::decode(&bytes);
+    let document = parse_playlist(&content, format)?;
+    let base_dir = input_path.parent().map(absolute_dir).unwrap_or_default();
+
+    let name = name
+        .map(String::from)
+        .or_else(|| document.title.clone().filter(|t| !t.trim().is_empty()))
+        .or_else(|| input_path.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "Imported Playlist".to_string());
+
+    let library = LibraryIndex::load(conn)?;
+    let tx = conn.unchecked_transaction()?;
+    let playlist_id = db::create_playlist(&tx, &name, None, PlaylistType::Manual)?;
+    let mut report = ImportReport {
+        playlist_id,
+        name,
+        imported: 0,
+        fuzzy_matched: 0,
+        duplicates: 0,
+        unresolved: Vec::new(),
+    };
+    let mut added = std::collections::HashSet::new();
+
+    for (i, entry) in document.entries.iter().enumerate() {
+        let resolved = match entry_path(&entry.location, format, &base_dir) {
+            Some(path) => library.resolve(&path),
+            None => None,
+        };
+
+        let Some((media_id, fuzzy)) = resolved else {
+            report.unresolved.push(UnresolvedEntry {
+                index: i + 1,
+                location: entry.location.clone(),
+                title: entry.title.clone(),
+            });
+            continue;
+        };
+
+        if !added.insert(media_id) {
+            report.duplicates += 1;
+            continue;
+        }
+
+        db::add_media_to_playlist(&tx, playlist_id, media_id)?;
+        report.imported += 1;
+        if fuzzy {
+            report.fuzzy_matched += 1;
+        }
+    }
+
+    tx.commit()?;
+    Ok(report)
+}
+
+/// Turn an entry location into an absolute filesystem path; None for non-file URLs
+fn entry_path(location: &str, format: PlaylistFormat, base_dir: &Path) -> Option<PathBuf> {
+    let location = location.trim();
+    if location.is_empty() {
+        return None;
+    }
+
+    let path = if let Some(rest) = location.strip_prefix("file://") {
+        let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+        let decoded = xspf::percent_decode(rest);
+        // file:///C:/Movies -> C:/Movies
+        match decoded.as_bytes() {
+            [b'/', _, b':', ..] => decoded[1..].to_string(),
+            _ => decoded,
+        }
+    } else if location.contains("://") {
+        return None;
+    } else if format == PlaylistFormat::Xspf {
+        xspf::percent_decode(location)
+    } else {
+        location.to_string()
+    };
+
+    // Playlists written on Windows use backslashes
+    let path = if cfg!(windows) { path } else { path.replace('\\', "/") };
+    let path = PathBuf::from(path);
+
+    Some(if path.is_absolute() { normalize(&path) } else { normalize(&base_dir.join(path)) })
+}
+
+/// The library's files, loaded once per import and looked up per entry
+struct LibraryIndex {
+    by_path: HashMap<String, i64>,
+    files: Vec<LibraryFile>,
+}
+
+struct LibraryFile {
+    id: i64,
+    path: String,
+    name: String,
+    stem: String,
+    numbers: Vec<u64>,
+}
+
+impl LibraryIndex {
+    fn load(conn: &Connection) -> Result<Self, PlaylistIoError> {
+        let mut stmt = conn.prepare("SELECT id, file_path, file_name FROM media_files WHERE is_deleted = 0")?;
+        let files = stmt
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?
+            .map(|row| row.map(|(id, path, name)| LibraryFile {
+                id,
+                stem: stem_key(&name),
+                numbers: name_numbers(&name),
+                path,
+                name,
+            }))
+            .collect::<Result<Vec<_>, _>>()?;
+        let by_path = files.iter().map(|file| (file.path.clone(), file.id)).collect();
+        Ok(LibraryIndex { by_path, files })
+    }
+
+    /// Match a path to a library file: exact path, then file name, then a close
+    /// file name stem with the same numbers, so `S01E01` never resolves to `S01E02`
+    /// nor "Movie 2" to "Movie 3"
+    fn resolve(&self, path: &Path) -> Option<(i64, bool)> {
+        if let Some(id) = self.by_path.get(path.to_string_lossy().as_ref()) {
+            return Some((*id, false));
+        }
+
+        let file_name = path.file_name()?.to_string_lossy().to_string();
+        let wanted_stem = stem_key(&file_name);
+        let wanted_numbers = name_numbers(&file_name);
+        let max = if wanted_stem.chars().count() >= 8 { 2 } else { 0 };
+
+        // Lower is better: (name distance, negative shared folder depth)
+        self.files
+            .iter()
+            .filter_map(|file| {
+                let distance = if file.name.eq_ignore_ascii_case(&file_name) {
+                    0
+                } else {
+                    if file.numbers != wanted_numbers {
+                        return None;
+                    }
+                    // +1 so any exact name match beats a stem match
+                    let d = edit_distance(&wanted_stem, &file.stem);
+                    if d > max {
+                        return None;
+                    }
+                    d + 1
+                };
+                let shared = shared_suffix(path, Path::new(&file.path));
+                Some(((distance, std::cmp::Reverse(shared)), file.id))
+            })
+            .min()
+            .map(|(_, id)| (id, true))
+    }
+}
+
+/// File name without extension, folded for comparison
+fn stem_key(file_name: &str) -> String {
+    let stem = Path::new(file_name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    fold_text(&stem).chars().filter(|c| c.is_alphanumeric()).collect()
+}
+
+/// The numbers in a file name without extension: episode, year, part
+fn name_numbers(file_name: &str) -> Vec<u64> {
+    let stem = Path::new(file_name).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    stem.split(|c: char| !c.is_ascii_digit()).filter_map(|run| run.parse().ok()).collect()
+}
+
+/// Number of trailing folder names two paths share (ignoring the file name)
+fn shared_suffix(a: &Path, b: &Path) -> usize {
+    let folders = |p: &Path| -> Vec<String> {
+        p.parent()
+            .map(|d| d.iter().map(|c| c.to_string_lossy().to_lowercase()).collect())
+            .unwrap_or_default()
+    };
+    let (a, b) = (folders(a), folders(b));
+    a.iter().rev().zip(b.iter().rev()).take_while(|(x, y)| x == y).count()
+}
+
+/// Relative path from `base_dir` to `target`; None if they share no root (e.g. different drives)
+fn relative_path(base_dir: &Path, target: &Path) -> Option<PathBuf> {
+    let base: Vec<Component> = base_dir.components().collect();
+    let target: Vec<Component> = target.components().collect();
+    if base.first() != target.first() {
+        return None;
+    }
+
+    let common = base.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for component in &target[common..] {
+        relative.push(component);
+    }
+    Some(relative)
+}
+
+fn absolute_dir(dir: &Path) -> PathBuf {
+    if dir.is_absolute() {
+        dir.to_path_buf()
+    } else {
+        std::env::current_dir().map(|cwd| cwd.join(dir)).unwrap_or_else(|_| dir.to_path_buf())
+    }
+}
+
+/// Resolve `.` and `..` without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use crate::db::test_support::{self, TestMedia};
+
+    fn add_media(conn: &Connection, path: &str, title: &str, duration: i64) -> i64 {
+        test_support::add_media(conn, &TestMedia { path, title: Some(title), duration: Some(duration), ..TestMedia::default() })
+    }
+
+    fn library(conn: &Connection) -> Vec<i64> {
+        vec![
+            add_media(conn, "/media/Movies/Alien (1979)/Alien.mkv", "Alien", 7020),
+            add_media(conn, "/media/Movies/Heat (1995)/Heat & Dust.mkv", "Heat & Dust", 10200),
+            add_media(conn, "/media/Movies/Amélie/Amélie 2001.mkv", "Amélie", 7320),
+        ]
+    }
+
+    #[test]
+    fn test_export_and_reimport_all_formats() {
+        let conn = init_db().unwrap();
+        let ids = library(&conn);
+        let playlist_id = db::create_playlist(&conn, "Round <Trip>", None, PlaylistType::Manual).unwrap();
+        for id in &ids {
+            db::add_media_to_playlist(&conn, playlist_id, *id).unwrap();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        for (ext, relative) in [("m3u8", true), ("m3u8", false), ("pls", true), ("xspf", true), ("xspf", false)] {
+            let path = dir.path().join(format!("out-{}.{}", relative, ext));
+            assert_eq!(export_playlist(&conn, playlist_id, &path, None, relative).unwrap(), 3);
+
+            let report = import_playlist(&conn, &path, None).unwrap();
+            // PLS has no playlist title, so the file name is used
+            let expected = if ext == "pls" { format!("out-{}", relative) } else { "Round <Trip>".to_string() };
+            assert_eq!(report.name, expected);
+            assert_eq!(report.imported, 3, "{} relative={}", ext, relative);
+            assert_eq!(report.fuzzy_matched, 0);
+            assert!(report.unresolved.is_empty());
+
+            let items = db::get_playlist_media(&conn, report.playlist_id).unwrap();
+            assert_eq!(items.iter().map(|i| i.id).collect::<Vec<_>>(), ids);
+        }
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let conn = init_db().unwrap();
+        let ids = library(&conn);
+        let playlist_id = db::create_playlist(&conn, "Mix", None, PlaylistType::Manual).unwrap();
+        db::add_media_to_playlist(&conn, playlist_id, ids[1]).unwrap();
+        db::add_media_to_playlist(&conn, playlist_id, ids[2]).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+
+        let m3u = dir.path().join("mix.m3u8");
+        export_playlist(&conn, playlist_id, &m3u, None, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&m3u).unwrap(),
+            "#EXTM3U\n#PLAYLIST:Mix\n#EXTINF:10200,Heat & Dust\n/media/Movies/Heat (1995)/Heat & Dust.mkv\n\
+             #EXTINF:7320,Amélie\n/media/Movies/Amélie/Amélie 2001.mkv\n"
+        );
+
+        let pls = dir.path().join("mix.pls");
+        export_playlist(&conn, playlist_id, &pls, None, false).unwrap();
+        let pls = fs::read_to_string(&pls).unwrap();
+        assert!(pls.starts_with("[playlist]\nFile1=/media/Movies/Heat (1995)/Heat & Dust.mkv\nTitle1=Heat & Dust\nLength1=10200\n"));
+        assert!(pls.ends_with("NumberOfEntries=2\nVersion=2\n"));
+
+        let xspf = dir.path().join("mix.xspf");
+        export_playlist(&conn, playlist_id, &xspf, None, false).unwrap();
+        let xspf = fs::read_to_string(&xspf).unwrap();
+        assert!(xspf.contains("<location>file:///media/Movies/Heat%20%281995%29/Heat%20%26%20Dust.mkv</location>"));
+        assert!(xspf.contains("<title>Heat &amp; Dust</title>"));
+        assert!(xspf.contains("<duration>10200000</duration>"));
+        assert!(xspf.contains("Am%C3%A9lie"));
+    }
+
+    #[test]
+    fn test_import_resolution_and_unresolved() {
+        let conn = init_db().unwrap();
+        let ids = library(&conn);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Old Library.m3u");
+
+        fs::write(
+            &path,
+            "#EXTM3U\r\n\
+             #EXTINF:7020,Alien\r\n\
+             D:\\Old\\Movies\\Alien (1979)\\Alien.avi\r\n\
+             #EXTINF:-1,Heat\r\n\
+             /somewhere/else/heat & dust.mkv\r\n\
+             /media/Movies/Alien (1979)/Alien.mkv\r\n\
+             http://example.com/stream.mp3\r\n\
+             #EXTINF:100,Missing\r\n\
+             /media/Movies/Missing.mkv\r\n",
+        )
+        .unwrap();
+
+        let report = import_playlist(&conn, &path, None).unwrap();
+        assert_eq!(report.name, "Old Library");
+        assert_eq!(report.imported, 2);
+        assert_eq!(report.fuzzy_matched, 2);
+        assert_eq!(report.duplicates, 1);
+        assert_eq!(report.unresolved.len(), 2);
+        assert_eq!(report.unresolved[0].index, 4);
+        assert_eq!(report.unresolved[1].title.as_deref(), Some("Missing"));
+
+        let items = db::get_playlist_media(&conn, report.playlist_id).unwrap();
+        assert_eq!(items.iter().map(|i| i.id).collect::<Vec<_>>(), vec![ids[0], ids[1]]);
+    }
+
+    #[test]
+    fn test_import_legacy_encoding() {
+        let conn = init_db().unwrap();
+        let ids = library(&conn);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Films.m3u");
+
+        let (cp1252, _, _) = encoding_rs::WINDOWS_1252.encode("#EXTINF:7320,Amélie\n/media/Movies/Amélie/Amélie 2001.mkv\n");
+        fs::write(&path, cp1252).unwrap();
+
+        let report = import_playlist(&conn, &path, None).unwrap();
+        assert_eq!((report.imported, report.fuzzy_matched), (1, 0));
+        let items = db::get_playlist_media(&conn, report.playlist_id).unwrap();
+        assert_eq!(items.iter().map(|i| i.id).collect::<Vec<_>>(), vec![ids[2]]);
+    }
+
+    #[test]
+    fn test_close_names_with_other_numbers_stay_unresolved() {
+        let conn = init_db().unwrap();
+        let e2 = add_media(&conn, "/media/TV/Show/Show.S01E02.mkv", "Show", 1500);
+        add_media(&conn, "/media/Movies/Movie 3.mkv", "Movie 3", 6000);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Shows.m3u");
+
+        fs::write(&path, "/old/Show.S01E01.mkv
+/old/Movie 2.mkv
+/old/Show_S1E2.mkv
+").unwrap();
+
+        let report = import_playlist(&conn, &path, None).unwrap();
+        assert_eq!(report.unresolved.iter().map(|e| e.index).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(report.fuzzy_matched, 1);
+        let items = db::get_playlist_media(&conn, report.playlist_id).unwrap();
+        assert_eq!(items.iter().map(|i| i.id).collect::<Vec<_>>(), vec![e2]);
+    }
+
+    #[test]
+    fn test_parse_pls_and_xspf() {
+        let pls = "[playlist]\nNumberOfEntries=2\nFile2=b.mkv\nTitle2=B\nFile1=a.mkv\nLength1=-1\nVersion=2\n";
+        let document = parse_playlist(pls, PlaylistFormat::Pls).unwrap();
+        assert_eq!(document.entries.len(), 2);
+        assert_eq!(document.entries[0], PlaylistEntry { location: "a.mkv".into(), title: None, duration: None });
+        assert_eq!(document.entries[1].title.as_deref(), Some("B"));
+
+        let xspf = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <playlist version="1" xmlns="http://xspf.org/ns/0/">
+              <title>Films</title>
+              <trackList>
+                <track><location>Heat%20%26%20Dust.mkv</location><duration>90400</duration></track>
+                <track><title>No location</title></track>
+              </trackList>
+            </playlist>"#;
+        let document = parse_playlist(xspf, PlaylistFormat::Xspf).unwrap();
+        assert_eq!(document.title.as_deref(), Some("Films"));
+        assert_eq!(document.entries.len(), 2);
+        assert_eq!(document.entries[0].duration, Some(90));
+        assert_eq!(
+            entry_path(&document.entries[0].location, PlaylistFormat::Xspf, Path::new("/lists")),
+            Some(PathBuf::from("/lists/Heat & Dust.mkv"))
+        );
+
+        assert!(matches!(parse_playlist("<html/>", PlaylistFormat::Xspf), Err(PlaylistIoError::Parse(_))));
+    }
+
+    #[test]
+    fn test_relative_paths() {
+        assert_eq!(
+            relative_path(Path::new("/media/Lists"), Path::new("/media/Movies/a.mkv")),
+            Some(PathBuf::from("../Movies/a.mkv"))
+        );
+        assert_eq!(
+            normalize(Path::new("/media/Lists/../Movies/./a.mkv")),
+            PathBuf::from("/media/Movies/a.mkv")
+        );
+    }
+}
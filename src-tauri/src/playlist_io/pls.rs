@@ -0,0 +1,65 @@
+//! PLS (INI-style) playlists
+
+use std::collections::BTreeMap;
+use super::{PlaylistDocument, PlaylistEntry, PlaylistIoError};
+
+pub fn parse(content: &str) -> Result<PlaylistDocument, PlaylistIoError> {
+    let mut seen_header = false;
+    // Entries keyed by their number; FileN/TitleN/LengthN may come in any order
+    let mut entries: BTreeMap<u32, PlaylistEntry> = BTreeMap::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        if line.eq_ignore_ascii_case("[playlist]") {
+            seen_header = true;
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+
+        let (field, number) = match ["file", "title", "length"].iter().find(|f| key.starts_with(*f)) {
+            Some(field) => match key[field.len()..].parse::<u32>() {
+                Ok(number) => (*field, number),
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+
+        let entry = entries.entry(number).or_default();
+        match field {
+            "file" => entry.location = value.to_string(),
+            "title" => entry.title = Some(value.to_string()).filter(|t| !t.is_empty()),
+            _ => entry.duration = value.parse::<i64>().ok().filter(|d| *d >= 0),
+        }
+    }
+
+    if !seen_header {
+        return Err(PlaylistIoError::Parse("missing [playlist] section".to_string()));
+    }
+
+    Ok(PlaylistDocument {
+        title: None,
+        entries: entries.into_values().filter(|e| !e.location.is_empty()).collect(),
+    })
+}
+
+pub fn write(document: &PlaylistDocument) -> String {
+    let mut out = String::from("[playlist]\n");
+
+    for (i, entry) in document.entries.iter().enumerate() {
+        let n = i + 1;
+        out.push_str(&format!("File{}={}\n", n, entry.location));
+        if let Some(title) = &entry.title {
+            out.push_str(&format!("Title{}={}\n", n, title.replace(['\r', '\n'], " ")));
+        }
+        out.push_str(&format!("Length{}={}\n", n, entry.duration.unwrap_or(-1)));
+    }
+
+    out.push_str(&format!("NumberOfEntries={}\nVersion=2\n", document.entries.len()));
+    out
+}
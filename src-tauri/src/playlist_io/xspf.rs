@@ -0,0 +1,168 @@
+//! XSPF (XML Shareable Playlist Format)
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::path::{Component, Path};
+
+use super::{PlaylistDocument, PlaylistEntry, PlaylistIoError};
+
+pub fn parse(content: &str) -> Result<PlaylistDocument, PlaylistIoError> {
+    let mut reader = Reader::from_str(content);
+    reader.trim_text(true);
+
+    let mut document = PlaylistDocument::default();
+    let mut seen_root = false;
+    // Element names from the root down to the current element
+    let mut stack: Vec<String> = Vec::new();
+    let mut current: Option<PlaylistEntry> = None;
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| PlaylistIoError::Parse(format!("at byte {}: {}", reader.buffer_position(), e)))?;
+
+        match event {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_lowercase();
+                if stack.is_empty() {
+                    if name != "playlist" {
+                        return Err(PlaylistIoError::Parse(format!("unexpected root element <{}>", name)));
+                    }
+                    seen_root = true;
+                }
+                if name == "track" {
+                    current = Some(PlaylistEntry::default());
+                }
+                stack.push(name);
+            }
+            Event::Text(t) => {
+                let text = t
+                    .unescape()
+                    .map_err(|e| PlaylistIoError::Parse(e.to_string()))?
+                    .trim()
+                    .to_string();
+                if text.is_empty() {
+                    continue;
+                }
+
+                let path: Vec<&str> = stack.iter().skip(1).map(String::as_str).collect();
+                match path.as_slice() {
+                    ["title"] => document.title = Some(text),
+                    ["tracklist", "track", field] => {
+                        if let Some(entry) = current.as_mut() {
+                            match *field {
+                                // First location wins; later ones are alternatives
+                                "location" if entry.location.is_empty() => entry.location = text,
+                                "title" => entry.title = Some(text),
+                                "duration" => {
+                                    entry.duration = text.parse::<i64>().ok().map(|ms| (ms + 500) / 1000)
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(_) => {
+                if stack.pop().as_deref() == Some("track") {
+                    if let Some(entry) = current.take() {
+                        document.entries.push(entry);
+                    }
+                }
+                if stack.is_empty() {
+                    break;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if !seen_root {
+        return Err(PlaylistIoError::Parse("missing <playlist> element".to_string()));
+    }
+
+    Ok(document)
+}
+
+pub fn write(document: &PlaylistDocument) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+    if let Some(title) = &document.title {
+        xml.push_str(&format!("  <title>{}</title>\n", escape(title)));
+    }
+
+    xml.push_str("  <trackList>\n");
+    for entry in &document.entries {
+        xml.push_str("    <track>\n");
+        xml.push_str(&format!("      <location>{}</location>\n", escape(&entry.location)));
+        if let Some(title) = &entry.title {
+            xml.push_str(&format!("      <title>{}</title>\n", escape(title)));
+        }
+        if let Some(duration) = entry.duration {
+            xml.push_str(&format!("      <duration>{}</duration>\n", duration * 1000));
+        }
+        xml.push_str("    </track>\n");
+    }
+    xml.push_str("  </trackList>\n");
+    xml.push_str("</playlist>\n");
+
+    xml
+}
+
+/// `file://` URI for an absolute path
+pub fn file_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let encoded = path.split('/').map(percent_encode).collect::<Vec<_>>().join("/");
+    if encoded.starts_with('/') {
+        format!("file://{}", encoded)
+    } else {
+        // Windows drive paths: C:/Movies -> file:///C:/Movies
+        format!("file:///{}", encoded)
+    }
+}
+
+/// Relative URI reference for a relative path
+pub fn relative_uri(path: &Path) -> String {
+    path.components()
+        .map(|c| match c {
+            Component::ParentDir => "..".to_string(),
+            other => percent_encode(&other.as_os_str().to_string_lossy()),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn percent_encode(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b':' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(Ok(byte)) = text.get(i + 1..i + 3).map(|hex| u8::from_str_radix(hex, 16)) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+fn escape(value: &str) -> String {
+    quick_xml::escape::escape(value).to_string()
+}
@@ -0,0 +1,244 @@
+//! Advanced SubStation Alpha (`.ass`) and its predecessor SSA (`.ssa`).
+//!
+//! Styles become CSS classes and their alignment becomes cue settings.
+//! Override tags for bold, italic, underline, colour and alignment become
+//! cue markup; the rest (karaoke, transforms, positioning by pixel, ...) is
+//! dropped, as are drawings.
+
+use std::collections::HashMap;
+
+use super::{CueText, Cue, color_class, parse_timestamp};
+
+/// Event columns when a file leaves out its Format line
+const DEFAULT_EVENT_FORMAT: &[&str] = &["Layer", "Start", "End", "Style", "Name", "MarginL", "MarginR", "MarginV", "Effect", "Text"];
+
+struct Style {
+    /// The CSS class, when the style sets anything
+    class: Option<String>,
+    /// Numpad alignment
+    alignment: u8,
+}
+
+pub fn parse(text: &str, styles: &mut Vec<String>) -> Vec<Cue> {
+    let mut section = String::new();
+    let mut legacy = false;
+    let mut style_format: Vec<String> = Vec::new();
+    let mut event_format: Vec<String> = DEFAULT_EVENT_FORMAT.iter().map(|f| f.to_string()).collect();
+    let mut known: HashMap<String, Style> = HashMap::new();
+    let mut cues = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line.to_lowercase();
+            // SSA numbers alignments differently
+            legacy |= section == "[v4 styles]";
+            continue;
+        }
+        let Some((kind, value)) = line.split_once(':') else { continue };
+        let value = value.trim();
+        match (section.as_str(), kind.trim()) {
+            ("[v4+ styles]" | "[v4 styles]", "Format") => {
+                style_format = value.split(',').map(|f| f.trim().to_string()).collect();
+            }
+            ("[v4+ styles]" | "[v4 styles]", "Style") => {
+                let fields: Vec<&str> = value.splitn(style_format.len().max(1), ',').map(str::trim).collect();
+                let get = |name: &str| field(&style_format, &fields, name);
+                let Some(name) = get("Name") else { continue };
+                let alignment = get("Alignment").and_then(|a| a.parse().ok()).map(|a| numpad(a, legacy)).unwrap_or(2);
+                let class = style_class(name, &get, styles);
+                known.insert(name.trim_start_matches('*').to_string(), Style { class, alignment });
+            }
+            ("[events]", "Format") => {
+                event_format = value.split(',').map(|f| f.trim().to_string()).collect();
+            }
+            ("[events]", "Dialogue") => {
+                let fields: Vec<&str> = value.splitn(event_format.len(), ',').collect();
+                let get = |name: &str| field(&event_format, &fields, name);
+                let (Some(start), Some(end), Some(text)) = (
+                    get("Start").and_then(parse_timestamp),
+                    get("End").and_then(parse_timestamp),
+                    get("Text"),
+                ) else {
+                    continue;
+                };
+                let style = get("Style").and_then(|s| known.get(s.trim_start_matches('*')));
+                let mut out = CueText::default();
+                if let Some(class) = style.and_then(|s| s.class.as_deref()) {
+                    out.open("style", "c", Some(class));
+                }
+                let mut alignment = style.map(|s| s.alignment);
+                if let Some(text) = convert_text(text, out, styles, &mut alignment, legacy) {
+                    cues.push(Cue { id: None, start, end, text, settings: alignment.and_then(alignment_settings) });
+                }
+            }
+            _ => {}
+        }
+    }
+    cues
+}
+
+fn field<'a>(format: &[String], fields: &[&'a str], name: &str) -> Option<&'a str> {
+    format.iter().position(|f| f.eq_ignore_ascii_case(name)).and_then(|i| fields.get(i).copied())
+}
+
+/// Register the CSS of a style, returning its class when it sets anything
+fn style_class<'a>(name: &str, get: &impl Fn(&str) -> Option<&'a str>, styles: &mut Vec<String>) -> Option<String> {
+    let flag = |name: &str| get(name).is_some_and(|v| v != "0");
+    let mut declarations = Vec::new();
+    if let Some(color) = get("PrimaryColour").and_then(ass_color) {
+        declarations.push(format!("color: #{}", color));
+    }
+    if let Some(font) = get("Fontname").filter(|f| !f.is_empty()) {
+        declarations.push(format!("font-family: \"{}\"", font.replace('"', "")));
+    }
+    if flag("Bold") {
+        declarations.push("font-weight: bold".to_string());
+    }
+    if flag("Italic") {
+        declarations.push("font-style: italic".to_string());
+    }
+    if flag("Underline") {
+        declarations.push("text-decoration: underline".to_string());
+    }
+    if declarations.is_empty() {
+        return None;
+    }
+    let class: String = format!("style_{}", name.trim_start_matches('*'))
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    styles.push(format!("::cue(.{}) {{ {}; }}", class, declarations.join("; ")));
+    Some(class)
+}
+
+/// `rrggbb` from an ASS colour: `&HAABBGGRR`, `&HBBGGRR&` or, in SSA, decimal
+fn ass_color(value: &str) -> Option<String> {
+    let value = value.trim().trim_end_matches('&');
+    let bgr = match value.strip_prefix("&H").or_else(|| value.strip_prefix("&h")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => value.parse::<i64>().ok()? as u32,
+    };
+    Some(format!("{:02x}{:02x}{:02x}", bgr & 0xff, (bgr >> 8) & 0xff, (bgr >> 16) & 0xff))
+}
+
+/// SSA alignments are 1-3 along the bottom, +4 for the top and +8 for the
+/// middle; ASS uses the numpad layout
+fn numpad(alignment: u8, legacy: bool) -> u8 {
+    match (legacy, alignment) {
+        (false, 1..=9) => alignment,
+        (true, 1..=3) => alignment,
+        (true, 5..=7) => alignment + 2,
+        (true, 9..=11) => alignment - 5,
+        _ => 2,
+    }
+}
+
+/// WebVTT cue settings for a numpad alignment; bottom centre is the default
+pub(super) fn alignment_settings(numpad: u8) -> Option<String> {
+    let line = match numpad {
+        7..=9 => Some("line:0"),
+        4..=6 => Some("line:50%"),
+        _ => None,
+    };
+    let align = match numpad % 3 {
+        1 => Some("align:left"),
+        0 => Some("align:right"),
+        _ => None,
+    };
+    let settings: Vec<&str> = [line, align].into_iter().flatten().collect();
+    (!settings.is_empty()).then(|| settings.join(" "))
+}
+
+/// Cue text for dialogue text, or None for a drawing
+fn convert_text(text: &str, mut out: CueText, styles: &mut Vec<String>, alignment: &mut Option<u8>, legacy: bool) -> Option<String> {
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let Some(length) = rest[start..].find('}') else { break };
+        out.text(&unescape(&rest[..start]));
+        if !apply_overrides(&rest[start + 1..start + length], &mut out, styles, alignment, legacy) {
+            return None;
+        }
+        rest = &rest[start + length + 1..];
+    }
+    out.text(&unescape(rest));
+    Some(out.finish())
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("\\N", "\n").replace("\\n", "\n").replace("\\h", "\u{a0}")
+}
+
+/// Apply the tags of one override block (the text between `{` and `}`).
+/// Returns false when the block starts a drawing.
+pub(super) fn apply_overrides(block: &str, out: &mut CueText, styles: &mut Vec<String>, alignment: &mut Option<u8>, legacy: bool) -> bool {
+    for tag in block.split('\\').map(str::trim).filter(|t| !t.is_empty()) {
+        let number = |prefix: &str| tag.strip_prefix(prefix).and_then(|v| v.parse::<u32>().ok());
+        if let Some(weight) = number("b") {
+            if weight == 0 {
+                out.close("b");
+            } else if !out.is_open("b") {
+                out.open("b", "b", None);
+            }
+        } else if let Some(on) = number("i").or_else(|| number("u")) {
+            let key = if tag.starts_with('i') { "i" } else { "u" };
+            if on == 0 {
+                out.close(key);
+            } else if !out.is_open(key) {
+                out.open(key, key, None);
+            }
+        } else if let Some(value) = number("an") {
+            *alignment = Some(numpad(value as u8, false));
+        } else if let Some(value) = number("a") {
+            *alignment = Some(numpad(value as u8, legacy));
+        } else if let Some(color) = tag.strip_prefix("1c").or_else(|| tag.strip_prefix('c')) {
+            match ass_color(color) {
+                Some(rgb) => {
+                    let class = color_class(styles, &rgb);
+                    out.open("color", "c", Some(&class));
+                }
+                None => out.close("color"),
+            }
+        } else if tag == "r" || tag.starts_with('r') && !tag.starts_with("re") {
+            for key in ["b", "i", "u", "color"] {
+                out.close(key);
+            }
+        } else if number("p").is_some_and(|scale| scale > 0) {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ass_styles_and_overrides() {
+        let script = "[Script Info]\nScriptType: v4.00+\n\n[V4+ Styles]\n\
+            Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+            Style: Default,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,2,2,10,10,10,1\n\
+            Style: Sign Top,Arial,20,&H0000FFFF,&H000000FF,&H00000000,&H00000000,-1,0,0,0,100,100,0,0,1,2,2,8,10,10,10,1\n\n\
+            [Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+            Dialogue: 0,0:00:05.00,0:00:07.50,Default,,0,0,0,,Hello, {\\i1}world{\\i0}!\\NSecond {\\c&H0000FF&}line\n\
+            Dialogue: 0,0:00:01.00,0:00:03.00,Sign Top,,0,0,0,,{\\an7}Café sign\n\
+            Dialogue: 0,0:00:02.00,0:00:03.00,Default,,0,0,0,,{\\p1}m 0 0 l 100 0 100 100\n\
+            Comment: 0,0:00:02.00,0:00:03.00,Default,,0,0,0,,not shown\n";
+        let mut styles = Vec::new();
+        let cues = parse(script, &mut styles);
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].start, 5000);
+        assert_eq!(cues[0].end, 7500);
+        assert_eq!(
+            cues[0].text,
+            "<c.style_Default>Hello, <i>world</i>!\nSecond <c.color_ff0000>line</c></c>"
+        );
+        assert_eq!(cues[0].settings, None);
+        assert_eq!(cues[1].text, "<c.style_Sign_Top>Café sign</c>");
+        assert_eq!(cues[1].settings.as_deref(), Some("line:0 align:left"));
+        assert_eq!(styles[0], "::cue(.style_Default) { color: #ffffff; font-family: \"Arial\"; }");
+        assert_eq!(styles[1], "::cue(.style_Sign_Top) { color: #ffff00; font-family: \"Arial\"; font-weight: bold; }");
+        assert_eq!(styles[2], "::cue(.color_ff0000) { color: #ff0000; }");
+    }
+}
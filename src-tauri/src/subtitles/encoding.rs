@@ -0,0 +1,65 @@
+//! Guessing the encoding of subtitle files, which are often UTF-16 from
+//! Windows tools or in a legacy code page (CP1252, GB18030, Shift_JIS, ...).
+//! Playlist imports use it too.
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+/// Decode subtitle bytes, returning the text and the encoding's name
+pub fn decode(bytes: &[u8]) -> (String, &'static str) {
+    let (encoding, bom_length) = Encoding::for_bom(bytes).unwrap_or_else(|| (guess(bytes), 0));
+    let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+    (text.into_owned(), encoding.name())
+}
+
+fn guess(bytes: &[u8]) -> &'static Encoding {
+    if let Some(encoding) = utf16_without_bom(bytes) {
+        return encoding;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+
+/// UTF-16 without a BOM: subtitles are mostly ASCII, so nearly every other
+/// byte is zero
+fn utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(4096) & !1];
+    let units = sample.len() / 2;
+    if units < 2 {
+        return None;
+    }
+    let zeros_at = |offset: usize| sample.iter().skip(offset).step_by(2).filter(|b| **b == 0).count();
+    let (even, odd) = (zeros_at(0), zeros_at(1));
+    if odd * 2 > units && even * 10 < units {
+        Some(UTF_16LE)
+    } else if even * 2 > units && odd * 10 < units {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_detects_encodings() {
+        let text = "1\n00:00:01,000 --> 00:00:02,000\nCafé crème, déjà vu\n";
+        assert_eq!(decode(text.as_bytes()), (text.to_string(), "UTF-8"));
+
+        let utf16: Vec<u8> = text.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect();
+        assert_eq!(decode(&utf16), (text.to_string(), "UTF-16LE"));
+        let with_bom: Vec<u8> = [0xFE, 0xFF].into_iter().chain(text.encode_utf16().flat_map(|unit| unit.to_be_bytes())).collect();
+        assert_eq!(decode(&with_bom), (text.to_string(), "UTF-16BE"));
+
+        let (cp1252, _, _) = encoding_rs::WINDOWS_1252.encode(text);
+        assert_eq!(decode(&cp1252), (text.to_string(), "windows-1252"));
+        let chinese = "1\n00:00:01,000 --> 00:00:02,000\n我们今天晚上去看电影吧，好不好？\n";
+        let (gbk, _, _) = encoding_rs::GB18030.encode(chinese);
+        assert_eq!(decode(&gbk).0, chinese);
+    }
+}
@@ -0,0 +1,188 @@
+//! Extracting embedded subtitle streams with ffmpeg into the app cache, so
+//! the player can show them like any external track.
+//!
+//! Only text streams can be extracted; image-based ones (PGS, VobSub, DVB)
+//! would need OCR. ASS/SSA streams are copied as they are so their styles
+//! survive; other text streams are converted to WebVTT unless SubRip is
+//! asked for.
+
+use rusqlite::{Connection, OptionalExtension, params};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::{SubtitleError, SubtitleFormat};
+use crate::db::subtitles::{add_subtitle_track, get_subtitle_track_by_id};
+
+/// Codecs ffmpeg can't turn into text
+const IMAGE_CODECS: &[&str] = &["hdmv_pgs_subtitle", "dvd_subtitle", "dvb_subtitle", "xsub", "dvb_teletext"];
+
+/// An embedded stream ready to extract. Everything ffmpeg needs is read from
+/// the database up front, so the extraction itself can run without it.
+#[derive(Debug, Clone)]
+pub struct SubtitleExtraction {
+    pub media_id: i64,
+    pub video: PathBuf,
+    pub stream: i32,
+    pub encoder: &'static str,
+    pub extension: &'static str,
+    pub output: PathBuf,
+    pub language: Option<String>,
+    pub label: String,
+    /// The track registered for an earlier extraction to the same file
+    pub existing: Option<i64>,
+}
+
+/// Check that an embedded track can be extracted and work out where to.
+/// `format` picks SubRip or WebVTT; by default ASS/SSA streams stay ASS and
+/// others become WebVTT. A stream already extracted to the same file is not
+/// extracted again.
+pub fn plan_extraction(
+    conn: &Connection,
+    subtitle_id: i64,
+    cache_dir: &Path,
+    format: Option<SubtitleFormat>,
+) -> Result<SubtitleExtraction, SubtitleError> {
+    let track = get_subtitle_track_by_id(conn, subtitle_id)?.ok_or(SubtitleError::NotFound(subtitle_id))?;
+    let (true, Some(stream)) = (track.is_embedded, track.track_index) else {
+        return Err(SubtitleError::NotEmbedded(subtitle_id));
+    };
+    let codec = track.codec.as_deref().unwrap_or_default().to_lowercase();
+    if IMAGE_CODECS.contains(&codec.as_str()) {
+        return Err(SubtitleError::NotText(codec));
+    }
+    let is_ass = matches!(codec.as_str(), "ass" | "ssa");
+    let (extension, encoder) = match format {
+        None | Some(SubtitleFormat::Ass | SubtitleFormat::Ssa) if is_ass => ("ass", "copy"),
+        None | Some(SubtitleFormat::WebVtt) => ("vtt", "webvtt"),
+        Some(SubtitleFormat::Srt) => ("srt", "srt"),
+        Some(other) => return Err(SubtitleError::UnsupportedFormat(format!("can't extract {} stream as {:?}", codec, other))),
+    };
+
+    let output = cache_dir
+        .join("subtitles")
+        .join(format!("{}-{}.{}", track.media_id, stream, extension));
+    let existing = if output.exists() { extracted_track(conn, track.media_id, &output)? } else { None };
+
+    let video: String = conn.query_row(
+        "SELECT file_path FROM media_files WHERE id = ?1",
+        params![track.media_id],
+        |row| row.get(0),
+    )?;
+
+    Ok(SubtitleExtraction {
+        media_id: track.media_id,
+        video: PathBuf::from(video),
+        stream,
+        encoder,
+        extension,
+        output,
+        language: track.language,
+        label: track.label.as_deref().unwrap_or("Subtitles").replace(" (Embedded)", ""),
+        existing,
+    })
+}
+
+impl SubtitleExtraction {
+    /// Run ffmpeg, unless the stream was extracted before
+    pub fn run(&self) -> Result<(), SubtitleError> {
+        if self.existing.is_some() {
+            return Ok(());
+        }
+        run_ffmpeg(&self.video, self.stream, self.encoder, &self.output)
+    }
+}
+
+/// Register an extracted file as an external track, returning its id
+pub fn register_extraction(conn: &Connection, extraction: &SubtitleExtraction) -> Result<i64, SubtitleError> {
+    if let Some(existing) = extraction.existing {
+        return Ok(existing);
+    }
+    Ok(add_subtitle_track(
+        conn,
+        extraction.media_id,
+        &extraction.output.to_string_lossy(),
+        extraction.language.as_deref(),
+        Some(&format!("{} (Extracted)", extraction.label)),
+        Some(extraction.extension),
+        false,
+        None,
+    )?)
+}
+
+fn extracted_track(conn: &Connection, media_id: i64, output: &Path) -> Result<Option<i64>, SubtitleError> {
+    Ok(conn
+        .query_row(
+            "SELECT id FROM subtitle_tracks WHERE media_id = ?1 AND file_path = ?2 AND is_embedded = 0",
+            params![media_id, output.to_string_lossy()],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+fn run_ffmpeg(video: &Path, stream: i32, encoder: &str, output: &Path) -> Result<(), SubtitleError> {
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Write next to the target first so a failed run leaves nothing behind
+    let partial = output.with_extension(format!(
+        "partial.{}",
+        output.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default()
+    ));
+    let result = Command::new("ffmpeg")
+        .args(["-y", "-v", "error", "-i"])
+        .arg(video)
+        .args(["-map", &format!("0:{}", stream), "-c:s", encoder])
+        .arg(&partial)
+        .output()
+        .map_err(|e| SubtitleError::Ffmpeg(format!("Failed to run ffmpeg: {}", e)))?;
+
+    if !result.status.success() {
+        let _ = fs::remove_file(&partial);
+        return Err(SubtitleError::Ffmpeg(String::from_utf8_lossy(&result.stderr).trim().to_string()));
+    }
+    fs::rename(&partial, output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use crate::db::test_support::{self, TestMedia};
+
+    fn extract_embedded_subtitle(
+        conn: &Connection,
+        subtitle_id: i64,
+        cache_dir: &Path,
+        format: Option<SubtitleFormat>,
+    ) -> Result<i64, SubtitleError> {
+        let extraction = plan_extraction(conn, subtitle_id, cache_dir, format)?;
+        extraction.run()?;
+        register_extraction(conn, &extraction)
+    }
+
+    #[test]
+    fn test_extract_checks_tracks_and_reuses_earlier_extraction() {
+        let conn = init_db().unwrap();
+        let cache = tempfile::tempdir().unwrap();
+        test_support::add_media(&conn, &TestMedia::default());
+        let external = add_subtitle_track(&conn, 1, "/m/movie.srt", None, None, None, false, None).unwrap();
+        let pgs = add_subtitle_track(&conn, 1, "/m/movie.mkv", Some("en"), None, Some("hdmv_pgs_subtitle"), true, Some(3)).unwrap();
+        let text = add_subtitle_track(&conn, 1, "/m/movie.mkv", Some("en"), Some("EN (Embedded)"), Some("subrip"), true, Some(2)).unwrap();
+
+        assert!(matches!(extract_embedded_subtitle(&conn, external, cache.path(), None), Err(SubtitleError::NotEmbedded(_))));
+        assert!(matches!(extract_embedded_subtitle(&conn, pgs, cache.path(), None), Err(SubtitleError::NotText(_))));
+        assert!(matches!(
+            extract_embedded_subtitle(&conn, text, cache.path(), Some(SubtitleFormat::Ass)),
+            Err(SubtitleError::UnsupportedFormat(_))
+        ));
+
+        // Already extracted: the registered track is returned without running ffmpeg
+        let output = cache.path().join("subtitles/1-2.vtt");
+        fs::create_dir_all(output.parent().unwrap()).unwrap();
+        fs::write(&output, "WEBVTT\n\n00:01.000 --> 00:02.000\nHi\n").unwrap();
+        let extracted = add_subtitle_track(&conn, 1, &output.to_string_lossy(), Some("en"), None, Some("vtt"), false, None).unwrap();
+        assert_eq!(extract_embedded_subtitle(&conn, text, cache.path(), None).unwrap(), extracted);
+    }
+}
@@ -0,0 +1,108 @@
+//! MicroDVD (`.sub`): `{start frame}{end frame}text` lines with `|` between
+//! text lines. Control codes like `{y:i}` style one line, `{Y:i}` every
+//! line. A first cue of `{1}{1}23.976` states the frame rate.
+
+use super::{Cue, CueText, color_class};
+
+/// How long a cue without an end frame stays up, at most
+const OPEN_ENDED_MILLIS: i64 = 3000;
+
+pub fn parse(text: &str, framerate: f64, styles: &mut Vec<String>) -> Vec<Cue> {
+    let line_pattern = regex::Regex::new(r"^\{(\d+)\}\{(\d*)\}(.*)$").unwrap();
+    let mut framerate = framerate;
+    let mut cues: Vec<Cue> = Vec::new();
+    let mut open_ended = Vec::new();
+
+    for (index, line) in text.lines().map(str::trim).filter(|l| !l.is_empty()).enumerate() {
+        let Some(caps) = line_pattern.captures(line) else { continue };
+        let start_frame: u64 = caps[1].parse().unwrap_or_default();
+        let end_frame: Option<u64> = caps[2].parse().ok();
+        let body = &caps[3];
+        if index == 0 && start_frame <= 1 && end_frame == Some(1) {
+            if let Some(stated) = body.trim().parse::<f64>().ok().filter(|fps| *fps > 0.0) {
+                framerate = stated;
+                continue;
+            }
+        }
+
+        let millis = |frame: u64| (frame as f64 * 1000.0 / framerate).round() as i64;
+        let start = millis(start_frame);
+        if end_frame.is_none() {
+            open_ended.push(cues.len());
+        }
+        let end = end_frame.map(millis).unwrap_or(start + OPEN_ENDED_MILLIS);
+        cues.push(Cue { id: None, start, end, text: convert_text(body, styles), settings: None });
+    }
+
+    // A cue without an end frame lasts until the next one
+    for index in open_ended {
+        if let Some(next) = cues.get(index + 1).map(|c| c.start) {
+            let cue = &mut cues[index];
+            cue.end = cue.end.min(next);
+        }
+    }
+    cues
+}
+
+fn convert_text(body: &str, styles: &mut Vec<String>) -> String {
+    let code = regex::Regex::new(r"\{([a-zA-Z]):([^}]*)\}").unwrap();
+    // Upper-case codes apply to every line
+    let global: Vec<(char, String)> = code
+        .captures_iter(body)
+        .filter(|caps| caps[1].starts_with(|c: char| c.is_ascii_uppercase()))
+        .map(|caps| (caps[1].to_ascii_lowercase().chars().next().unwrap_or_default(), caps[2].to_string()))
+        .collect();
+
+    let lines: Vec<String> = body
+        .split('|')
+        .map(|line| {
+            let local = code
+                .captures_iter(line)
+                .filter(|caps| caps[1].starts_with(|c: char| c.is_ascii_lowercase()))
+                .map(|caps| (caps[1].chars().next().unwrap_or_default(), caps[2].to_string()));
+            let mut out = CueText::default();
+            for (kind, value) in global.iter().cloned().chain(local) {
+                match kind {
+                    'y' => {
+                        for style in value.to_lowercase().split(',').map(str::trim) {
+                            if let key @ ("b" | "i" | "u") = style {
+                                out.open(key, key, None);
+                            }
+                        }
+                    }
+                    'c' => {
+                        // $BBGGRR
+                        let bgr = value.trim_start_matches('$');
+                        if bgr.len() == 6 && bgr.chars().all(|c| c.is_ascii_hexdigit()) {
+                            let rgb = format!("{}{}{}", &bgr[4..6], &bgr[2..4], &bgr[0..2]);
+                            let class = color_class(styles, &rgb);
+                            out.open("color", "c", Some(&class));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            out.text(code.replace_all(line, "").trim());
+            out.finish()
+        })
+        .collect();
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_microdvd() {
+        let text = "{1}{1}25\n{25}{75}{Y:i}Hello|world\n{100}{}{c:$0000FF}Red {y:b}bold\n{150}{200}Last";
+        let mut styles = Vec::new();
+        let cues = parse(text, 23.976, &mut styles);
+        assert_eq!(cues.len(), 3);
+        assert_eq!((cues[0].start, cues[0].end), (1000, 3000));
+        assert_eq!(cues[0].text, "<i>Hello</i>\n<i>world</i>");
+        assert_eq!((cues[1].start, cues[1].end), (4000, 6000));
+        assert_eq!(cues[1].text, "<c.color_ff0000><b>Red bold</b></c>");
+        assert_eq!(styles, vec!["::cue(.color_ff0000) { color: #ff0000; }".to_string()]);
+    }
+}
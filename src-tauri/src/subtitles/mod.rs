@@ -0,0 +1,385 @@
+//! Subtitle files: decoding them, parsing SRT, WebVTT, ASS/SSA and MicroDVD
+//! into cues, and writing cues out as WebVTT for the player.
+//!
+//! Cue text is WebVTT cue markup (`<b>`, `<i>`, `<u>` and `<c.class>`), with
+//! the CSS for the classes in `SubtitleDocument::styles`, so font colours and
+//! ASS styles survive the conversion.
+
+pub mod ass;
+pub mod encoding;
+pub mod extract;
+pub mod microdvd;
+pub mod srt;
+pub mod timing;
+pub mod vtt;
+
+pub use extract::*;
+pub use timing::*;
+
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::db::subtitles::{SubtitleTrack, get_subtitle_track_by_id};
+
+/// Frame rate for MicroDVD files that don't state one, when the video's is unknown
+const DEFAULT_FRAMERATE: f64 = 23.976;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SubtitleError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Unsupported subtitle format: {0}")]
+    UnsupportedFormat(String),
+    #[error("Subtitle track {0} not found")]
+    NotFound(i64),
+    /// Embedded tracks are inside the video and have to be extracted first
+    #[error("Subtitle track {0} is embedded in the video; extract it first")]
+    Embedded(i64),
+    #[error("Subtitle track {0} is not embedded in a video")]
+    NotEmbedded(i64),
+    /// Image-based subtitles (PGS, VobSub) can't be turned into text
+    #[error("Subtitle codec {0} is image-based")]
+    NotText(String),
+    #[error("ffmpeg error: {0}")]
+    Ffmpeg(String),
+    #[error("Invalid timing: {0}")]
+    InvalidTiming(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtitleFormat {
+    Srt,
+    WebVtt,
+    Ass,
+    Ssa,
+    MicroDvd,
+}
+
+impl SubtitleFormat {
+    /// Recognise a format from the text of a file
+    pub fn detect(text: &str) -> Option<Self> {
+        let text = text.trim_start_matches('\u{feff}').trim_start();
+        if text.starts_with("WEBVTT") {
+            return Some(Self::WebVtt);
+        }
+        let head: String = text.chars().take(4096).collect::<String>().to_lowercase();
+        if head.contains("[script info]") || head.contains("[events]") {
+            return Some(if head.contains("[v4 styles]") { Self::Ssa } else { Self::Ass });
+        }
+        let first_line = text.lines().next()?.trim();
+        if regex::Regex::new(r"^\{\d+\}\{\d*\}").unwrap().is_match(first_line) {
+            return Some(Self::MicroDvd);
+        }
+        text.lines().take(10).any(|line| line.contains("-->")).then_some(Self::Srt)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cue {
+    /// The WebVTT cue identifier, kept from WebVTT input
+    pub id: Option<String>,
+    /// Milliseconds from the start of the video
+    pub start: i64,
+    pub end: i64,
+    /// WebVTT cue markup
+    pub text: String,
+    /// WebVTT cue settings, such as `line:0` for a cue at the top
+    pub settings: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubtitleDocument {
+    pub format: SubtitleFormat,
+    /// The encoding the file was decoded from
+    pub encoding: String,
+    /// CSS rules for the classes used in cue text
+    pub styles: Vec<String>,
+    /// In start order
+    pub cues: Vec<Cue>,
+}
+
+impl SubtitleDocument {
+    /// Parse decoded subtitle text. MicroDVD files count frames, so they
+    /// need the video's frame rate unless they state their own.
+    pub fn parse(text: &str, framerate: Option<f64>) -> Result<Self, SubtitleError> {
+        let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n").replace('\r', "\n");
+        let format = SubtitleFormat::detect(&text)
+            .ok_or_else(|| SubtitleError::UnsupportedFormat("unrecognised subtitle text".to_string()))?;
+        let mut styles = Vec::new();
+        let mut cues = match format {
+            SubtitleFormat::Srt => srt::parse(&text, &mut styles),
+            SubtitleFormat::WebVtt => vtt::parse(&text, &mut styles),
+            SubtitleFormat::Ass | SubtitleFormat::Ssa => ass::parse(&text, &mut styles),
+            SubtitleFormat::MicroDvd => microdvd::parse(&text, framerate.unwrap_or(DEFAULT_FRAMERATE), &mut styles),
+        };
+        cues.retain(|cue| cue.end > cue.start && !cue.text.trim().is_empty());
+        cues.sort_by_key(|cue| (cue.start, cue.end));
+        Ok(Self { format, encoding: "UTF-8".to_string(), styles, cues })
+    }
+
+    pub fn to_webvtt(&self) -> String {
+        let mut out = String::from("WEBVTT\n\n");
+        if !self.styles.is_empty() {
+            out.push_str("STYLE\n");
+            for rule in &self.styles {
+                out.push_str(rule);
+                out.push('\n');
+            }
+            out.push('\n');
+        }
+        for cue in &self.cues {
+            if let Some(id) = &cue.id {
+                out.push_str(id);
+                out.push('\n');
+            }
+            out.push_str(&format!("{} --> {}", format_timestamp(cue.start), format_timestamp(cue.end)));
+            if let Some(settings) = &cue.settings {
+                out.push(' ');
+                out.push_str(settings);
+            }
+            out.push('\n');
+            // A blank line would end the cue and "-->" would start another
+            for line in cue.text.lines().filter(|line| !line.trim().is_empty()) {
+                out.push_str(&line.replace("-->", "--&gt;"));
+                out.push('\n');
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// SubRip keeps bold, italic, underline and colours; other markup and
+    /// cue settings other than a top position are lost
+    pub fn to_srt(&self) -> String {
+        let mut out = String::new();
+        for (index, cue) in self.cues.iter().enumerate() {
+            let top = cue.settings.as_deref().is_some_and(|settings| settings.split_whitespace().any(|s| s == "line:0"));
+            out.push_str(&format!(
+                "{}\n{} --> {}\n{}{}\n\n",
+                index + 1,
+                format_timestamp(cue.start).replace('.', ","),
+                format_timestamp(cue.end).replace('.', ","),
+                if top { "{\\an8}" } else { "" },
+                cue_text_to_srt(&cue.text)
+            ));
+        }
+        out
+    }
+}
+
+fn cue_text_to_srt(text: &str) -> String {
+    let tag = regex::Regex::new(r"<(/?)([^\s>.]*)([^\s>]*)[^>]*>").unwrap();
+    let unescape = |text: &str| text.replace("&lt;", "<").replace("&gt;", ">").replace("&nbsp;", "\u{a0}").replace("&amp;", "&");
+    let mut out = String::new();
+    // Whether each open <c> became a <font>
+    let mut classes = Vec::new();
+    let mut last = 0;
+    for caps in tag.captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        out.push_str(&unescape(&text[last..whole.start()]));
+        last = whole.end();
+        let closing = !caps[1].is_empty();
+        match (closing, &caps[2]) {
+            (_, name @ ("b" | "i" | "u")) => out.push_str(&format!("<{}{}>", &caps[1], name)),
+            (false, "c") => {
+                let color = caps[3].split('.').find_map(|class| class.strip_prefix("color_"));
+                if let Some(rgb) = color {
+                    out.push_str(&format!("<font color=\"#{}\">", rgb));
+                }
+                classes.push(color.is_some());
+            }
+            (true, "c") => out.push_str(if classes.pop() == Some(true) { "</font>" } else { "" }),
+            _ => {}
+        }
+    }
+    out.push_str(&unescape(&text[last..]));
+    out.lines().filter(|line| !line.trim().is_empty()).collect::<Vec<_>>().join("\n")
+}
+
+/// Read, decode and parse a subtitle file
+pub fn load_subtitle_file(path: &Path, framerate: Option<f64>) -> Result<SubtitleDocument, SubtitleError> {
+    let bytes = fs::read(path)?;
+    let (text, encoding) = encoding::decode(&bytes);
+    let mut document = SubtitleDocument::parse(&text, framerate).map_err(|e| match e {
+        SubtitleError::UnsupportedFormat(_) => SubtitleError::UnsupportedFormat(path.display().to_string()),
+        e => e,
+    })?;
+    document.encoding = encoding.to_string();
+    Ok(document)
+}
+
+/// Parse an external subtitle track of the library, with its timing
+/// correction applied
+pub fn load_subtitle_track(conn: &Connection, subtitle_id: i64) -> Result<SubtitleDocument, SubtitleError> {
+    let (_, mut document) = read_track(conn, subtitle_id)?;
+    get_subtitle_timing(conn, subtitle_id)?.apply(&mut document);
+    Ok(document)
+}
+
+/// A track and its file as written
+fn read_track(conn: &Connection, subtitle_id: i64) -> Result<(SubtitleTrack, SubtitleDocument), SubtitleError> {
+    let track = get_subtitle_track_by_id(conn, subtitle_id)?.ok_or(SubtitleError::NotFound(subtitle_id))?;
+    if track.is_embedded {
+        return Err(SubtitleError::Embedded(subtitle_id));
+    }
+    let framerate: Option<f64> = conn
+        .query_row("SELECT framerate FROM media_files WHERE id = ?1", params![track.media_id], |row| row.get(0))
+        .optional()?
+        .flatten();
+    let document = load_subtitle_file(Path::new(&track.file_path), framerate)?;
+    Ok((track, document))
+}
+
+/// Milliseconds from `h:mm:ss,mmm`, `hh:mm:ss.mmm`, `mm:ss.mmm` or ASS's
+/// `h:mm:ss.cc`
+pub(crate) fn parse_timestamp(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (clock, fraction) = match value.rfind(['.', ',']) {
+        Some(index) => (&value[..index], &value[index + 1..]),
+        None => (value, ""),
+    };
+    let mut millis: i64 = 0;
+    if !fraction.is_empty() {
+        if !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let digits: String = fraction.chars().chain("00".chars()).take(3).collect();
+        millis = digits.parse().ok()?;
+    }
+    let parts = clock.split(':').map(|part| part.trim().parse::<i64>().ok()).collect::<Option<Vec<_>>>()?;
+    let seconds = match parts.as_slice() {
+        [h, m, s] => h * 3600 + m * 60 + s,
+        [m, s] => m * 60 + s,
+        _ => return None,
+    };
+    Some(seconds * 1000 + millis)
+}
+
+/// `hh:mm:ss.mmm`, as WebVTT writes times
+pub(crate) fn format_timestamp(millis: i64) -> String {
+    let millis = millis.max(0);
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// The class for a text colour (`rrggbb`), adding its rule to `styles`
+pub(crate) fn color_class(styles: &mut Vec<String>, rgb: &str) -> String {
+    let rgb = rgb.to_lowercase();
+    let class = format!("color_{}", rgb);
+    let rule = format!("::cue(.{}) {{ color: #{}; }}", class, rgb);
+    if !styles.contains(&rule) {
+        styles.push(rule);
+    }
+    class
+}
+
+/// WebVTT cue text being built from another format's markup: text is
+/// escaped and tags are kept properly nested
+#[derive(Debug, Default)]
+pub(crate) struct CueText {
+    out: String,
+    /// What each open tag is for (`"i"`, `"color"`, ...) and its opening
+    open: Vec<(String, String)>,
+}
+
+impl CueText {
+    pub fn text(&mut self, text: &str) {
+        for c in text.chars() {
+            match c {
+                '&' => self.out.push_str("&amp;"),
+                '<' => self.out.push_str("&lt;"),
+                '>' => self.out.push_str("&gt;"),
+                c => self.out.push(c),
+            }
+        }
+    }
+
+    pub fn is_open(&self, key: &str) -> bool {
+        self.open.iter().any(|(k, _)| *k == key)
+    }
+
+    /// Open `<tag>` or `<tag.class>` for `key`, replacing a tag already open for it
+    pub fn open(&mut self, key: &str, tag: &str, class: Option<&str>) {
+        self.close(key);
+        let opening = match class {
+            Some(class) => format!("{}.{}", tag, class),
+            None => tag.to_string(),
+        };
+        self.out.push_str(&format!("<{}>", opening));
+        self.open.push((key.to_string(), opening));
+    }
+
+    /// Close the tag open for `key`, reopening the tags opened inside it
+    pub fn close(&mut self, key: &str) {
+        let Some(index) = self.open.iter().rposition(|(k, _)| *k == key) else {
+            return;
+        };
+        let inner = self.open.split_off(index + 1);
+        for (_, opening) in inner.iter().rev() {
+            self.push_closing(opening.clone());
+        }
+        if let Some((_, opening)) = self.open.pop() {
+            self.push_closing(opening);
+        }
+        for (key, opening) in inner {
+            self.out.push_str(&format!("<{}>", opening));
+            self.open.push((key, opening));
+        }
+    }
+
+    fn push_closing(&mut self, opening: String) {
+        let tag = opening.split('.').next().unwrap_or_default();
+        self.out.push_str(&format!("</{}>", tag));
+    }
+
+    pub fn finish(mut self) -> String {
+        while let Some((_, opening)) = self.open.pop() {
+            self.push_closing(opening);
+        }
+        self.out.trim().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamps_and_webvtt_output() {
+        assert_eq!(parse_timestamp("01:02:03,456"), Some(3_723_456));
+        assert_eq!(parse_timestamp("0:00:05.50"), Some(5_500));
+        assert_eq!(parse_timestamp("02:03.4"), Some(123_400));
+        assert_eq!(parse_timestamp("1:xx:00"), None);
+        assert_eq!(format_timestamp(3_723_456), "01:02:03.456");
+
+        let mut text = CueText::default();
+        text.open("i", "i", None);
+        text.text("a < b");
+        text.open("color", "c", Some("color_ff0000"));
+        text.text("red");
+        text.close("i");
+        text.text(" & plain red");
+        assert_eq!(text.finish(), "<i>a &lt; b<c.color_ff0000>red</c></i><c.color_ff0000> &amp; plain red</c>");
+
+        let document = SubtitleDocument {
+            format: SubtitleFormat::Srt,
+            encoding: "UTF-8".to_string(),
+            styles: vec!["::cue(.color_ff0000) { color: #ff0000; }".to_string()],
+            cues: vec![Cue { id: None, start: 1000, end: 2500, text: "one\n\ntwo --> three".to_string(), settings: Some("line:0".to_string()) }],
+        };
+        assert_eq!(
+            document.to_webvtt(),
+            "WEBVTT\n\nSTYLE\n::cue(.color_ff0000) { color: #ff0000; }\n\n00:00:01.000 --> 00:00:02.500 line:0\none\ntwo --&gt; three\n\n"
+        );
+    }
+}
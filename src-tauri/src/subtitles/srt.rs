@@ -0,0 +1,93 @@
+//! SubRip (`.srt`): numbered blocks of a timing line and text. Text may use
+//! HTML-like `<b>`, `<i>`, `<u>` and `<font color>` tags and ASS override
+//! blocks such as `{\an8}`.
+
+use super::{Cue, CueText, ass, color_class, parse_timestamp};
+
+pub fn parse(text: &str, styles: &mut Vec<String>) -> Vec<Cue> {
+    let timing = regex::Regex::new(r"^\s*(\S+)\s*-->\s*(\S+)").unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    let mut cues = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let times = timing
+            .captures(lines[index])
+            .and_then(|caps| Some((parse_timestamp(&caps[1])?, parse_timestamp(&caps[2])?)));
+        index += 1;
+        let Some((start, end)) = times else { continue };
+
+        let mut body = Vec::new();
+        while let Some(line) = lines.get(index) {
+            // Text runs to a blank line, or to the next block when that's missing
+            let next_block = line.trim().chars().all(|c| c.is_ascii_digit())
+                && lines.get(index + 1).is_some_and(|next| timing.is_match(next));
+            if line.trim().is_empty() || next_block || timing.is_match(line) {
+                break;
+            }
+            body.push(line.trim_end());
+            index += 1;
+        }
+        let (text, settings) = convert_markup(&body.join("\n"), styles);
+        cues.push(Cue { id: None, start, end, text, settings });
+    }
+    cues
+}
+
+fn convert_markup(text: &str, styles: &mut Vec<String>) -> (String, Option<String>) {
+    let tag = regex::Regex::new(r"(?i)<(/?)([a-z]+)([^>]*)>|\{(\\[^}]*)\}").unwrap();
+    let font_color = regex::Regex::new(r#"(?i)color\s*=\s*["']?#?([0-9a-f]{6})\b"#).unwrap();
+    let mut out = CueText::default();
+    let mut alignment = None;
+    let mut last = 0;
+    for caps in tag.captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        out.text(&text[last..whole.start()]);
+        last = whole.end();
+        if let Some(block) = caps.get(4) {
+            ass::apply_overrides(block.as_str(), &mut out, styles, &mut alignment, false);
+            continue;
+        }
+        let closing = !caps[1].is_empty();
+        match caps[2].to_lowercase().as_str() {
+            key @ ("b" | "i" | "u") => {
+                if closing {
+                    out.close(key);
+                } else if !out.is_open(key) {
+                    out.open(key, key, None);
+                }
+            }
+            "font" if closing => out.close("color"),
+            "font" => {
+                if let Some(color) = font_color.captures(&caps[3]) {
+                    let class = color_class(styles, &color[1]);
+                    out.open("color", "c", Some(&class));
+                }
+            }
+            // Other tags are dropped
+            _ => {}
+        }
+    }
+    out.text(&text[last..]);
+    (out.finish(), alignment.and_then(ass::alignment_settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_srt() {
+        let text = "1\n00:00:01,000 --> 00:00:02,500\n<i>Hello</i> <font color=\"#FF0000\">there</font>\nR&D <unknown>tag</unknown>\n\n\
+                    2\n00:00:03,000 --> 00:00:04,000 X1:10 X2:20\n{\\an8}On top\n\
+                    3\n00:00:05,000 --> 00:00:06,000\nNo blank line before me\n\n\nbroken\n00:00:xx,000 --> 00:00:07,000\nskipped\n";
+        let mut styles = Vec::new();
+        let cues = parse(text, &mut styles);
+        assert_eq!(cues.len(), 3);
+        assert_eq!((cues[0].start, cues[0].end), (1000, 2500));
+        assert_eq!(cues[0].text, "<i>Hello</i> <c.color_ff0000>there</c>\nR&amp;D tag");
+        assert_eq!(styles, vec!["::cue(.color_ff0000) { color: #ff0000; }".to_string()]);
+        assert_eq!(cues[1].text, "On top");
+        assert_eq!(cues[1].settings.as_deref(), Some("line:0"));
+        assert_eq!(cues[2].text, "No blank line before me");
+    }
+}
@@ -0,0 +1,258 @@
+//! Fixing subtitles that are out of sync with the video: a per-track offset,
+//! frame rate conversion (a file timed for 23.976 fps against a 25 fps
+//! release) and two-point resync, which stretches and shifts the times so two
+//! cues land where they should. Corrections are stored per track and applied
+//! whenever the track is read, or baked into a new file.
+
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use super::{SubtitleDocument, SubtitleError, SubtitleFormat, read_track};
+use crate::db::subtitles::{SubtitleTrack, add_subtitle_track};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SubtitleTiming {
+    /// Added to every time after scaling
+    pub offset_ms: i64,
+    /// Stretch from resyncing; 1.0 keeps the speed
+    pub scale: f64,
+    /// The frame rate the file was timed for
+    pub source_framerate: Option<f64>,
+    /// The frame rate of the video it plays with
+    pub target_framerate: Option<f64>,
+}
+
+impl Default for SubtitleTiming {
+    fn default() -> Self {
+        Self { offset_ms: 0, scale: 1.0, source_framerate: None, target_framerate: None }
+    }
+}
+
+impl SubtitleTiming {
+    fn factor(&self) -> f64 {
+        match (self.source_framerate, self.target_framerate) {
+            (Some(source), Some(target)) => self.scale * source / target,
+            _ => self.scale,
+        }
+    }
+
+    /// Where a time from the file ends up
+    pub fn adjust(&self, millis: i64) -> i64 {
+        (millis as f64 * self.factor()).round() as i64 + self.offset_ms
+    }
+
+    /// Adjust every cue; cues moved entirely before the start are dropped
+    pub fn apply(&self, document: &mut SubtitleDocument) {
+        for cue in &mut document.cues {
+            cue.start = self.adjust(cue.start).max(0);
+            cue.end = self.adjust(cue.end);
+        }
+        document.cues.retain(|cue| cue.end > cue.start);
+    }
+
+    fn validate(&self) -> Result<(), SubtitleError> {
+        let positive = |value: f64| value.is_finite() && value > 0.0;
+        if !positive(self.scale) {
+            return Err(SubtitleError::InvalidTiming("scale must be positive".to_string()));
+        }
+        if self.source_framerate.is_some() != self.target_framerate.is_some() {
+            return Err(SubtitleError::InvalidTiming("frame rate conversion needs both frame rates".to_string()));
+        }
+        if self.source_framerate.into_iter().chain(self.target_framerate).any(|fps| !positive(fps)) {
+            return Err(SubtitleError::InvalidTiming("frame rates must be positive".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// A cue time as the track shows it now and the time it should show at
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SyncPoint {
+    pub shown_ms: i64,
+    pub wanted_ms: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResyncResult {
+    pub timing: SubtitleTiming,
+    /// The new track holding the corrected file, when one was written
+    pub written_track_id: Option<i64>,
+}
+
+/// The stored correction of a track; no correction when none was set
+pub fn get_subtitle_timing(conn: &Connection, subtitle_id: i64) -> Result<SubtitleTiming, SubtitleError> {
+    let timing = conn
+        .query_row(
+            "SELECT offset_ms, scale, source_framerate, target_framerate FROM subtitle_timing WHERE subtitle_id = ?1",
+            params![subtitle_id],
+            |row| {
+                Ok(SubtitleTiming {
+                    offset_ms: row.get(0)?,
+                    scale: row.get(1)?,
+                    source_framerate: row.get(2)?,
+                    target_framerate: row.get(3)?,
+                })
+            },
+        )
+        .optional()?;
+    Ok(timing.unwrap_or_default())
+}
+
+pub fn set_subtitle_timing(conn: &Connection, subtitle_id: i64, timing: &SubtitleTiming) -> Result<(), SubtitleError> {
+    timing.validate()?;
+    if timing == &SubtitleTiming::default() {
+        conn.execute("DELETE FROM subtitle_timing WHERE subtitle_id = ?1", params![subtitle_id])?;
+        return Ok(());
+    }
+    conn.execute(
+        "INSERT INTO subtitle_timing (subtitle_id, offset_ms, scale, source_framerate, target_framerate, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, CURRENT_TIMESTAMP)
+         ON CONFLICT(subtitle_id) DO UPDATE SET
+            offset_ms = excluded.offset_ms,
+            scale = excluded.scale,
+            source_framerate = excluded.source_framerate,
+            target_framerate = excluded.target_framerate,
+            updated_at = excluded.updated_at",
+        params![subtitle_id, timing.offset_ms, timing.scale, timing.source_framerate, timing.target_framerate],
+    )?;
+    Ok(())
+}
+
+/// Stretch and shift a track so the two cue times land where they should.
+/// The points are on top of the track's current correction. The result is
+/// stored as the track's correction, or with `write` baked into a new track
+/// next to the original, which keeps its timing.
+pub fn resync_subtitle_track(conn: &Connection, subtitle_id: i64, points: [SyncPoint; 2], write: bool) -> Result<ResyncResult, SubtitleError> {
+    let [first, second] = points;
+    if first.shown_ms == second.shown_ms {
+        return Err(SubtitleError::InvalidTiming("sync points need two different cue times".to_string()));
+    }
+    let stretch = (second.wanted_ms - first.wanted_ms) as f64 / (second.shown_ms - first.shown_ms) as f64;
+    let shift = first.wanted_ms as f64 - first.shown_ms as f64 * stretch;
+
+    // shown = t * factor + offset, so wanted = t * factor * stretch + offset * stretch + shift
+    let current = get_subtitle_timing(conn, subtitle_id)?;
+    let timing = SubtitleTiming {
+        offset_ms: (current.offset_ms as f64 * stretch + shift).round() as i64,
+        scale: current.scale * stretch,
+        ..current
+    };
+    timing.validate()?;
+
+    if !write {
+        set_subtitle_timing(conn, subtitle_id, &timing)?;
+        return Ok(ResyncResult { timing, written_track_id: None });
+    }
+    let (track, mut document) = read_track(conn, subtitle_id)?;
+    timing.apply(&mut document);
+    let written_track_id = write_track_copy(conn, &track, &document)?;
+    Ok(ResyncResult { timing, written_track_id: Some(written_track_id) })
+}
+
+/// Write a track with its correction baked in as a new track, returning its id
+pub fn save_adjusted_subtitle_track(conn: &Connection, subtitle_id: i64) -> Result<i64, SubtitleError> {
+    let (track, mut document) = read_track(conn, subtitle_id)?;
+    get_subtitle_timing(conn, subtitle_id)?.apply(&mut document);
+    write_track_copy(conn, &track, &document)
+}
+
+/// SubRip stays SubRip; everything else is written as WebVTT, which keeps
+/// the styles
+fn write_track_copy(conn: &Connection, track: &SubtitleTrack, document: &SubtitleDocument) -> Result<i64, SubtitleError> {
+    let (extension, content) = match document.format {
+        SubtitleFormat::Srt => ("srt", document.to_srt()),
+        _ => ("vtt", document.to_webvtt()),
+    };
+    let source = Path::new(&track.file_path);
+    let directory = source.parent().unwrap_or(Path::new(""));
+    let stem = source.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let mut target = directory.join(format!("{}.synced.{}", stem, extension));
+    let mut copy = 2;
+    while target.exists() {
+        target = directory.join(format!("{}.synced-{}.{}", stem, copy, extension));
+        copy += 1;
+    }
+    fs::write(&target, content)?;
+
+    let label = format!("{} (synced)", track.label.as_deref().or(track.language.as_deref()).unwrap_or("Subtitles"));
+    Ok(add_subtitle_track(
+        conn,
+        track.media_id,
+        &target.to_string_lossy(),
+        track.language.as_deref(),
+        Some(&label),
+        Some(extension),
+        false,
+        None,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use crate::db::test_support::{self, TestMedia};
+    use crate::subtitles::load_subtitle_track;
+
+    const SRT: &str = "1\n00:00:10,000 --> 00:00:12,000\n<i>First</i>\n\n2\n00:01:40,000 --> 00:01:42,000\nSecond\n";
+
+    fn add_track(conn: &Connection, path: &Path) -> i64 {
+        fs::write(path, SRT).unwrap();
+        let media_id = test_support::add_media(conn, &TestMedia::default());
+        add_subtitle_track(conn, media_id, &path.to_string_lossy(), Some("en"), Some("English"), Some("srt"), false, None).unwrap()
+    }
+
+    fn times(conn: &Connection, subtitle_id: i64) -> Vec<(i64, i64)> {
+        load_subtitle_track(conn, subtitle_id).unwrap().cues.iter().map(|c| (c.start, c.end)).collect()
+    }
+
+    #[test]
+    fn test_offset_and_framerate_are_applied_on_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db().unwrap();
+        let id = add_track(&conn, &dir.path().join("movie.en.srt"));
+
+        let timing = SubtitleTiming { offset_ms: -10_500, ..Default::default() };
+        set_subtitle_timing(&conn, id, &timing).unwrap();
+        assert_eq!(get_subtitle_timing(&conn, id).unwrap(), timing);
+        // The first cue now starts before the video and is cut at zero
+        assert_eq!(times(&conn, id), vec![(0, 1_500), (89_500, 91_500)]);
+
+        let timing = SubtitleTiming { source_framerate: Some(25.0), target_framerate: Some(23.976), ..Default::default() };
+        set_subtitle_timing(&conn, id, &timing).unwrap();
+        assert_eq!(times(&conn, id), vec![(10_427, 12_513), (104_271, 106_356)]);
+
+        let invalid = SubtitleTiming { source_framerate: Some(25.0), ..Default::default() };
+        assert!(matches!(set_subtitle_timing(&conn, id, &invalid), Err(SubtitleError::InvalidTiming(_))));
+        set_subtitle_timing(&conn, id, &SubtitleTiming::default()).unwrap();
+        assert_eq!(times(&conn, id), vec![(10_000, 12_000), (100_000, 102_000)]);
+    }
+
+    #[test]
+    fn test_two_point_resync() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db().unwrap();
+        let id = add_track(&conn, &dir.path().join("movie.en.srt"));
+        set_subtitle_timing(&conn, id, &SubtitleTiming { offset_ms: 1000, ..Default::default() }).unwrap();
+
+        // Shown at 11s and 101s, but should be at 12s and 112s
+        let points = [SyncPoint { shown_ms: 11_000, wanted_ms: 12_000 }, SyncPoint { shown_ms: 101_000, wanted_ms: 112_000 }];
+        let written = resync_subtitle_track(&conn, id, points, true).unwrap();
+        let new_id = written.written_track_id.unwrap();
+        assert_eq!(times(&conn, new_id), vec![(12_000, 14_222), (112_000, 114_222)]);
+        // Writing a copy leaves the original as it was
+        assert_eq!(get_subtitle_timing(&conn, id).unwrap().offset_ms, 1000);
+        let copy = fs::read_to_string(dir.path().join("movie.en.synced.srt")).unwrap();
+        assert!(copy.starts_with("1\n00:00:12,000 --> 00:00:14,222\n<i>First</i>\n\n"));
+
+        let stored = resync_subtitle_track(&conn, id, points, false).unwrap();
+        assert_eq!(stored.timing, written.timing);
+        assert_eq!(stored.written_track_id, None);
+        assert_eq!(times(&conn, id), times(&conn, new_id));
+
+        let same = [points[0], points[0]];
+        assert!(matches!(resync_subtitle_track(&conn, id, same, false), Err(SubtitleError::InvalidTiming(_))));
+    }
+}
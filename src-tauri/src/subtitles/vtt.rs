@@ -0,0 +1,70 @@
+//! WebVTT input. Cues are kept as they are, with their identifiers and
+//! settings; STYLE blocks are kept, NOTE and REGION blocks dropped.
+
+use super::{Cue, parse_timestamp};
+
+pub fn parse(text: &str, styles: &mut Vec<String>) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    // The first block is the WEBVTT header
+    for block in text.split("\n\n").skip(1).map(|b| b.trim_matches('\n')).filter(|b| !b.trim().is_empty()) {
+        let mut lines = block.lines();
+        let first = lines.next().unwrap_or_default();
+        if first.starts_with("NOTE") || first.starts_with("REGION") {
+            continue;
+        }
+        if first.trim() == "STYLE" {
+            styles.extend(lines.map(String::from));
+            continue;
+        }
+
+        let (id, timing) = if first.contains("-->") {
+            (None, first)
+        } else {
+            match lines.next() {
+                Some(timing) if timing.contains("-->") => (Some(first.to_string()), timing),
+                _ => continue,
+            }
+        };
+        let Some((start, rest)) = timing.split_once("-->") else { continue };
+        let rest = rest.trim();
+        let (end, settings) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let (Some(start), Some(end)) = (parse_timestamp(start), parse_timestamp(end)) else {
+            continue;
+        };
+        let settings = settings.trim();
+        cues.push(Cue {
+            id,
+            start,
+            end,
+            text: lines.collect::<Vec<_>>().join("\n"),
+            settings: (!settings.is_empty()).then(|| settings.to_string()),
+        });
+    }
+    cues
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::subtitles::{SubtitleDocument, SubtitleFormat};
+
+    #[test]
+    fn test_webvtt_round_trip() {
+        let text = "WEBVTT - a title\n\nSTYLE\n::cue(.loud) { font-weight: bold; }\n\nNOTE a comment\n\n\
+                    intro\n00:01.000 --> 00:02.000 line:0 align:left\n<c.loud>Hi</c> &amp; bye\n\n\
+                    00:00:03.000 --> 00:00:04.000\n<v Joe>Two\nlines\n";
+        let document = SubtitleDocument::parse(text, None).unwrap();
+        assert_eq!(document.format, SubtitleFormat::WebVtt);
+        assert_eq!(document.styles, vec!["::cue(.loud) { font-weight: bold; }".to_string()]);
+        assert_eq!(document.cues.len(), 2);
+        assert_eq!(document.cues[0].id.as_deref(), Some("intro"));
+        assert_eq!(document.cues[0].settings.as_deref(), Some("line:0 align:left"));
+        assert_eq!(document.cues[1].text, "<v Joe>Two\nlines");
+        assert_eq!(
+            document.to_webvtt(),
+            "WEBVTT\n\nSTYLE\n::cue(.loud) { font-weight: bold; }\n\n\
+             intro\n00:00:01.000 --> 00:00:02.000 line:0 align:left\n<c.loud>Hi</c> &amp; bye\n\n\
+             00:00:03.000 --> 00:00:04.000\n<v Joe>Two\nlines\n\n"
+        );
+        assert_eq!(SubtitleDocument::parse(&document.to_webvtt(), None).unwrap().cues, document.cues);
+    }
+}
@@ -0,0 +1,419 @@
+//! Offline TMDB bundles: the cached `tmdb_*` tables exported from an online
+//! machine, as JSON lines or a standalone SQLite file, and imported on an
+//! air-gapped one. Bundles never contain library data or API keys.
+
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, OpenFlags, params_from_iter};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use super::TmdbError;
+
+const BUNDLE_NAME: &str = "cinevault-tmdb";
+const BUNDLE_VERSION: i64 = 1;
+
+/// A bundled table and the columns an imported row is merged on
+struct BundleTable {
+    name: &'static str,
+    key: &'static [&'static str],
+    /// Rows belong to a title and are replaced as a set (no natural key)
+    per_title: bool,
+}
+
+/// Parents before children, so foreign keys hold during import
+const BUNDLE_TABLES: &[BundleTable] = &[
+    BundleTable { name: "tmdb_metadata", key: &["tmdb_id", "media_type"], per_title: false },
+    BundleTable { name: "tmdb_cast", key: &[], per_title: true },
+    BundleTable { name: "tmdb_images", key: &[], per_title: true },
+    BundleTable { name: "tmdb_tv_shows", key: &["tmdb_id"], per_title: false },
+    BundleTable { name: "tmdb_seasons", key: &["tmdb_show_id", "season_number"], per_title: false },
+    BundleTable { name: "tmdb_episodes", key: &["tmdb_show_id", "season_number", "episode_number"], per_title: false },
+    BundleTable { name: "tmdb_collections", key: &["tmdb_id"], per_title: false },
+    BundleTable { name: "tmdb_collection_items", key: &["collection_id", "tmdb_movie_id"], per_title: false },
+];
+
+/// Columns that only make sense on the machine that wrote them
+const LOCAL_COLUMNS: &[&str] = &["id", "local_path"];
+
+type Row = Vec<(String, SqlValue)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BundleFormat {
+    Jsonl,
+    Sqlite,
+}
+
+impl BundleFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_string_lossy().to_lowercase().as_str() {
+            "jsonl" | "ndjson" | "json" => Some(Self::Jsonl),
+            "sqlite" | "sqlite3" | "db" => Some(Self::Sqlite),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct BundleReport {
+    /// Rows written or merged, per table
+    pub rows: BTreeMap<String, usize>,
+    /// Rows for tables that aren't part of a bundle
+    pub skipped: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct BundleImportReport {
+    pub bundle: BundleReport,
+    /// Result of re-running the matcher against the imported records
+    pub matches: super::MatchReport,
+    /// Episode files linked against the imported episodes
+    pub episodes: super::EpisodeLinkReport,
+}
+
+fn table_spec(name: &str) -> Option<&'static BundleTable> {
+    BUNDLE_TABLES.iter().find(|t| t.name == name)
+}
+
+/// Columns of a table that a bundle carries
+fn bundle_columns(conn: &Connection, table: &str) -> Result<Vec<String>, TmdbError> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(columns.into_iter().filter(|c| !LOCAL_COLUMNS.contains(&c.as_str())).collect())
+}
+
+fn read_rows(conn: &Connection, table: &str, columns: &[String]) -> Result<Vec<Row>, TmdbError> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM {}", columns.join(", "), table))?;
+    let rows = stmt
+        .query_map([], |row| {
+            columns
+                .iter()
+                .enumerate()
+                .map(|(i, column)| Ok((column.clone(), row.get::<_, SqlValue>(i)?)))
+                .collect::<Result<Row, _>>()
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+fn to_json(value: &SqlValue) -> Value {
+    match value {
+        SqlValue::Null | SqlValue::Blob(_) => Value::Null,
+        SqlValue::Integer(i) => Value::from(*i),
+        SqlValue::Real(f) => Value::from(*f),
+        SqlValue::Text(s) => Value::from(s.as_str()),
+    }
+}
+
+fn from_json(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => n.as_i64().map(SqlValue::Integer).unwrap_or_else(|| SqlValue::Real(n.as_f64().unwrap_or(0.0))),
+        Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+/// Write every cached TMDB table to `path`. The format follows the extension
+/// unless given. Returns row counts per table.
+pub fn export_bundle(conn: &Connection, path: &Path, format: Option<BundleFormat>) -> Result<BundleReport, TmdbError> {
+    let format = format
+        .or_else(|| BundleFormat::from_path(path))
+        .ok_or_else(|| TmdbError::Invalid(format!("can't tell bundle format from {}", path.display())))?;
+    let mut report = BundleReport::default();
+
+    match format {
+        BundleFormat::Jsonl => {
+            let mut out = BufWriter::new(File::create(path)?);
+            let header = serde_json::json!({
+                "bundle": BUNDLE_NAME,
+                "version": BUNDLE_VERSION,
+                "exported_at": chrono::Utc::now().to_rfc3339(),
+            });
+            writeln!(out, "{}", header)?;
+
+            for table in BUNDLE_TABLES {
+                let columns = bundle_columns(conn, table.name)?;
+                let rows = read_rows(conn, table.name, &columns)?;
+                for row in &rows {
+                    let fields: Map<String, Value> = row.iter().map(|(k, v)| (k.clone(), to_json(v))).collect();
+                    writeln!(out, "{}", serde_json::json!({ "table": table.name, "row": fields }))?;
+                }
+                report.rows.insert(table.name.to_string(), rows.len());
+            }
+            out.flush()?;
+        }
+        BundleFormat::Sqlite => {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+            let mut bundle = Connection::open(path)?;
+            let tx = bundle.transaction()?;
+            tx.execute_batch(&format!(
+                "CREATE TABLE bundle_info (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+                 INSERT INTO bundle_info VALUES ('bundle', '{}'), ('version', '{}'), ('exported_at', '{}');",
+                BUNDLE_NAME,
+                BUNDLE_VERSION,
+                chrono::Utc::now().to_rfc3339(),
+            ))?;
+
+            for table in BUNDLE_TABLES {
+                let create: String = conn.query_row(
+                    "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                    [table.name],
+                    |row| row.get(0),
+                )?;
+                tx.execute_batch(&create)?;
+
+                let columns = bundle_columns(conn, table.name)?;
+                let rows = read_rows(conn, table.name, &columns)?;
+                let sql = format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    table.name,
+                    columns.join(", "),
+                    vec!["?"; columns.len()].join(", ")
+                );
+                let mut stmt = tx.prepare(&sql)?;
+                for row in &rows {
+                    stmt.execute(params_from_iter(row.iter().map(|(_, v)| v)))?;
+                }
+                report.rows.insert(table.name.to_string(), rows.len());
+            }
+            tx.commit()?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Rows of a bundle file, tagged with their table, in file order
+fn read_bundle(path: &Path) -> Result<Vec<(String, Row)>, TmdbError> {
+    let mut magic = [0u8; 16];
+    let is_sqlite = File::open(path)?.read(&mut magic)? == 16 && &magic == b"SQLite format 3\0";
+
+    let mut rows = Vec::new();
+    if is_sqlite {
+        let bundle = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let version: Option<String> = bundle
+            .query_row("SELECT value FROM bundle_info WHERE key = 'version'", [], |row| row.get(0))
+            .ok();
+        check_version(version.and_then(|v| v.parse().ok()))?;
+
+        let mut tables = bundle.prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name LIKE 'tmdb_%'")?;
+        let present: Vec<String> = tables.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?;
+        // Parents first, whatever order the file lists them in
+        for table in BUNDLE_TABLES.iter().filter(|t| present.iter().any(|p| p == t.name)) {
+            let columns = bundle_columns(&bundle, table.name)?;
+            for row in read_rows(&bundle, table.name, &columns)? {
+                rows.push((table.name.to_string(), row));
+            }
+        }
+        for table in present.iter().filter(|p| table_spec(p).is_none()) {
+            rows.push((table.clone(), Vec::new()));
+        }
+        return Ok(rows);
+    }
+
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Value = serde_json::from_str(&line)?;
+        if record.get("bundle").is_some() {
+            check_version(record.get("version").and_then(Value::as_i64))?;
+            continue;
+        }
+        let (Some(table), Some(fields)) = (
+            record.get("table").and_then(Value::as_str),
+            record.get("row").and_then(Value::as_object),
+        ) else {
+            return Err(TmdbError::Invalid(format!("bundle line isn't a table row: {}", line)));
+        };
+        rows.push((table.to_string(), fields.iter().map(|(k, v)| (k.clone(), from_json(v))).collect()));
+    }
+    Ok(rows)
+}
+
+fn check_version(version: Option<i64>) -> Result<(), TmdbError> {
+    match version {
+        Some(v) if v > BUNDLE_VERSION => Err(TmdbError::Invalid(format!(
+            "bundle version {} is newer than this app supports ({})",
+            v, BUNDLE_VERSION
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Merge a bundle (JSON lines or SQLite, detected from the content) into
+/// the TMDB cache, then re-run the matcher. Rows replace cached ones with the
+/// same key; cast and images replace a title's existing set. Everything is
+/// applied in one transaction.
+pub fn import_bundle(conn: &Connection, path: &Path) -> Result<BundleImportReport, TmdbError> {
+    let rows = read_bundle(path)?;
+    let mut report = BundleReport::default();
+
+    let tx = conn.unchecked_transaction()?;
+    let mut known_columns: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    let mut cleared: HashSet<(&str, i64, String)> = HashSet::new();
+
+    for (table_name, row) in rows {
+        let Some(table) = table_spec(&table_name) else {
+            report.skipped += 1;
+            continue;
+        };
+        if !known_columns.contains_key(table.name) {
+            known_columns.insert(table.name, bundle_columns(&tx, table.name)?);
+        }
+        let allowed = &known_columns[table.name];
+        // Column names are only ever taken from our own schema
+        let row: Row = row.into_iter().filter(|(column, _)| allowed.contains(column)).collect();
+        if row.is_empty() {
+            report.skipped += 1;
+            continue;
+        }
+
+        if table.per_title {
+            let field = |name: &str| row.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone());
+            let Some(SqlValue::Integer(media_id)) = field("tmdb_media_id") else {
+                report.skipped += 1;
+                continue;
+            };
+            let media_type = match field("media_type") {
+                Some(SqlValue::Text(t)) => t,
+                _ => "movie".to_string(),
+            };
+            if cleared.insert((table.name, media_id, media_type.clone())) {
+                tx.execute(
+                    &format!("DELETE FROM {} WHERE tmdb_media_id = ?1 AND media_type = ?2", table.name),
+                    rusqlite::params![media_id, media_type],
+                )?;
+            }
+        }
+
+        let columns: Vec<&str> = row.iter().map(|(k, _)| k.as_str()).collect();
+        let mut sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            table.name,
+            columns.join(", "),
+            vec!["?"; columns.len()].join(", ")
+        );
+        if !table.key.is_empty() {
+            let updates: Vec<String> = columns
+                .iter()
+                .filter(|c| !table.key.contains(c))
+                .map(|c| format!("{} = excluded.{}", c, c))
+                .collect();
+            sql.push_str(&format!(" ON CONFLICT({}) DO ", table.key.join(", ")));
+            sql.push_str(&if updates.is_empty() { "NOTHING".to_string() } else { format!("UPDATE SET {}", updates.join(", ")) });
+        }
+        tx.prepare_cached(&sql)?.execute(params_from_iter(row.iter().map(|(_, v)| v)))?;
+        *report.rows.entry(table.name.to_string()).or_default() += 1;
+    }
+
+    let matches = super::match_library(&tx, super::AUTO_ACCEPT_CONFIDENCE)?;
+    let episodes = super::link_episodes(&tx, None)?;
+    tx.commit()?;
+
+    Ok(BundleImportReport { bundle: report, matches, episodes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use rusqlite::params;
+
+    fn seed(conn: &Connection) {
+        conn.execute_batch(
+            "INSERT INTO tmdb_metadata (tmdb_id, media_type, title, release_date, runtime, genres_json)
+                VALUES (603, 'movie', 'The Matrix', '1999-03-30', 136, '[\"Action\"]');
+             INSERT INTO tmdb_metadata (tmdb_id, media_type, title, release_date, number_of_episodes)
+                VALUES (1396, 'tv', 'Breaking Bad', '2008-01-20', 62);
+             INSERT INTO tmdb_cast (tmdb_media_id, media_type, tmdb_person_id, name, character, role, order_position)
+                VALUES (603, 'movie', 6384, 'Keanu Reeves', 'Neo', 'cast', 0);
+             INSERT INTO tmdb_images (tmdb_media_id, media_type, image_type, file_path, local_path, is_primary)
+                VALUES (603, 'movie', 'poster', '/p1.jpg', '/home/me/.cache/p1.jpg', 1);
+             INSERT INTO tmdb_tv_shows (tmdb_id, show_name) VALUES (1396, 'Breaking Bad');
+             INSERT INTO tmdb_seasons (tmdb_show_id, season_number, episode_count) VALUES (1396, 1, 7);
+             INSERT INTO tmdb_episodes (tmdb_show_id, season_number, episode_number, name) VALUES (1396, 1, 1, 'Pilot');
+             INSERT INTO tmdb_collections (tmdb_id, name) VALUES (2344, 'The Matrix Collection');
+             INSERT INTO tmdb_collection_items (collection_id, tmdb_movie_id) VALUES (2344, 603);",
+        )
+        .unwrap();
+    }
+
+    fn count(conn: &Connection, table: &str) -> i64 {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_bundle_round_trip() -> Result<(), TmdbError> {
+        let dir = tempfile::tempdir()?;
+        let source = init_db()?;
+        seed(&source);
+
+        for file in ["bundle.jsonl", "bundle.sqlite"] {
+            let path = dir.path().join(file);
+            let exported = export_bundle(&source, &path, None)?;
+            assert_eq!(exported.rows["tmdb_cast"], 1);
+
+            let target = init_db()?;
+            target.execute(
+                "INSERT INTO media_files (file_path, file_hash, file_name, file_size, media_type, title, year, last_modified)
+                 VALUES ('/m/The Matrix (1999).mkv', 'h', 'The Matrix (1999).mkv', 1, 'movie', 'The Matrix', 1999, datetime('now'))",
+                [],
+            )?;
+
+            let imported = import_bundle(&target, &path)?;
+            assert_eq!(imported.bundle.rows.values().sum::<usize>(), 9, "{}", file);
+            assert_eq!(imported.matches.auto_matched, 1);
+            for table in BUNDLE_TABLES {
+                assert_eq!(count(&target, table.name), count(&source, table.name), "{} in {}", table.name, file);
+            }
+            let local_path: Option<String> = target.query_row("SELECT local_path FROM tmdb_images", [], |row| row.get(0))?;
+            assert_eq!(local_path, None);
+
+            // Importing again merges rather than duplicating
+            import_bundle(&target, &path)?;
+            assert_eq!(count(&target, "tmdb_cast"), 1);
+            assert_eq!(count(&target, "tmdb_episodes"), 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_rejects_foreign_rows() -> Result<(), TmdbError> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("bundle.jsonl");
+        std::fs::write(
+            &path,
+            concat!(
+                "{\"bundle\": \"cinevault-tmdb\", \"version\": 1}\n",
+                "{\"table\": \"tmdb_metadata\", \"row\": {\"tmdb_id\": 1, \"media_type\": \"movie\", \"title\": \"Heat\", \"bogus; DROP TABLE x\": 1}}\n",
+                "{\"table\": \"settings\", \"row\": {\"key\": \"tmdb_api_key\", \"value\": \"stolen\"}}\n",
+                "\n",
+                "{\"table\": \"tmdb_metadata\", \"row\": {\"tmdb_id\": 1, \"media_type\": \"movie\", \"title\": \"Heat (1995)\"}}\n",
+            ),
+        )?;
+
+        let conn = init_db()?;
+        let report = import_bundle(&conn, &path)?;
+        assert_eq!(report.bundle.skipped, 1);
+        assert_eq!(report.bundle.rows["tmdb_metadata"], 2);
+        let title: String = conn.query_row("SELECT title FROM tmdb_metadata WHERE tmdb_id = 1", [], |row| row.get(0))?;
+        assert_eq!(title, "Heat (1995)");
+        let key: String = conn.query_row("SELECT value FROM settings WHERE key = 'tmdb_api_key'", params![], |row| row.get(0))?;
+        assert_eq!(key, "");
+
+        std::fs::write(&path, "{\"bundle\": \"cinevault-tmdb\", \"version\": 99}\n")?;
+        assert!(matches!(import_bundle(&conn, &path), Err(TmdbError::Invalid(_))));
+        Ok(())
+    }
+}
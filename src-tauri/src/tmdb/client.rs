@@ -0,0 +1,904 @@
+//! TMDB API client. Requests go through a [`Transport`] so tests can serve
+//! fixtures; responses are revalidated with ETags and written into the
+//! `tmdb_*` tables.
+
+use rusqlite::{Connection, OptionalExtension, params};
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::TmdbError;
+
+pub const DEFAULT_BASE_URL: &str = "https://api.themoviedb.org/3";
+/// TMDB allows roughly 40 requests per 10 seconds per client
+const DEFAULT_MIN_INTERVAL: Duration = Duration::from_millis(250);
+const MAX_RETRIES: u32 = 3;
+/// Longest Retry-After we'll honour before giving up
+const MAX_RETRY_AFTER_SECS: u64 = 30;
+/// Cast members kept per title
+const MAX_CAST: usize = 20;
+
+/// Where the client gets its database from: a connection of its own, or a
+/// shared one that is locked only while the database is used, so requests
+/// and rate-limit waits don't hold it
+pub trait ConnectionSource {
+    fn with_conn<R>(&self, f: impl FnOnce(&Connection) -> R) -> R;
+}
+
+impl ConnectionSource for Connection {
+    fn with_conn<R>(&self, f: impl FnOnce(&Connection) -> R) -> R {
+        f(self)
+    }
+}
+
+impl ConnectionSource for Mutex<Connection> {
+    fn with_conn<R>(&self, f: impl FnOnce(&Connection) -> R) -> R {
+        f(&self.lock().unwrap())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Performs GET requests. Non-2xx statuses are returned as responses, not errors.
+pub trait Transport: Send + Sync {
+    fn get(&self, url: &str, headers: &[(String, String)]) -> Result<HttpResponse, TmdbError>;
+}
+
+pub struct UreqTransport {
+    agent: ureq::Agent,
+}
+
+impl Default for UreqTransport {
+    fn default() -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(Duration::from_secs(30)).build(),
+        }
+    }
+}
+
+impl Transport for UreqTransport {
+    fn get(&self, url: &str, headers: &[(String, String)]) -> Result<HttpResponse, TmdbError> {
+        let mut request = self.agent.get(url);
+        for (name, value) in headers {
+            request = request.set(name, value);
+        }
+
+        let response = match request.call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(TmdbError::Http(e.to_string())),
+        };
+
+        let status = response.status();
+        let headers = response
+            .headers_names()
+            .into_iter()
+            .filter_map(|name| response.header(&name).map(|value| (name.clone(), value.to_string())))
+            .collect();
+        let body = response.into_string().map_err(|e| TmdbError::Http(e.to_string()))?;
+
+        Ok(HttpResponse { status, headers, body })
+    }
+}
+
+/// TMDB settings from the `settings` table
+#[derive(Debug, Clone, PartialEq)]
+pub struct TmdbConfig {
+    pub enabled: bool,
+    pub api_key: String,
+    pub language: String,
+}
+
+pub fn load_config(conn: &Connection) -> Result<TmdbConfig, TmdbError> {
+    let setting = |key: &str| -> Result<Option<String>, TmdbError> {
+        Ok(conn
+            .query_row("SELECT value FROM settings WHERE key = ?1", params![key], |row| row.get(0))
+            .optional()?)
+    };
+
+    Ok(TmdbConfig {
+        enabled: setting("tmdb_enabled")?.map(|v| v == "true").unwrap_or(false),
+        api_key: setting("tmdb_api_key")?.unwrap_or_default(),
+        language: setting("tmdb_language")?.filter(|v| !v.is_empty()).unwrap_or_else(|| "en-US".to_string()),
+    })
+}
+
+pub struct TmdbClient<T: Transport = UreqTransport> {
+    transport: T,
+    api_key: String,
+    language: String,
+    base_url: String,
+    min_interval: Duration,
+    last_request: Mutex<Option<Instant>>,
+}
+
+impl<T: Transport> TmdbClient<T> {
+    /// Build a client from the stored settings. Fails with `Disabled` when
+    /// `tmdb_enabled` is off, before the transport is ever used.
+    pub fn from_settings(conn: &Connection, transport: T) -> Result<Self, TmdbError> {
+        let config = load_config(conn)?;
+        if !config.enabled {
+            return Err(TmdbError::Disabled);
+        }
+        if config.api_key.trim().is_empty() {
+            return Err(TmdbError::MissingApiKey);
+        }
+
+        Ok(Self {
+            transport,
+            api_key: config.api_key.trim().to_string(),
+            language: config.language,
+            base_url: DEFAULT_BASE_URL.to_string(),
+            min_interval: DEFAULT_MIN_INTERVAL,
+            last_request: Mutex::new(None),
+        })
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    /// Space requests at least `min_interval` apart
+    fn throttle(&self) {
+        let mut last = self.last_request.lock().unwrap();
+        if let Some(previous) = *last {
+            let elapsed = previous.elapsed();
+            if elapsed < self.min_interval {
+                std::thread::sleep(self.min_interval - elapsed);
+            }
+        }
+        *last = Some(Instant::now());
+    }
+
+    /// v4 read access tokens are JWTs and go in a header; v3 keys go in the query
+    fn uses_bearer_token(&self) -> bool {
+        self.api_key.starts_with("eyJ")
+    }
+
+    /// GET a path, revalidating any cached copy with its ETag and retrying
+    /// on 429/503 as the server directs
+    fn get_json(&self, db: &impl ConnectionSource, path: &str, query: &[(&str, String)]) -> Result<Value, TmdbError> {
+        let mut params: Vec<(&str, String)> = query.to_vec();
+        params.push(("language", self.language.clone()));
+        params.sort();
+        let query_string = params
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, encode_component(value)))
+            .collect::<Vec<_>>()
+            .join("&");
+        // The cache key never contains the API key
+        let request = format!("{}?{}", path, query_string);
+
+        let mut url = format!("{}{}", self.base_url, request);
+        let mut headers = vec![("Accept".to_string(), "application/json".to_string())];
+        if self.uses_bearer_token() {
+            headers.push(("Authorization".to_string(), format!("Bearer {}", self.api_key)));
+        } else {
+            url.push_str(&format!("&api_key={}", encode_component(&self.api_key)));
+        }
+
+        let cached: Option<(Option<String>, String)> = db.with_conn(|conn| {
+            conn.query_row(
+                "SELECT etag, body FROM tmdb_http_cache WHERE request = ?1",
+                params![request],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+        })?;
+        if let Some(etag) = cached.as_ref().and_then(|(etag, _)| etag.clone()) {
+            headers.push(("If-None-Match".to_string(), etag));
+        }
+
+        let mut attempt = 0;
+        let response = loop {
+            self.throttle();
+            let response = self.transport.get(&url, &headers)?;
+            if !matches!(response.status, 429 | 503) || attempt >= MAX_RETRIES {
+                break response;
+            }
+            attempt += 1;
+            let wait = response
+                .header("Retry-After")
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(attempt as u64);
+            if wait > MAX_RETRY_AFTER_SECS {
+                return Err(TmdbError::RateLimited);
+            }
+            std::thread::sleep(Duration::from_secs(wait));
+        };
+
+        let body = match response.status {
+            200..=299 => {
+                db.with_conn(|conn| {
+                    conn.execute(
+                        "INSERT INTO tmdb_http_cache (request, etag, body, fetched_at) VALUES (?1, ?2, ?3, ?4)
+                         ON CONFLICT(request) DO UPDATE SET etag = excluded.etag, body = excluded.body, fetched_at = excluded.fetched_at",
+                        params![request, response.header("ETag"), response.body, chrono::Utc::now().to_rfc3339()],
+                    )
+                })?;
+                response.body
+            }
+            304 => match cached {
+                Some((_, body)) => {
+                    db.with_conn(|conn| {
+                        conn.execute(
+                            "UPDATE tmdb_http_cache SET fetched_at = ?1 WHERE request = ?2",
+                            params![chrono::Utc::now().to_rfc3339(), request],
+                        )
+                    })?;
+                    body
+                }
+                None => return Err(TmdbError::Status(304)),
+            },
+            401 => return Err(TmdbError::Unauthorized),
+            404 => return Err(TmdbError::NotFound(path.to_string())),
+            429 | 503 => return Err(TmdbError::RateLimited),
+            status => return Err(TmdbError::Status(status)),
+        };
+
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Search for a movie or show and cache the results as `tmdb_metadata`
+    /// rows for the matcher. Returns the TMDB ids found, best first.
+    pub fn search(&self, db: &impl ConnectionSource, media_type: &str, query: &str, year: Option<i32>) -> Result<Vec<i64>, TmdbError> {
+        let (path, year_param) = match media_type {
+            "movie" => ("/search/movie", "year"),
+            "tv" => ("/search/tv", "first_air_date_year"),
+            other => return Err(TmdbError::Invalid(format!("unknown media type '{}'", other))),
+        };
+        let mut params = vec![("query", query.to_string())];
+        if let Some(year) = year {
+            params.push((year_param, year.to_string()));
+        }
+
+        let body = self.get_json(db, path, &params)?;
+        db.with_conn(|conn| {
+            let mut ids = Vec::new();
+            for result in body.get("results").and_then(Value::as_array).into_iter().flatten() {
+                let Some(tmdb_id) = result.get("id").and_then(Value::as_i64) else { continue };
+                conn.execute(
+                    "INSERT INTO tmdb_metadata
+                        (tmdb_id, media_type, title, original_title, overview, release_date, vote_average, vote_count, popularity, fetched_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                     ON CONFLICT(tmdb_id, media_type) DO UPDATE SET
+                        title = excluded.title,
+                        original_title = excluded.original_title,
+                        overview = excluded.overview,
+                        release_date = excluded.release_date,
+                        vote_average = excluded.vote_average,
+                        vote_count = excluded.vote_count,
+                        popularity = excluded.popularity",
+                    params![
+                        tmdb_id,
+                        media_type,
+                        str_field(result, &["title", "name"]),
+                        str_field(result, &["original_title", "original_name"]),
+                        str_field(result, &["overview"]),
+                        str_field(result, &["release_date", "first_air_date"]),
+                        result.get("vote_average").and_then(Value::as_f64),
+                        result.get("vote_count").and_then(Value::as_i64),
+                        result.get("popularity").and_then(Value::as_f64),
+                        chrono::Utc::now().to_rfc3339(),
+                    ],
+                )?;
+                ids.push(tmdb_id);
+            }
+            Ok(ids)
+        })
+    }
+
+    /// Fetch full movie details with credits, images, keywords and franchise
+    pub fn fetch_movie(&self, db: &impl ConnectionSource, tmdb_id: i64) -> Result<(), TmdbError> {
+        let body = self.get_json(
+            db,
+            &format!("/movie/{}", tmdb_id),
+            &[
+                ("append_to_response", "credits,images,keywords,external_ids".to_string()),
+                ("include_image_language", "en,null".to_string()),
+            ],
+        )?;
+
+        db.with_conn(|conn| {
+            let tx = conn.unchecked_transaction()?;
+            store_details(&tx, tmdb_id, "movie", &body)?;
+
+            if let Some(collection) = body.get("belongs_to_collection").filter(|c| c.is_object()) {
+                if let (Some(collection_id), Some(name)) = (
+                    collection.get("id").and_then(Value::as_i64),
+                    collection.get("name").and_then(Value::as_str),
+                ) {
+                    tx.execute(
+                        "INSERT INTO tmdb_collections (tmdb_id, name, poster_path, backdrop_path) VALUES (?1, ?2, ?3, ?4)
+                         ON CONFLICT(tmdb_id) DO UPDATE SET
+                            name = excluded.name, poster_path = excluded.poster_path, backdrop_path = excluded.backdrop_path",
+                        params![
+                            collection_id,
+                            name,
+                            str_field(collection, &["poster_path"]),
+                            str_field(collection, &["backdrop_path"]),
+                        ],
+                    )?;
+                    tx.execute(
+                        "INSERT OR IGNORE INTO tmdb_collection_items (collection_id, tmdb_movie_id) VALUES (?1, ?2)",
+                        params![collection_id, tmdb_id],
+                    )?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    /// Fetch show details, credits, images and the season list
+    pub fn fetch_tv(&self, db: &impl ConnectionSource, tmdb_id: i64) -> Result<(), TmdbError> {
+        let body = self.get_json(
+            db,
+            &format!("/tv/{}", tmdb_id),
+            &[
+                ("append_to_response", "credits,images,keywords,external_ids".to_string()),
+                ("include_image_language", "en,null".to_string()),
+            ],
+        )?;
+
+        db.with_conn(|conn| {
+            let tx = conn.unchecked_transaction()?;
+            store_details(&tx, tmdb_id, "tv", &body)?;
+
+            let networks: Vec<&str> = names(&body, "networks", "name");
+            tx.execute(
+                "INSERT INTO tmdb_tv_shows
+                    (tmdb_id, show_name, first_air_date, last_air_date, in_production, next_episode_to_air_json, networks_json)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT(tmdb_id) DO UPDATE SET
+                    show_name = excluded.show_name,
+                    first_air_date = excluded.first_air_date,
+                    last_air_date = excluded.last_air_date,
+                    in_production = excluded.in_production,
+                    next_episode_to_air_json = excluded.next_episode_to_air_json,
+                    networks_json = excluded.networks_json",
+                params![
+                    tmdb_id,
+                    str_field(&body, &["name"]).unwrap_or_default(),
+                    str_field(&body, &["first_air_date"]),
+                    str_field(&body, &["last_air_date"]),
+                    body.get("in_production").and_then(Value::as_bool).unwrap_or(false) as i32,
+                    body.get("next_episode_to_air").filter(|v| !v.is_null()).map(|v| v.to_string()),
+                    serde_json::to_string(&networks)?,
+                ],
+            )?;
+
+            for season in body.get("seasons").and_then(Value::as_array).into_iter().flatten() {
+                let Some(number) = season.get("season_number").and_then(Value::as_i64) else { continue };
+                tx.execute(
+                    "INSERT INTO tmdb_seasons
+                        (tmdb_show_id, season_number, tmdb_id, name, overview, air_date, episode_count, poster_path)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                     ON CONFLICT(tmdb_show_id, season_number) DO UPDATE SET
+                        tmdb_id = excluded.tmdb_id,
+                        name = excluded.name,
+                        overview = excluded.overview,
+                        air_date = excluded.air_date,
+                        episode_count = excluded.episode_count,
+                        poster_path = excluded.poster_path",
+                    params![
+                        tmdb_id,
+                        number,
+                        season.get("id").and_then(Value::as_i64),
+                        str_field(season, &["name"]),
+                        str_field(season, &["overview"]),
+                        str_field(season, &["air_date"]),
+                        season.get("episode_count").and_then(Value::as_i64),
+                        str_field(season, &["poster_path"]),
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    /// Fetch one season's episodes
+    pub fn fetch_season(&self, db: &impl ConnectionSource, show_id: i64, season_number: i32) -> Result<(), TmdbError> {
+        let body = self.get_json(db, &format!("/tv/{}/season/{}", show_id, season_number), &[])?;
+        db.with_conn(|conn| {
+            for episode in body.get("episodes").and_then(Value::as_array).into_iter().flatten() {
+                let Some(number) = episode.get("episode_number").and_then(Value::as_i64) else { continue };
+                conn.execute(
+                    "INSERT INTO tmdb_episodes
+                        (tmdb_show_id, season_number, episode_number, tmdb_id, name, overview, air_date, runtime, still_path, vote_average)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                     ON CONFLICT(tmdb_show_id, season_number, episode_number) DO UPDATE SET
+                        tmdb_id = excluded.tmdb_id,
+                        name = excluded.name,
+                        overview = excluded.overview,
+                        air_date = excluded.air_date,
+                        runtime = excluded.runtime,
+                        still_path = excluded.still_path,
+                        vote_average = excluded.vote_average",
+                    params![
+                        show_id,
+                        season_number,
+                        number,
+                        episode.get("id").and_then(Value::as_i64),
+                        str_field(episode, &["name"]),
+                        str_field(episode, &["overview"]),
+                        str_field(episode, &["air_date"]),
+                        episode.get("runtime").and_then(Value::as_i64),
+                        str_field(episode, &["still_path"]),
+                        episode.get("vote_average").and_then(Value::as_f64),
+                    ],
+                )?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// First non-empty string among `keys`
+fn str_field(value: &Value, keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .filter_map(|key| value.get(*key).and_then(Value::as_str))
+        .find(|s| !s.is_empty())
+        .map(String::from)
+}
+
+/// `field` of every object in the array at `key`
+fn names<'a>(value: &'a Value, key: &str, field: &str) -> Vec<&'a str> {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|item| item.get(field).and_then(Value::as_str))
+        .collect()
+}
+
+/// Write a movie or show details response (with appended credits, images,
+/// keywords and external ids) into tmdb_metadata, tmdb_cast and tmdb_images.
+/// Cast and images are replaced wholesale, so callers run this inside a
+/// transaction
+fn store_details(conn: &Connection, tmdb_id: i64, media_type: &str, body: &Value) -> Result<(), TmdbError> {
+    // Movies list keywords under "keywords", shows under "results"
+    let keywords = body.get("keywords").map(|k| {
+        let key = if k.get("keywords").is_some() { "keywords" } else { "results" };
+        names(k, key, "name")
+    });
+    let languages = if media_type == "movie" {
+        names(body, "spoken_languages", "iso_639_1")
+    } else {
+        body.get("languages")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect()
+    };
+    let imdb_id = str_field(body, &["imdb_id"]).or_else(|| body.get("external_ids").and_then(|e| str_field(e, &["imdb_id"])));
+
+    conn.execute(
+        "INSERT INTO tmdb_metadata
+            (tmdb_id, media_type, title, original_title, overview, release_date, runtime, status, tagline,
+             vote_average, vote_count, popularity, imdb_id, genres_json, keywords_json, languages_json,
+             production_countries_json, number_of_seasons, number_of_episodes, episode_runtime_json, fetched_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21)
+         ON CONFLICT(tmdb_id, media_type) DO UPDATE SET
+            title = excluded.title,
+            original_title = excluded.original_title,
+            overview = excluded.overview,
+            release_date = excluded.release_date,
+            runtime = excluded.runtime,
+            status = excluded.status,
+            tagline = excluded.tagline,
+            vote_average = excluded.vote_average,
+            vote_count = excluded.vote_count,
+            popularity = excluded.popularity,
+            imdb_id = excluded.imdb_id,
+            genres_json = excluded.genres_json,
+            keywords_json = excluded.keywords_json,
+            languages_json = excluded.languages_json,
+            production_countries_json = excluded.production_countries_json,
+            number_of_seasons = excluded.number_of_seasons,
+            number_of_episodes = excluded.number_of_episodes,
+            episode_runtime_json = excluded.episode_runtime_json,
+            fetched_at = excluded.fetched_at",
+        params![
+            tmdb_id,
+            media_type,
+            str_field(body, &["title", "name"]),
+            str_field(body, &["original_title", "original_name"]),
+            str_field(body, &["overview"]),
+            str_field(body, &["release_date", "first_air_date"]),
+            body.get("runtime").and_then(Value::as_i64),
+            str_field(body, &["status"]),
+            str_field(body, &["tagline"]),
+            body.get("vote_average").and_then(Value::as_f64),
+            body.get("vote_count").and_then(Value::as_i64),
+            body.get("popularity").and_then(Value::as_f64),
+            imdb_id,
+            serde_json::to_string(&names(body, "genres", "name"))?,
+            keywords.map(|k| serde_json::to_string(&k)).transpose()?,
+            serde_json::to_string(&languages)?,
+            serde_json::to_string(&names(body, "production_countries", "iso_3166_1"))?,
+            body.get("number_of_seasons").and_then(Value::as_i64),
+            body.get("number_of_episodes").and_then(Value::as_i64),
+            body.get("episode_run_time").map(|v| v.to_string()),
+            chrono::Utc::now().to_rfc3339(),
+        ],
+    )?;
+
+    if let Some(credits) = body.get("credits") {
+        conn.execute(
+            "DELETE FROM tmdb_cast WHERE tmdb_media_id = ?1 AND media_type = ?2",
+            params![tmdb_id, media_type],
+        )?;
+
+        for member in credits.get("cast").and_then(Value::as_array).into_iter().flatten().take(MAX_CAST) {
+            let (Some(person_id), Some(name)) = (member.get("id").and_then(Value::as_i64), str_field(member, &["name"])) else {
+                continue;
+            };
+            conn.execute(
+                "INSERT INTO tmdb_cast (tmdb_media_id, media_type, tmdb_person_id, name, character, role, order_position, profile_path)
+                 VALUES (?1, ?2, ?3, ?4, ?5, 'cast', ?6, ?7)",
+                params![
+                    tmdb_id,
+                    media_type,
+                    person_id,
+                    name,
+                    str_field(member, &["character"]),
+                    member.get("order").and_then(Value::as_i64),
+                    str_field(member, &["profile_path"]),
+                ],
+            )?;
+        }
+
+        for member in credits.get("crew").and_then(Value::as_array).into_iter().flatten() {
+            let role = match member.get("job").and_then(Value::as_str) {
+                Some("Director") => "director",
+                Some("Screenplay" | "Writer" | "Teleplay") => "writer",
+                Some("Producer") => "producer",
+                _ => continue,
+            };
+            let (Some(person_id), Some(name)) = (member.get("id").and_then(Value::as_i64), str_field(member, &["name"])) else {
+                continue;
+            };
+            conn.execute(
+                "INSERT INTO tmdb_cast (tmdb_media_id, media_type, tmdb_person_id, name, role, profile_path)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![tmdb_id, media_type, person_id, name, role, str_field(member, &["profile_path"])],
+            )?;
+        }
+    }
+
+    if let Some(images) = body.get("images") {
+        conn.execute(
+            "DELETE FROM tmdb_images WHERE tmdb_media_id = ?1 AND media_type = ?2",
+            params![tmdb_id, media_type],
+        )?;
+
+        for (key, image_type, primary) in [("posters", "poster", "poster_path"), ("backdrops", "backdrop", "backdrop_path")] {
+            let primary_path = str_field(body, &[primary]);
+            for image in images.get(key).and_then(Value::as_array).into_iter().flatten() {
+                let path = str_field(image, &["file_path"]);
+                conn.execute(
+                    "INSERT INTO tmdb_images
+                        (tmdb_media_id, media_type, image_type, file_path, language, width, height, vote_average, is_primary)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        tmdb_id,
+                        media_type,
+                        image_type,
+                        path,
+                        str_field(image, &["iso_639_1"]),
+                        image.get("width").and_then(Value::as_i64),
+                        image.get("height").and_then(Value::as_i64),
+                        image.get("vote_average").and_then(Value::as_f64),
+                        (path.is_some() && path == primary_path) as i32,
+                    ],
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Record a per-title failure, or pass on errors that would fail every request
+fn note_failure(report: &mut TmdbSyncReport, what: String, err: TmdbError) -> Result<(), TmdbError> {
+    match err {
+        TmdbError::Unauthorized | TmdbError::RateLimited | TmdbError::Database(_) => Err(err),
+        other => {
+            report.errors.push(format!("{}: {}", what, other));
+            Ok(())
+        }
+    }
+}
+
+/// Percent-encode a query parameter value
+fn encode_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct TmdbSyncReport {
+    /// Searches run for unmatched movies and shows
+    pub searched: usize,
+    pub matches: super::MatchReport,
+    /// Titles whose details were fetched or revalidated
+    pub refreshed: usize,
+    pub episodes: super::EpisodeLinkReport,
+    pub errors: Vec<String>,
+}
+
+/// Search TMDB for everything unmatched, run the matcher, then fetch details
+/// for matched titles not synced within `max_age_days`. Per-title failures
+/// are collected in the report; a missing key or rate limiting aborts.
+pub fn sync_library<T: Transport>(
+    db: &impl ConnectionSource,
+    client: &TmdbClient<T>,
+    max_age_days: i64,
+) -> Result<TmdbSyncReport, TmdbError> {
+    let mut report = TmdbSyncReport::default();
+
+    for (media_type, title, year) in db.with_conn(super::matcher::unmatched_search_terms)? {
+        match client.search(db, media_type, &title, year) {
+            Ok(_) => report.searched += 1,
+            Err(e) => note_failure(&mut report, title, e)?,
+        }
+    }
+
+    report.matches = db.with_conn(|conn| super::match_library(conn, super::AUTO_ACCEPT_CONFIDENCE))?;
+
+    let cutoff = (chrono::Utc::now() - chrono::Duration::days(max_age_days)).to_rfc3339();
+    let stale: Vec<(i64, String)> = db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT tmdb_id, media_type FROM tmdb_media
+             WHERE media_type IN ('movie', 'tv') AND (last_synced_at IS NULL OR last_synced_at < ?1)",
+        )?;
+        let rows = stmt.query_map(params![cutoff], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<Vec<_>, _>>()
+    })?;
+
+    for (tmdb_id, media_type) in stale {
+        let result = if media_type == "movie" {
+            client.fetch_movie(db, tmdb_id)
+        } else {
+            client.fetch_tv(db, tmdb_id).and_then(|_| {
+                // Every season, so the episode report can list what we're missing
+                let seasons: Vec<i32> = db.with_conn(|conn| {
+                    let mut stmt = conn.prepare(
+                        "SELECT season_number FROM tmdb_seasons WHERE tmdb_show_id = ?1 ORDER BY season_number",
+                    )?;
+                    let rows = stmt.query_map(params![tmdb_id], |row| row.get(0))?;
+                    rows.collect::<Result<Vec<_>, _>>()
+                })?;
+                seasons.into_iter().try_for_each(|season| client.fetch_season(db, tmdb_id, season))
+            })
+        };
+
+        match result {
+            Ok(()) => {
+                db.with_conn(|conn| {
+                    conn.execute(
+                        "UPDATE tmdb_media SET last_synced_at = ?1 WHERE tmdb_id = ?2 AND media_type = ?3",
+                        params![chrono::Utc::now().to_rfc3339(), tmdb_id, media_type],
+                    )
+                })?;
+                report.refreshed += 1;
+            }
+            Err(e) => note_failure(&mut report, format!("{} {}", media_type, tmdb_id), e)?,
+        }
+    }
+
+    report.episodes = db.with_conn(|conn| super::link_episodes(conn, None))?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use std::collections::HashMap;
+
+    type Headers = Vec<(String, String)>;
+
+    /// Serves canned responses by path and records every request
+    #[derive(Default)]
+    struct FixtureTransport {
+        responses: Mutex<HashMap<String, Vec<HttpResponse>>>,
+        requests: Mutex<Vec<(String, Headers)>>,
+    }
+
+    impl FixtureTransport {
+        fn respond(self, path: &str, status: u16, headers: &[(&str, &str)], body: &str) -> Self {
+            self.responses.lock().unwrap().entry(path.to_string()).or_default().push(HttpResponse {
+                status,
+                headers: headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+                body: body.to_string(),
+            });
+            self
+        }
+
+        fn request_count(&self) -> usize {
+            self.requests.lock().unwrap().len()
+        }
+    }
+
+    impl Transport for FixtureTransport {
+        fn get(&self, url: &str, headers: &[(String, String)]) -> Result<HttpResponse, TmdbError> {
+            self.requests.lock().unwrap().push((url.to_string(), headers.to_vec()));
+            let path = url.trim_start_matches("http://tmdb.test").split('?').next().unwrap().to_string();
+            let mut responses = self.responses.lock().unwrap();
+            let queue = responses.get_mut(&path).filter(|q| !q.is_empty());
+            match queue {
+                // The last response for a path repeats
+                Some(queue) if queue.len() > 1 => Ok(queue.remove(0)),
+                Some(queue) => Ok(queue[0].clone()),
+                None => Ok(HttpResponse { status: 404, headers: vec![], body: "{}".to_string() }),
+            }
+        }
+    }
+
+    fn enable(conn: &Connection, key: &str) {
+        conn.execute("UPDATE settings SET value = 'true' WHERE key = 'tmdb_enabled'", []).unwrap();
+        conn.execute("UPDATE settings SET value = ?1 WHERE key = 'tmdb_api_key'", params![key]).unwrap();
+    }
+
+    fn client(conn: &Connection, transport: FixtureTransport) -> TmdbClient<FixtureTransport> {
+        TmdbClient::from_settings(conn, transport)
+            .unwrap()
+            .with_base_url("http://tmdb.test")
+            .with_min_interval(Duration::ZERO)
+    }
+
+    const MOVIE: &str = r#"{
+        "id": 603, "title": "The Matrix", "original_title": "The Matrix", "release_date": "1999-03-30",
+        "runtime": 136, "poster_path": "/p1.jpg", "imdb_id": "tt0133093",
+        "genres": [{"id": 28, "name": "Action"}, {"id": 878, "name": "Science Fiction"}],
+        "spoken_languages": [{"iso_639_1": "en"}],
+        "production_countries": [{"iso_3166_1": "US"}],
+        "belongs_to_collection": {"id": 2344, "name": "The Matrix Collection"},
+        "keywords": {"keywords": [{"id": 1, "name": "simulation"}]},
+        "credits": {
+            "cast": [{"id": 6384, "name": "Keanu Reeves", "character": "Neo", "order": 0}],
+            "crew": [{"id": 9340, "name": "Lana Wachowski", "job": "Director"}, {"id": 1, "name": "Grip", "job": "Grip"}]
+        },
+        "images": {"posters": [{"file_path": "/p1.jpg", "width": 1000, "height": 1500}, {"file_path": "/p2.jpg"}]}
+    }"#;
+
+    #[test]
+    fn test_disabled_never_touches_network() {
+        let conn = init_db().unwrap();
+        let transport = FixtureTransport::default();
+        assert!(matches!(TmdbClient::from_settings(&conn, transport), Err(TmdbError::Disabled)));
+
+        conn.execute("UPDATE settings SET value = 'true' WHERE key = 'tmdb_enabled'", []).unwrap();
+        assert!(matches!(
+            TmdbClient::from_settings(&conn, FixtureTransport::default()),
+            Err(TmdbError::MissingApiKey)
+        ));
+    }
+
+    #[test]
+    fn test_fetch_movie_populates_tables() -> Result<(), TmdbError> {
+        let conn = init_db()?;
+        enable(&conn, "abc123");
+        let tmdb = client(&conn, FixtureTransport::default().respond("/movie/603", 200, &[("ETag", "\"v1\"")], MOVIE));
+
+        tmdb.fetch_movie(&conn, 603)?;
+
+        let (runtime, genres, imdb): (i64, String, String) = conn.query_row(
+            "SELECT runtime, genres_json, imdb_id FROM tmdb_metadata WHERE tmdb_id = 603 AND media_type = 'movie'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        assert_eq!((runtime, genres.as_str(), imdb.as_str()), (136, r#"["Action","Science Fiction"]"#, "tt0133093"));
+
+        let roles: Vec<String> = conn
+            .prepare("SELECT role FROM tmdb_cast WHERE tmdb_media_id = 603 ORDER BY id")?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        assert_eq!(roles, vec!["cast", "director"]);
+
+        let primary: String = conn.query_row("SELECT file_path FROM tmdb_images WHERE is_primary = 1", [], |row| row.get(0))?;
+        assert_eq!(primary, "/p1.jpg");
+        let in_collection: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM tmdb_collection_items WHERE collection_id = 2344 AND tmdb_movie_id = 603",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(in_collection, 1);
+
+        // The key goes in the query for v3 keys and never into the cache
+        let (url, _) = tmdb.transport.requests.lock().unwrap()[0].clone();
+        assert!(url.contains("api_key=abc123") && url.contains("language=en-US"));
+        let cached: i64 = conn.query_row("SELECT COUNT(*) FROM tmdb_http_cache WHERE request LIKE '%abc123%'", [], |row| row.get(0))?;
+        assert_eq!(cached, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_etag_revalidation_and_rate_limit_retry() -> Result<(), TmdbError> {
+        let conn = init_db()?;
+        enable(&conn, "eyJhbGciOi.token");
+        let transport = FixtureTransport::default()
+            .respond("/movie/603", 200, &[("ETag", "\"v1\"")], MOVIE)
+            .respond("/movie/603", 429, &[("Retry-After", "0")], "{}")
+            .respond("/movie/603", 304, &[], "");
+        let tmdb = client(&conn, transport);
+
+        tmdb.fetch_movie(&conn, 603)?;
+        conn.execute("UPDATE tmdb_metadata SET runtime = NULL", [])?;
+        tmdb.fetch_movie(&conn, 603)?;
+
+        // The 304 reused the cached body
+        let runtime: i64 = conn.query_row("SELECT runtime FROM tmdb_metadata WHERE tmdb_id = 603", [], |row| row.get(0))?;
+        assert_eq!(runtime, 136);
+
+        let requests = tmdb.transport.requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 3);
+        let header = |i: usize, name: &str| requests[i].1.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone());
+        assert_eq!(header(0, "If-None-Match"), None);
+        assert_eq!(header(2, "If-None-Match").as_deref(), Some("\"v1\""));
+        assert_eq!(header(0, "Authorization").as_deref(), Some("Bearer eyJhbGciOi.token"));
+        assert!(!requests[0].0.contains("api_key"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_library_searches_matches_and_fetches() -> Result<(), TmdbError> {
+        let conn = init_db()?;
+        enable(&conn, "abc123");
+        conn.execute(
+            "INSERT INTO media_files (file_path, file_hash, file_name, file_size, media_type, title, year, duration, last_modified)
+             VALUES ('/m/The.Matrix.1999.mkv', 'h', 'The.Matrix.1999.mkv', 1, 'movie', 'The Matrix', 1999, 8160, datetime('now'))",
+            [],
+        )?;
+        let search = r#"{"results": [{"id": 603, "title": "The Matrix", "release_date": "1999-03-30"}]}"#;
+        let tmdb = client(
+            &conn,
+            FixtureTransport::default()
+                .respond("/search/movie", 200, &[], search)
+                .respond("/movie/603", 200, &[], MOVIE),
+        );
+
+        // Shared the way the app holds it, locked per statement
+        let db = Mutex::new(conn);
+        let report = sync_library(&db, &tmdb, 30)?;
+        assert_eq!((report.searched, report.matches.auto_matched, report.refreshed), (1, 1, 1));
+        assert!(report.errors.is_empty());
+        assert_eq!(tmdb.transport.request_count(), 2);
+
+        // Fresh data isn't fetched again
+        let again = sync_library(&db, &tmdb, 30)?;
+        assert_eq!((again.searched, again.refreshed), (0, 0));
+        assert_eq!(tmdb.transport.request_count(), 2);
+        Ok(())
+    }
+}
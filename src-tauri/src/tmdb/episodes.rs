@@ -0,0 +1,450 @@
+//! Linking episode files to TMDB episodes, and per-show reports of missing,
+//! duplicated and unrecognised episodes.
+//!
+//! Links live in `tmdb_episode_links`, one row per episode a file contains,
+//! so a double episode (S01E01E02) covers both. Files are placed by their
+//! season and episode numbers, falling back to absolute numbering and to an
+//! air date in the file name. Manual links are never replaced automatically.
+
+use rusqlite::{Connection, OptionalExtension, params};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+
+use super::TmdbError;
+use crate::db::models::MediaFile;
+use crate::db::operations::{MEDIA_COLUMNS, media_file_from_row};
+
+/// A TMDB episode
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct EpisodeInfo {
+    pub tmdb_show_id: i64,
+    pub season_number: i32,
+    pub episode_number: i32,
+    pub tmdb_id: Option<i64>,
+    pub name: Option<String>,
+    pub overview: Option<String>,
+    pub air_date: Option<String>,
+    pub runtime: Option<i64>,
+    pub still_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct EpisodeLinkReport {
+    /// Files linked to at least one episode
+    pub linked: usize,
+    /// Files of matched shows that don't map to any known episode
+    pub unmapped: usize,
+}
+
+/// An episode owned as more than one file
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DuplicateEpisode {
+    pub episode: EpisodeInfo,
+    pub media: Vec<MediaFile>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ShowEpisodeReport {
+    pub tmdb_id: i64,
+    pub name: Option<String>,
+    /// Regular (non-special) episodes that have aired
+    pub aired: usize,
+    /// Distinct episodes we have files for, specials included
+    pub owned: usize,
+    /// Aired regular episodes we don't own, in order
+    pub missing: Vec<EpisodeInfo>,
+    pub duplicates: Vec<DuplicateEpisode>,
+    /// Files of the show not linked to any known episode
+    pub unmapped: Vec<MediaFile>,
+}
+
+/// An episode file of a matched show: media id, file name, season, episode, show id
+type EpisodeFile = (i64, String, Option<i32>, Option<i32>, i64);
+
+const EPISODE_COLUMNS: &str =
+    "e.tmdb_show_id, e.season_number, e.episode_number, e.tmdb_id, e.name, e.overview, e.air_date, e.runtime, e.still_path";
+
+fn episode_from_row(row: &rusqlite::Row, offset: usize) -> rusqlite::Result<EpisodeInfo> {
+    Ok(EpisodeInfo {
+        tmdb_show_id: row.get(offset)?,
+        season_number: row.get(offset + 1)?,
+        episode_number: row.get(offset + 2)?,
+        tmdb_id: row.get(offset + 3)?,
+        name: row.get(offset + 4)?,
+        overview: row.get(offset + 5)?,
+        air_date: row.get(offset + 6)?,
+        runtime: row.get(offset + 7)?,
+        still_path: row.get(offset + 8)?,
+    })
+}
+
+/// Episode numbers a file name lists after its first: S01E01E02, S01E01-E03, S01E01-03
+fn extra_episodes(file_name: &str, episode: i32) -> Vec<i32> {
+    let Some(caps) = regex::Regex::new(r"(?i)s\d+e(\d+)((?:-?e\d+)+|-\d+)")
+        .ok()
+        .and_then(|re| re.captures(file_name))
+    else {
+        return Vec::new();
+    };
+    if caps[1].parse::<i32>().ok() != Some(episode) {
+        return Vec::new();
+    }
+
+    let numbers: Vec<i32> = caps[2]
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|n| n.parse().ok())
+        .collect();
+    let is_range = caps[2].contains('-');
+    match numbers.as_slice() {
+        // A range like E01-E03 includes E02
+        [last] if is_range && *last > episode && *last - episode <= 10 => (episode + 1..=*last).collect(),
+        _ => numbers.into_iter().filter(|n| *n != episode).collect(),
+    }
+}
+
+/// A YYYY-MM-DD air date in a file name (dots, dashes, underscores or spaces)
+fn air_date_in_name(file_name: &str) -> Option<String> {
+    let caps = regex::Regex::new(r"((?:19|20)\d{2})[-._ ](\d{2})[-._ ](\d{2})")
+        .ok()?
+        .captures(file_name)?;
+    Some(format!("{}-{}-{}", &caps[1], &caps[2], &caps[3]))
+}
+
+/// Where a file belongs among a show's known episodes, as (season, episode)
+/// pairs; empty when it can't be placed
+fn place_file(
+    file_name: &str,
+    season: Option<i32>,
+    episode: Option<i32>,
+    episodes: &[(i32, i32, Option<String>)],
+) -> Vec<(i32, i32)> {
+    let known = |s: i32, e: i32| episodes.iter().any(|(es, ee, _)| *es == s && *ee == e);
+
+    if let Some(episode) = episode {
+        let mut numbers = vec![episode];
+        numbers.extend(extra_episodes(file_name, episode));
+
+        if let Some(season) = season {
+            let placed: Vec<(i32, i32)> = numbers.iter().filter(|e| known(season, **e)).map(|e| (season, *e)).collect();
+            if !placed.is_empty() {
+                return placed;
+            }
+        }
+
+        // Absolute numbering ("Show - 113"), counted over the regular seasons
+        if season.unwrap_or(1) == 1 {
+            let regular: Vec<(i32, i32)> = episodes.iter().filter(|(s, _, _)| *s > 0).map(|(s, e, _)| (*s, *e)).collect();
+            let placed: Vec<(i32, i32)> = numbers
+                .iter()
+                .filter_map(|n| usize::try_from(*n - 1).ok().and_then(|i| regular.get(i)).copied())
+                .collect();
+            if !placed.is_empty() {
+                return placed;
+            }
+        }
+    }
+
+    // Daily shows are named by air date
+    if let Some(date) = air_date_in_name(file_name) {
+        let placed: Vec<(i32, i32)> = episodes
+            .iter()
+            .filter(|(_, _, air_date)| air_date.as_deref() == Some(date.as_str()))
+            .map(|(s, e, _)| (*s, *e))
+            .collect();
+        // Several episodes on one day are ambiguous
+        if placed.len() == 1 {
+            return placed;
+        }
+    }
+
+    Vec::new()
+}
+
+/// Link the episode files of matched shows (or just `tmdb_show_id`) to their
+/// TMDB episodes, replacing earlier automatic links
+pub fn link_episodes(conn: &Connection, tmdb_show_id: Option<i64>) -> Result<EpisodeLinkReport, TmdbError> {
+    let files: Vec<EpisodeFile> = {
+        let mut stmt = conn.prepare(
+            "SELECT m.id, m.file_name, m.season_number, m.episode_number, t.tmdb_id
+             FROM media_files m
+             JOIN tmdb_media t ON t.media_id = m.id AND t.media_type = 'tv'
+             WHERE m.is_deleted = 0 AND m.media_type = 'tv_episode'
+               AND (?1 IS NULL OR t.tmdb_id = ?1)
+               AND NOT EXISTS (SELECT 1 FROM tmdb_episode_links l WHERE l.media_id = m.id AND l.is_manual = 1)",
+        )?;
+        let rows = stmt.query_map(params![tmdb_show_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+        })?;
+        rows.collect::<Result<Vec<_>, _>>()?
+    };
+
+    let mut episodes: HashMap<i64, Vec<(i32, i32, Option<String>)>> = HashMap::new();
+    let mut report = EpisodeLinkReport::default();
+    // Callers such as bundle import may already be inside a transaction
+    let tx = if conn.is_autocommit() { Some(conn.unchecked_transaction()?) } else { None };
+
+    for (media_id, file_name, season, episode, show_id) in files {
+        if let Entry::Vacant(entry) = episodes.entry(show_id) {
+            let mut stmt = conn.prepare(
+                "SELECT season_number, episode_number, air_date FROM tmdb_episodes
+                 WHERE tmdb_show_id = ?1 ORDER BY season_number, episode_number",
+            )?;
+            let rows = stmt.query_map(params![show_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            entry.insert(rows.collect::<Result<Vec<_>, _>>()?);
+        }
+
+        conn.execute("DELETE FROM tmdb_episode_links WHERE media_id = ?1", params![media_id])?;
+        let placed = place_file(&file_name, season, episode, &episodes[&show_id]);
+        if placed.is_empty() {
+            report.unmapped += 1;
+            continue;
+        }
+        for (season_number, episode_number) in placed {
+            conn.execute(
+                "INSERT OR IGNORE INTO tmdb_episode_links (media_id, tmdb_show_id, season_number, episode_number)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![media_id, show_id, season_number, episode_number],
+            )?;
+        }
+        report.linked += 1;
+    }
+
+    if let Some(tx) = tx {
+        tx.commit()?;
+    }
+    Ok(report)
+}
+
+/// Link a file to episodes of its matched show by hand; automatic linking
+/// leaves it alone afterwards. An empty list hands the file back to
+/// automatic linking.
+pub fn set_episode_links(conn: &Connection, media_id: i64, episodes: &[(i32, i32)]) -> Result<(), TmdbError> {
+    let show_id: i64 = conn
+        .query_row(
+            "SELECT tmdb_id FROM tmdb_media WHERE media_id = ?1 AND media_type = 'tv'",
+            params![media_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| TmdbError::NotFound(format!("TV match for media {}", media_id)))?;
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM tmdb_episode_links WHERE media_id = ?1", params![media_id])?;
+    for (season, episode) in episodes {
+        let exists: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM tmdb_episodes
+                            WHERE tmdb_show_id = ?1 AND season_number = ?2 AND episode_number = ?3)",
+            params![show_id, season, episode],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Err(TmdbError::NotFound(format!("episode S{:02}E{:02} of show {}", season, episode, show_id)));
+        }
+        tx.execute(
+            "INSERT OR IGNORE INTO tmdb_episode_links (media_id, tmdb_show_id, season_number, episode_number, is_manual)
+             VALUES (?1, ?2, ?3, ?4, 1)",
+            params![media_id, show_id, season, episode],
+        )?;
+    }
+    tx.commit()?;
+
+    if episodes.is_empty() {
+        link_episodes(conn, Some(show_id))?;
+    }
+    Ok(())
+}
+
+/// The TMDB episodes a file is linked to, in order
+pub fn get_episode_info(conn: &Connection, media_id: i64) -> Result<Vec<EpisodeInfo>, TmdbError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM tmdb_episode_links l
+         JOIN tmdb_episodes e ON e.tmdb_show_id = l.tmdb_show_id
+            AND e.season_number = l.season_number AND e.episode_number = l.episode_number
+         WHERE l.media_id = ?1
+         ORDER BY e.season_number, e.episode_number",
+        EPISODE_COLUMNS
+    ))?;
+    let episodes = stmt.query_map(params![media_id], |row| episode_from_row(row, 0))?;
+    Ok(episodes.collect::<Result<Vec<_>, _>>()?)
+}
+
+/// Missing, duplicated and unmapped episodes of every matched show in the
+/// library, or of one show
+pub fn get_episode_report(conn: &Connection, tmdb_show_id: Option<i64>) -> Result<Vec<ShowEpisodeReport>, TmdbError> {
+    let shows: Vec<(i64, Option<String>)> = {
+        let mut stmt = conn.prepare(
+            "SELECT t.tmdb_id, COALESCE(s.show_name, md.title)
+             FROM tmdb_media t
+             JOIN media_files m ON m.id = t.media_id AND m.is_deleted = 0
+             LEFT JOIN tmdb_tv_shows s ON s.tmdb_id = t.tmdb_id
+             LEFT JOIN tmdb_metadata md ON md.tmdb_id = t.tmdb_id AND md.media_type = 'tv'
+             WHERE t.media_type = 'tv' AND (?1 IS NULL OR t.tmdb_id = ?1)
+             GROUP BY t.tmdb_id
+             ORDER BY COALESCE(s.show_name, md.title) COLLATE NOCASE",
+        )?;
+        let rows = stmt.query_map(params![tmdb_show_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<Vec<_>, _>>()?
+    };
+
+    let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    let mut reports = Vec::new();
+
+    for (show_id, name) in shows {
+        let episodes: Vec<EpisodeInfo> = {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM tmdb_episodes e WHERE e.tmdb_show_id = ?1 ORDER BY e.season_number, e.episode_number",
+                EPISODE_COLUMNS
+            ))?;
+            let rows = stmt.query_map(params![show_id], |row| episode_from_row(row, 0))?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+
+        // Files per episode, and the show's files no episode claims
+        let mut owned: BTreeMap<(i32, i32), Vec<MediaFile>> = BTreeMap::new();
+        let mut unmapped = Vec::new();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, l.season_number, l.episode_number
+             FROM tmdb_media t
+             JOIN media_files m ON m.id = t.media_id AND m.is_deleted = 0
+             LEFT JOIN tmdb_episode_links l ON l.media_id = m.id
+             WHERE t.tmdb_id = ?1 AND t.media_type = 'tv'
+             ORDER BY m.season_number, m.episode_number, m.id",
+            MEDIA_COLUMNS
+        ))?;
+        let mut rows = stmt.query(params![show_id])?;
+        while let Some(row) = rows.next()? {
+            // Link columns follow the 22 media columns
+            let media = media_file_from_row(row)?;
+            match (row.get::<_, Option<i32>>(22)?, row.get::<_, Option<i32>>(23)?) {
+                (Some(season), Some(episode)) => owned.entry((season, episode)).or_default().push(media),
+                _ => unmapped.push(media),
+            }
+        }
+
+        let aired: Vec<&EpisodeInfo> = episodes
+            .iter()
+            .filter(|e| e.season_number > 0 && e.air_date.as_deref().is_some_and(|d| !d.is_empty() && d <= today.as_str()))
+            .collect();
+        let missing = aired
+            .iter()
+            .filter(|e| !owned.contains_key(&(e.season_number, e.episode_number)))
+            .map(|e| (*e).clone())
+            .collect();
+        let duplicates = episodes
+            .iter()
+            .filter_map(|e| {
+                owned
+                    .get(&(e.season_number, e.episode_number))
+                    .filter(|files| files.len() > 1)
+                    .map(|files| DuplicateEpisode { episode: e.clone(), media: files.clone() })
+            })
+            .collect();
+
+        reports.push(ShowEpisodeReport {
+            tmdb_id: show_id,
+            name,
+            aired: aired.len(),
+            owned: owned.len(),
+            missing,
+            duplicates,
+            unmapped,
+        });
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+
+    fn library() -> Connection {
+        let conn = init_db().unwrap();
+        conn.execute_batch(
+            "INSERT INTO tmdb_metadata (tmdb_id, media_type, title) VALUES (1396, 'tv', 'Breaking Bad');
+             INSERT INTO tmdb_tv_shows (tmdb_id, show_name) VALUES (1396, 'Breaking Bad');
+             INSERT INTO tmdb_episodes (tmdb_show_id, season_number, episode_number, name, air_date) VALUES
+                (1396, 1, 1, 'Pilot', '2008-01-20'),
+                (1396, 1, 2, 'Cat''s in the Bag...', '2008-01-27'),
+                (1396, 1, 3, '...And the Bag''s in the River', '2008-02-10'),
+                (1396, 2, 1, 'Seven Thirty-Seven', '2009-03-08'),
+                (1396, 2, 2, 'Grilled', '2009-03-15'),
+                (1396, 2, 3, 'Unannounced', '2999-01-01'),
+                (1396, 0, 1, 'Good Cop Bad Cop', '2009-02-17');",
+        )
+        .unwrap();
+
+        let files: &[(&str, Option<i32>, Option<i32>)] = &[
+            ("Breaking.Bad.S01E01E02.mkv", Some(1), Some(1)),
+            ("Breaking.Bad.S02E01.mkv", Some(2), Some(1)),
+            ("Breaking.Bad.S02E01.720p.mkv", Some(2), Some(1)),
+            // Absolute numbering: the 5th regular episode is S02E02
+            ("Breaking Bad - 05.mkv", None, Some(5)),
+            ("Breaking.Bad.S05E20.mkv", Some(5), Some(20)),
+        ];
+        for (name, season, episode) in files {
+            conn.execute(
+                "INSERT INTO media_files (file_path, file_hash, file_name, file_size, media_type, season_number, episode_number, last_modified)
+                 VALUES (?1, 'hash', ?2, 1, 'tv_episode', ?3, ?4, datetime('now'))",
+                params![format!("/tv/Breaking Bad/{}", name), name, season, episode],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO tmdb_media (media_id, tmdb_id, media_type) VALUES (?1, 1396, 'tv')",
+                params![conn.last_insert_rowid()],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn test_link_episodes_and_report() {
+        let conn = library();
+        let report = link_episodes(&conn, None).unwrap();
+        assert_eq!(report, EpisodeLinkReport { linked: 4, unmapped: 1 });
+
+        let double = get_episode_info(&conn, 1).unwrap();
+        assert_eq!(double.iter().map(|e| e.name.as_deref().unwrap()).collect::<Vec<_>>(), vec!["Pilot", "Cat's in the Bag..."]);
+        assert_eq!(get_episode_info(&conn, 4).unwrap()[0].name.as_deref(), Some("Grilled"));
+
+        let report = &get_episode_report(&conn, None).unwrap()[0];
+        assert_eq!(report.name.as_deref(), Some("Breaking Bad"));
+        // Specials and unaired episodes aren't missing
+        assert_eq!((report.aired, report.owned), (5, 4));
+        assert_eq!(report.missing.iter().map(|e| (e.season_number, e.episode_number)).collect::<Vec<_>>(), vec![(1, 3)]);
+        assert_eq!(report.duplicates.len(), 1);
+        assert_eq!(report.duplicates[0].media.len(), 2);
+        assert_eq!(report.unmapped.iter().map(|m| m.file_name.as_str()).collect::<Vec<_>>(), vec!["Breaking.Bad.S05E20.mkv"]);
+    }
+
+    #[test]
+    fn test_manual_episode_links() {
+        let conn = library();
+        set_episode_links(&conn, 5, &[(1, 3)]).unwrap();
+        assert!(matches!(set_episode_links(&conn, 5, &[(9, 9)]), Err(TmdbError::NotFound(_))));
+
+        // Automatic linking keeps the manual link
+        link_episodes(&conn, None).unwrap();
+        assert_eq!(get_episode_info(&conn, 5).unwrap()[0].name.as_deref(), Some("...And the Bag's in the River"));
+        assert!(get_episode_report(&conn, Some(1396)).unwrap()[0].missing.is_empty());
+
+        // Clearing hands it back, and S05E20 doesn't exist
+        set_episode_links(&conn, 5, &[]).unwrap();
+        assert!(get_episode_info(&conn, 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_place_file() {
+        let episodes: Vec<(i32, i32, Option<String>)> = vec![
+            (1, 1, Some("2024-03-04".to_string())),
+            (1, 2, Some("2024-03-05".to_string())),
+            (1, 3, Some("2024-03-06".to_string())),
+        ];
+        assert_eq!(place_file("Show.S01E01-E03.mkv", Some(1), Some(1), &episodes), vec![(1, 1), (1, 2), (1, 3)]);
+        assert_eq!(place_file("Show.S01E02-03.mkv", Some(1), Some(2), &episodes), vec![(1, 2), (1, 3)]);
+        assert_eq!(place_file("Show.2024.03.05.mkv", None, None, &episodes), vec![(1, 2)]);
+        assert!(place_file("Show.S03E01.mkv", Some(3), Some(1), &episodes).is_empty());
+    }
+}
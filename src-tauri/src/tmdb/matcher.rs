@@ -0,0 +1,645 @@
+//! Offline matcher: scores local movies and shows against the TMDB records
+//! already in `tmdb_metadata`, accepts confident matches and queues the
+//! rest for review.
+
+use rusqlite::{Connection, OptionalExtension, params};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use super::TmdbError;
+use crate::db::search::{edit_distance, fold_text};
+use crate::indexer::metadata::parse_filename;
+
+/// Matches at or above this confidence are accepted without review
+pub const AUTO_ACCEPT_CONFIDENCE: f64 = 0.85;
+/// Candidates below this aren't worth showing
+const REVIEW_MIN_CONFIDENCE: f64 = 0.4;
+/// Lead the best candidate needs over the runner-up to be auto-accepted
+const REQUIRED_MARGIN: f64 = 0.05;
+/// Candidates kept per item for review
+const MAX_CANDIDATES: usize = 5;
+/// Title similarity below this rules a record out entirely
+const MIN_TITLE_SIMILARITY: f64 = 0.5;
+
+/// Breakdown of a candidate's confidence; each part is 0.0 to 1.0
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct MatchScore {
+    pub confidence: f64,
+    pub title: f64,
+    pub year: f64,
+    pub runtime: f64,
+    pub episodes: f64,
+}
+
+/// A pending candidate, as shown for review
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MatchCandidate {
+    pub media_id: i64,
+    pub local_title: Option<String>,
+    pub tmdb_id: i64,
+    pub media_type: String,
+    pub title: Option<String>,
+    pub release_date: Option<String>,
+    pub score: MatchScore,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct MatchReport {
+    /// Files matched automatically
+    pub auto_matched: usize,
+    /// Files with candidates waiting for review
+    pub queued_for_review: usize,
+    /// Files with no plausible candidate
+    pub unmatched: usize,
+}
+
+/// A movie, or a show made up of episode files
+#[derive(Debug, Clone)]
+struct LocalItem {
+    media_type: &'static str,
+    media_ids: Vec<i64>,
+    title: String,
+    year: Option<i32>,
+    /// Seconds (average episode length for shows)
+    duration: Option<i64>,
+    episode_count: usize,
+    season_count: i32,
+    tmdb_id: Option<i64>,
+    imdb_id: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct TmdbRecord {
+    tmdb_id: i64,
+    media_type: String,
+    title: Option<String>,
+    original_title: Option<String>,
+    year: Option<i32>,
+    /// Minutes (typical episode runtime for shows)
+    runtime: Option<i64>,
+    number_of_seasons: Option<i32>,
+    number_of_episodes: Option<i64>,
+    imdb_id: Option<String>,
+}
+
+/// Lowercase, accent-free, punctuation-free title without a leading article
+pub(crate) fn normalize_title(title: &str) -> String {
+    let folded = fold_text(&title.replace('&', " and "));
+    let words: Vec<&str> = folded
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    let words = match words.split_first() {
+        Some((first, rest)) if !rest.is_empty() && matches!(*first, "the" | "a" | "an") => rest,
+        _ => &words[..],
+    };
+    words.join(" ")
+}
+
+/// Similarity of two normalized titles: the better of edit distance and word overlap
+fn title_similarity(a: &str, b: &str) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+
+    let longest = a.chars().count().max(b.chars().count()) as f64;
+    let by_edits = 1.0 - edit_distance(a, b) as f64 / longest;
+
+    let words_a: HashSet<&str> = a.split(' ').collect();
+    let words_b: HashSet<&str> = b.split(' ').collect();
+    let overlap = words_a.intersection(&words_b).count() as f64 / words_a.union(&words_b).count() as f64;
+
+    by_edits.max(overlap * 0.95)
+}
+
+fn year_score(local: Option<i32>, remote: Option<i32>) -> f64 {
+    match (local, remote) {
+        (Some(a), Some(b)) => match (a - b).abs() {
+            0 => 1.0,
+            1 => 0.8,
+            2 => 0.4,
+            _ => 0.0,
+        },
+        _ => 0.5,
+    }
+}
+
+fn runtime_score(duration_secs: Option<i64>, runtime_minutes: Option<i64>) -> f64 {
+    match (duration_secs, runtime_minutes) {
+        (Some(secs), Some(minutes)) if secs > 0 && minutes > 0 => match (secs / 60 - minutes).abs() {
+            0..=3 => 1.0,
+            4..=10 => 0.7,
+            11..=20 => 0.4,
+            _ => 0.0,
+        },
+        _ => 0.5,
+    }
+}
+
+/// Local episodes and seasons should fit within the show's totals
+fn episode_score(item: &LocalItem, record: &TmdbRecord) -> f64 {
+    let Some(total) = record.number_of_episodes.filter(|n| *n > 0) else {
+        return 0.5;
+    };
+    let local = item.episode_count as i64;
+    let mut score = if local <= total { 1.0 } else { total as f64 / local as f64 };
+    if let Some(seasons) = record.number_of_seasons {
+        if item.season_count > seasons {
+            score *= 0.5;
+        }
+    }
+    score
+}
+
+fn score(item: &LocalItem, record: &TmdbRecord) -> Option<MatchScore> {
+    let local = normalize_title(&item.title);
+    let title = [&record.title, &record.original_title]
+        .iter()
+        .filter_map(|t| t.as_deref())
+        .map(|t| title_similarity(&local, &normalize_title(t)))
+        .fold(0.0, f64::max);
+    if title < MIN_TITLE_SIMILARITY {
+        return None;
+    }
+
+    let year = year_score(item.year, record.year);
+    let runtime = runtime_score(item.duration, record.runtime);
+    let (confidence, episodes) = if item.media_type == "tv" {
+        let episodes = episode_score(item, record);
+        (title * 0.65 + year * 0.1 + runtime * 0.05 + episodes * 0.2, episodes)
+    } else {
+        (title * 0.6 + year * 0.25 + runtime * 0.15, 0.5)
+    };
+
+    Some(MatchScore { confidence, title, year, runtime, episodes })
+}
+
+/// Show name and year for an episode: the NFO show title, else the show folder
+/// (skipping "Season 1"-style folders)
+pub(crate) fn show_identity(file_path: &str, metadata_json: Option<&str>) -> Option<(String, Option<i32>)> {
+    let from_nfo = metadata_json
+        .and_then(|json| serde_json::from_str::<serde_json::Value>(json).ok())
+        .and_then(|v| v.get("show_title").and_then(|t| t.as_str()).map(String::from));
+    if let Some(title) = from_nfo {
+        return Some((title, None));
+    }
+
+    let normalized = file_path.replace('\\', "/");
+    let mut folder = Path::new(&normalized).parent()?;
+    let is_season_folder = |name: &str| {
+        let lower = name.to_lowercase();
+        lower == "specials"
+            || ["season", "series", "s"].iter().any(|prefix| {
+                lower
+                    .strip_prefix(prefix)
+                    .map(|rest| !rest.trim().is_empty() && rest.trim().chars().all(|c| c.is_ascii_digit()))
+                    .unwrap_or(false)
+            })
+    };
+    if folder.file_name().map(|n| is_season_folder(&n.to_string_lossy())).unwrap_or(false) {
+        folder = folder.parent()?;
+    }
+
+    let name = folder.file_name()?.to_string_lossy().to_string();
+    let (title, year) = parse_filename(&name);
+    Some((title, year.map(|y| y as i32))).filter(|(t, _)| !t.is_empty())
+}
+
+fn json_id(metadata: &Option<serde_json::Value>, key: &str) -> Option<serde_json::Value> {
+    metadata.as_ref().and_then(|m| m.get(key)).cloned()
+}
+
+/// Unmatched movies and shows
+fn unmatched_items(conn: &Connection) -> Result<Vec<LocalItem>, TmdbError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, media_type, file_path, title, year, duration, season_number, metadata_json
+         FROM media_files
+         WHERE is_deleted = 0 AND media_type IN ('movie', 'tv_episode')
+           AND id NOT IN (SELECT media_id FROM tmdb_media)
+         ORDER BY id",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<i32>>(4)?,
+                row.get::<_, Option<i64>>(5)?,
+                row.get::<_, Option<i32>>(6)?,
+                row.get::<_, Option<String>>(7)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut items = Vec::new();
+    let mut shows: BTreeMap<String, (LocalItem, Vec<i64>, HashSet<i32>)> = BTreeMap::new();
+
+    for (id, media_type, file_path, title, year, duration, season, metadata_json) in rows {
+        let metadata: Option<serde_json::Value> = metadata_json.as_deref().and_then(|j| serde_json::from_str(j).ok());
+
+        if media_type == "movie" {
+            let Some(title) = title.filter(|t| !t.is_empty()) else { continue };
+            items.push(LocalItem {
+                media_type: "movie",
+                media_ids: vec![id],
+                title,
+                year,
+                duration,
+                episode_count: 0,
+                season_count: 0,
+                tmdb_id: json_id(&metadata, "tmdb_id").and_then(|v| v.as_i64()),
+                imdb_id: json_id(&metadata, "imdb_id").and_then(|v| v.as_str().map(String::from)),
+            });
+            continue;
+        }
+
+        let Some((show, show_year)) = show_identity(&file_path, metadata_json.as_deref()) else { continue };
+        let entry = shows.entry(normalize_title(&show)).or_insert_with(|| {
+            (
+                LocalItem {
+                    media_type: "tv",
+                    media_ids: Vec::new(),
+                    title: show,
+                    year: show_year,
+                    duration: None,
+                    episode_count: 0,
+                    season_count: 0,
+                    tmdb_id: None,
+                    imdb_id: None,
+                },
+                Vec::new(),
+                HashSet::new(),
+            )
+        });
+        entry.0.media_ids.push(id);
+        if let Some(d) = duration {
+            entry.1.push(d);
+        }
+        if let Some(s) = season {
+            entry.2.insert(s);
+        }
+    }
+
+    for (_, (mut show, durations, seasons)) in shows {
+        show.episode_count = show.media_ids.len();
+        show.season_count = seasons.into_iter().filter(|s| *s > 0).count() as i32;
+        if !durations.is_empty() {
+            show.duration = Some(durations.iter().sum::<i64>() / durations.len() as i64);
+        }
+        items.push(show);
+    }
+
+    Ok(items)
+}
+
+/// Media type ("movie" or "tv"), title and year to search TMDB with
+pub(crate) type SearchTerm = (&'static str, String, Option<i32>);
+
+/// Search terms for everything still unmatched, one per movie or show
+pub(crate) fn unmatched_search_terms(conn: &Connection) -> Result<Vec<SearchTerm>, TmdbError> {
+    Ok(unmatched_items(conn)?
+        .into_iter()
+        .map(|item| (item.media_type, item.title, item.year))
+        .collect())
+}
+
+fn load_records(conn: &Connection) -> Result<Vec<TmdbRecord>, TmdbError> {
+    let mut stmt = conn.prepare(
+        "SELECT tmdb_id, media_type, title, original_title, release_date, runtime,
+                number_of_seasons, number_of_episodes, episode_runtime_json, imdb_id
+         FROM tmdb_metadata
+         WHERE media_type IN ('movie', 'tv')",
+    )?;
+
+    let records = stmt
+        .query_map([], |row| {
+            let release_date: Option<String> = row.get(4)?;
+            let episode_runtimes: Option<String> = row.get(8)?;
+            let runtime: Option<i64> = row.get(5)?;
+            Ok(TmdbRecord {
+                tmdb_id: row.get(0)?,
+                media_type: row.get(1)?,
+                title: row.get(2)?,
+                original_title: row.get(3)?,
+                year: release_date.as_deref().and_then(|d| d.get(..4)).and_then(|y| y.parse().ok()),
+                runtime: runtime.or_else(|| {
+                    episode_runtimes
+                        .and_then(|json| serde_json::from_str::<Vec<i64>>(&json).ok())
+                        .and_then(|r| r.first().copied())
+                }),
+                number_of_seasons: row.get(6)?,
+                number_of_episodes: row.get(7)?,
+                imdb_id: row.get(9)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(records)
+}
+
+/// Ranked candidates for an item; an exact TMDB/IMDb id from an NFO wins outright
+fn rank_candidates(item: &LocalItem, records: &[TmdbRecord]) -> Vec<(i64, MatchScore)> {
+    let records = records.iter().filter(|r| r.media_type == item.media_type);
+
+    let by_id = records.clone().find(|r| {
+        Some(r.tmdb_id) == item.tmdb_id
+            || (item.imdb_id.is_some() && r.imdb_id.is_some() && r.imdb_id == item.imdb_id)
+    });
+    if let Some(record) = by_id {
+        let exact = MatchScore { confidence: 1.0, title: 1.0, year: 1.0, runtime: 1.0, episodes: 1.0 };
+        return vec![(record.tmdb_id, exact)];
+    }
+
+    let mut ranked: Vec<(i64, MatchScore)> = records
+        .filter_map(|record| score(item, record).map(|s| (record.tmdb_id, s)))
+        .filter(|(_, s)| s.confidence >= REVIEW_MIN_CONFIDENCE)
+        .collect();
+    ranked.sort_by(|a, b| b.1.confidence.total_cmp(&a.1.confidence));
+    ranked.truncate(MAX_CANDIDATES);
+    ranked
+}
+
+fn record_match(
+    conn: &Connection,
+    media_ids: &[i64],
+    tmdb_id: i64,
+    media_type: &str,
+    confidence: f64,
+    manual: bool,
+) -> Result<(), TmdbError> {
+    for media_id in media_ids {
+        conn.execute(
+            "INSERT INTO tmdb_media (media_id, tmdb_id, media_type, match_confidence, matched_at, is_manual_match)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(media_id) DO UPDATE SET
+                tmdb_id = excluded.tmdb_id,
+                media_type = excluded.media_type,
+                match_confidence = excluded.match_confidence,
+                matched_at = excluded.matched_at,
+                is_manual_match = excluded.is_manual_match",
+            params![media_id, tmdb_id, media_type, confidence, chrono::Utc::now().to_rfc3339(), manual as i32],
+        )?;
+        conn.execute("DELETE FROM tmdb_match_candidates WHERE media_id = ?1", params![media_id])?;
+    }
+    Ok(())
+}
+
+/// Match every unmatched movie and show against the cached TMDB records.
+///
+/// Candidates at or above `auto_accept` (and clearly ahead of the runner-up)
+/// are recorded in `tmdb_media`; weaker ones are queued for review. Existing
+/// matches, manual or not, are left alone.
+pub fn match_library(conn: &Connection, auto_accept: f64) -> Result<MatchReport, TmdbError> {
+    let records = load_records(conn)?;
+    let mut report = MatchReport::default();
+
+    for item in unmatched_items(conn)? {
+        let ranked = rank_candidates(&item, &records);
+        let files = item.media_ids.len();
+
+        let Some((best_id, best)) = ranked.first().copied() else {
+            report.unmatched += files;
+            continue;
+        };
+        let margin = ranked.get(1).map(|(_, s)| best.confidence - s.confidence).unwrap_or(1.0);
+
+        if best.confidence >= auto_accept && margin >= REQUIRED_MARGIN {
+            record_match(conn, &item.media_ids, best_id, item.media_type, best.confidence, false)?;
+            report.auto_matched += files;
+            continue;
+        }
+
+        // Shows are reviewed once, through their first episode
+        let media_id = item.media_ids[0];
+        conn.execute(
+            "DELETE FROM tmdb_match_candidates WHERE media_id = ?1 AND status = 'pending'",
+            params![media_id],
+        )?;
+        for (tmdb_id, s) in &ranked {
+            // Rejected candidates stay rejected
+            conn.execute(
+                "INSERT OR IGNORE INTO tmdb_match_candidates
+                    (media_id, tmdb_id, media_type, confidence, title_score, year_score, runtime_score, episode_score)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![media_id, tmdb_id, item.media_type, s.confidence, s.title, s.year, s.runtime, s.episodes],
+            )?;
+        }
+        report.queued_for_review += files;
+    }
+
+    Ok(report)
+}
+
+/// Pending candidates, best first; all of them, or only those for one file
+pub fn get_match_candidates(conn: &Connection, media_id: Option<i64>) -> Result<Vec<MatchCandidate>, TmdbError> {
+    let mut stmt = conn.prepare(
+        "SELECT c.media_id, m.title, c.tmdb_id, c.media_type, t.title, t.release_date,
+                c.confidence, c.title_score, c.year_score, c.runtime_score, c.episode_score
+         FROM tmdb_match_candidates c
+         JOIN media_files m ON m.id = c.media_id
+         LEFT JOIN tmdb_metadata t ON t.tmdb_id = c.tmdb_id AND t.media_type = c.media_type
+         WHERE c.status = 'pending' AND (?1 IS NULL OR c.media_id = ?1)
+         ORDER BY c.media_id, c.confidence DESC",
+    )?;
+
+    let candidates = stmt
+        .query_map(params![media_id], |row| {
+            Ok(MatchCandidate {
+                media_id: row.get(0)?,
+                local_title: row.get(1)?,
+                tmdb_id: row.get(2)?,
+                media_type: row.get(3)?,
+                title: row.get(4)?,
+                release_date: row.get(5)?,
+                score: MatchScore {
+                    confidence: row.get(6)?,
+                    title: row.get(7)?,
+                    year: row.get(8)?,
+                    runtime: row.get(9)?,
+                    episodes: row.get(10)?,
+                },
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(candidates)
+}
+
+/// Accept a match chosen by the user. For an episode, every episode of the
+/// same show that isn't already manually matched gets the match too.
+/// Returns the number of files matched.
+pub fn confirm_match(conn: &Connection, media_id: i64, tmdb_id: i64, media_type: &str) -> Result<usize, TmdbError> {
+    if !matches!(media_type, "movie" | "tv") {
+        return Err(TmdbError::Invalid(format!("media type must be 'movie' or 'tv', got '{}'", media_type)));
+    }
+
+    let (file_path, metadata_json): (String, Option<String>) = conn
+        .query_row(
+            "SELECT file_path, metadata_json FROM media_files WHERE id = ?1",
+            params![media_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?
+        .ok_or_else(|| TmdbError::NotFound(format!("media {}", media_id)))?;
+
+    let confidence: f64 = conn
+        .query_row(
+            "SELECT confidence FROM tmdb_match_candidates WHERE media_id = ?1 AND tmdb_id = ?2 AND media_type = ?3",
+            params![media_id, tmdb_id, media_type],
+            |row| row.get(0),
+        )
+        .optional()?
+        .unwrap_or(1.0);
+
+    let mut media_ids = vec![media_id];
+    if media_type == "tv" {
+        if let Some((show, _)) = show_identity(&file_path, metadata_json.as_deref()) {
+            let key = normalize_title(&show);
+            let mut stmt = conn.prepare(
+                "SELECT id, file_path, metadata_json FROM media_files
+                 WHERE is_deleted = 0 AND media_type = 'tv_episode' AND id != ?1
+                   AND id NOT IN (SELECT media_id FROM tmdb_media WHERE is_manual_match = 1)",
+            )?;
+            let episodes = stmt
+                .query_map(params![media_id], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            media_ids.extend(episodes.into_iter().filter_map(|(id, path, json)| {
+                show_identity(&path, json.as_deref())
+                    .filter(|(name, _)| normalize_title(name) == key)
+                    .map(|_| id)
+            }));
+        }
+    }
+
+    record_match(conn, &media_ids, tmdb_id, media_type, confidence, true)?;
+    if media_type == "tv" {
+        super::link_episodes(conn, Some(tmdb_id))?;
+    }
+    Ok(media_ids.len())
+}
+
+/// Dismiss a candidate so it isn't suggested again
+pub fn reject_match_candidate(conn: &Connection, media_id: i64, tmdb_id: i64) -> Result<(), TmdbError> {
+    let updated = conn.execute(
+        "UPDATE tmdb_match_candidates SET status = 'rejected' WHERE media_id = ?1 AND tmdb_id = ?2",
+        params![media_id, tmdb_id],
+    )?;
+    if updated == 0 {
+        return Err(TmdbError::NotFound(format!("candidate {} for media {}", tmdb_id, media_id)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::connection::init_db;
+    use crate::db::test_support::{self, TestMedia};
+
+    fn add_media(conn: &Connection, path: &str, media_type: &str, title: &str, year: Option<i32>, duration: Option<i64>, season: Option<i32>) -> i64 {
+        let media = TestMedia { path, media_type, title: Some(title), year, duration, season, ..TestMedia::default() };
+        test_support::add_media(conn, &media)
+    }
+
+    fn add_record(conn: &Connection, tmdb_id: i64, media_type: &str, title: &str, date: &str, runtime: Option<i64>, episodes: Option<i64>) {
+        conn.execute(
+            "INSERT INTO tmdb_metadata (tmdb_id, media_type, title, release_date, runtime, number_of_episodes)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![tmdb_id, media_type, title, date, runtime, episodes],
+        ).unwrap();
+    }
+
+    fn matched(conn: &Connection, media_id: i64) -> Option<(i64, bool)> {
+        conn.query_row(
+            "SELECT tmdb_id, is_manual_match FROM tmdb_media WHERE media_id = ?1",
+            params![media_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional().unwrap()
+    }
+
+    #[test]
+    fn test_title_normalization_and_scores() {
+        assert_eq!(normalize_title("The Lord of the Rings: The Two Towers"), "lord of the rings the two towers");
+        assert_eq!(normalize_title("Amélie"), "amelie");
+        assert_eq!(normalize_title("Fast & Furious"), "fast and furious");
+        assert_eq!(title_similarity("matrix", "matrix"), 1.0);
+        assert!(title_similarity("matrix reloaded", "matrix revolutions") < 0.6);
+        assert_eq!(year_score(Some(1999), Some(2000)), 0.8);
+        assert_eq!(runtime_score(Some(136 * 60), Some(136)), 1.0);
+        assert_eq!(runtime_score(None, Some(136)), 0.5);
+    }
+
+    #[test]
+    fn test_show_identity() {
+        assert_eq!(
+            show_identity("/tv/Breaking Bad (2008)/Season 2/Breaking.Bad.S02E01.mkv", None),
+            Some(("Breaking Bad".to_string(), Some(2008)))
+        );
+        assert_eq!(
+            show_identity("/tv/Dark/Dark.S01E01.mkv", Some(r#"{"show_title": "Dark (DE)"}"#)).map(|s| s.0),
+            Some("Dark (DE)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_match_library() -> Result<(), TmdbError> {
+        let conn = init_db()?;
+        add_record(&conn, 603, "movie", "The Matrix", "1999-03-30", Some(136), None);
+        add_record(&conn, 604, "movie", "The Matrix Reloaded", "2003-05-15", Some(138), None);
+        add_record(&conn, 1, "movie", "Heat", "1995-12-15", Some(170), None);
+        add_record(&conn, 2, "movie", "Heat", "1986-03-14", Some(101), None);
+        add_record(&conn, 1396, "tv", "Breaking Bad", "2008-01-20", None, Some(62));
+
+        let matrix = add_media(&conn, "/m/The.Matrix.1999.mkv", "movie", "The Matrix", Some(1999), Some(136 * 60), None);
+        let heat = add_media(&conn, "/m/Heat.mkv", "movie", "Heat", None, None, None);
+        let nothing = add_media(&conn, "/m/Unknown Film.mkv", "movie", "Unknown Film", None, None, None);
+        let e1 = add_media(&conn, "/tv/Breaking Bad/Season 1/S01E01.mkv", "tv_episode", "x", None, Some(2900), Some(1));
+        let e2 = add_media(&conn, "/tv/Breaking Bad/Season 1/S01E02.mkv", "tv_episode", "x", None, Some(2800), Some(1));
+
+        let report = match_library(&conn, AUTO_ACCEPT_CONFIDENCE)?;
+        assert_eq!(report, MatchReport { auto_matched: 3, queued_for_review: 1, unmatched: 1 });
+        assert_eq!(matched(&conn, matrix), Some((603, false)));
+        assert_eq!(matched(&conn, e2), Some((1396, false)));
+        assert_eq!(matched(&conn, nothing), None);
+
+        // Two equally good "Heat" records: ask the user
+        let candidates = get_match_candidates(&conn, Some(heat))?;
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].score.title, 1.0);
+
+        reject_match_candidate(&conn, heat, 2)?;
+        assert_eq!(get_match_candidates(&conn, None)?.len(), 1);
+        // Re-running doesn't bring back the rejected candidate
+        match_library(&conn, AUTO_ACCEPT_CONFIDENCE)?;
+        assert_eq!(get_match_candidates(&conn, Some(heat))?.len(), 1);
+
+        assert_eq!(confirm_match(&conn, heat, 1, "movie")?, 1);
+        assert_eq!(matched(&conn, heat), Some((1, true)));
+        assert!(get_match_candidates(&conn, None)?.is_empty());
+
+        // Confirming an episode covers the whole show
+        assert_eq!(confirm_match(&conn, e1, 1396, "tv")?, 2);
+        assert_eq!(matched(&conn, e2), Some((1396, true)));
+        assert!(matches!(confirm_match(&conn, 999, 1, "movie"), Err(TmdbError::NotFound(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_nfo_ids_match_exactly() -> Result<(), TmdbError> {
+        let conn = init_db()?;
+        add_record(&conn, 2, "movie", "Heat", "1986-03-14", Some(101), None);
+        let heat = add_media(&conn, "/m/Heat.mkv", "movie", "Heat", Some(1995), None, None);
+        conn.execute("UPDATE media_files SET metadata_json = '{\"tmdb_id\": 2}' WHERE id = ?1", params![heat])?;
+
+        assert_eq!(match_library(&conn, AUTO_ACCEPT_CONFIDENCE)?.auto_matched, 1);
+        assert_eq!(matched(&conn, heat), Some((2, false)));
+        Ok(())
+    }
+}
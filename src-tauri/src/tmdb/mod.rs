@@ -0,0 +1,38 @@
+//! TMDB integration: the API client, offline bundles, matching local files
+//! against cached TMDB records and linking episode files to TMDB episodes.
+
+pub mod bundle;
+pub mod client;
+pub mod episodes;
+pub mod matcher;
+
+pub use bundle::*;
+pub use client::*;
+pub use episodes::*;
+pub use matcher::*;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TmdbError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Invalid input: {0}")]
+    Invalid(String),
+    #[error("TMDB is disabled in settings")]
+    Disabled,
+    #[error("No TMDB API key configured")]
+    MissingApiKey,
+    #[error("TMDB rejected the API key")]
+    Unauthorized,
+    #[error("TMDB rate limit exceeded")]
+    RateLimited,
+    #[error("TMDB returned HTTP {0}")]
+    Status(u16),
+    #[error("HTTP error: {0}")]
+    Http(String),
+    #[error("Invalid TMDB response: {0}")]
+    Json(#[from] serde_json::Error),
+}
@@ -21,6 +21,15 @@ const FIELD_OPTIONS = [
   { id: 'title', label: 'Title' },
   { id: 'year', label: 'Year' },
   { id: 'duration', label: 'Duration (sec)' },
+  { id: 'codec', label: 'Codec' },
+  { id: 'resolution', label: 'Resolution' },
+  { id: 'watch_status', label: 'Watch Status' },
+  { id: 'last_played', label: 'Last Played' },
+  { id: 'play_count', label: 'Play Count' },
+  { id: 'date_added', label: 'Date Added' },
+  { id: 'file_size', label: 'File Size (bytes)' },
+  { id: 'collection', label: 'Collection' },
+  { id: 'audio_language', label: 'Audio Language' },
 ];
 
 const OPERATOR_OPTIONS = [
@@ -30,6 +39,17 @@ const OPERATOR_OPTIONS = [
   { id: 'ends_with', label: 'Ends With' },
   { id: 'gt', label: 'Greater Than (>)' },
   { id: 'lt', label: 'Less Than (<)' },
+  { id: 'notequals', label: 'Not Equals (≠)' },
+  { id: 'not_contains', label: 'Does Not Contain' },
+  { id: 'gte', label: 'At Least (≥)' },
+  { id: 'lte', label: 'At Most (≤)' },
+  { id: 'between', label: 'Between (a..b)' },
+  { id: 'before', label: 'Before' },
+  { id: 'after', label: 'After' },
+  { id: 'in_last', label: 'In the Last (days)' },
+  { id: 'not_in_last', label: 'Not in the Last (days)' },
+  { id: 'in', label: 'In' },
+  { id: 'not_in', label: 'Not In' },
 ];
 
 export const RuleEditor: React.FC<RuleEditorProps> = ({ rules, onChange, readOnly = false }) => {
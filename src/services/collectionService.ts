@@ -7,6 +7,16 @@ export interface Collection {
   created_at: string;
   updated_at: string;
   item_count: number;
+  /** e.g. 'franchise' */
+  collection_type?: string | null;
+  /** Generated from TMDB collections or folder layout */
+  is_auto: boolean;
+}
+
+export interface AutoCollectionReport {
+  created: number;
+  updated: number;
+  removed: number;
 }
 
 export interface CollectionMediaItem {
@@ -18,6 +28,7 @@ export interface CollectionMediaItem {
   media_type: string;
   duration?: number;
   added_at: string;
+  position: number;
 }
 
 export const collectionService = {
@@ -41,6 +52,24 @@ export const collectionService = {
     await invoke('remove_from_collection', { collectionId, mediaId });
   },
 
+  /**
+   * Rebuild franchise collections from TMDB data and folder layout
+   */
+  async syncAutoCollections(): Promise<AutoCollectionReport> {
+    return await invoke<AutoCollectionReport>('sync_auto_collections');
+  },
+
+  async reorderItem(collectionId: number, mediaId: number, newPosition: number): Promise<void> {
+    await invoke('reorder_collection_item', { collectionId, mediaId, newPosition });
+  },
+
+  /**
+   * Move items as a block so the first one lands at toIndex
+   */
+  async moveItems(collectionId: number, mediaIds: number[], toIndex: number): Promise<number> {
+    return await invoke<number>('move_collection_items', { collectionId, mediaIds, toIndex });
+  },
+
   async updateCollection(collectionId: number, name: string, description?: string): Promise<void> {
     await invoke('update_collection', { collectionId, name, description });
   },
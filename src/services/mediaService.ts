@@ -23,9 +23,19 @@ export interface MediaFile {
   last_modified: string;
   is_deleted: boolean;
   metadata_json?: string;
+  /** Some field is locked to a manual edit */
   is_locked?: boolean;
 }
 
+export interface SearchResult extends MediaFile {
+  /** Relevance score, higher is better */
+  score: number;
+  /** HTML-escaped title with matches wrapped in <mark> */
+  title_highlight?: string;
+  /** HTML-escaped overview excerpt with matches wrapped in <mark> */
+  snippet?: string;
+}
+
 export interface ScanProgress {
   current_file: string;
   files_scanned: number;
@@ -48,6 +58,54 @@ export interface FilterCriteria {
   resolutions?: string[];
   codecs?: string[];
   media_types?: string[];
+  /** TMDB facets of the matched title; any listed value matches */
+  genres?: string[];
+  keywords?: string[];
+  /** ISO 3166-1 production country codes */
+  countries?: string[];
+  /** ISO 639-1 spoken language codes */
+  languages?: string[];
+  /** TMDB rating range, 0-10 */
+  min_rating?: number;
+  max_rating?: number;
+  /** Query language, e.g. `codec:hevc year:>2015 res:4k unwatched` */
+  query?: string;
+}
+
+export interface FacetCount {
+  value: string;
+  count: number;
+}
+
+export interface MediaFacets {
+  genres: FacetCount[];
+  /** The most common keywords */
+  keywords: FacetCount[];
+  countries: FacetCount[];
+  languages: FacetCount[];
+  /** Whole-number buckets: "7" covers 7.0 up to 8.0 */
+  ratings: FacetCount[];
+  /** "1980" covers 1980-1989 */
+  decades: FacetCount[];
+}
+
+export type SortKey =
+  | 'relevance'
+  | 'title'
+  | 'year'
+  | 'added'
+  | 'size'
+  | 'duration'
+  | 'last_played'
+  /** Best first in the last computed recommendation ranking */
+  | 'recommended';
+
+/** Sorting and keyset pagination; pass the last item's id as `after_id` for the next page */
+export interface PageRequest {
+  sort?: SortKey;
+  descending?: boolean;
+  limit?: number;
+  after_id?: number;
 }
 
 export const mediaService = {
@@ -78,33 +136,41 @@ export const mediaService = {
   /**
    * Get all media files
    */
-  async getAllMedia(): Promise<MediaFile[]> {
-    return invoke<MediaFile[]>('get_all_media');
+  async getAllMedia(page?: PageRequest): Promise<MediaFile[]> {
+    return invoke<MediaFile[]>('get_all_media', { page });
   },
 
   /**
    * Get media files by type
    */
-  async getMediaByType(type: 'movie' | 'tv_episode' | 'music'): Promise<MediaFile[]> {
-    return invoke<MediaFile[]>('get_media_by_type', { mediaType: type });
+  async getMediaByType(type: 'movie' | 'tv_episode' | 'music', page?: PageRequest): Promise<MediaFile[]> {
+    return invoke<MediaFile[]>('get_media_by_type', { mediaType: type, page });
   },
 
   /**
    * Search media files
    */
-  async searchMedia(query: string): Promise<MediaFile[]> {
-    return invoke<MediaFile[]>('search_media', { query });
+  async searchMedia(query: string, page?: PageRequest): Promise<SearchResult[]> {
+    return invoke<SearchResult[]>('search_media', { query, page });
   },
 
   /**
    * Filter media files
    */
-  async filterMedia(criteria: FilterCriteria): Promise<MediaFile[]> {
-    return invoke<MediaFile[]>('filter_media', { criteria });
+  async filterMedia(criteria: FilterCriteria, page?: PageRequest): Promise<MediaFile[]> {
+    return invoke<MediaFile[]>('filter_media', { criteria, page });
+  },
+
+  /**
+   * Facet counts over the files matching the criteria
+   */
+  async getFacets(criteria?: FilterCriteria): Promise<MediaFacets> {
+    return invoke<MediaFacets>('get_media_facets', { criteria });
   },
 
   /**
-   * Update media metadata
+   * Update media metadata by hand. Only the given fields change, and each is
+   * locked against later scans.
    */
   async updateMetadata(
     mediaId: number,
@@ -0,0 +1,230 @@
+import { invoke } from '@tauri-apps/api/tauri';
+import type { MediaFile } from './mediaService';
+
+/** Built-in providers; precedence lists name them */
+export type MetadataProviderName = 'override' | 'nfo' | 'tmdb';
+
+export type MetadataField =
+  | 'title'
+  | 'year'
+  | 'season'
+  | 'episode'
+  | 'original_title'
+  | 'show_title'
+  | 'premiered'
+  | 'overview'
+  | 'tagline'
+  | 'runtime'
+  | 'rating'
+  | 'genres'
+  | 'directors'
+  | 'writers'
+  | 'cast'
+  | 'imdb_id'
+  | 'tmdb_id'
+  | 'poster_path'
+  | 'backdrop_path';
+
+export interface MetadataSearchQuery {
+  title: string;
+  year?: number | null;
+  media_type: MediaFile['media_type'];
+}
+
+export interface MetadataSearchResult {
+  provider: string;
+  /** The provider's id for the title */
+  id: string;
+  title: string | null;
+  year: number | null;
+  overview: string | null;
+}
+
+export interface ProviderImage {
+  provider: string;
+  kind: 'poster' | 'backdrop' | 'still' | 'logo';
+  /** A TMDB path, URL or local file path */
+  path: string;
+  language: string | null;
+  width: number | null;
+  height: number | null;
+}
+
+export interface MetadataConfig {
+  /** Provider names, highest precedence first */
+  precedence: string[];
+  /** Per-field orders replacing `precedence`; providers not listed are ignored for the field */
+  fields: Partial<Record<MetadataField, string[]>>;
+  /** The user's JSON or YAML override file */
+  overrides_path: string | null;
+}
+
+export interface ResolvedField {
+  value: unknown;
+  provider: string;
+  /** Every provider's value for the field */
+  candidates: Record<string, unknown>;
+}
+
+export interface ResolvedMetadata {
+  media_id: number;
+  fields: Partial<Record<MetadataField, ResolvedField>>;
+  /** Providers that failed, with the error */
+  errors: Record<string, string>;
+  /** Fields the user locked, which keep their values */
+  locked: string[];
+}
+
+export interface FieldProvenance {
+  field: string;
+  /** A provider name, or 'user' for locked fields */
+  provider: string;
+  value: unknown;
+  updated_at: string;
+}
+
+export interface MetadataRefreshReport {
+  updated: number;
+  /** Locked fields, left as the user edited them */
+  locked: number;
+  errors: string[];
+}
+
+/** A field locked to the user's value */
+export interface MetadataOverride {
+  field: string;
+  value: unknown;
+  /** The value scans and providers last produced; reverting restores it */
+  scanned: unknown;
+  updated_at: string;
+}
+
+export type MetadataEditAction = 'edit' | 'lock' | 'unlock' | 'revert' | 'undo';
+
+export interface MetadataEdit {
+  id: number;
+  media_id: number;
+  field: string;
+  action: MetadataEditAction;
+  old_value: unknown;
+  new_value: unknown;
+  /** Whether the field was locked before the change */
+  was_locked: boolean;
+  undone: boolean;
+  edited_at: string;
+}
+
+/** One change applied to every file of a bulk edit */
+export type BulkOperation =
+  | { op: 'set_show'; show_title: string }
+  | { op: 'set_season'; season: number }
+  /** Number episodes in the order the ids are given */
+  | { op: 'renumber_episodes'; start: number }
+  /** null clears the year */
+  | { op: 'set_year'; year: number | null }
+  /** Replace matches in titles; the replacement may use $1 or $name */
+  | { op: 'title_regex'; pattern: string; replacement: string };
+
+export interface BulkEdit {
+  media_ids: number[];
+  /** Applied in order */
+  operations: BulkOperation[];
+}
+
+export interface FieldChange {
+  old: unknown;
+  new: unknown;
+}
+
+export interface BulkEditResult {
+  media_id: number;
+  file_name: string;
+  changes: Record<string, FieldChange>;
+}
+
+export const metadataService = {
+  async search(query: MetadataSearchQuery): Promise<MetadataSearchResult[]> {
+    return await invoke<MetadataSearchResult[]>('search_metadata', { query });
+  },
+
+  /**
+   * What the provider chain would set for a file, without changing it
+   */
+  async preview(mediaId: number): Promise<ResolvedMetadata> {
+    return await invoke<ResolvedMetadata>('preview_metadata', { mediaId });
+  },
+
+  /**
+   * Apply the provider chain to the given files, or to the whole library
+   */
+  async refresh(mediaIds?: number[]): Promise<MetadataRefreshReport> {
+    return await invoke<MetadataRefreshReport>('refresh_metadata', { mediaIds });
+  },
+
+  /**
+   * The provider behind each stored field of a file
+   */
+  async getProvenance(mediaId: number): Promise<FieldProvenance[]> {
+    return await invoke<FieldProvenance[]>('get_metadata_provenance', { mediaId });
+  },
+
+  async getImages(mediaId: number): Promise<ProviderImage[]> {
+    return await invoke<ProviderImage[]>('get_metadata_images', { mediaId });
+  },
+
+  async getConfig(): Promise<MetadataConfig> {
+    return await invoke<MetadataConfig>('get_metadata_config');
+  },
+
+  async setConfig(config: MetadataConfig): Promise<void> {
+    await invoke('set_metadata_config', { config });
+  },
+
+  /**
+   * Set fields by hand, locking each against scans and providers. Only the
+   * given fields change; null clears a field.
+   */
+  async edit(mediaId: number, changes: Record<string, unknown>): Promise<MetadataEdit[]> {
+    return await invoke<MetadataEdit[]>('edit_media_metadata', { mediaId, changes });
+  },
+
+  /**
+   * Apply changes to many files in one transaction, or with dryRun only
+   * preview the resulting values. Files that wouldn't change are left out.
+   */
+  async bulkEdit(edit: BulkEdit, dryRun = false): Promise<BulkEditResult[]> {
+    return await invoke<BulkEditResult[]>('bulk_edit_metadata', { edit, dryRun });
+  },
+
+  async getOverrides(mediaId: number): Promise<MetadataOverride[]> {
+    return await invoke<MetadataOverride[]>('get_metadata_overrides', { mediaId });
+  },
+
+  /**
+   * Lock a field at its current value, or unlock it; null when it already was
+   */
+  async setFieldLock(mediaId: number, field: string, locked: boolean): Promise<MetadataEdit | null> {
+    return await invoke<MetadataEdit | null>('set_metadata_field_lock', { mediaId, field, locked });
+  },
+
+  /**
+   * Unlock a field and restore its scanned value
+   */
+  async revertField(mediaId: number, field: string): Promise<MetadataEdit | null> {
+    return await invoke<MetadataEdit | null>('revert_metadata_field', { mediaId, field });
+  },
+
+  /**
+   * Undo the latest change to a field; null when there's nothing to undo
+   */
+  async undoEdit(mediaId: number, field: string): Promise<MetadataEdit | null> {
+    return await invoke<MetadataEdit | null>('undo_metadata_edit', { mediaId, field });
+  },
+
+  /**
+   * Every change to a file's fields, newest first
+   */
+  async getEdits(mediaId: number): Promise<MetadataEdit[]> {
+    return await invoke<MetadataEdit[]>('get_metadata_edits', { mediaId });
+  },
+};
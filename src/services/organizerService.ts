@@ -0,0 +1,59 @@
+import { invoke } from '@tauri-apps/api/tauri';
+
+/**
+ * Naming templates are paths relative to a library root. Fields are
+ * {title}, {year}, {show}, {season}, {episode}, {episode_title},
+ * {original_title}, {resolution} and {ext}; {season:02} zero-pads.
+ */
+export const DEFAULT_MOVIE_TEMPLATE = '{title} ({year})/{title} ({year}).{ext}';
+export const DEFAULT_EPISODE_TEMPLATE =
+  '{show}/Season {season:02}/{show} - S{season:02}E{episode:02} - {episode_title}.{ext}';
+
+export interface OrganizeRequest {
+  /** Movies are left alone without a template */
+  movie_template?: string | null;
+  /** TV episodes are left alone without a template */
+  episode_template?: string | null;
+  /** Destination root; by default the library path each file is under */
+  root?: string | null;
+  /** Only these files; by default the whole library */
+  media_ids?: number[] | null;
+}
+
+export interface FileMove {
+  from: string;
+  to: string;
+}
+
+export interface PlannedMove {
+  media_id: number;
+  from: string;
+  to: string;
+  /** Subtitles, NFOs and artwork moving along */
+  sidecars: FileMove[];
+  root: string;
+}
+
+export interface SkippedFile {
+  media_id: number;
+  file_path: string;
+  /** A missing template value or a conflicting target */
+  reason: string;
+}
+
+export interface OrganizePlan {
+  moves: PlannedMove[];
+  /** Files already where their template puts them */
+  unchanged: number;
+  skipped: SkippedFile[];
+}
+
+export const organizerService = {
+  /**
+   * Plan moving files to their templates and, unless dryRun, move them.
+   * If any move fails, the files already moved are put back.
+   */
+  async organize(request: OrganizeRequest, dryRun = false): Promise<OrganizePlan> {
+    return await invoke<OrganizePlan>('organize_files', { request, dryRun });
+  },
+};
@@ -0,0 +1,54 @@
+import { invoke } from '@tauri-apps/api/tauri';
+import type { MediaFile } from './mediaService';
+
+export type CreditRole = 'cast' | 'director' | 'writer' | 'producer';
+
+export interface Person {
+  person_id: number;
+  name: string;
+  profile_path: string | null;
+  roles: CreditRole[];
+  /** Movies and shows (not files) they're credited on */
+  title_count: number;
+  file_count: number;
+  watched_count: number;
+}
+
+export interface FilmographyEntry {
+  tmdb_id: number;
+  media_type: 'movie' | 'tv';
+  title: string | null;
+  release_date: string | null;
+  roles: CreditRole[];
+  characters: string[];
+  /** The owned files: the movie, or the show's episodes */
+  media: MediaFile[];
+  watched_count: number;
+  watched: boolean;
+}
+
+export interface PersonDetails {
+  person: Person;
+  /** Newest first */
+  filmography: FilmographyEntry[];
+}
+
+export const peopleService = {
+  /**
+   * People credited on files in the library, most prolific first
+   */
+  async listPeople(role?: CreditRole, query?: string, limit?: number, offset?: number): Promise<Person[]> {
+    return await invoke<Person[]>('list_people', { role, query, limit, offset });
+  },
+
+  /**
+   * A person's filmography, limited to titles in the library
+   */
+  async getPerson(personId: number): Promise<PersonDetails | null> {
+    return await invoke<PersonDetails | null>('get_person', { personId });
+  },
+
+  async getMediaByPerson(personId: number, role?: CreditRole): Promise<MediaFile[]> {
+    return await invoke<MediaFile[]>('get_media_by_person', { personId, role });
+  },
+};
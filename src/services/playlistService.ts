@@ -1,4 +1,5 @@
 import { invoke } from '@tauri-apps/api/tauri';
+import type { SortKey } from './mediaService';
 
 export interface Playlist {
   id: number;
@@ -8,6 +9,35 @@ export interface Playlist {
   created_at: string;
   updated_at: string;
   item_count: number;
+  /** Total duration of the items in seconds */
+  total_duration: number;
+  /** Why a smart playlist's rules couldn't be evaluated */
+  rule_error?: string | null;
+}
+
+export interface SmartPlaylistSettings {
+  sort?: SortKey | null;
+  descending?: boolean | null;
+  limit?: number | null;
+  /** Shuffle with a stable order for this seed */
+  random_seed?: number | null;
+}
+
+export type PlaylistFileFormat = 'm3u8' | 'pls' | 'xspf';
+
+export interface UnresolvedEntry {
+  index: number;
+  location: string;
+  title: string | null;
+}
+
+export interface PlaylistImportReport {
+  playlist_id: number;
+  name: string;
+  imported: number;
+  fuzzy_matched: number;
+  duplicates: number;
+  unresolved: UnresolvedEntry[];
 }
 
 export interface PlaylistMediaItem {
@@ -27,6 +57,8 @@ export interface PlaylistRule {
   rule_type: string;
   operator: string;
   value: string;
+  /** Id of the `group` rule containing this rule, null at top level */
+  parent_id?: number | null;
 }
 
 export const playlistService = {
@@ -54,6 +86,17 @@ export const playlistService = {
     await invoke('remove_from_playlist', { playlistId, mediaId });
   },
 
+  async reorderItem(playlistId: number, mediaId: number, newPosition: number): Promise<void> {
+    await invoke('reorder_playlist_item', { playlistId, mediaId, newPosition });
+  },
+
+  /**
+   * Move items as a block so the first one lands at toIndex
+   */
+  async moveItems(playlistId: number, mediaIds: number[], toIndex: number): Promise<number> {
+    return await invoke<number>('move_playlist_items', { playlistId, mediaIds, toIndex });
+  },
+
   async updatePlaylist(playlistId: number, name: string, description?: string): Promise<void> {
     await invoke('update_playlist', { playlistId, name, description });
   },
@@ -63,15 +106,41 @@ export const playlistService = {
   },
 
   // Smart Playlist Rules
-  async addRule(playlistId: number, ruleType: string, operator: string, value: string): Promise<number> {
+  async addRule(playlistId: number, ruleType: string, operator: string, value: string, parentId?: number): Promise<number> {
     return await invoke<number>('add_playlist_rule', {
       playlistId,
       ruleType,
       operator,
-      value
+      value,
+      parentId
     });
   },
 
+  async getSmartSettings(playlistId: number): Promise<SmartPlaylistSettings> {
+    return await invoke<SmartPlaylistSettings>('get_smart_playlist_settings', { playlistId });
+  },
+
+  async setSmartSettings(playlistId: number, settings: SmartPlaylistSettings): Promise<void> {
+    await invoke('set_smart_playlist_settings', { playlistId, settings });
+  },
+
+  async reshuffle(playlistId: number): Promise<number> {
+    return await invoke<number>('reshuffle_smart_playlist', { playlistId });
+  },
+
+  async exportPlaylist(
+    playlistId: number,
+    outputPath: string,
+    format?: PlaylistFileFormat,
+    relativePaths?: boolean
+  ): Promise<number> {
+    return await invoke<number>('export_playlist', { playlistId, outputPath, format, relativePaths });
+  },
+
+  async importPlaylist(inputPath: string, name?: string): Promise<PlaylistImportReport> {
+    return await invoke<PlaylistImportReport>('import_playlist', { inputPath, name });
+  },
+
   async getRules(playlistId: number): Promise<PlaylistRule[]> {
     return await invoke<PlaylistRule[]>('get_playlist_rules', { playlistId });
   },
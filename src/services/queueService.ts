@@ -0,0 +1,81 @@
+import { invoke } from '@tauri-apps/api/tauri';
+import type { MediaFile } from './mediaService';
+
+export type RepeatMode = 'off' | 'one' | 'all';
+
+export type QueueSource =
+  | { type: 'playlist'; id: number }
+  | { type: 'collection'; id: number }
+  | { type: 'season'; show: string; season: number }
+  | { type: 'search'; query: string }
+  | { type: 'media'; ids: number[] };
+
+export interface QueueEntry {
+  queue_item_id: number;
+  position: number;
+  media: MediaFile;
+}
+
+export interface PlayQueue {
+  current: QueueEntry | null;
+  /** Items after the current one, in play order */
+  upcoming: QueueEntry[];
+  /** Items before the current one, in play order */
+  history: QueueEntry[];
+  shuffle: boolean;
+  repeat_mode: RepeatMode;
+  source: QueueSource | null;
+  source_label: string | null;
+}
+
+export const queueService = {
+  async getQueue(): Promise<PlayQueue> {
+    return await invoke<PlayQueue>('get_play_queue');
+  },
+
+  /**
+   * Replace the queue with a playlist, collection, season, search or list of media
+   */
+  async start(source: QueueSource, startMediaId?: number, shuffle?: boolean): Promise<PlayQueue> {
+    return await invoke<PlayQueue>('start_play_queue', { source, startMediaId, shuffle });
+  },
+
+  async playNext(mediaIds: number[]): Promise<PlayQueue> {
+    return await invoke<PlayQueue>('enqueue_media', { mediaIds, playNext: true });
+  },
+
+  async addToQueue(mediaIds: number[]): Promise<PlayQueue> {
+    return await invoke<PlayQueue>('enqueue_media', { mediaIds, playNext: false });
+  },
+
+  /**
+   * Move to the next item; pass finished=true when the current item ended on its own
+   */
+  async next(finished = false): Promise<QueueEntry | null> {
+    return await invoke<QueueEntry | null>('queue_next', { finished });
+  },
+
+  async previous(): Promise<QueueEntry | null> {
+    return await invoke<QueueEntry | null>('queue_previous');
+  },
+
+  async jumpTo(queueItemId: number): Promise<QueueEntry> {
+    return await invoke<QueueEntry>('jump_to_queue_item', { queueItemId });
+  },
+
+  async remove(queueItemId: number): Promise<PlayQueue> {
+    return await invoke<PlayQueue>('remove_from_queue', { queueItemId });
+  },
+
+  async clear(): Promise<void> {
+    await invoke('clear_play_queue');
+  },
+
+  async setShuffle(shuffle: boolean): Promise<PlayQueue> {
+    return await invoke<PlayQueue>('set_queue_shuffle', { shuffle });
+  },
+
+  async setRepeatMode(repeatMode: RepeatMode): Promise<void> {
+    await invoke('set_queue_repeat_mode', { repeatMode });
+  },
+};
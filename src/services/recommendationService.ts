@@ -0,0 +1,36 @@
+import { invoke } from '@tauri-apps/api/tauri';
+import type { MediaFile } from './mediaService';
+
+export type RecommendationReasonKind = 'director' | 'writer' | 'cast' | 'genre' | 'keyword' | 'era';
+
+/** What a recommendation shares with one watched title */
+export interface RecommendationReason {
+  kind: RecommendationReasonKind;
+  /** The shared names, genres, keywords or decade */
+  values: string[];
+  because_media_id: number;
+  because_title: string;
+  /** e.g. "Shares director Ridley Scott with Alien" */
+  text: string;
+}
+
+export interface Recommendation {
+  /** The movie, or the first episode of a show */
+  media: MediaFile;
+  title: string;
+  tmdb_id: number | null;
+  score: number;
+  /** Strongest first */
+  reasons: RecommendationReason[];
+}
+
+export const recommendationService = {
+  /**
+   * Unwatched titles ranked by what they share with what's been watched.
+   * Also refreshes the ranking used by smart playlists
+   * (`recommendation_rank` rules and the `recommended` sort).
+   */
+  async getRecommendations(limit?: number): Promise<Recommendation[]> {
+    return await invoke<Recommendation[]>('get_recommendations', { limit });
+  },
+};
@@ -10,6 +10,60 @@ export interface SubtitleTrack {
   is_embedded: boolean;
   track_index?: number;
   added_at: string;
+  /** Only translates foreign dialogue and signs */
+  is_forced: boolean;
+  /** Describes sounds for the deaf and hard of hearing */
+  is_sdh: boolean;
+  is_default: boolean;
+}
+
+export type SubtitleFormat = 'srt' | 'web_vtt' | 'ass' | 'ssa' | 'micro_dvd';
+
+export interface SubtitleCue {
+  /** WebVTT cue identifier, kept from WebVTT files */
+  id: string | null;
+  /** Milliseconds */
+  start: number;
+  end: number;
+  /** WebVTT cue markup (<b>, <i>, <u>, <c.class>) */
+  text: string;
+  /** WebVTT cue settings, e.g. "line:0" for a cue at the top */
+  settings: string | null;
+}
+
+export interface SubtitleDocument {
+  format: SubtitleFormat;
+  /** The encoding the file was decoded from */
+  encoding: string;
+  /** CSS rules for the classes used in cue text */
+  styles: string[];
+  cues: SubtitleCue[];
+}
+
+/**
+ * Correction applied whenever a track is read: times are scaled by
+ * scale × source_framerate / target_framerate, then shifted by offset_ms
+ */
+export interface SubtitleTiming {
+  offset_ms: number;
+  /** Stretch from resyncing; 1 keeps the speed */
+  scale: number;
+  /** The frame rate the file was timed for, e.g. 23.976 */
+  source_framerate: number | null;
+  /** The frame rate of the video, e.g. 25 */
+  target_framerate: number | null;
+}
+
+/** A cue time as the track shows it now, and when it should show */
+export interface SyncPoint {
+  shown_ms: number;
+  wanted_ms: number;
+}
+
+export interface ResyncResult {
+  timing: SubtitleTiming;
+  /** The new track holding the corrected file, when one was written */
+  written_track_id: number | null;
 }
 
 export const subtitleService = {
@@ -41,6 +95,57 @@ export const subtitleService = {
     await invoke('remove_subtitle_track', { subtitleId });
   },
 
+  /**
+   * Parse an external track (SRT, WebVTT, ASS/SSA or MicroDVD)
+   */
+  async getCues(subtitleId: number): Promise<SubtitleDocument> {
+    return await invoke<SubtitleDocument>('get_subtitle_cues', { subtitleId });
+  },
+
+  /**
+   * An external track as WebVTT, for a <track> element via a blob URL
+   */
+  async getWebVtt(subtitleId: number): Promise<string> {
+    return await invoke<string>('get_subtitle_webvtt', { subtitleId });
+  },
+
+  /**
+   * Extract an embedded text stream (found by extractMetadata) into the app
+   * cache as a new external track, returning its id. ASS streams stay ASS
+   * and others become WebVTT unless 'srt' is asked for; image-based streams
+   * (PGS, VobSub) can't be extracted.
+   */
+  async extractEmbedded(subtitleId: number, format?: SubtitleFormat): Promise<number> {
+    return await invoke<number>('extract_embedded_subtitle', { subtitleId, format });
+  },
+
+  async getTiming(subtitleId: number): Promise<SubtitleTiming> {
+    return await invoke<SubtitleTiming>('get_subtitle_timing', { subtitleId });
+  },
+
+  async setTiming(subtitleId: number, timing: SubtitleTiming): Promise<void> {
+    await invoke('set_subtitle_timing', { subtitleId, timing });
+  },
+
+  /**
+   * Stretch and shift a track so two cues land where they should. Stored on
+   * the track, or with write saved as a new track next to the original.
+   */
+  async resync(subtitleId: number, points: [SyncPoint, SyncPoint], write = false): Promise<ResyncResult> {
+    return await invoke<ResyncResult>('resync_subtitle_track', { subtitleId, points, write });
+  },
+
+  /**
+   * Save a track with its timing correction baked in, returning the new track id
+   */
+  async saveAdjusted(subtitleId: number): Promise<number> {
+    return await invoke<number>('save_adjusted_subtitle_track', { subtitleId });
+  },
+
+  /**
+   * Register subtitle files next to the video and in its Subs/ folder,
+   * returning the ids of tracks that weren't registered yet
+   */
   async scanSubtitles(mediaId: number, mediaPath: string): Promise<number[]> {
     return await invoke<number[]>('scan_subtitles', { mediaId, mediaPath });
   },
@@ -0,0 +1,166 @@
+import { invoke } from '@tauri-apps/api/tauri';
+import type { MediaFile } from './mediaService';
+
+export type TmdbMediaType = 'movie' | 'tv';
+
+export interface MatchScore {
+  confidence: number;
+  title: number;
+  year: number;
+  runtime: number;
+  episodes: number;
+}
+
+export interface MatchCandidate {
+  media_id: number;
+  local_title: string | null;
+  tmdb_id: number;
+  media_type: TmdbMediaType;
+  title: string | null;
+  release_date: string | null;
+  score: MatchScore;
+}
+
+export interface MatchReport {
+  auto_matched: number;
+  queued_for_review: number;
+  unmatched: number;
+}
+
+export interface EpisodeLinkReport {
+  /** Files linked to at least one episode */
+  linked: number;
+  /** Files of matched shows that don't map to any known episode */
+  unmapped: number;
+}
+
+export interface TmdbSyncReport {
+  searched: number;
+  matches: MatchReport;
+  refreshed: number;
+  episodes: EpisodeLinkReport;
+  errors: string[];
+}
+
+export interface EpisodeInfo {
+  tmdb_show_id: number;
+  season_number: number;
+  episode_number: number;
+  tmdb_id: number | null;
+  name: string | null;
+  overview: string | null;
+  air_date: string | null;
+  runtime: number | null;
+  still_path: string | null;
+}
+
+export interface DuplicateEpisode {
+  episode: EpisodeInfo;
+  media: MediaFile[];
+}
+
+export interface ShowEpisodeReport {
+  tmdb_id: number;
+  name: string | null;
+  /** Regular (non-special) episodes that have aired */
+  aired: number;
+  /** Distinct episodes we have files for, specials included */
+  owned: number;
+  /** Aired regular episodes we don't own */
+  missing: EpisodeInfo[];
+  duplicates: DuplicateEpisode[];
+  /** Files of the show not linked to any known episode */
+  unmapped: MediaFile[];
+}
+
+export type TmdbBundleFormat = 'jsonl' | 'sqlite';
+
+export interface TmdbBundleReport {
+  /** Rows written or merged, per table */
+  rows: Record<string, number>;
+  skipped: number;
+}
+
+export interface TmdbBundleImportReport {
+  bundle: TmdbBundleReport;
+  matches: MatchReport;
+  episodes: EpisodeLinkReport;
+}
+
+export const tmdbService = {
+  /**
+   * Match unmatched files against cached TMDB records; confident matches are
+   * accepted, the rest are queued for review
+   */
+  async matchLibrary(autoAccept?: number): Promise<MatchReport> {
+    return await invoke<MatchReport>('match_library', { autoAccept });
+  },
+
+  /**
+   * Search TMDB for unmatched titles, match them and refresh metadata older
+   * than maxAgeDays (default 30). Fails when TMDB is disabled in settings.
+   */
+  async sync(maxAgeDays?: number): Promise<TmdbSyncReport> {
+    return await invoke<TmdbSyncReport>('sync_tmdb', { maxAgeDays });
+  },
+
+  async getMatchCandidates(mediaId?: number): Promise<MatchCandidate[]> {
+    return await invoke<MatchCandidate[]>('get_match_candidates', { mediaId });
+  },
+
+  /**
+   * Accept a match; for an episode this applies to the whole show.
+   * Returns the number of files matched.
+   */
+  async confirmMatch(mediaId: number, tmdbId: number, mediaType: TmdbMediaType): Promise<number> {
+    return await invoke<number>('confirm_match', { mediaId, tmdbId, mediaType });
+  },
+
+  async rejectCandidate(mediaId: number, tmdbId: number): Promise<void> {
+    await invoke('reject_match_candidate', { mediaId, tmdbId });
+  },
+
+  /**
+   * Write the cached TMDB data to a bundle for machines without network
+   * access. The format follows the extension (.jsonl or .sqlite) unless given.
+   */
+  async exportBundle(outputPath: string, format?: TmdbBundleFormat): Promise<TmdbBundleReport> {
+    return await invoke<TmdbBundleReport>('export_tmdb_bundle', { outputPath, format });
+  },
+
+  /**
+   * Merge a bundle into the TMDB cache and re-run the matcher
+   */
+  async importBundle(inputPath: string): Promise<TmdbBundleImportReport> {
+    return await invoke<TmdbBundleImportReport>('import_tmdb_bundle', { inputPath });
+  },
+
+  /**
+   * Re-link episode files to TMDB episodes, for every matched show or one
+   */
+  async linkEpisodes(tmdbShowId?: number): Promise<EpisodeLinkReport> {
+    return await invoke<EpisodeLinkReport>('link_episodes', { tmdbShowId });
+  },
+
+  /**
+   * The TMDB episodes a file contains; two for a double episode
+   */
+  async getEpisodeInfo(mediaId: number): Promise<EpisodeInfo[]> {
+    return await invoke<EpisodeInfo[]>('get_episode_info', { mediaId });
+  },
+
+  /**
+   * Link a file to [season, episode] pairs by hand; an empty list returns it
+   * to automatic linking
+   */
+  async setEpisodeLinks(mediaId: number, episodes: [number, number][]): Promise<void> {
+    await invoke('set_episode_links', { mediaId, episodes });
+  },
+
+  /**
+   * Missing, duplicated and unmapped episodes per show
+   */
+  async getEpisodeReport(tmdbShowId?: number): Promise<ShowEpisodeReport[]> {
+    return await invoke<ShowEpisodeReport[]>('get_episode_report', { tmdbShowId });
+  },
+};